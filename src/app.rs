@@ -1,39 +1,229 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
 use ratatui::layout::Rect;
+use ratatui::style::Style;
 use ratatui::widgets::{ListState, Block, Borders};
 use tokio::sync::mpsc;
 use tui_textarea::TextArea;
 use redis::aio::MultiplexedConnection;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 use crate::config::{self, Config};
-use crate::context;
-use crate::llm::Model;
+use crate::attachments;
+use crate::hints;
+use crate::autocomplete;
+use crate::budget::{self, AttachedItem, AttachedSource, ContextItem, ContextSection};
+use crate::context::{self, ProjectContext, ProjectProfile};
+use crate::rag;
+use crate::session::{self, BranchPoint, Session, SessionMeta, SessionNotes};
+use crate::message::{ChatMessage, Role};
+use crate::llm::{Model, Prompt, PromptBuilder, ProviderKind};
+use crate::llm::ollama::{LoadedModel, ModelInfo};
+use crate::model_cache::ModelCache;
+use crate::options::ModelOptions;
+use crate::templates::ConversationTemplate;
+use crate::i18n::{t, tf};
 use crate::ui::{AppMode, Focus, Action};
+use crate::theme::Theme;
+use crate::views::chat::{TurnCache, WrapCache};
+use crate::vim::Vim;
+use crate::keymap::InputMapper;
+use crate::mouse;
+use lucius::mcp::{self, TaskType, ToolCall, ToolSchema};
+
+/// How long a status bar message stays up before the default line returns.
+const STATUS_MESSAGE_SECS: u64 = 5;
+/// Half-period of the streaming cursor blink.
+const CURSOR_BLINK_MS: u128 = 500;
+/// How long the status line's context usage is reused while nothing is
+/// added to the conversation.
+const CONTEXT_USAGE_TTL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How long new messages wait to be saved, so a burst of them (a tool run,
+/// a streak of errors) rewrites the session file and index once.
+const SESSION_SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Context usage as counted for a conversation with some number of
+/// messages, and when.
+type CountedUsage = ((String, usize), Instant, (usize, usize));
+
+/// The files the input box attaches, with their sizes, as worked out for
+/// some input and hint mode attachments.
+type AttachedFiles = ((String, Vec<String>), Vec<(String, usize)>);
+
+/// The input box's ghost text, as completed for some input and number of
+/// earlier prompts.
+type Completion = ((String, usize), Option<String>);
 
 /// Data that can be safely shared between the UI and background threads.
 pub struct SharedState {
     pub mode: AppMode,
     pub models: Vec<Model>, // The actual data
-    pub chat_history: Vec<String>,
+    /// When the list in `models` was fetched, while it is still the one
+    /// cached by an earlier run; the first refresh clears it.
+    pub models_cached: Option<DateTime<Utc>>,
+    pub chat_history: Vec<ChatMessage>,
+    /// Bumped whenever `chat_history` changes other than by a message added
+    /// at its end, so views can keep what they work out from it.
+    pub history_revision: u64,
     pub status: bool,
     pub lucius_context: Option<String>,
+    /// The LUCIUS.md files `lucius_context` was merged from.
+    pub lucius_files: Vec<PathBuf>,
+    /// The LUCIUS.md text this conversation keeps after an edit was applied
+    /// to new conversations only; empty when it had none.
+    pub pinned_context: Option<String>,
+    /// The front matter kept along with `pinned_context`.
+    pub pinned_profile: Option<ProjectProfile>,
+    /// A LUCIUS.md edit waiting for the user to say where it applies.
+    pub context_change: Option<ContextChange>,
     pub config: config::Config,
     pub status_message: Option<(String, Instant)>,
     pub redis_conn: Option<MultiplexedConnection>,
+    /// The `[encryption]` key task payloads are sealed with, or why it failed
+    /// to load; tools are not run then rather than sent in the clear.
+    pub task_key: Result<Option<mcp::PayloadKey>, String>,
+    /// Templates the other machines share under `[sync]`, offered after the
+    /// config's own but never written to it.
+    pub shared_templates: Vec<ConversationTemplate>,
+    /// Extra system prompt for this conversation, layered on top of LUCIUS.md.
+    pub session_system_prompt: Option<String>,
+    /// Tools the model may call in this conversation. `None` allows all tools.
+    pub enabled_tools: Option<Vec<String>>,
+    /// Parameters set with `/set` for this conversation, on top of `[options]`.
+    pub session_options: ModelOptions,
+    /// Suggested follow-up prompts for each conversation's latest answer, by
+    /// conversation id. The active conversation's are sent with F1-F3.
+    pub followups: HashMap<String, Vec<String>>,
+    /// Tool tasks submitted to Redis whose result has not arrived yet.
+    pub pending_tasks: Vec<PendingTask>,
+    /// Tools advertised by the workers in the Redis registry.
+    pub worker_tools: Vec<ToolSchema>,
+    /// The workers in the Redis registry, for whom a tool would run as.
+    pub workers: Vec<mcp::WorkerInfo>,
+    /// The workers' latest inventory snapshots, loaded by `/inventory`.
+    pub inventory: Vec<mcp::Inventory>,
+    /// Embedded chunks of the project, built by `/index`.
+    pub rag_index: Option<Arc<rag::Index>>,
+    /// Project excerpts retrieved for each conversation's latest prompt, by
+    /// conversation id.
+    pub retrieved: HashMap<String, Vec<rag::Excerpt>>,
+    /// Excerpts removed in the attached context panel, by path and line;
+    /// they are not sent or retrieved again in this conversation.
+    pub skipped_excerpts: HashSet<(String, usize)>,
+    /// The model being loaded after it was selected, and since when.
+    pub warming_up: Option<(String, Instant)>,
+    /// The model being downloaded from the settings screen.
+    pub pulling: Option<ModelPull>,
+    /// Details of a model from the settings list, shown in a popup while set.
+    pub model_info: Option<ModelInfo>,
+    /// Models the Ollama server has in memory, as of the last refresh.
+    pub loaded_models: Vec<LoadedModel>,
+    /// Tasks parked in the MCP dead-letter queue as of the last refresh.
+    pub dead_letter_count: usize,
+    /// Identifies this conversation in task envelopes and the audit log.
+    pub conversation_id: String,
+    /// Name given to the conversation in the session browser.
+    pub session_title: Option<String>,
+    /// Starred conversations are exempt from session pruning.
+    pub session_starred: bool,
+    /// Tags given to the conversation with `/tag`.
+    pub session_tags: Vec<String>,
+    /// Notes the model wrote on the conversation when it was last closed,
+    /// shown under it in the session browser.
+    pub session_notes: Option<SessionNotes>,
+    /// The conversation this one was forked off, if it is a branch.
+    pub session_branch: Option<BranchPoint>,
+    /// Conversations closed since the UI last looked, waiting for their notes
+    /// to be written.
+    pub closed_sessions: Vec<Session>,
+    /// Parts of the context left out of requests from the budget panel.
+    pub excluded_context: HashSet<ContextSection>,
+    /// The tasks of answers being generated, by conversation id. Esc aborts
+    /// the active conversation's.
+    pub generations: HashMap<String, tokio::task::AbortHandle>,
+    /// Conversations waiting on the LLM, by id, with when their prompt was
+    /// sent: set with the prompt, cleared once the answer or error is in.
+    /// Covers retrieval and the request queue, before a generation starts.
+    pub busy: HashMap<String, Instant>,
+    /// The summary of the turns left out of each conversation's requests,
+    /// by conversation id, extended as more turns give way.
+    pub earlier_summaries: HashMap<String, budget::EarlierSummary>,
+    /// Every open conversation. The active one lives in the fields above;
+    /// its entry here is only refreshed when switching away from it.
+    pub tabs: Vec<Tab>,
+    pub active_tab: usize,
+    /// A `lucius -p` run, whose conversation is not saved.
+    pub headless: bool,
+    /// When a message was first added since the conversation was last saved.
+    unsaved_since: Option<Instant>,
+}
+
+/// An open conversation and where it was scrolled to.
+#[derive(Clone)]
+pub struct Tab {
+    pub session: Session,
+    pub scroll: u16,
+}
+
+/// Progress of `ollama pull`, drawn as a gauge in the settings screen.
+pub struct ModelPull {
+    pub model: String,
+    /// Ollama's latest status line, e.g. "pulling manifest".
+    pub status: String,
+    /// Completed and total bytes of the layer being downloaded.
+    pub bytes: Option<(u64, u64)>,
+}
+
+/// A submitted tool task, shown as a live indicator until its result arrives.
+pub struct PendingTask {
+    pub id: String,
+    /// The conversation its result goes to.
+    pub conversation: String,
+    pub tool: String,
+    /// Hostname of the worker that picked the task up, once one has.
+    pub worker: Option<String>,
+    pub started: Instant,
 }
 
 impl SharedState {
+    /// The state of the TUI: the latest session restored, old ones pruned.
     pub async fn new(initial_config: config::Config) -> Self {
-        let lucius_context = context::load_lucius_context();
-        if let Some(ctx) = &lucius_context {
-            log::info!("Loaded LUCIUS.md context: {} bytes", ctx.len());
+        let mut state = Self::empty(initial_config).await;
+        let pruned = session::prune(&state.config.sessions);
+        if pruned > 0 {
+            log::info!("Pruned {} session(s) past the retention limits", pruned);
+        }
+        if let Some(session) = session::load_latest() {
+            log::info!("Restoring session {} ({} messages)", session.id, session.messages.len());
+            state.restore_session(session);
+        }
+        state.tabs.push(Tab { session: state.to_session(), scroll: 0 });
+        state
+    }
+
+    /// The state of a `lucius -p` run: a new conversation that is never
+    /// saved, leaving the user's sessions alone.
+    pub async fn headless(initial_config: config::Config) -> Self {
+        let mut state = Self::empty(initial_config).await;
+        state.headless = true;
+        state
+    }
+
+    async fn empty(initial_config: config::Config) -> Self {
+        let project = context::load_project_context();
+        if let Some(ctx) = &project.prompt {
+            log::info!("Loaded LUCIUS.md context from {} file(s): {} bytes", project.files.len(), ctx.len());
         } else {
             log::info!("No LUCIUS.md context found.");
         }
+        let mut state = Self::blank(initial_config, project);
 
-        let redis_host = initial_config.mcp_redis_host.clone().unwrap_or_else(|| "127.0.0.1".to_string());
+        let redis_host = state.config.mcp_redis_host.clone().unwrap_or_else(|| "127.0.0.1".to_string());
         let redis_url = format!("redis://{}/", redis_host);
-        let redis_conn = match redis::Client::open(redis_url) {
+        state.redis_conn = match redis::Client::open(redis_url) {
             Ok(client) => match client.get_multiplexed_async_connection().await {
                 Ok(conn) => {
                     log::info!("Successfully connected to Redis for MCP.");
@@ -50,31 +240,1035 @@ impl SharedState {
             }
         };
 
+        // Last run's model list, until the first refresh answers
+        if let Some(cache) = ModelCache::load(&state.config) {
+            state.models = cache.models();
+            state.models_cached = Some(cache.fetched_at);
+        }
+        state.task_key = state.config.encryption.load();
+        if let Err(e) = &state.task_key {
+            log::error!("{}", e);
+        }
+        state
+    }
+
+    /// An unsaved conversation with nothing connected, for tests.
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self { headless: true, ..Self::blank(Config::default(), ProjectContext::default()) }
+    }
+
+    /// A new conversation under `project`, before anything is connected or
+    /// read from disk.
+    fn blank(mut initial_config: config::Config, project: ProjectContext) -> Self {
+        initial_config.apply_project(project.profile);
         Self {
             mode: AppMode::Chat,
             models: vec![],
+            models_cached: None,
             chat_history: vec![],
+            history_revision: 0,
             status: false,
-            lucius_context,
+            lucius_context: project.prompt,
+            lucius_files: project.files,
+            pinned_context: None,
+            pinned_profile: None,
+            context_change: None,
             config: initial_config,
-            status_message: Some(("Connecting to Ollama...".to_string(), Instant::now())),
-            redis_conn,
+            status_message: Some((t("status.connecting").to_string(), Instant::now())),
+            redis_conn: None,
+            task_key: Ok(None),
+            shared_templates: vec![],
+            session_system_prompt: None,
+            enabled_tools: None,
+            session_options: ModelOptions::default(),
+            followups: HashMap::new(),
+            pending_tasks: vec![],
+            worker_tools: vec![],
+            workers: vec![],
+            inventory: vec![],
+            rag_index: None,
+            retrieved: HashMap::new(),
+            skipped_excerpts: HashSet::new(),
+            warming_up: None,
+            pulling: None,
+            model_info: None,
+            loaded_models: vec![],
+            dead_letter_count: 0,
+            conversation_id: Uuid::new_v4().to_string(),
+            session_title: None,
+            session_starred: false,
+            session_tags: vec![],
+            session_notes: None,
+            session_branch: None,
+            closed_sessions: vec![],
+            excluded_context: HashSet::new(),
+            generations: HashMap::new(),
+            busy: HashMap::new(),
+            earlier_summaries: HashMap::new(),
+            tabs: vec![],
+            active_tab: 0,
+            headless: false,
+            unsaved_since: None,
+        }
+    }
+
+    /// Whether the settings model list offers the catalog: Ollama answered,
+    /// but has no models installed.
+    pub fn shows_catalog(&self) -> bool {
+        self.config.provider == ProviderKind::Ollama && self.status && self.models.is_empty()
+    }
+
+    /// Whether an answer is being generated in the active conversation.
+    pub fn generating(&self) -> bool {
+        self.generations.contains_key(&self.conversation_id)
+    }
+
+    /// When the active conversation's pending prompt was sent, while it waits
+    /// on the LLM.
+    pub fn busy_since(&self) -> Option<Instant> {
+        self.busy.get(&self.conversation_id).copied()
+    }
+
+    /// Stashes the active conversation, remembering `scroll`.
+    fn stash_tab(&mut self, scroll: u16) {
+        self.save_session();
+        let session = self.to_session();
+        if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+            *tab = Tab { session, scroll };
+        }
+    }
+
+    /// Opens an empty conversation in a new tab after the others.
+    pub fn new_tab(&mut self, scroll: u16) {
+        self.stash_tab(scroll);
+        self.clear_history();
+        self.session_system_prompt = None;
+        self.enabled_tools = None;
+        self.session_options = ModelOptions::default();
+        self.tabs.push(Tab { session: self.to_session(), scroll: 0 });
+        self.active_tab = self.tabs.len() - 1;
+    }
+
+    /// Forks the conversation after message `at` into a new tab, leaving the
+    /// original thread as it was.
+    pub fn branch_tab(&mut self, at: usize, scroll: u16) {
+        let mut branch = self.to_session();
+        self.stash_tab(scroll);
+        branch.messages.truncate(at + 1);
+        branch.branch_of = Some(BranchPoint { session: branch.id, message: at });
+        branch.id = Uuid::new_v4().to_string();
+        branch.starred = false;
+        branch.notes = None;
+        self.restore_session(branch);
+        self.save_session();
+        self.tabs.push(Tab { session: self.to_session(), scroll: 0 });
+        self.active_tab = self.tabs.len() - 1;
+    }
+
+    /// Makes tab `index` the active conversation and returns its scroll
+    /// position, or `None` if there is no such tab.
+    pub fn switch_tab(&mut self, index: usize, scroll: u16) -> Option<u16> {
+        if index >= self.tabs.len() {
+            return None;
+        }
+        self.stash_tab(scroll);
+        let tab = self.tabs[index].clone();
+        self.restore_session(tab.session);
+        self.active_tab = index;
+        Some(tab.scroll)
+    }
+
+    /// Closes the active tab (its session stays on disk) and returns the
+    /// scroll position of the one now shown. The last tab is never closed.
+    pub fn close_tab(&mut self) -> Option<u16> {
+        if self.tabs.len() < 2 {
+            return None;
+        }
+        self.note_closing();
+        self.save_session();
+        self.tabs.remove(self.active_tab);
+        let index = self.active_tab.min(self.tabs.len() - 1);
+        let tab = self.tabs[index].clone();
+        self.restore_session(tab.session);
+        self.active_tab = index;
+        Some(tab.scroll)
+    }
+
+    /// Short names for the tab bar: each conversation's title or first prompt,
+    /// marked while an answer is being generated in it.
+    pub fn tab_labels(&self) -> Vec<String> {
+        (0..self.tabs.len())
+            .map(|index| {
+                let (id, title, messages) = if index == self.active_tab {
+                    (&self.conversation_id, &self.session_title, &self.chat_history)
+                } else {
+                    let session = &self.tabs[index].session;
+                    (&session.id, &session.title, &session.messages)
+                };
+                let label: String = title
+                    .as_deref()
+                    .or_else(|| messages.iter().find(|msg| msg.role == Role::User).map(|msg| msg.content.as_str()))
+                    .map(|prompt| budget::preview(prompt).chars().take(16).collect())
+                    .unwrap_or_else(|| t("chat.new_tab").to_string());
+                // Answers keep coming in tabs that are not shown
+                if self.generations.contains_key(id) {
+                    format!("{} …", label)
+                } else {
+                    label
+                }
+            })
+            .collect()
+    }
+
+    /// Shows a saved session in the active tab, or switches to the tab it is
+    /// already open in. Returns the scroll position to show it at.
+    pub fn open_session(&mut self, session: Session, scroll: u16) -> u16 {
+        if session.id == self.conversation_id {
+            return scroll;
+        }
+        if let Some(index) = self.tabs.iter().position(|tab| tab.session.id == session.id) {
+            return self.switch_tab(index, scroll).unwrap_or(scroll);
+        }
+        self.note_closing();
+        self.stash_tab(scroll);
+        self.restore_session(session);
+        let session = self.to_session();
+        if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+            *tab = Tab { session, scroll: 0 };
+        }
+        0
+    }
+
+    /// Renames a saved session, including any open copy of it so the next
+    /// save does not bring the old title back.
+    pub fn rename_session(&mut self, id: &str, title: &str) {
+        for tab in self.tabs.iter_mut().filter(|tab| tab.session.id == id) {
+            tab.session.title = Some(title.to_string());
+        }
+        if id == self.conversation_id {
+            self.session_title = Some(title.to_string());
+        }
+        if !self.headless {
+            session::rename(id, title);
+        }
+    }
+
+    /// Whether conversation `id` has had its first answer but no title yet.
+    pub fn needs_title(&self, id: &str) -> bool {
+        let (title, messages) = if id == self.conversation_id {
+            (&self.session_title, &self.chat_history)
+        } else if let Some(tab) = self.tabs.iter().find(|tab| tab.session.id == id) {
+            (&tab.session.title, &tab.session.messages)
+        } else {
+            return false;
+        };
+        title.is_none() && messages.iter().filter(|msg| msg.role == Role::Assistant).count() == 1
+    }
+
+    /// Titles conversation `id` with a generated title, unless it was given
+    /// one, e.g. with `/rename`, while the title was being written.
+    pub fn set_generated_title(&mut self, id: &str, title: &str) {
+        let titled = if id == self.conversation_id {
+            self.session_title.is_some()
+        } else if let Some(tab) = self.tabs.iter().find(|tab| tab.session.id == id) {
+            tab.session.title.is_some()
+        } else {
+            session::find(id).and_then(|meta| session::load(&meta)).is_none_or(|saved| saved.title.is_some())
+        };
+        if !titled {
+            self.rename_session(id, title);
+        }
+    }
+
+    /// Stores notes written for a session, like `rename_session` does titles.
+    pub fn set_session_notes(&mut self, id: &str, notes: SessionNotes) {
+        for tab in self.tabs.iter_mut().filter(|tab| tab.session.id == id) {
+            tab.session.notes = Some(notes.clone());
+        }
+        if id == self.conversation_id {
+            self.session_notes = Some(notes.clone());
+        }
+        if !self.headless {
+            session::set_notes(id, notes);
+        }
+    }
+
+    /// Queues the active conversation for notes, when `[sessions] notes` is
+    /// on, as it is about to be closed.
+    pub fn note_closing(&mut self) {
+        if !self.config.sessions.notes {
+            return;
+        }
+        let closing = self.to_session();
+        if closing.needs_notes() {
+            self.closed_sessions.push(closing);
+        }
+    }
+
+    /// Stars or unstars a saved session, like `rename_session` does titles.
+    pub fn star_session(&mut self, id: &str, starred: bool) {
+        for tab in self.tabs.iter_mut().filter(|tab| tab.session.id == id) {
+            tab.session.starred = starred;
+        }
+        if id == self.conversation_id {
+            self.session_starred = starred;
+        }
+        if !self.headless {
+            session::set_starred(id, starred);
+        }
+    }
+
+    /// Deletes a saved session. The active conversation starts over; one open
+    /// in another tab is kept, since saving that tab would recreate it.
+    pub fn delete_session(&mut self, meta: &SessionMeta) -> bool {
+        let id = meta.id.as_str();
+        let open_elsewhere = self
+            .tabs
+            .iter()
+            .enumerate()
+            .any(|(index, tab)| index != self.active_tab && tab.session.id == id);
+        if open_elsewhere {
+            return false;
+        }
+        session::delete(std::slice::from_ref(meta));
+        self.closed_sessions.retain(|closed| closed.id != id);
+        if id == self.conversation_id {
+            self.clear_history();
+            let session = self.to_session();
+            if let Some(tab) = self.tabs.get_mut(self.active_tab) {
+                *tab = Tab { session, scroll: 0 };
+            }
+        }
+        true
+    }
+
+    /// Takes in the LUCIUS.md files read again after one changed on disk.
+    /// An edit to the text of a conversation already under way waits in
+    /// `context_change` for the user to pick where it applies; anything
+    /// else is applied right away. Returns whether it was.
+    pub fn reload_lucius_context(&mut self, project: ProjectContext) -> bool {
+        let old = self.conversation_context();
+        if project.prompt == old || self.chat_history.is_empty() {
+            self.pinned_context = None;
+            self.pinned_profile = None;
+            self.apply_project_context(project);
+            return true;
+        }
+        // A later edit replaces the pending one, still compared to what the conversation has
+        self.context_change = Some(ContextChange { old, project });
+        false
+    }
+
+    /// Settles the pending LUCIUS.md edit: into this conversation as well,
+    /// or only into new ones, with this one keeping the text, front matter
+    /// and model it has.
+    pub fn resolve_context_change(&mut self, this_conversation: bool) {
+        let Some(change) = self.context_change.take() else {
+            return;
+        };
+        if this_conversation {
+            self.pinned_context = None;
+            self.pinned_profile = None;
+            self.apply_project_context(change.project);
+            return;
+        }
+        self.pinned_context = Some(change.old.unwrap_or_default());
+        let profile = self.pinned_profile.take().unwrap_or_else(|| self.config.project.clone());
+        let model = self.config.selected_model.clone();
+        self.apply_project_context(change.project);
+        self.config.selected_model = model;
+        self.pinned_profile = Some(profile);
+    }
+
+    /// The LUCIUS.md front matter this conversation goes by.
+    pub fn project_profile(&self) -> &ProjectProfile {
+        self.pinned_profile.as_ref().unwrap_or(&self.config.project)
+    }
+
+    /// The LUCIUS.md text this conversation is sent with.
+    pub fn conversation_context(&self) -> Option<String> {
+        match &self.pinned_context {
+            Some(pinned) => Some(pinned.clone()).filter(|text| !text.is_empty()),
+            None => self.lucius_context.clone(),
+        }
+    }
+
+    /// The front matter's model is only switched to when the file names a
+    /// different one, so an edit to the prose keeps the model picked since.
+    fn apply_project_context(&mut self, project: ProjectContext) {
+        if project.profile.model != self.config.project.model {
+            self.config.apply_project(project.profile);
+        } else {
+            self.config.project = project.profile;
+        }
+        self.lucius_context = project.prompt;
+        self.lucius_files = project.files;
+    }
+
+    /// Picks up a saved conversation where it was left.
+    pub fn restore_session(&mut self, session: Session) {
+        if self.unsaved_since.is_some() {
+            self.save_session();
+        }
+        self.chat_history = session.messages;
+        self.history_revision += 1;
+        self.conversation_id = session.id;
+        self.session_title = session.title;
+        self.session_starred = session.starred;
+        self.session_tags = session.tags;
+        self.session_notes = session.notes;
+        self.session_branch = session.branch_of;
+        self.session_system_prompt = session.system_prompt;
+        self.enabled_tools = session.enabled_tools;
+        self.session_options = session.options;
+        self.pinned_context = session.lucius_context;
+        self.pinned_profile = session.lucius_profile;
+        if session.model.is_some() {
+            self.config.selected_model = session.model;
+        }
+        self.excluded_context.clear();
+    }
+
+    /// Saves the conversation to its session file. Empty conversations are
+    /// not worth a file, and headless runs are not kept.
+    pub fn save_session(&mut self) {
+        self.unsaved_since = None;
+        if self.chat_history.is_empty() || self.headless {
+            return;
+        }
+        session::save(&self.to_session());
+    }
+
+    /// Saves the messages `push_message` added once they have waited
+    /// `SESSION_SAVE_DELAY`; called on every tick of the UI loop.
+    pub fn save_session_if_due(&mut self) {
+        if self.unsaved_since.is_some_and(|since| since.elapsed() >= SESSION_SAVE_DELAY) {
+            self.save_session();
+        }
+    }
+
+    /// The active conversation as a saveable session.
+    pub fn to_session(&self) -> Session {
+        Session {
+            id: self.conversation_id.clone(),
+            title: self.session_title.clone(),
+            starred: self.session_starred,
+            tags: self.session_tags.clone(),
+            notes: self.session_notes.clone(),
+            model: self.config.selected_model.clone(),
+            messages: self.chat_history.clone(),
+            system_prompt: self.session_system_prompt.clone(),
+            enabled_tools: self.enabled_tools.clone(),
+            options: self.session_options.clone(),
+            lucius_context: self.pinned_context.clone(),
+            lucius_profile: self.pinned_profile.clone(),
+            branch_of: self.session_branch.clone(),
+        }
+    }
+
+    /// The parameters this conversation's requests are sent with.
+    pub fn model_options(&self) -> ModelOptions {
+        self.config.model_options(self.project_profile(), &self.session_options)
+    }
+
+    /// Clears the status bar message once it has been up long enough.
+    pub fn expire_status_message(&mut self) {
+        if self.status_message.as_ref().is_some_and(|(_, shown)| shown.elapsed().as_secs() >= STATUS_MESSAGE_SECS) {
+            self.status_message = None;
+        }
+    }
+
+    /// Appends a message to the chat history. The session is saved a moment
+    /// later, with any others that follow.
+    pub fn push_message(&mut self, message: ChatMessage) {
+        // An error that keeps coming back (e.g. the provider is down and
+        // every send fails) bumps a counter on its earlier copy
+        match self.repeated_error(&message) {
+            Some(index) => {
+                let previous = &mut self.chat_history[index];
+                previous.repeats += 1;
+                previous.timestamp = message.timestamp;
+            }
+            None => self.chat_history.push(message),
+        }
+        self.unsaved_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Takes a message out of the history, keeping the context exclusions
+    /// on the messages after it.
+    pub fn remove_message(&mut self, index: usize) -> ChatMessage {
+        self.excluded_context = self
+            .excluded_context
+            .drain()
+            .filter_map(|section| match section {
+                ContextSection::Message(i) | ContextSection::Attachment(i, _) if i == index => None,
+                ContextSection::Message(i) if i > index => Some(ContextSection::Message(i - 1)),
+                ContextSection::Attachment(i, ordinal) if i > index => Some(ContextSection::Attachment(i - 1, ordinal)),
+                section => Some(section),
+            })
+            .collect();
+        self.history_revision += 1;
+        self.chat_history.remove(index)
+    }
+
+    /// Appends a message to conversation `id`, wherever it is: the active
+    /// one, another tab, or only its session file once its tab was closed.
+    pub fn push_to(&mut self, id: &str, message: ChatMessage) {
+        if id == self.conversation_id {
+            self.push_message(message);
+        } else if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.session.id == id) {
+            tab.session.messages.push(message);
+            session::save(&tab.session);
+        } else if !session::update(id, |session| session.messages.push(message)) {
+            log::warn!("Dropping a message for unknown conversation {}", id);
+        }
+    }
+
+    /// The earlier copy of the error `message`: the same error with nothing
+    /// after it but the user's retries.
+    fn repeated_error(&self, message: &ChatMessage) -> Option<usize> {
+        if message.role != Role::Error {
+            return None;
+        }
+        let index = self.chat_history.iter().rposition(|msg| msg.role != Role::User)?;
+        let previous = &self.chat_history[index];
+        (previous.role == Role::Error && previous.content == message.content && previous.tool_call == message.tool_call)
+            .then_some(index)
+    }
+
+    /// Empties the conversation along with everything derived from it.
+    pub fn clear_history(&mut self) {
+        if self.unsaved_since.is_some() {
+            self.save_session();
+        }
+        self.chat_history.clear();
+        self.history_revision += 1;
+        self.skipped_excerpts.clear();
+        // Follow-ups and excerpts are kept by id, so the new one has none
+        self.conversation_id = Uuid::new_v4().to_string();
+        self.session_title = None;
+        self.session_starred = false;
+        self.session_tags.clear();
+        self.session_notes = None;
+        self.session_branch = None;
+        self.pinned_context = None;
+        self.pinned_profile = None;
+        self.excluded_context.retain(|section| !matches!(section, ContextSection::Message(_) | ContextSection::Attachment(..)));
+    }
+
+    /// The next request: a system prompt of LUCIUS.md, the conversation's
+    /// own prompt, the tools registered by workers, the inventory, the
+    /// verbosity instruction and retrieved excerpts, whichever are present
+    /// and not excluded, then the history and the tools on offer.
+    pub fn prompt(&self) -> Prompt {
+        let mut builder = PromptBuilder::new(self.config.selected_model.clone().unwrap_or_default());
+        for (section, text) in self.system_sections() {
+            if !self.excluded_context.contains(&section) {
+                builder = builder.system(Some(text));
+            }
+        }
+        let tools = if self.excluded_context.contains(&ContextSection::Tools) { vec![] } else { self.available_tools() };
+        builder.history(self.history_for_llm()).tools(tools).build()
+    }
+
+    /// The parts of the system prompt that are present, in order.
+    fn system_sections(&self) -> Vec<(ContextSection, String)> {
+        [
+            (ContextSection::LuciusMd, self.conversation_context()),
+            (ContextSection::SessionPrompt, self.session_system_prompt.clone()),
+            (ContextSection::Tools, self.tools_prompt()),
+            (ContextSection::Inventory, self.inventory_prompt()),
+            (ContextSection::Verbosity, self.config.verbosity.instruction()),
+            (ContextSection::Retrieved, rag::excerpts_prompt(&self.retrieved_in_use())),
+        ]
+        .into_iter()
+        .filter_map(|(section, text)| text.map(|text| (section, text)))
+        .collect()
+    }
+
+    /// Index of the user message that starts the turn `index` belongs to.
+    pub fn turn_start(&self, index: usize) -> Option<usize> {
+        self.chat_history.get(..=index)?.iter().rposition(|msg| msg.role == Role::User)
+    }
+
+    /// The tool calls, results and worker errors between the turn's prompt
+    /// and the next one: what folding a turn hides.
+    pub fn turn_tool_steps(&self, start: usize) -> Vec<usize> {
+        self.chat_history
+            .iter()
+            .enumerate()
+            .skip(start + 1)
+            .take_while(|(_, msg)| msg.role != Role::User)
+            .filter(|(_, msg)| msg.is_tool_step())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// One-line description of folded tool steps, e.g. "exec ×2, docker".
+    pub fn tool_steps_summary(&self, steps: &[usize]) -> String {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for msg in steps.iter().filter_map(|&index| self.chat_history.get(index)) {
+            let Some(call) = msg.tool_call.as_ref().filter(|_| msg.role == Role::ToolCall) else {
+                continue;
+            };
+            match counts.iter_mut().find(|(tool, _)| *tool == call.tool) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((call.tool.clone(), 1)),
+            }
+        }
+        counts
+            .into_iter()
+            .map(|(tool, count)| if count > 1 { format!("{} ×{}", tool, count) } else { tool })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The chat history as sent to the model: no errors or notices, and no
+    /// excluded messages or attachments.
+    pub fn history_for_llm(&self) -> Vec<ChatMessage> {
+        self.chat_history
+            .iter()
+            .enumerate()
+            .filter(|(index, msg)| msg.is_sent() && !self.excluded_context.contains(&ContextSection::Message(*index)))
+            .map(|(index, msg)| self.sent_message(index, msg))
+            .collect()
+    }
+
+    /// `msg` as it goes out, without the attached files removed from it.
+    fn sent_message(&self, index: usize, msg: &ChatMessage) -> ChatMessage {
+        let removed = |ordinal| self.excluded_context.contains(&ContextSection::Attachment(index, ordinal));
+        if msg.role != Role::User || !(0..attachments::attached(&msg.content).len()).any(removed) {
+            return msg.clone();
+        }
+        ChatMessage { content: attachments::strip(&msg.content, removed), ..msg.clone() }
+    }
+
+    /// The active conversation's suggested follow-ups.
+    pub fn active_followups(&self) -> &[String] {
+        self.followups.get(&self.conversation_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The excerpts retrieved for the active conversation's latest prompt.
+    fn active_retrieved(&self) -> &[rag::Excerpt] {
+        self.retrieved.get(&self.conversation_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// How many messages conversation `id` has, wherever it is open.
+    pub fn history_len(&self, id: &str) -> Option<usize> {
+        if id == self.conversation_id {
+            return Some(self.chat_history.len());
+        }
+        self.tabs.iter().find(|tab| tab.session.id == id).map(|tab| tab.session.messages.len())
+    }
+
+    /// The retrieved excerpts not removed in the attached context panel.
+    fn retrieved_in_use(&self) -> Vec<rag::Excerpt> {
+        self.active_retrieved().iter().filter(|excerpt| !self.skipped_excerpts.contains(&excerpt.key())).cloned().collect()
+    }
+
+    /// The files attached to sent prompts and the retrieved excerpts, for
+    /// the attached context panel.
+    pub fn attached_context(&self) -> Vec<AttachedItem> {
+        let files = self.chat_history.iter().enumerate().filter(|(_, msg)| msg.role == Role::User).flat_map(|(index, msg)| {
+            attachments::attached(&msg.content).into_iter().enumerate().map(move |(ordinal, attached)| {
+                let section = ContextSection::Attachment(index, ordinal);
+                AttachedItem {
+                    label: attached.path,
+                    bytes: attached.range.len(),
+                    included: !self.excluded_context.contains(&section) && !self.excluded_context.contains(&ContextSection::Message(index)),
+                    source: AttachedSource::Sent(section),
+                }
+            })
+        });
+        let excerpts = self.active_retrieved().iter().map(|excerpt| AttachedItem {
+            source: AttachedSource::Excerpt(excerpt.path.clone(), excerpt.line),
+            label: format!("{}:{}", excerpt.path, excerpt.line),
+            bytes: excerpt.text.len(),
+            included: !self.skipped_excerpts.contains(&excerpt.key()),
+        });
+        files.chain(excerpts).collect()
+    }
+
+    /// Estimated tokens of the next request, and the context window it has
+    /// to fit in.
+    pub fn context_usage(&self) -> (usize, usize) {
+        let tokens = self.context_items().iter().filter(|item| item.included).map(|item| item.tokens).sum();
+        (tokens, self.config.context.window(self.config.provider, self.model_options().num_ctx))
+    }
+
+    /// Everything the next request would carry, for the context budget panel.
+    pub fn context_items(&self) -> Vec<ContextItem> {
+        let system = self.system_sections().into_iter().filter(|(section, _)| *section != ContextSection::Retrieved).map(|(section, text)| {
+            let label = match section {
+                ContextSection::LuciusMd => t("context.lucius_md").to_string(),
+                ContextSection::SessionPrompt => t("context.session_prompt").to_string(),
+                ContextSection::Inventory => t("context.inventory").to_string(),
+                ContextSection::Verbosity => t("context.verbosity").to_string(),
+                _ => t("context.tools").to_string(),
+            };
+            (section, label, text)
+        });
+        let excerpts = self.active_retrieved().iter().enumerate().map(|(position, excerpt)| {
+            let label = tf("context.excerpt", &[("path", &excerpt.path), ("line", &excerpt.line.to_string())]);
+            let text = rag::excerpts_prompt(std::slice::from_ref(excerpt)).unwrap_or_default();
+            (ContextSection::Excerpt(position), label, text)
+        });
+        let messages = self
+            .chat_history
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| msg.is_sent())
+            .map(|(index, msg)| {
+                let text = self.sent_message(index, msg).display_text();
+                (ContextSection::Message(index), budget::preview(&text), text)
+            });
+        system
+            .chain(excerpts)
+            .chain(messages)
+            .map(|(section, label, text)| ContextItem {
+                section,
+                label,
+                tokens: budget::estimate_tokens(&text),
+                included: !self.context_excluded(section),
+            })
+            .collect()
+    }
+
+    /// Whether `section` is left out of the next request.
+    fn context_excluded(&self, section: ContextSection) -> bool {
+        match section {
+            ContextSection::Excerpt(position) => {
+                self.active_retrieved().get(position).is_none_or(|excerpt| self.skipped_excerpts.contains(&excerpt.key()))
+            }
+            _ => self.excluded_context.contains(&section),
+        }
+    }
+
+    /// Leaves `section` out of the next request, or puts it back.
+    pub fn toggle_context(&mut self, section: ContextSection) {
+        if let ContextSection::Excerpt(position) = section {
+            if let Some(key) = self.active_retrieved().get(position).map(rag::Excerpt::key) {
+                if !self.skipped_excerpts.remove(&key) {
+                    self.skipped_excerpts.insert(key);
+                }
+            }
+        } else if !self.excluded_context.remove(&section) {
+            self.excluded_context.insert(section);
+        }
+    }
+
+    /// The registered tools the model may call in this conversation.
+    pub fn available_tools(&self) -> Vec<ToolSchema> {
+        self.worker_tools.iter().filter(|tool| self.tool_enabled(&tool.name)).cloned().collect()
+    }
+
+    /// Describes the registered tools the model may call in this conversation.
+    fn tools_prompt(&self) -> Option<String> {
+        let tools: Vec<String> = self
+            .available_tools()
+            .iter()
+            .map(|tool| format!("- {}: {}\n  params schema: {}", tool.name, tool.description, tool.parameters))
+            .collect();
+        if tools.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "You can run these tools on the user's machines:\n{}\n\nIf you cannot call them natively, reply with [TOOL_CALL] {{\"tool\": \"<name>\", \"params\": {{...}}}} [END_TOOL_CALL]\n\
+             Optionally add \"expect\": [\"exit_code == 0\", \"stdout contains 'active (running)'\"] to have the worker check the result; \
+             its \"verification\" field then says whether each expectation held.",
+            tools.join("\n")
+        ))
+    }
+
+    /// The inventory snapshots as JSON, so the model can answer questions
+    /// about the user's actual hosts, containers and ports.
+    fn inventory_prompt(&self) -> Option<String> {
+        if self.inventory.is_empty() {
+            return None;
+        }
+        let snapshot = serde_json::to_string_pretty(&self.inventory).ok()?;
+        Some(format!(
+            "Inventory of the user's machines (hostname, OS, running containers, listening TCP ports), \
+             as reported by the workers; prefer it over guessing about their environment:\n{}",
+            snapshot
+        ))
+    }
+
+    /// Drops a model deleted from the Ollama server. Returns whether it was
+    /// the selected one, which is then unset and the config needs saving.
+    pub fn forget_model(&mut self, model: &str) -> bool {
+        self.models.retain(|known| known.name != model);
+        self.loaded_models.retain(|loaded| loaded.name != model);
+        if self.config.selected_model.as_deref() != Some(model) {
+            return false;
+        }
+        self.config.selected_model = None;
+        true
+    }
+
+    /// The queue to submit `tool` to: the one its worker registered, or the
+    /// built-in default.
+    pub fn tool_task_type(&self, tool: &str) -> TaskType {
+        self.worker_tools
+            .iter()
+            .find(|schema| schema.name == tool)
+            .map(|schema| schema.task_type.clone())
+            .unwrap_or_else(|| mcp::default_task_type(tool))
+    }
+
+    /// The priority of tool calls from this state: nobody waits on a
+    /// `lucius -p` run, which is how scripts and cron jobs call Lucius.
+    pub fn task_priority(&self) -> mcp::Priority {
+        if self.headless {
+            mcp::Priority::Background
+        } else {
+            mcp::Priority::Interactive
+        }
+    }
+
+    /// Whether the model may call `tool` in this conversation. A template's
+    /// or session's tool list wins over the project's, but never over its
+    /// `deny_tools`.
+    pub fn tool_enabled(&self, tool: &str) -> bool {
+        let allowed = self
+            .enabled_tools
+            .as_ref()
+            .or(self.project_profile().tools.as_ref())
+            .is_none_or(|tools| tools.iter().any(|t| t == tool));
+        allowed && !self.project_profile().forbids(tool)
+    }
+
+    /// Whether the message at `index` reports a failed tool execution: a
+    /// submit/poll error or a result the worker marked as failed.
+    pub fn is_failed_tool_message(&self, index: usize) -> bool {
+        self.chat_history.get(index).is_some_and(ChatMessage::is_failed_tool_step)
+    }
+
+    /// The call behind the failed tool result at `index`, so it can be
+    /// re-submitted without another round trip to the model.
+    pub fn failed_tool_call(&self, index: usize) -> Option<ToolCall> {
+        self.chat_history.get(index).filter(|msg| msg.is_failed_tool_step())?.tool_call.clone()
+    }
+
+    /// Starts a fresh conversation from `template`.
+    pub fn apply_template(&mut self, template: &ConversationTemplate) {
+        self.note_closing();
+        self.clear_history();
+        self.session_system_prompt = template.system_prompt.clone();
+        self.enabled_tools = template.tools.clone();
+    }
+}
+
+/// An edit to LUCIUS.md made while a conversation was under way.
+pub struct ContextChange {
+    /// The text the conversation has been sent with.
+    pub old: Option<String>,
+    /// The files as read after the edit.
+    pub project: ProjectContext,
+}
+
+/// The Ctrl+F search through the conversation.
+#[derive(Debug, Default)]
+pub struct ChatSearch {
+    pub query: String,
+    /// Whether keys still go to the query; after Enter, n and N step through
+    /// the matches.
+    pub editing: bool,
+    /// Matches in the rows last drawn, from the top, each as the
+    /// `(row, from, to)` pieces of the rows it is wrapped over.
+    pub hits: Vec<Vec<(usize, usize, usize)>>,
+    /// The match scrolled to. Past the end means the last, nearest the
+    /// bottom, where a new query starts.
+    pub current: usize,
+    /// Scroll to the current match at the next draw.
+    pub jump: bool,
+}
+
+impl ChatSearch {
+    pub fn new() -> Self {
+        Self { editing: true, current: usize::MAX, ..Self::default() }
+    }
+
+    /// Searches again for the edited query, from the bottom up.
+    pub fn restart(&mut self) {
+        self.current = usize::MAX;
+        self.jump = true;
+    }
+
+    /// Steps `delta` matches down, or up when negative, wrapping around.
+    pub fn step(&mut self, delta: isize) {
+        if self.hits.is_empty() {
+            return;
+        }
+        let len = self.hits.len() as isize;
+        self.current = (self.current.min(self.hits.len() - 1) as isize + delta).rem_euclid(len) as usize;
+        self.jump = true;
+    }
+
+    /// Matches the query in `rows`, ignoring case, and keeps the current one
+    /// among them.
+    pub fn find(&mut self, rows: &[mouse::Row]) {
+        self.hits = find_in_rows(rows, &self.query);
+        if self.current >= self.hits.len() {
+            self.current = self.hits.len().saturating_sub(1);
         }
     }
 }
 
+/// Where `query` appears in the lines `rows` are wrapped from, each match
+/// as the `(row, from, to)` pieces it covers, in characters.
+fn find_in_rows(rows: &[mouse::Row], query: &str) -> Vec<Vec<(usize, usize, usize)>> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let query: Vec<char> = query.chars().map(lower).collect();
+    if query.is_empty() {
+        return vec![];
+    }
+    let mut hits = Vec::new();
+    let mut first = 0;
+    while first < rows.len() {
+        let last = first + rows[first + 1..].iter().take_while(|row| row.continues).count();
+        // Each character of the line, with the row and column it is drawn at
+        let cells: Vec<(char, usize, usize)> = (first..=last)
+            .flat_map(|index| rows[index].text.chars().enumerate().map(move |(column, c)| (lower(c), index, column)))
+            .collect();
+        let mut from = 0;
+        while from + query.len() <= cells.len() {
+            if cells[from..from + query.len()].iter().map(|cell| cell.0).eq(query.iter().copied()) {
+                let mut pieces: Vec<(usize, usize, usize)> = Vec::new();
+                for &(_, row, column) in &cells[from..from + query.len()] {
+                    match pieces.last_mut() {
+                        Some(piece) if piece.0 == row => piece.2 = column + 1,
+                        _ => pieces.push((row, column, column + 1)),
+                    }
+                }
+                hits.push(pieces);
+                from += query.len();
+            } else {
+                from += 1;
+            }
+        }
+        first = last + 1;
+    }
+    hits
+}
 
 /// The main application struct, holding UI-specific state.
 pub struct App<'a> {
     // UI-specific state
     pub model_list_state: ListState, // The UI state for the list
+    pub template_list_state: ListState,
+    pub tool_list_state: ListState,
+    pub context_list_state: ListState,
+    pub attached_list_state: ListState,
+    pub session_list_state: ListState,
+    /// New title being typed for the selected session in the browser.
+    pub session_rename: Option<TextArea<'a>>,
+    /// Set by the first `d` in the session browser; the second one deletes.
+    pub session_delete_armed: bool,
+    /// Tag the session browser is limited to, without the `#`.
+    pub session_tag_filter: Option<String>,
+    /// Query of the global search screen.
+    pub search_input: TextArea<'a>,
+    pub search_list_state: ListState,
+    /// When the search query was last edited, until it is searched for.
+    pub search_edited: Option<Instant>,
+    /// The conversation's system prompt while `AppMode::PromptEditor` is open.
+    pub prompt_editor: TextArea<'a>,
     pub textarea: TextArea<'a>,
     pub url_editor: TextArea<'a>,
     pub mcp_url_editor: TextArea<'a>,
+    /// The `[options]` parameters as `key=value` pairs.
+    pub options_editor: TextArea<'a>,
+    /// Anthropic API key, masked on screen.
+    pub api_key_editor: TextArea<'a>,
+    /// Free-form model name, for providers whose models aren't all listed.
+    pub model_name_editor: TextArea<'a>,
+    /// Name of the model to pull, typed after `p` in the settings model list.
+    pub model_pull: Option<TextArea<'a>>,
+    /// Set by the first `d` in the settings model list; the second one deletes.
+    pub model_delete_armed: bool,
+    /// Set by the first `d` on a focused message; the second one deletes it.
+    pub message_delete_armed: bool,
+    /// Set by Enter on a model too big for the host; the second one picks it.
+    pub model_fit_armed: bool,
+    /// Lines scrolled in the model info popup.
+    pub model_info_scroll: u16,
+    /// Rows scrolled in the LUCIUS.md diff.
+    pub context_diff_scroll: u16,
     pub focus: Focus,
     pub scroll: u16,
+    /// The mouse selection being dragged, from where it started to where
+    /// the pointer is, as row and column of `conversation_rows`.
     pub selection_range: Option<((usize, usize), (usize, usize))>,
+    /// Where the conversation text is drawn, inside its borders and padding.
     pub conversation_area: Rect,
+    /// Where the status line was drawn; clicking it opens the attached
+    /// context panel.
+    pub status_area: Rect,
+    /// Whether mouse events are captured. Off with `[mouse] enabled = false`
+    /// and on terminals that do not report them, where Up/Down scroll the
+    /// conversation instead.
+    pub mouse_capture: bool,
+    /// The conversation's rows as last laid out, for the mouse selection.
+    pub conversation_rows: Vec<mouse::Row>,
+    /// The conversation's lines as last wrapped into those rows.
+    pub wrap_cache: WrapCache,
+    pub turns: TurnCache,
+    /// The Ctrl+F search, while its bar is open.
+    pub chat_search: Option<ChatSearch>,
+    /// The assistant's answers so far while they stream in, by conversation
+    /// id, with the number of their generation; only the active
+    /// conversation's is shown, while it is busy.
+    pub streaming: HashMap<String, (u64, String)>,
+    /// Prompts sent this session, oldest first, for input autocompletion.
+    pub prompt_history: Vec<String>,
+    /// Files picked in hint mode, attached to the next prompt.
+    pub attachments: Vec<String>,
+    /// A path to open in `$EDITOR` once the main loop has released the terminal.
+    pub pending_editor: Option<String>,
+    /// Show absolute timestamps instead of "2m ago".
+    pub expand_timestamps: bool,
+    /// Show timestamps and generation times after messages.
+    pub show_stamps: bool,
+    /// Horizontal scroll offset for code blocks and tables wider than the
+    /// conversation pane. Applies to the focused message, or to all of them.
+    pub hscroll: u16,
+    /// Index into the chat history of the message picked with Alt+Up/Down.
+    pub focused_message: Option<usize>,
+    /// Messages whose code blocks are soft-wrapped instead of scrolled.
+    pub wrapped_messages: HashSet<usize>,
+    /// Answers showing how the backend said they ended.
+    pub debug_messages: HashSet<usize>,
+    /// The code block last copied with Ctrl+K, as message and block index.
+    pub code_block: Option<(usize, usize)>,
+    /// Turns key presses into the commands of each mode.
+    pub keymap: InputMapper,
+    /// Turns (by the index of their "You: " message) whose tool steps are
+    /// collapsed to one line.
+    pub folded_turns: HashSet<usize>,
+    /// How many messages had been scrolled into view; later ones are unread.
+    pub read_messages: usize,
+    /// Line of the "new" divider in the last drawn conversation, if shown.
+    pub unread_line: Option<u16>,
+    /// Line count and bottom state of the last drawn conversation, used to
+    /// follow new output.
+    pub drawn_lines: usize,
+    pub was_at_bottom: bool,
+    /// Whether spinners turn and the streaming cursor blinks; off when
+    /// `[animation] fps = 0`.
+    pub animate: bool,
+    /// When the UI started, the clock animations are timed against.
+    pub started: Instant,
+    /// The status line's last context usage, as tokens and window.
+    context_usage: Option<CountedUsage>,
+    /// The input box's attached files, as last looked up on disk.
+    attached_files: Option<AttachedFiles>,
+    /// The input box's ghost text, as last completed; `@path` completions
+    /// read the directory.
+    completion: Option<Completion>,
+    /// Modal editing state of the input box when `[input] vim` is on.
+    pub vim: Vim,
     // Action channel to the background worker
     pub action_tx: mpsc::Sender<Action>,
 }
@@ -84,21 +1278,14 @@ impl<'a> App<'a> {
         action_tx: mpsc::Sender<Action>,
         initial_config: &Config
     ) -> App<'a> {
-        let mut textarea = TextArea::default();
-        textarea.set_placeholder_text("Ask me anything...");
-        textarea.set_block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Input")
-                .border_type(ratatui::widgets::BorderType::Rounded),
-        );
-        
+        let textarea = new_input_textarea("");
+
         let url_editor_content = initial_config.ollama_url.clone().unwrap_or_default();
         let mut url_editor = TextArea::new(vec![url_editor_content]);
         url_editor.set_block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Ollama URL"),
+                .title(t("settings.ollama_url")),
         );
 
         let mcp_url_editor_content = initial_config.mcp_redis_host.clone().unwrap_or_default();
@@ -106,22 +1293,288 @@ impl<'a> App<'a> {
         mcp_url_editor.set_block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("MCP Redis Host"),
+                .title(t("settings.mcp_host")),
         );
 
+        let options_editor = TextArea::new(vec![initial_config.options.describe()]);
+
+        let mut api_key_editor = TextArea::new(vec![initial_config.anthropic.api_key.clone().unwrap_or_default()]);
+        api_key_editor.set_mask_char('•');
+        let model_name_editor = TextArea::new(vec![initial_config.selected_model.clone().unwrap_or_default()]);
+
         App {
             model_list_state: ListState::default(),
+            template_list_state: ListState::default(),
+            tool_list_state: ListState::default(),
+            context_list_state: ListState::default(),
+            attached_list_state: ListState::default(),
+            session_list_state: ListState::default(),
+            session_rename: None,
+            session_delete_armed: false,
+            session_tag_filter: None,
+            search_input: TextArea::default(),
+            search_list_state: ListState::default(),
+            search_edited: None,
+            prompt_editor: TextArea::default(),
             textarea,
             url_editor,
             mcp_url_editor,
-            focus: Focus::Url,
+            options_editor,
+            api_key_editor,
+            model_name_editor,
+            model_pull: None,
+            model_delete_armed: false,
+            message_delete_armed: false,
+            model_fit_armed: false,
+            model_info_scroll: 0,
+            context_diff_scroll: 0,
+            focus: Focus::Provider,
             scroll: 0,
             selection_range: None,
             conversation_area: Rect::default(),
+            status_area: Rect::default(),
+            mouse_capture: true,
+            conversation_rows: Vec::new(),
+            wrap_cache: WrapCache::default(),
+            turns: TurnCache::default(),
+            chat_search: None,
+            streaming: HashMap::new(),
+            prompt_history: vec![],
+            attachments: vec![],
+            pending_editor: None,
+            expand_timestamps: false,
+            show_stamps: initial_config.time.show,
+            hscroll: 0,
+            focused_message: None,
+            wrapped_messages: HashSet::new(),
+            debug_messages: HashSet::new(),
+            code_block: None,
+            keymap: InputMapper::default(),
+            folded_turns: HashSet::new(),
+            read_messages: 0,
+            unread_line: None,
+            drawn_lines: 0,
+            was_at_bottom: true,
+            animate: initial_config.animation.frame_interval().is_some(),
+            started: Instant::now(),
+            context_usage: None,
+            attached_files: None,
+            completion: None,
+            vim: Vim::default(),
             action_tx,
         }
     }
     
+    /// Hands `action` to the background worker. Its queue only fills up when
+    /// the worker is stuck: then what the user asked for waits for room in a
+    /// task of its own, while background refreshes are skipped. Either way
+    /// the status line says so.
+    pub fn dispatch(&self, state: &mut SharedState, action: Action) {
+        let action = match self.action_tx.try_send(action) {
+            Ok(()) => return,
+            Err(mpsc::error::TrySendError::Full(action)) => action,
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                log::error!("The background worker has stopped; dropping an action");
+                state.status_message = Some((t("status.worker_stopped").to_string(), Instant::now()));
+                return;
+            }
+        };
+        if action.is_refresh() {
+            log::warn!("Action queue full; skipping a background refresh");
+            state.status_message = Some((t("status.queue_full_skipped").to_string(), Instant::now()));
+            return;
+        }
+        log::warn!("Action queue full; waiting for room");
+        state.status_message = Some((t("status.queue_full_waiting").to_string(), Instant::now()));
+        let action_tx = self.action_tx.clone();
+        tokio::spawn(async move {
+            if action_tx.send(action).await.is_err() {
+                log::error!("The background worker stopped before a queued action could be sent");
+            }
+        });
+    }
+
+    /// Whether the streaming cursor is in the "on" half of its blink.
+    pub fn cursor_visible(&self) -> bool {
+        !self.animate || (self.started.elapsed().as_millis() / CURSOR_BLINK_MS).is_multiple_of(2)
+    }
+
+    /// Drops per-message view state once the history it indexes is gone.
+    pub fn reset_message_view(&mut self) {
+        self.scroll = 0;
+        self.hscroll = 0;
+        self.focused_message = None;
+        self.wrapped_messages.clear();
+        self.debug_messages.clear();
+        self.code_block = None;
+        self.folded_turns.clear();
+        self.read_messages = 0;
+        self.unread_line = None;
+        self.drawn_lines = 0;
+        self.was_at_bottom = true;
+    }
+
+    /// Ghost text completing the input box, when autocomplete is enabled and
+    /// the cursor sits at the end of a single-line input. Worked out again
+    /// only once the input or the prompt history changes.
+    pub fn input_completion(&mut self, config: &Config) -> Option<String> {
+        if !config.autocomplete.enabled {
+            return None;
+        }
+        let lines = self.textarea.lines();
+        let (row, col) = self.textarea.cursor();
+        if lines.len() != 1 || row != 0 || col != lines[0].chars().count() {
+            return None;
+        }
+        let key = (lines[0].clone(), self.prompt_history.len());
+        if self.completion.as_ref().is_none_or(|(completed_for, _)| *completed_for != key) {
+            let completion = autocomplete::complete(&key.0, &self.prompt_history);
+            self.completion = Some((key, completion));
+        }
+        self.completion.as_ref().and_then(|(_, completion)| completion.clone())
+    }
+
+    /// `SharedState::context_usage` for the status line, counted again when
+    /// a message comes or goes and otherwise at most once a second.
+    pub fn context_usage(&mut self, state: &SharedState) -> (usize, usize) {
+        let key = (state.conversation_id.clone(), state.chat_history.len());
+        match &self.context_usage {
+            Some((counted_for, at, usage)) if *counted_for == key && at.elapsed() < CONTEXT_USAGE_TTL => *usage,
+            _ => {
+                let usage = state.context_usage();
+                self.context_usage = Some((key, Instant::now(), usage));
+                usage
+            }
+        }
+    }
+
+    /// Files a prompt of `input` carries: those attached from hint mode, then
+    /// the ones it mentions as `@path`.
+    pub fn attached_paths(&self, input: &str) -> Vec<String> {
+        let mut paths = self.attachments.clone();
+        for path in attachments::mentions(input) {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// `attached_paths` of the input box with the bytes each adds, looked
+    /// up on disk again only once the input or the attachments change.
+    pub fn attached_files(&mut self) -> &[(String, usize)] {
+        let key = (self.textarea.lines().join("\n"), self.attachments.clone());
+        if self.attached_files.as_ref().is_none_or(|(looked_up_for, _)| *looked_up_for != key) {
+            let files = self
+                .attached_paths(&key.0)
+                .into_iter()
+                .map(|path| {
+                    let bytes = std::fs::metadata(hints::expand_path(&path))
+                        .map_or(0, |meta| (meta.len() as usize).min(attachments::MAX_ATTACHMENT_BYTES));
+                    (path, bytes)
+                })
+                .collect();
+            self.attached_files = Some((key, files));
+        }
+        self.attached_files.as_ref().map_or(&[], |(_, files)| files)
+    }
+
+    /// The rows of the attached context panel: files the next prompt
+    /// attaches or mentions, then those sent with earlier ones and the
+    /// retrieved excerpts.
+    pub fn attached_items(&mut self, state: &SharedState) -> Vec<AttachedItem> {
+        let pending = self.attached_files().iter().map(|(path, bytes)| AttachedItem {
+            source: AttachedSource::Pending(path.clone()),
+            label: path.clone(),
+            bytes: *bytes,
+            included: true,
+        });
+        pending.chain(state.attached_context()).collect()
+    }
+
+    /// Shows another conversation scrolled to `scroll`, without the first
+    /// draw jumping to the bottom.
+    pub fn restore_message_view(&mut self, scroll: u16) {
+        self.reset_message_view();
+        self.scroll = scroll;
+        self.drawn_lines = usize::MAX;
+    }
+
+    /// Settles the conversation's scroll offset for `lines` laid out lines
+    /// shown `height` at a time, before they are drawn: jumps to the focused
+    /// message, follows new output as `follow` says and keeps the offset in
+    /// range. Messages count as read once the view reaches the bottom; until
+    /// then the line where the unread ones start is returned, for the "new"
+    /// divider to be inserted at, and Ctrl+U jumps there.
+    pub fn settle_scroll(
+        &mut self,
+        lines: usize,
+        height: usize,
+        focused_start: Option<usize>,
+        message_starts: &[usize],
+        history_len: usize,
+        follow: config::FollowStream,
+    ) -> Option<usize> {
+        let mut max_scroll_offset = max_scroll(lines, height);
+        if let Some(start) = focused_start {
+            self.scroll = start as u16;
+        } else if lines > self.drawn_lines {
+            let follow = match follow {
+                config::FollowStream::Always => true,
+                config::FollowStream::WhenAtBottom => self.was_at_bottom,
+                config::FollowStream::Never => false,
+            };
+            if follow {
+                self.scroll = max_scroll_offset;
+            }
+        }
+        self.drawn_lines = lines;
+
+        self.unread_line = None;
+        self.read_messages = self.read_messages.min(history_len);
+        let mut divider = None;
+        if self.scroll >= max_scroll_offset {
+            self.read_messages = history_len;
+        } else if let Some(&start) = message_starts.get(self.read_messages).filter(|_| self.read_messages > 0) {
+            divider = Some(start);
+            self.unread_line = Some(start as u16);
+            max_scroll_offset = max_scroll(lines + 1, height);
+        }
+
+        self.scroll = self.scroll.min(max_scroll_offset);
+        self.was_at_bottom = self.scroll >= max_scroll_offset;
+        divider
+    }
+
+    /// Moves what is kept per message index past a message taken out of a
+    /// history that has `len` messages left. The focus moves to the message
+    /// that took its place.
+    pub fn forget_message(&mut self, index: usize, len: usize) {
+        let shift = |set: &mut HashSet<usize>| {
+            *set = set.drain().filter(|&i| i != index).map(|i| if i > index { i - 1 } else { i }).collect();
+        };
+        shift(&mut self.wrapped_messages);
+        shift(&mut self.debug_messages);
+        shift(&mut self.folded_turns);
+        self.code_block = self.code_block.filter(|(message, _)| *message != index).map(|(message, block)| (if message > index { message - 1 } else { message }, block));
+        self.focused_message = self.focused_message.map(|focused| focused.min(len)).filter(|_| len > 0).map(|focused| focused.min(len - 1));
+        if self.read_messages > index {
+            self.read_messages -= 1;
+        }
+    }
+
+    /// Replaces the input box with a fresh one holding `content`.
+    pub fn reset_input(&mut self, content: &str) {
+        self.textarea = new_input_textarea(content);
+        self.vim.reset();
+    }
+
+    /// How far PageUp/PageDown move: the visible rows, less one kept for
+    /// context.
+    pub fn page_height(&self) -> u16 {
+        self.conversation_area.height.saturating_sub(1).max(1)
+    }
+
     pub fn scroll_up(&mut self) {
         self.scroll = self.scroll.saturating_sub(1);
     }
@@ -155,3 +1608,370 @@ impl<'a> App<'a> {
         self.model_list_state.select(Some(i));
     }
 }
+
+/// The largest scroll offset that still fills a `height`-line viewport.
+fn max_scroll(lines: usize, height: usize) -> u16 {
+    lines.saturating_sub(height) as u16
+}
+
+/// The frame around the chat input: a rounded box, or just a rule above it
+/// in the compact layout.
+pub fn input_block(compact: bool, theme: &Theme) -> Block<'static> {
+    let block = Block::default().title(t("chat.input_title"));
+    if compact {
+        block.borders(Borders::TOP).border_style(Style::default().fg(theme.muted))
+    } else {
+        block.borders(Borders::ALL).border_type(ratatui::widgets::BorderType::Rounded).border_style(Style::default().fg(theme.border))
+    }
+}
+
+/// Builds the chat input box, pre-filled with `content` and the cursor at its
+/// end. Its frame is set when it is drawn, in the configured theme.
+fn new_input_textarea<'a>(content: &str) -> TextArea<'a> {
+    let mut textarea = if content.is_empty() {
+        TextArea::default()
+    } else {
+        TextArea::new(content.split('\n').map(|l| l.to_string()).collect())
+    };
+    textarea.move_cursor(tui_textarea::CursorMove::Bottom);
+    textarea.move_cursor(tui_textarea::CursorMove::End);
+    textarea.set_placeholder_text(t("chat.placeholder"));
+    textarea
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FollowStream;
+
+    #[test]
+    fn scroll_follows_new_output_and_marks_unread_messages() {
+        let (action_tx, _action_rx) = mpsc::channel(1);
+        let mut app = App::new(action_tx, &Config::default());
+
+        // 30 lines in a 10-line pane: the view follows them to the bottom
+        assert_eq!(app.settle_scroll(30, 10, None, &[0, 10, 20], 3, FollowStream::WhenAtBottom), None);
+        assert_eq!((app.scroll, app.read_messages), (20, 3));
+
+        // Scrolled up when a fourth message arrives: the view stays put and
+        // the new message is marked
+        app.scroll = 5;
+        app.was_at_bottom = false;
+        assert_eq!(app.settle_scroll(40, 10, None, &[0, 10, 20, 30], 4, FollowStream::WhenAtBottom), Some(30));
+        assert_eq!((app.scroll, app.unread_line), (5, Some(30)));
+
+        // A focused message is scrolled to the top
+        app.settle_scroll(40, 10, Some(10), &[0, 10, 20, 30], 4, FollowStream::Always);
+        assert_eq!(app.scroll, 10);
+    }
+
+    #[test]
+    fn chat_search_steps_through_matches_from_the_bottom() {
+        let rows: Vec<mouse::Row> = ["You: disk full?", "Lucius: Disk usage is at 98%,", "the disk is full"]
+            .iter()
+            .map(|text| mouse::Row { text: text.to_string(), continues: false })
+            .collect();
+        let mut search = ChatSearch::new();
+        search.query = "DISK".to_string();
+        search.restart();
+        search.find(&rows);
+        assert_eq!(search.hits, vec![vec![(0, 5, 9)], vec![(1, 8, 12)], vec![(2, 4, 8)]]);
+        assert_eq!(search.current, 2);
+
+        search.step(-1);
+        assert_eq!(search.current, 1);
+        search.step(2);
+        assert_eq!(search.current, 0);
+
+        // A match wrapped over two rows is found, and highlighted on both
+        let rows = [("Lucius: the di", false), ("sk is full", true), ("disk", false)].map(|(text, continues)| mouse::Row { text: text.to_string(), continues });
+        search.find(&rows);
+        assert_eq!(search.hits, vec![vec![(0, 12, 14), (1, 0, 2)], vec![(2, 0, 4)]]);
+    }
+
+    #[test]
+    fn repeated_errors_bump_a_counter_in_place() {
+        let mut state = SharedState::for_tests();
+        state.push_message(ChatMessage::user("hi"));
+        state.push_message(ChatMessage::error("connection refused"));
+        state.excluded_context.insert(ContextSection::Message(1));
+        let first = state.chat_history[1].timestamp;
+
+        // The user's retry is kept, and the error stays where it was
+        state.push_message(ChatMessage::user("hi"));
+        state.push_message(ChatMessage::error("connection refused"));
+        assert_eq!(state.chat_history.len(), 3);
+        assert_eq!(state.chat_history[1].repeats, 1);
+        assert!(state.chat_history[1].timestamp >= first);
+        assert!(state.excluded_context.contains(&ContextSection::Message(1)));
+
+        // Another error, or one after an answer, starts over
+        state.push_message(ChatMessage::error("timed out"));
+        state.push_message(ChatMessage::assistant("back"));
+        state.push_message(ChatMessage::error("timed out"));
+        assert_eq!(state.chat_history.iter().map(|msg| msg.repeats).collect::<Vec<_>>(), vec![0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn lucius_md_edits_wait_for_a_conversation_under_way() {
+        let project = |prompt: &str, model: &str, deny: &[&str]| ProjectContext {
+            profile: ProjectProfile { model: Some(model.into()), temperature: Some(0.2), deny_tools: deny.iter().map(|tool| tool.to_string()).collect(), ..Default::default() },
+            prompt: Some(prompt.into()),
+            files: vec![],
+        };
+        let mut state = SharedState::for_tests();
+        assert!(state.reload_lucius_context(project("Be brief.", "llama3.1:8b", &[])));
+        assert_eq!(state.config.selected_model.as_deref(), Some("llama3.1:8b"));
+
+        // Once the conversation has started, the edit waits for an answer
+        state.push_message(ChatMessage::user("hi"));
+        assert!(!state.reload_lucius_context(project("Be thorough.", "qwen2.5:14b", &["exec"])));
+        assert_eq!(state.conversation_context().as_deref(), Some("Be brief."));
+
+        // `n` keeps this conversation's text, front matter and model
+        state.resolve_context_change(false);
+        assert!(state.context_change.is_none());
+        assert_eq!(state.conversation_context().as_deref(), Some("Be brief."));
+        assert_eq!(state.lucius_context.as_deref(), Some("Be thorough."));
+        assert_eq!(state.config.selected_model.as_deref(), Some("llama3.1:8b"));
+        assert!(state.tool_enabled("exec"));
+        assert_eq!(state.to_session().lucius_profile, state.pinned_profile);
+
+        // New conversations get the edit
+        state.clear_history();
+        assert_eq!(state.conversation_context().as_deref(), Some("Be thorough."));
+        assert!(!state.tool_enabled("exec"));
+
+        // `a` applies it to the conversation under way too
+        state.push_message(ChatMessage::user("hi"));
+        state.reload_lucius_context(project("Be terse.", "qwen2.5:14b", &[]));
+        state.resolve_context_change(true);
+        assert_eq!(state.conversation_context().as_deref(), Some("Be terse."));
+        assert!(state.pinned_profile.is_none() && state.tool_enabled("exec"));
+    }
+
+    #[test]
+    fn only_the_first_answer_asks_for_a_title() {
+        let mut state = SharedState::for_tests();
+        state.tabs.push(Tab { session: state.to_session(), scroll: 0 });
+        let first = state.conversation_id.clone();
+        state.push_message(ChatMessage::user("restart nginx"));
+        assert!(!state.needs_title(&first));
+        state.push_message(ChatMessage::assistant("Done."));
+        assert!(state.needs_title(&first));
+
+        // Still asked for while the conversation sits in another tab
+        state.new_tab(0);
+        assert!(state.needs_title(&first) && !state.needs_title(&state.conversation_id.clone()));
+        assert!(!state.needs_title("unknown"));
+
+        state.set_generated_title(&first, "Restart nginx");
+        assert_eq!(state.tabs[0].session.title.as_deref(), Some("Restart nginx"));
+        assert!(!state.needs_title(&first));
+
+        // A title given while the generated one was being written is kept
+        let second = state.conversation_id.clone();
+        state.push_message(ChatMessage::user("disk usage"));
+        state.push_message(ChatMessage::assistant("98%"));
+        state.rename_session(&second, "Disk alert");
+        state.set_generated_title(&second, "Disk usage");
+        assert_eq!(state.session_title.as_deref(), Some("Disk alert"));
+        state.push_message(ChatMessage::user("and now?"));
+        state.push_message(ChatMessage::assistant("40%"));
+        assert!(!state.needs_title(&second));
+    }
+
+    #[test]
+    fn follow_ups_and_excerpts_belong_to_their_conversation() {
+        let mut state = SharedState::for_tests();
+        state.tabs.push(Tab { session: state.to_session(), scroll: 0 });
+        let first = state.conversation_id.clone();
+        state.push_message(ChatMessage::user("disk full?"));
+        state.followups.insert(first.clone(), vec!["Which mount?".to_string()]);
+        state.retrieved.insert(first.clone(), vec![rag::Excerpt { path: "ops.md".to_string(), line: 3, text: "df -h".to_string() }]);
+        assert_eq!(state.attached_context().len(), 1);
+
+        state.new_tab(0);
+        assert!(state.active_followups().is_empty() && state.attached_context().is_empty());
+        assert_eq!(state.history_len(&first), Some(1));
+        assert_eq!(state.history_len("unknown"), None);
+
+        state.switch_tab(0, 0);
+        assert_eq!(state.active_followups(), ["Which mount?"]);
+        assert_eq!(state.attached_context().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_scheduled_refreshes_give_way_to_a_full_queue() {
+        let (action_tx, mut action_rx) = mpsc::channel(1);
+        let app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        app.dispatch(&mut state, Action::ListDeadLetters);
+
+        app.dispatch(&mut state, Action::RefreshModelsAndStatus { scheduled: true });
+        assert_eq!(state.status_message.as_ref().map(|(msg, _)| msg.as_str()), Some(t("status.queue_full_skipped")));
+        app.dispatch(&mut state, Action::WarmUpModel("llama3".to_string()));
+        app.dispatch(&mut state, Action::RefreshModelsAndStatus { scheduled: false });
+        assert_eq!(state.status_message.as_ref().map(|(msg, _)| msg.as_str()), Some(t("status.queue_full_waiting")));
+
+        assert!(matches!(action_rx.recv().await, Some(Action::ListDeadLetters)));
+        let mut waited = [action_rx.recv().await, action_rx.recv().await];
+        waited.sort_by_key(|action| matches!(action, Some(Action::WarmUpModel(_))));
+        assert!(matches!(waited[0], Some(Action::RefreshModelsAndStatus { scheduled: false })));
+        assert!(matches!(waited[1], Some(Action::WarmUpModel(_))));
+    }
+
+    #[test]
+    fn retrieved_excerpts_are_listed_in_the_context_budget() {
+        let mut state = SharedState::for_tests();
+        state.chat_history.push(ChatMessage::user("Why is the disk full?"));
+        let excerpt = |line| rag::Excerpt { path: "ops.md".to_string(), line, text: "df -h".to_string() };
+        state.retrieved.insert(state.conversation_id.clone(), vec![excerpt(3), excerpt(40)]);
+        let excerpt_rows = |state: &SharedState| {
+            state.context_items().into_iter().filter(|item| matches!(item.section, ContextSection::Excerpt(_))).map(|item| item.included).collect::<Vec<_>>()
+        };
+        assert_eq!(excerpt_rows(&state), [true, true]);
+
+        state.toggle_context(ContextSection::Excerpt(1));
+        assert_eq!(excerpt_rows(&state), [true, false]);
+        assert!(state.skipped_excerpts.contains(&("ops.md".to_string(), 40)));
+        state.toggle_context(ContextSection::Excerpt(0));
+        assert_eq!(excerpt_rows(&state), [false, false]);
+        assert!(state.prompt().system.is_none_or(|system| !system.contains("df -h")));
+
+        state.toggle_context(ContextSection::Excerpt(1));
+        assert_eq!(excerpt_rows(&state), [false, true]);
+        assert!(state.excluded_context.is_empty());
+    }
+
+    #[test]
+    fn removing_a_message_shifts_what_is_kept_by_index() {
+        let mut state = SharedState::for_tests();
+        for text in ["a", "b", "c", "d"] {
+            state.chat_history.push(ChatMessage::user(text));
+        }
+        state.excluded_context.extend([ContextSection::Message(1), ContextSection::Attachment(1, 0), ContextSection::Message(3), ContextSection::Attachment(2, 1), ContextSection::Tools]);
+        assert_eq!(state.remove_message(1).content, "b");
+        assert_eq!(state.chat_history.iter().map(|msg| msg.content.as_str()).collect::<Vec<_>>(), ["a", "c", "d"]);
+        let expected: HashSet<_> = [ContextSection::Message(2), ContextSection::Attachment(1, 1), ContextSection::Tools].into();
+        assert_eq!(state.excluded_context, expected);
+
+        let mut app = App::new(mpsc::channel(1).0, &Config::default());
+        app.wrapped_messages.extend([0, 1, 3]);
+        app.debug_messages.insert(1);
+        app.folded_turns.insert(2);
+        app.code_block = Some((3, 1));
+        app.focused_message = Some(3);
+        app.read_messages = 4;
+        app.forget_message(1, 3);
+        assert_eq!(app.wrapped_messages, [0, 2].into());
+        assert!(app.debug_messages.is_empty());
+        assert_eq!(app.folded_turns, [1].into());
+        assert_eq!((app.code_block, app.focused_message, app.read_messages), (Some((2, 1)), Some(2), 3));
+
+        // Deleting the last message moves the focus up, and an empty
+        // history has none
+        app.focused_message = Some(2);
+        app.forget_message(2, 2);
+        assert_eq!(app.focused_message, Some(1));
+        app.forget_message(0, 0);
+        assert_eq!(app.focused_message, None);
+    }
+
+    #[test]
+    fn new_messages_are_saved_together_a_moment_later() {
+        let mut state = SharedState::for_tests();
+        state.push_message(ChatMessage::user("a"));
+        let first = state.unsaved_since;
+        state.push_message(ChatMessage::error("down"));
+        assert!(first.is_some() && state.unsaved_since == first);
+        state.save_session_if_due();
+        assert!(state.unsaved_since.is_some());
+
+        state.unsaved_since = Some(Instant::now() - SESSION_SAVE_DELAY);
+        state.save_session_if_due();
+        assert!(state.unsaved_since.is_none());
+        // Starting over saves what was still waiting
+        state.push_message(ChatMessage::user("b"));
+        state.clear_history();
+        assert!(state.unsaved_since.is_none());
+    }
+
+    // Tests run from the crate root
+    #[test]
+    fn mentioned_files_are_listed_as_attached() {
+        let state = SharedState::for_tests();
+        let mut app = App::new(mpsc::channel(1).0, &Config::default());
+        app.attachments.push("Cargo.toml".to_string());
+        app.reset_input("compare @Cargo.toml with @README.md");
+        let labels = |app: &mut App| app.attached_items(&state).into_iter().map(|item| item.label).collect::<Vec<_>>();
+        assert_eq!(labels(&mut app), ["Cargo.toml", "README.md"]);
+        assert!(app.attached_files().iter().all(|(_, bytes)| *bytes > 0));
+
+        app.reset_input("just @Cargo.toml");
+        app.attachments.clear();
+        assert_eq!(labels(&mut app), ["Cargo.toml"]);
+    }
+
+    #[test]
+    fn completions_follow_the_input_and_prompt_history() {
+        let config = Config::default();
+        let mut app = App::new(mpsc::channel(1).0, &config);
+        app.prompt_history.push("check disk".to_string());
+        app.reset_input("check d");
+        assert_eq!(app.input_completion(&config).as_deref(), Some("isk"));
+        app.prompt_history.push("check dns".to_string());
+        assert_eq!(app.input_completion(&config).as_deref(), Some("ns"));
+        app.reset_input("look at @Cargo.to");
+        assert_eq!(app.input_completion(&config).as_deref(), Some("ml"));
+        app.textarea.move_cursor(tui_textarea::CursorMove::Head);
+        assert_eq!(app.input_completion(&config), None);
+    }
+
+    #[test]
+    fn deleting_the_selected_model_unsets_it() {
+        let mut state = SharedState::for_tests();
+        let model = |name: &str| crate::llm::Model { name: name.to_string(), size: None };
+        state.models = vec![model("llama3"), model("qwen")];
+        state.loaded_models = vec![crate::llm::ollama::LoadedModel { name: "qwen".to_string(), size: 1, size_vram: 0 }];
+        state.config.selected_model = Some("llama3".to_string());
+
+        assert!(!state.forget_model("qwen"));
+        assert!(state.loaded_models.is_empty() && state.config.selected_model.as_deref() == Some("llama3"));
+        assert!(state.forget_model("llama3"));
+        assert!(state.models.is_empty() && state.config.selected_model.is_none());
+    }
+
+    #[test]
+    fn print_mode_tool_calls_wait_in_the_background_queue() {
+        let mut state = SharedState::for_tests();
+        assert_eq!(state.task_priority(), mcp::Priority::Background);
+        assert_eq!(mcp::queue_key(&TaskType::DOCKER, state.task_priority()), "mcp::tasks::docker::background");
+        state.headless = false;
+        assert_eq!(state.task_priority(), mcp::Priority::Interactive);
+        assert_eq!(mcp::queue_key(&TaskType::SHELL, state.task_priority()), "mcp::tasks::shell");
+    }
+
+    #[test]
+    fn branching_forks_the_conversation_into_a_new_tab() {
+        let mut state = SharedState::for_tests();
+        state.tabs.push(Tab { session: state.to_session(), scroll: 0 });
+        for text in ["disk full?", "98% used", "what uses it?", "/var/log"] {
+            state.push_message(ChatMessage::user(text));
+        }
+        state.session_starred = true;
+        let original = state.conversation_id.clone();
+
+        state.branch_tab(1, 7);
+        assert_eq!((state.tabs.len(), state.active_tab), (2, 1));
+        assert_ne!(state.conversation_id, original);
+        assert_eq!(state.chat_history.len(), 2);
+        assert_eq!(state.chat_history[1].content, "98% used");
+        assert!(!state.session_starred);
+        assert_eq!(state.to_session().branch_of, Some(BranchPoint { session: original.clone(), message: 1 }));
+
+        let first = &state.tabs[0];
+        assert_eq!((first.session.id.as_str(), first.session.messages.len(), first.scroll), (original.as_str(), 4, 7));
+        assert!(first.session.starred && first.session.branch_of.is_none());
+    }
+}
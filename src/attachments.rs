@@ -0,0 +1,166 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::hints;
+
+/// Files larger than this are truncated when attached to a prompt.
+pub const MAX_ATTACHMENT_BYTES: usize = 32 * 1024;
+
+const ATTACHED_PREFIX: &str = "\n\nAttached file `";
+const FAILED_PREFIX: &str = "\n\n(Could not attach `";
+
+/// A file `append_attachments` added to a sent message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attached {
+    pub path: String,
+    /// The bytes of the message its block takes up.
+    pub range: Range<usize>,
+}
+
+/// Appends the contents of each attached file to `input` as fenced blocks, so
+/// the model sees them as part of the user message.
+pub fn append_attachments(input: &str, paths: &[String]) -> String {
+    let mut message = input.to_string();
+    for path in paths {
+        match fs::read(hints::expand_path(path)) {
+            Ok(bytes) => {
+                let truncated = bytes.len() > MAX_ATTACHMENT_BYTES;
+                let content = String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_ATTACHMENT_BYTES)]);
+                message.push_str(&format!("\n\nAttached file `{}`:\n```\n{}\n```", path, content.trim_end()));
+                if truncated {
+                    message.push_str(&format!("\n(truncated to {} bytes)", MAX_ATTACHMENT_BYTES));
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to read attachment {}: {}", path, e);
+                message.push_str(&format!("\n\n(Could not attach `{}`: {})", path, e));
+            }
+        }
+    }
+    message
+}
+
+/// The files attached to `message`, in order. Attachments always come last,
+/// so each block runs to the next one or to the end.
+pub fn attached(message: &str) -> Vec<Attached> {
+    let mut starts: Vec<usize> = message
+        .match_indices(ATTACHED_PREFIX)
+        .chain(message.match_indices(FAILED_PREFIX))
+        .map(|(at, _)| at)
+        .collect();
+    starts.sort_unstable();
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &start)| {
+            let (path, _) = message[start..].strip_prefix(ATTACHED_PREFIX)?.split_once("`:\n")?;
+            let end = starts.get(i + 1).copied().unwrap_or(message.len());
+            Some(Attached { path: path.to_string(), range: start..end })
+        })
+        .collect()
+}
+
+/// `message` without the attachments whose position `remove` picks.
+pub fn strip(message: &str, remove: impl Fn(usize) -> bool) -> String {
+    let mut text = message.to_string();
+    for (ordinal, attached) in attached(message).into_iter().enumerate().rev() {
+        if remove(ordinal) {
+            text.replace_range(attached.range, "");
+        }
+    }
+    text
+}
+
+/// Paths mentioned as `@path` in `input` that name existing files, in the
+/// order they appear. Trailing punctuation is not part of the path.
+pub fn mentions(input: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for word in input.split_whitespace() {
+        let Some(path) = word.strip_prefix('@') else {
+            continue;
+        };
+        let path = path.trim_end_matches([',', '.', ';', ':', ')', '?', '!']);
+        if !path.is_empty() && hints::expand_path(path).is_file() && !paths.iter().any(|p| p == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
+
+/// `input` with its `@path` mentions of `path` left as plain text, so the
+/// file is no longer attached.
+pub fn unmention(input: &str, path: &str) -> String {
+    let mention = format!("@{}", path);
+    let mut text = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(at) = rest.find(&mention) {
+        let after = &rest[at + mention.len()..];
+        text.push_str(&rest[..at]);
+        let starts_word = text.chars().next_back().is_none_or(char::is_whitespace);
+        let ends_word = after.split(char::is_whitespace).next().unwrap_or("").chars().all(|c| matches!(c, ',' | '.' | ';' | ':' | ')' | '?' | '!'));
+        text.push_str(if starts_word && ends_word { path } else { &mention });
+        rest = after;
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Completes the `@path` at the end of `input` with the first matching entry
+/// of its directory, relative to the current one. Directories get a trailing
+/// `/` so completion can go on inside them; hidden entries only match once
+/// a `.` is typed.
+pub fn complete_mention(input: &str) -> Option<String> {
+    let partial = input.rsplit(char::is_whitespace).next()?.strip_prefix('@')?;
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(at) => (&partial[..=at], &partial[at + 1..]),
+        None => ("", partial),
+    };
+    let dir = if dir.is_empty() { Path::new(".").to_path_buf() } else { hints::expand_path(dir) };
+    let mut matches: Vec<(String, bool)> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+            Some((name, is_dir))
+        })
+        .filter(|(name, _)| name.starts_with(prefix) && (prefix.starts_with('.') || !name.starts_with('.')))
+        .collect();
+    matches.sort();
+    let (name, is_dir) = matches.into_iter().next()?;
+    let mut rest = name[prefix.len()..].to_string();
+    if is_dir {
+        rest.push('/');
+    }
+    Some(rest).filter(|rest| !rest.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tests run from the crate root
+    #[test]
+    fn finds_and_completes_file_mentions() {
+        assert_eq!(mentions("compare @Cargo.toml, @missing.rs and me@host"), vec!["Cargo.toml".to_string()]);
+        assert_eq!(complete_mention("look at @Cargo.to"), Some("ml".to_string()));
+        assert_eq!(complete_mention("@sr"), Some("c/".to_string()));
+        assert_eq!(complete_mention("@src/attachm"), Some("ents.rs".to_string()));
+        assert_eq!(complete_mention("@Cargo.toml"), None);
+        assert_eq!(complete_mention("no mention"), None);
+        assert_eq!(unmention("@Cargo.toml vs @Cargo.toml.bak, me@Cargo.toml and @Cargo.toml.", "Cargo.toml"), "Cargo.toml vs @Cargo.toml.bak, me@Cargo.toml and Cargo.toml.");
+    }
+
+    #[test]
+    fn attachments_are_found_and_stripped() {
+        let message = "compare these\n\nAttached file `a.rs`:\n```\nfn a() {}\n```\n\n(Could not attach `gone.rs`: not found)\n\nAttached file `b.rs`:\n```\nfn b() {}\n```";
+        let found = attached(message);
+        assert_eq!(found.iter().map(|a| a.path.as_str()).collect::<Vec<_>>(), ["a.rs", "b.rs"]);
+        assert!(message[found[0].range.clone()].ends_with("```"));
+        let stripped = strip(message, |ordinal| ordinal == 0);
+        assert!(!stripped.contains("fn a()"));
+        assert!(stripped.starts_with("compare these\n\n(Could not attach") && stripped.contains("fn b()"));
+        assert_eq!(strip("no files", |_| true), "no files");
+    }
+}
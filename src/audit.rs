@@ -0,0 +1,56 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::syslog::{self, SyslogConfig};
+use lucius::mcp::{TaskContext, ToolCall};
+
+const AUDIT_FILENAME: &str = "audit.log";
+
+/// One line of the audit trail: a tool call the model or user asked for, and
+/// what became of it.
+#[derive(Serialize)]
+struct AuditEntry<'a> {
+    timestamp: String,
+    /// Set once the task was submitted; declined calls never get one.
+    task_id: Option<&'a str>,
+    tool_call: &'a ToolCall,
+    context: &'a TaskContext,
+}
+
+/// The user name recorded with each task.
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Appends the call and its context as a JSON line to `audit.log` in the
+/// Lucius data directory, and mirrors it to the system log when `[syslog]`
+/// is enabled.
+pub fn record(syslog: &SyslogConfig, task_id: Option<&str>, tool_call: &ToolCall, context: &TaskContext) {
+    syslog::tool_call(syslog, task_id, tool_call, context);
+    let entry = AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        task_id,
+        tool_call,
+        context,
+    };
+    let path = Config::data_dir().join(AUDIT_FILENAME);
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(e) => {
+            log::error!("Failed to serialize audit entry: {}", e);
+            return;
+        }
+    };
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(e) = result {
+        log::error!("Failed to write audit log {}: {}", path.display(), e);
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::attachments;
+use crate::commands::COMMANDS;
+
+/// The `[autocomplete]` section: ghost-text completion in the input box.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AutocompleteConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for AutocompleteConfig {
+    fn default() -> Self {
+        Self { enabled: default_enabled() }
+    }
+}
+
+/// Returns the text to append to `input`: the rest of a slash command name,
+/// of an `@file` mention being typed, or the rest of the current sentence
+/// from the most recent earlier prompt that starts with `input`. Only the
+/// local prompt history is consulted.
+pub fn complete(input: &str, history: &[String]) -> Option<String> {
+    if input.trim().is_empty() || input.contains('\n') {
+        return None;
+    }
+
+    if let Some(typed) = input.strip_prefix('/') {
+        if typed.contains(char::is_whitespace) {
+            return None;
+        }
+        return COMMANDS
+            .iter()
+            .find(|command| command.name.len() > typed.len() && command.name.starts_with(typed))
+            .map(|command| command.name[typed.len()..].to_string());
+    }
+
+    if input.rsplit(char::is_whitespace).next().is_some_and(|word| word.starts_with('@')) {
+        return attachments::complete_mention(input);
+    }
+
+    let rest = history
+        .iter()
+        .rev()
+        .filter(|prompt| prompt.len() > input.len())
+        .find_map(|prompt| prompt.strip_prefix(input))?;
+    // Stop at the end of the sentence, or of the line
+    let end = rest
+        .char_indices()
+        .find(|(at, c)| matches!(c, '.' | '?' | '!') && *at > 0)
+        .map(|(at, c)| at + c.len_utf8())
+        .or_else(|| rest.find('\n'))
+        .unwrap_or(rest.len());
+    Some(rest[..end].to_string()).filter(|suffix| !suffix.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_commands_and_recent_prompts() {
+        assert_eq!(complete("/dead", &[]), Some("letters".to_string()));
+
+        let history = vec![
+            "check disk usage on web-1. Then restart nginx.".to_string(),
+            "check disk usage on db-1".to_string(),
+        ];
+        assert_eq!(complete("check disk", &history), Some(" usage on db-1".to_string()));
+        assert_eq!(complete("check disk usage on w", &history), Some("eb-1.".to_string()));
+        assert_eq!(complete("uptime", &history), None);
+    }
+}
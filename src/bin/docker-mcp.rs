@@ -1,24 +1,399 @@
+use redis::Commands;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::thread;
 use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct DockerTask {
+    #[serde(default)]
+    protocol_version: Option<String>,
     id: String,
     action: String,
     params: serde_json::Value,
+    /// Conversation, user and approval the task was sent with (protocol 1.2).
+    #[serde(default)]
+    context: Option<lucius::mcp::TaskContext>,
+    /// Post-conditions to check the result against (protocol 1.3).
+    #[serde(default)]
+    expect: Vec<String>,
+}
+
+/// Highest task envelope major version this worker understands.
+const SUPPORTED_PROTOCOL_MAJOR: u64 = 1;
+
+/// A worker whose heartbeat is older than this is considered dead.
+const HEARTBEAT_TTL_SECS: u64 = 30;
+
+/// How often the heartbeat thread refreshes it, well within the TTL.
+const HEARTBEAT_EVERY_SECS: u64 = 10;
+
+/// How often (in one-second loop iterations) to look for dead workers.
+const REAP_EVERY_ITERATIONS: u64 = 15;
+
+/// Interactive tasks taken in a row before a waiting background task gets a turn.
+const INTERACTIVE_BURST: u32 = 5;
+
+/// How long an unread result is kept.
+const RESULT_TTL_SECS: u64 = 300;
+
+/// Where to report tasks that finish with nobody watching: background tasks,
+/// and tasks the TUI was closed on (`mcp::notify::<id>`).
+struct Webhook {
+    url: String,
+    /// `slack` (the default), `matrix` or `json`.
+    format: String,
+    /// Named in every message, so one channel can serve several deployments.
+    environment: Option<String>,
+    /// Whether `json` payloads carry the task's result. Off by default: it
+    /// would leave the worker in plaintext even when results are sealed.
+    include_result: bool,
+}
+
+impl Webhook {
+    /// Reads `LUCIUS_WEBHOOK_URL`, `LUCIUS_WEBHOOK_FORMAT`, `LUCIUS_ENVIRONMENT`
+    /// and `LUCIUS_WEBHOOK_RESULTS` (`1` or `true`).
+    fn from_env() -> Option<Self> {
+        let url = std::env::var("LUCIUS_WEBHOOK_URL").ok().filter(|url| !url.is_empty())?;
+        Some(Self {
+            url,
+            format: std::env::var("LUCIUS_WEBHOOK_FORMAT").unwrap_or_else(|_| "slack".to_string()),
+            environment: std::env::var("LUCIUS_ENVIRONMENT").ok().filter(|env| !env.is_empty()),
+            include_result: std::env::var("LUCIUS_WEBHOOK_RESULTS").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    fn send(&self, task: &DockerTask, hostname: &str, result: &str) {
+        let sent = reqwest::blocking::Client::new()
+            .post(&self.url)
+            .json(&self.payload(task, hostname, result))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .and_then(|res| res.error_for_status());
+        match sent {
+            Ok(_) => log(&format!("Webhook notified for task {}", task.id)),
+            Err(e) => log(&format!("Webhook Error for task {}: {}", task.id, e)),
+        }
+    }
+
+    /// The message for `task` in the configured format. Only the id, action,
+    /// worker and outcome are sent unless results were asked for.
+    fn payload(&self, task: &DockerTask, hostname: &str, result: &str) -> serde_json::Value {
+        let failed = lucius::mcp::result_is_error(result);
+        let text = format!(
+            "{} Task {} ({}) {} on {}{}",
+            if failed { "✘" } else { "✔" },
+            task.id,
+            task.action,
+            if failed { "failed" } else { "finished" },
+            hostname,
+            self.environment.as_ref().map(|env| format!(" [{}]", env)).unwrap_or_default(),
+        );
+        match self.format.as_str() {
+            "matrix" => serde_json::json!({ "msgtype": "m.text", "body": text }),
+            "json" => {
+                let mut payload = serde_json::json!({
+                    "task_id": task.id,
+                    "action": task.action,
+                    "worker": hostname,
+                    "environment": self.environment,
+                    "failed": failed,
+                });
+                if self.include_result {
+                    payload["result"] = serde_json::from_str::<serde_json::Value>(result).unwrap_or_else(|_| result.into());
+                }
+                payload
+            }
+            _ => serde_json::json!({ "text": text }),
+        }
+    }
+}
+
+/// Docker actions that only read. Everything else can change what runs on
+/// the host and is refused unless `LUCIUS_ALLOW_PRIVILEGED` is set.
+const READ_ONLY_ACTIONS: &[&str] = &["ps", "logs", "inspect", "images", "top", "port", "diff", "version", "info"];
+
+/// How long a docker command may run before it is killed, short of the
+/// client's `mcp::RESULT_TIMEOUT_SECS` so the result still reaches it.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Whom the worker runs tasks as, and what it is allowed to run.
+struct Privileges {
+    /// Account the worker process runs as.
+    user: String,
+    root: bool,
+    allow_privileged: bool,
+    /// Action -> user to run it as through `sudo -n -u <user>`.
+    run_as: BTreeMap<String, String>,
+}
+
+impl Privileges {
+    /// Detects the worker's user and reads `LUCIUS_ALLOW_PRIVILEGED` (`1` or
+    /// `true`) and `LUCIUS_RUN_AS`, e.g. `restart=deploy,logs=viewer`.
+    fn from_env() -> Self {
+        let user = command_output("id", &["-un"])
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| "unknown".to_string());
+        let root = command_output("id", &["-u"]).map_or(user == "root", |uid| uid.trim() == "0");
+        let allow_privileged = std::env::var("LUCIUS_ALLOW_PRIVILEGED").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"));
+        let run_as = parse_run_as(&std::env::var("LUCIUS_RUN_AS").unwrap_or_default());
+        Self { user, root, allow_privileged, run_as }
+    }
+
+    /// The user `action` runs as.
+    fn effective_user(&self, action: &str) -> &str {
+        self.run_as.get(action).map_or(self.user.as_str(), String::as_str)
+    }
+
+    /// Why `action` may not run here, if it may not. Everything a root worker
+    /// or a root sudo profile runs counts as privileged.
+    fn refusal(&self, action: &str) -> Option<String> {
+        if self.allow_privileged {
+            return None;
+        }
+        let as_root = match self.run_as.get(action) {
+            Some(user) => user == "root",
+            None => self.root,
+        };
+        if as_root {
+            Some(format!("Refusing '{}': it would run as root. Run the worker as a dedicated user or set LUCIUS_ALLOW_PRIVILEGED=1.", action))
+        } else if !READ_ONLY_ACTIONS.contains(&action) {
+            Some(format!("Refusing privileged action '{}'. Set LUCIUS_ALLOW_PRIVILEGED=1 on the worker to allow it.", action))
+        } else {
+            None
+        }
+    }
+
+    /// The command line for `action` with the task's `params`: its `args`
+    /// and then its `container`, through sudo when the action has a profile.
+    fn command(&self, action: &str, params: &serde_json::Value) -> Vec<String> {
+        let mut command = match self.run_as.get(action) {
+            Some(user) => vec!["sudo".to_string(), "-n".to_string(), "-u".to_string(), user.clone()],
+            None => vec![],
+        };
+        command.extend(["docker".to_string(), action.to_string()]);
+        if let Some(args) = params["args"].as_array() {
+            command.extend(args.iter().map(|arg| arg.as_str().map_or_else(|| arg.to_string(), str::to_string)));
+        }
+        if let Some(container) = params["container"].as_str() {
+            command.push(container.to_string());
+        }
+        command
+    }
+}
+
+/// The sudo profiles of `LUCIUS_RUN_AS`: `action=user` pairs separated by
+/// commas. Pairs missing either side are skipped.
+fn parse_run_as(value: &str) -> BTreeMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|profile| {
+            let (action, user) = profile.split_once('=')?;
+            Some((action.trim().to_string(), user.trim().to_string()))
+        })
+        .filter(|(action, user)| !action.is_empty() && !user.is_empty())
+        .collect()
+}
+
+/// The key task payloads are sealed with: base64 in `LUCIUS_TASK_KEY`, or
+/// read from the file named by `LUCIUS_TASK_KEY_FILE`.
+fn payload_key() -> Result<Option<lucius::mcp::PayloadKey>, String> {
+    let key = match std::env::var("LUCIUS_TASK_KEY_FILE") {
+        Ok(path) => Some(std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?),
+        Err(_) => std::env::var("LUCIUS_TASK_KEY").ok().filter(|key| !key.is_empty()),
+    };
+    key.map(|key| lucius::mcp::PayloadKey::from_base64(&key)).transpose()
+}
+
+/// Replaces a sealed task's `details` with the plain JSON, so the task
+/// parses the same as an unencrypted one. A worker with a key only runs
+/// sealed tasks: anyone who can write to Redis could queue a plain one.
+fn open_details(json_str: &str, key: Option<&lucius::mcp::PayloadKey>) -> Result<String, String> {
+    let mut task: serde_json::Value = serde_json::from_str(json_str).map_err(|e| format!("JSON Parse Error: {}", e))?;
+    let Some(key) = key else {
+        if task["encrypted"] == serde_json::Value::Bool(true) {
+            return Err("Task is encrypted, but this worker has no LUCIUS_TASK_KEY.".to_string());
+        }
+        return Ok(json_str.to_string());
+    };
+    if task["encrypted"] != serde_json::Value::Bool(true) {
+        return Err("Refused a plaintext task: this worker has LUCIUS_TASK_KEY set and only runs sealed tasks.".to_string());
+    }
+    let id = task["id"].as_str().ok_or("Encrypted task has no id.")?;
+    let sealed = task["details"].as_str().ok_or("Encrypted task has no sealed details.")?;
+    task["details"] = serde_json::from_str(&key.open(sealed, id)?).map_err(|e| format!("JSON Parse Error in details: {}", e))?;
+    Ok(task.to_string())
 }
 
 fn log(msg: &str) {
     println!("{}", msg);
-    if let Ok(mut file) = OpenOptions::new().create(true).write(true).append(true).open("docker-mcp.log") {
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open("docker-mcp.log") {
         writeln!(file, "{}", msg).ok();
         file.flush().ok();
     }
 }
 
+/// How long a task's "picked up by" marker outlives the task.
+const STATUS_TTL_SECS: u64 = 300;
+
+fn worker_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Rejects tasks from a client speaking a newer major protocol version with a
+/// message the user can act on.
+fn protocol_error(task: &DockerTask, hostname: &str) -> Option<String> {
+    let version = task.protocol_version.as_deref().unwrap_or("0.0");
+    let major = lucius::mcp::protocol_major(version)?;
+    if major > SUPPORTED_PROTOCOL_MAJOR {
+        Some(format!(
+            "Task protocol {} is newer than this worker supports ({}.x). Upgrade the worker on {}.",
+            version, SUPPORTED_PROTOCOL_MAJOR, hostname
+        ))
+    } else {
+        None
+    }
+}
+
+fn process(task: &DockerTask, privileges: &Privileges) -> Result<String, String> {
+    let command = privileges.command(&task.action, &task.params);
+    log(&format!("Running docker action '{}' as {}: {}", task.action, privileges.effective_user(&task.action), command.join(" ")));
+    let mut result = run(&command, COMMAND_TIMEOUT)?;
+    result["run_as"] = privileges.effective_user(&task.action).into();
+    if !task.expect.is_empty() {
+        let verification = lucius::mcp::verify(&result, &task.expect);
+        log(&format!("Verification of task {}: {:?}", task.id, verification.status));
+        result["verification"] = serde_json::to_value(&verification).map_err(|e| e.to_string())?;
+    }
+    Ok(result.to_string())
+}
+
+/// Runs `command`, killing it after `timeout`, and returns its exit code and
+/// output. A command that was killed has neither.
+fn run(command: &[String], timeout: Duration) -> Result<serde_json::Value, String> {
+    let (program, args) = command.split_first().ok_or("Empty command.")?;
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    // Read both pipes while waiting, so a chatty command never blocks on a full one
+    let stdout = child.stdout.take().map(read_all);
+    let stderr = child.stderr.take().map(read_all);
+
+    let started = std::time::Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break Some(status),
+            None if started.elapsed() >= timeout => {
+                child.kill().ok();
+                child.wait().ok();
+                break None;
+            }
+            None => thread::sleep(Duration::from_millis(50)),
+        }
+    };
+    let Some(status) = status else {
+        // Left to finish on their own: whatever the command started may
+        // still hold the pipes open
+        return Ok(serde_json::json!({ "exit_code": null, "error": format!("Killed after {}s.", timeout.as_secs()) }));
+    };
+    Ok(serde_json::json!({
+        "exit_code": status.code(),
+        "stdout": stdout.and_then(|reader| reader.join().ok()).unwrap_or_default(),
+        "stderr": stderr.and_then(|reader| reader.join().ok()).unwrap_or_default(),
+    }))
+}
+
+/// Reads `pipe` to the end on a thread of its own.
+fn read_all(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        pipe.read_to_end(&mut bytes).ok();
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+/// Parks a task that cannot be run on `mcp::dead_letter` with the reason,
+/// instead of dropping it, so it can be inspected and requeued from the TUI.
+fn dead_letter(conn: &mut redis::Connection, queue: &str, payload: &str, error: &str, hostname: &str) {
+    let entry = serde_json::json!({
+        "queue": queue,
+        "payload": payload,
+        "error": error,
+        "worker": hostname,
+        "failed_at": chrono::Utc::now().to_rfc3339(),
+    });
+    match redis::cmd("RPUSH").arg("mcp::dead_letter").arg(entry.to_string()).query::<()>(conn) {
+        Ok(_) => log("Task moved to mcp::dead_letter."),
+        Err(e) => log(&format!("Redis Error writing dead letter: {:?}", e)),
+    }
+}
+
+/// `result` as it should go back: sealed whenever this worker has a key,
+/// since the TUI then refuses plain results.
+fn sealed(key: Option<&lucius::mcp::PayloadKey>, task_id: &str, result: String) -> String {
+    lucius::mcp::seal_result(key, task_id, &result)
+        .unwrap_or_else(|error| serde_json::json!({ "status": "error", "error": error }).to_string())
+}
+
+/// Pushes a result to the list the client is blocking on.
+fn write_result(conn: &mut redis::Connection, task_id: &str, result: &str) {
+    let res_key = format!("mcp::result::{}", task_id);
+    let pushed: redis::RedisResult<()> = redis::pipe()
+        .cmd("RPUSH").arg(&res_key).arg(result).ignore()
+        .cmd("EXPIRE").arg(&res_key).arg(RESULT_TTL_SECS).ignore()
+        .query(conn);
+    match pushed {
+        Ok(_) => log("Result written to Redis."),
+        Err(e) => log(&format!("Redis Error writing result: {:?}", e)),
+    }
+}
+
+/// Publishes this worker's task types and tool schemas to the registry so the
+/// TUI offers its tools to the model without any config changes.
+fn register(conn: &mut redis::Connection, hostname: &str, privileges: &Privileges, encryption: bool) {
+    let info = serde_json::json!({
+        "hostname": hostname,
+        "encryption": encryption,
+        "user": privileges.user,
+        "allow_privileged": privileges.allow_privileged,
+        "run_as": privileges.run_as,
+        "task_types": ["DOCKER"],
+        "tools": [{
+            "name": "docker",
+            "description": "Run a Docker action (ps, logs, inspect, restart) on this host.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "action": { "type": "string" },
+                    "params": {
+                        "type": "object",
+                        "description": "`args`: extra arguments, e.g. [\"--tail\", \"50\"]; `container`: the container to act on."
+                    }
+                },
+                "required": ["action"]
+            },
+            "task_type": "DOCKER"
+        }]
+    });
+    match redis::cmd("HSET").arg(lucius::mcp::WORKER_REGISTRY_KEY).arg(hostname).arg(info.to_string()).query::<()>(conn) {
+        Ok(_) => log(&format!("Registered as {} in mcp::workers", hostname)),
+        Err(e) => log(&format!("Registry Error: {:?}", e)),
+    }
+}
+
 fn main() {
     log("--- PANIC-PROOF RUN ---");
     
@@ -36,31 +411,81 @@ fn main() {
         Err(e) => { log(&format!("FATAL: Connection failed: {}", e)); return; }
     };
 
-    let queue_key = "mcp::tasks::docker";
+    // Interactive tasks keep the original queue name; background ones wait in their own list
+    let queue_keys = ["mcp::tasks::docker".to_string(), "mcp::tasks::docker::background".to_string()];
+    let hostname = worker_hostname();
+    let webhook = Webhook::from_env();
+    let privileges = Privileges::from_env();
+    log(&format!(
+        "Running as {}{}; privileged actions {}",
+        privileges.user,
+        if privileges.root { " (root, use a dedicated user)" } else { "" },
+        if privileges.allow_privileged { "allowed" } else { "refused" },
+    ));
+    for (action, user) in &privileges.run_as {
+        log(&format!("Profile: '{}' runs as {} through sudo", action, user));
+    }
+    // Refusing to start beats serving tasks in the clear with a broken key
+    let key = match payload_key() {
+        Ok(key) => key,
+        Err(e) => { log(&format!("FATAL: Invalid task key: {}", e)); return; }
+    };
+    log(if key.is_some() { "Task payload encryption enabled" } else { "Task payloads are not encrypted (no LUCIUS_TASK_KEY)" });
+    register(&mut conn, &hostname, &privileges, key.is_some());
+    spawn_heartbeat(client.clone(), hostname.clone());
     log("Entering Bulletproof Loop...");
 
+    let processing_keys: Vec<String> = queue_keys.iter().map(|queue| format!("{}::processing::{}", queue, hostname)).collect();
+    // Tasks left over from a previous run on this host were never finished
+    for (queue_key, processing_key) in queue_keys.iter().zip(&processing_keys) {
+        requeue_all(&mut conn, processing_key, queue_key);
+    }
+    let mut iterations: u64 = 0;
+    let mut interactive_streak: u32 = 0;
+    // Publish a snapshot on startup, then whenever the TUI runs /inventory
+    let mut inventory_request: Option<u64> = None;
+
     loop {
-        // 1. Safe Pop
-        let pop_result: redis::RedisResult<Option<String>> = redis::cmd("LPOP").arg(queue_key).query(&mut conn);
+        let requested: u64 = conn.get(lucius::mcp::INVENTORY_REQUEST_KEY).unwrap_or(0);
+        if inventory_request != Some(requested) {
+            publish_inventory(&mut conn, &hostname, requested);
+            inventory_request = Some(requested);
+        }
+        if iterations.is_multiple_of(REAP_EVERY_ITERATIONS) {
+            for queue_key in &queue_keys {
+                reap_dead_workers(&mut conn, queue_key);
+            }
+            reap_dead_registrations(&mut conn);
+            // A peer drops our entry too if our heartbeat lapsed, e.g. while Redis was unreachable
+            let registered: bool = conn.hexists(lucius::mcp::WORKER_REGISTRY_KEY, &hostname).unwrap_or(true);
+            if !registered {
+                register(&mut conn, &hostname, &privileges, key.is_some());
+            }
+        }
+        iterations += 1;
+
+        // Interactive first, but let a background task through after a burst
+        // of interactive ones so scheduled jobs are not starved
+        let order = if interactive_streak >= INTERACTIVE_BURST { [1, 0] } else { [0, 1] };
+        let mut pop_result: redis::RedisResult<Option<(usize, String)>> = Ok(None);
+        for queue in order {
+            pop_result = take_task(&mut conn, &queue_keys[queue], &processing_keys[queue]).map(|task| task.map(|json| (queue, json)));
+            if !matches!(pop_result, Ok(None)) {
+                break;
+            }
+        }
 
         match pop_result {
-            Ok(Some(json_str)) => {
+            Ok(Some((queue, json_str))) => {
+                interactive_streak = if queue == 0 { interactive_streak + 1 } else { 0 };
                 log(&format!(">>> RECEIVED: {}", json_str));
-
-                // 2. Safe Parse
-                match serde_json::from_str::<DockerTask>(&json_str) {
-                    Ok(task) => {
-                        log(&format!("Processing Task ID: {}", task.id));
-                        // Mock processing success
-                        let res_key = format!("mcp::result::{}", task.id);
-                        let _: () = redis::cmd("SET").arg(res_key).arg("Success").query(&mut conn).unwrap_or(());
-                        log("Result written to Redis.");
-                    },
-                    Err(e) => log(&format!("JSON Parse Error: {}", e)),
-                }
+                handle_task(&mut conn, &queue_keys[queue], &json_str, &hostname, &privileges, key.as_ref(), webhook.as_ref());
+                // Acknowledge: the task has a result or is in the dead-letter queue
+                let _: () = redis::cmd("LREM").arg(&processing_keys[queue]).arg(1).arg(&json_str).query(&mut conn).unwrap_or(());
             },
             Ok(None) => {
-                // Queue empty, stay silent or log sparingly
+                // Queues empty, stay silent or log sparingly
+                interactive_streak = 0;
             },
             Err(e) => {
                 log(&format!("Redis Error in Loop: {:?}", e));
@@ -72,10 +497,323 @@ fn main() {
     }
 }
 
+/// 1. Safe Pop: the task stays in our processing list until acknowledged, so a
+///    crash mid-task leaves it for the reaper instead of losing it.
+fn take_task(conn: &mut redis::Connection, queue_key: &str, processing_key: &str) -> redis::RedisResult<Option<String>> {
+    redis::cmd("LMOVE")
+        .arg(queue_key)
+        .arg(processing_key)
+        .arg("LEFT")
+        .arg("RIGHT")
+        .query(conn)
+}
+
+fn handle_task(conn: &mut redis::Connection, queue_key: &str, json_str: &str, hostname: &str, privileges: &Privileges, key: Option<&lucius::mcp::PayloadKey>, webhook: Option<&Webhook>) {
+    // 2. Safe Parse; the dead letter keeps the payload as it arrived, sealed or not
+    let opened = match open_details(json_str, key) {
+        Ok(opened) => opened,
+        Err(error) => {
+            log(&error);
+            dead_letter(conn, queue_key, json_str, &error, hostname);
+            // Answer so the client does not wait out its timeout
+            let id = serde_json::from_str::<serde_json::Value>(json_str).ok().and_then(|task| task["id"].as_str().map(str::to_string));
+            if let Some(id) = id {
+                write_result(conn, &id, &sealed(key, &id, serde_json::json!({ "status": "error", "error": error }).to_string()));
+            }
+            return;
+        }
+    };
+    match serde_json::from_str::<DockerTask>(&opened) {
+        Ok(task) => {
+            if let Some(error) = protocol_error(&task, hostname) {
+                log(&error);
+                write_result(conn, &task.id, &sealed(key, &task.id, serde_json::json!({ "status": "error", "error": error }).to_string()));
+                return;
+            }
+            let context = task.context.as_ref();
+            log(&format!(
+                "Processing Task ID: {} (conversation {}, user {}, approval {}, confirmed as {}, runs as {})",
+                task.id,
+                context.map_or("-", |c| c.conversation_id.as_str()),
+                context.map_or("-", |c| c.user.as_str()),
+                context.and_then(|c| c.approval.as_ref()).map_or("-", |a| a.hash.as_str()),
+                context.and_then(|c| c.run_as.as_deref()).unwrap_or("-"),
+                privileges.effective_user(&task.action),
+            ));
+            // A policy decision, not a failure, so it is not dead-lettered
+            if let Some(error) = privileges.refusal(&task.action) {
+                log(&error);
+                write_result(conn, &task.id, &sealed(key, &task.id, serde_json::json!({ "status": "error", "error": error }).to_string()));
+                return;
+            }
+            // Let the client show which host is running the task
+            let status_key = format!("mcp::status::{}", task.id);
+            let _: () = redis::cmd("SET").arg(&status_key).arg(hostname).arg("EX").arg(STATUS_TTL_SECS).query(conn).unwrap_or(());
+            let result = match std::panic::catch_unwind(|| process(&task, privileges)) {
+                Ok(Ok(result)) => result,
+                Ok(Err(error)) => {
+                    dead_letter(conn, queue_key, json_str, &error, hostname);
+                    serde_json::json!({ "status": "error", "error": error }).to_string()
+                }
+                Err(_) => {
+                    let error = format!("Worker crashed while processing task {}", task.id);
+                    dead_letter(conn, queue_key, json_str, &error, hostname);
+                    serde_json::json!({ "status": "error", "error": error }).to_string()
+                }
+            };
+            write_result(conn, &task.id, &sealed(key, &task.id, result.clone()));
+            if let Some(webhook) = webhook {
+                if wants_notification(conn, queue_key, &task.id) {
+                    webhook.send(&task, hostname, &result);
+                }
+            }
+        },
+        Err(e) => {
+            log(&format!("JSON Parse Error: {}", e));
+            dead_letter(conn, queue_key, json_str, &format!("JSON Parse Error: {}", e), hostname);
+        }
+    }
+}
+
+/// Answers inventory request `request` with a snapshot of this host.
+fn publish_inventory(conn: &mut redis::Connection, hostname: &str, request: u64) {
+    let inventory = lucius::mcp::Inventory {
+        hostname: hostname.to_string(),
+        os: os_name(),
+        containers: containers(),
+        listening_ports: listening_ports(),
+        request,
+        collected_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let json = match serde_json::to_string(&inventory) {
+        Ok(json) => json,
+        Err(e) => { log(&format!("Inventory Error: {}", e)); return; }
+    };
+    match conn.hset::<_, _, _, ()>(lucius::mcp::INVENTORY_KEY, hostname, json) {
+        Ok(_) => log(&format!("Published inventory for request {}", request)),
+        Err(e) => log(&format!("Redis Error writing inventory: {:?}", e)),
+    }
+}
+
+/// Output of a command, or `None` if it could not run or failed.
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `PRETTY_NAME` from os-release, or the bare OS family.
+fn os_name() -> String {
+    std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+                .map(|name| name.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| std::env::consts::OS.to_string())
+}
+
+fn containers() -> Vec<lucius::mcp::Container> {
+    command_output("docker", &["ps", "--format", "{{.Names}}\t{{.Image}}\t{{.Status}}"])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            Some(lucius::mcp::Container {
+                name: fields.next()?.to_string(),
+                image: fields.next()?.to_string(),
+                status: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Listening TCP ports from `ss`, e.g. `LISTEN 0 4096 0.0.0.0:22 0.0.0.0:*`.
+fn listening_ports() -> Vec<u16> {
+    let mut ports: Vec<u16> = command_output("ss", &["-Htln"])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(3)?.rsplit(':').next()?.parse().ok())
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// Whether nobody is waiting for the task in the TUI: it came from the
+/// background queue, or the TUI asked for a notification when it quit.
+fn wants_notification(conn: &mut redis::Connection, queue_key: &str, task_id: &str) -> bool {
+    let notify_key = format!("{}{}", lucius::mcp::NOTIFY_PREFIX, task_id);
+    let requested: i64 = conn.del(&notify_key).unwrap_or(0);
+    queue_key.ends_with("::background") || requested > 0
+}
+
+/// Keeps this worker's heartbeat fresh from its own thread and connection,
+/// so a task running longer than the TTL is not reaped and run a second
+/// time by another worker while it is still going.
+fn spawn_heartbeat(client: redis::Client, hostname: String) {
+    thread::spawn(move || {
+        let mut conn: Option<redis::Connection> = None;
+        loop {
+            if conn.is_none() {
+                conn = client.get_connection().map_err(|e| log(&format!("Redis Error connecting for the heartbeat: {:?}", e))).ok();
+            }
+            // Reconnect on the next beat after a failed write
+            if conn.as_mut().is_some_and(|conn| !heartbeat(conn, &hostname)) {
+                conn = None;
+            }
+            thread::sleep(Duration::from_secs(HEARTBEAT_EVERY_SECS));
+        }
+    });
+}
+
+/// Marks this worker as alive. Its processing list is only reaped once the
+/// key has expired.
+fn heartbeat(conn: &mut redis::Connection, hostname: &str) -> bool {
+    let key = format!("{}{}", lucius::mcp::HEARTBEAT_PREFIX, hostname);
+    match redis::cmd("SET").arg(&key).arg(chrono::Utc::now().to_rfc3339()).arg("EX").arg(HEARTBEAT_TTL_SECS).query::<()>(conn) {
+        Ok(()) => true,
+        Err(e) => {
+            log(&format!("Redis Error writing heartbeat: {:?}", e));
+            false
+        }
+    }
+}
+
+/// Requeues the unacknowledged tasks of workers on `queue_key` whose heartbeat
+/// has expired.
+fn reap_dead_workers(conn: &mut redis::Connection, queue_key: &str) {
+    let pattern = format!("{}::processing::*", queue_key);
+    let keys: Vec<String> = match conn.scan_match::<_, String>(&pattern) {
+        Ok(keys) => keys.collect(),
+        Err(e) => {
+            log(&format!("Redis Error listing processing lists: {:?}", e));
+            return;
+        }
+    };
+    for key in keys {
+        let worker = key.rsplit("::").next().unwrap_or_default();
+        let alive: bool = redis::cmd("EXISTS").arg(format!("{}{}", lucius::mcp::HEARTBEAT_PREFIX, worker)).query(conn).unwrap_or(true);
+        if !alive {
+            log(&format!("Worker {} missed its heartbeat, requeueing its tasks", worker));
+            requeue_all(conn, &key, queue_key);
+        }
+    }
+}
+
+/// Removes the registry entries of workers whose heartbeat has expired, so
+/// the TUI stops offering their tools.
+fn reap_dead_registrations(conn: &mut redis::Connection) {
+    let workers: Vec<String> = match conn.hkeys(lucius::mcp::WORKER_REGISTRY_KEY) {
+        Ok(workers) => workers,
+        Err(e) => {
+            log(&format!("Redis Error listing registered workers: {:?}", e));
+            return;
+        }
+    };
+    for worker in workers {
+        let alive: bool = redis::cmd("EXISTS").arg(format!("{}{}", lucius::mcp::HEARTBEAT_PREFIX, worker)).query(conn).unwrap_or(true);
+        if !alive {
+            log(&format!("Worker {} missed its heartbeat, removing its registration", worker));
+            let _: () = conn.hdel(lucius::mcp::WORKER_REGISTRY_KEY, &worker).unwrap_or(());
+        }
+    }
+}
+
+/// Moves every task in `processing_key` back to the front of `queue_key`.
+fn requeue_all(conn: &mut redis::Connection, processing_key: &str, queue_key: &str) {
+    loop {
+        let moved: redis::RedisResult<Option<String>> = redis::cmd("LMOVE")
+            .arg(processing_key)
+            .arg(queue_key)
+            .arg("RIGHT")
+            .arg("LEFT")
+            .query(conn);
+        match moved {
+            Ok(Some(task)) => log(&format!("Requeued unacknowledged task: {}", task)),
+            Ok(None) => break,
+            Err(e) => {
+                log(&format!("Redis Error requeueing from {}: {:?}", processing_key, e));
+                break;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn privileges(root: bool, allow_privileged: bool, run_as: &str) -> Privileges {
+        Privileges { user: if root { "root" } else { "lucius" }.to_string(), root, allow_privileged, run_as: parse_run_as(run_as) }
+    }
+
+    #[test]
+    fn webhooks_leave_results_out_unless_asked() {
+        let task: DockerTask = serde_json::from_value(serde_json::json!({ "id": "t1", "action": "logs", "params": {} })).unwrap();
+        let mut webhook = Webhook { url: String::new(), format: "json".to_string(), environment: None, include_result: false };
+        let result = r#"{"exit_code": 1, "stdout": "db password: hunter2"}"#;
+        assert_eq!(
+            webhook.payload(&task, "web-1", result),
+            serde_json::json!({ "task_id": "t1", "action": "logs", "worker": "web-1", "environment": null, "failed": true })
+        );
+        webhook.include_result = true;
+        assert_eq!(webhook.payload(&task, "web-1", result)["result"]["stdout"], "db password: hunter2");
+        webhook.format = "slack".to_string();
+        assert_eq!(webhook.payload(&task, "web-1", result), serde_json::json!({ "text": "✘ Task t1 (logs) failed on web-1" }));
+    }
+
+    #[test]
+    fn run_as_profiles_are_parsed_from_pairs() {
+        let run_as = parse_run_as(" restart = deploy ,logs=viewer,broken,=nobody,ps=");
+        assert_eq!(run_as.len(), 2);
+        assert_eq!(run_as.get("restart").map(String::as_str), Some("deploy"));
+        assert_eq!(run_as.get("logs").map(String::as_str), Some("viewer"));
+        assert!(parse_run_as("").is_empty());
+    }
+
     #[test]
-    fn simple_test() {
-        assert_eq!(2 + 2, 4);
+    fn root_and_privileged_actions_are_refused_unless_allowed() {
+        // A root worker refuses everything, even reads
+        let root = privileges(true, false, "");
+        assert!(root.refusal("ps").is_some_and(|refusal| refusal.contains("as root")));
+
+        // A dedicated user runs reads but not actions that change the host
+        let user = privileges(false, false, "");
+        assert_eq!(user.refusal("ps"), None);
+        assert!(user.refusal("restart").is_some_and(|refusal| refusal.contains("privileged action")));
+
+        // A sudo profile for root counts as root; one for another user
+        // takes a root worker off root for that action
+        let profiles = privileges(false, false, "logs=root");
+        assert!(profiles.refusal("logs").is_some_and(|refusal| refusal.contains("as root")));
+        let dropped = privileges(true, false, "ps=viewer");
+        assert_eq!(dropped.refusal("ps"), None);
+        assert_eq!(dropped.command("ps", &serde_json::json!({})), ["sudo", "-n", "-u", "viewer", "docker", "ps"]);
+        assert_eq!(dropped.effective_user("logs"), "root");
+
+        assert_eq!(privileges(true, true, "").refusal("restart"), None);
+        // Actions not known to only read count as privileged
+        assert!(user.refusal("volume").is_some_and(|refusal| refusal.contains("privileged action")));
+    }
+
+    #[test]
+    fn tasks_run_with_their_arguments_under_the_profile() {
+        let params = serde_json::json!({ "args": ["--tail", 50], "container": "web" });
+        assert_eq!(privileges(false, false, "").command("logs", &params), ["docker", "logs", "--tail", "50", "web"]);
+        assert_eq!(privileges(false, false, "logs=viewer").command("logs", &params), ["sudo", "-n", "-u", "viewer", "docker", "logs", "--tail", "50", "web"]);
+    }
+
+    #[test]
+    fn commands_report_their_output_and_are_killed_when_too_slow() {
+        let sh = |script: &str| ["sh".to_string(), "-c".to_string(), script.to_string()];
+        let result = run(&sh("echo up; echo warn >&2; exit 3"), Duration::from_secs(5)).unwrap();
+        assert_eq!(result, serde_json::json!({ "exit_code": 3, "stdout": "up\n", "stderr": "warn\n" }));
+
+        let result = run(&sh("sleep 5"), Duration::from_millis(100)).unwrap();
+        assert_eq!(result["exit_code"], serde_json::Value::Null);
+        assert!(result["error"].as_str().is_some_and(|error| error.starts_with("Killed")));
+        assert!(run(&["no-such-program-lucius".to_string()], Duration::from_secs(1)).is_err());
     }
 }
\ No newline at end of file
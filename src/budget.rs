@@ -0,0 +1,214 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use serde::{Deserialize, Serialize};
+
+use crate::llm::{Prompt, ProviderKind};
+use crate::message::{ChatMessage, Role};
+
+/// One part of what is sent to the model with the next request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContextSection {
+    LuciusMd,
+    /// The conversation template's system prompt.
+    SessionPrompt,
+    /// The tool descriptions appended to the system prompt.
+    Tools,
+    /// The hosts, containers and ports reported by `/inventory`.
+    Inventory,
+    /// The length instruction of the `/verbosity` preset.
+    Verbosity,
+    /// Project excerpts retrieved from the `/index` for the last prompt.
+    Retrieved,
+    /// One of those excerpts, by position; it is left out through
+    /// `skipped_excerpts`, like in the attached context panel.
+    Excerpt(usize),
+    /// A chat history entry, by index.
+    Message(usize),
+    /// A file attached to a chat history entry: the entry's index and the
+    /// file's position among its attachments.
+    Attachment(usize, usize),
+}
+
+/// A row of the context budget panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextItem {
+    pub section: ContextSection,
+    pub label: String,
+    pub tokens: usize,
+    pub included: bool,
+}
+
+/// What a row of the attached context panel stands for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachedSource {
+    /// A file attached from the hints, going out with the next prompt.
+    Pending(String),
+    /// A file attached to a sent prompt, as `ContextSection::Attachment`.
+    Sent(ContextSection),
+    /// A project excerpt retrieved from the `/index`, by path and line.
+    Excerpt(String, usize),
+}
+
+/// A row of the attached context panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachedItem {
+    pub source: AttachedSource,
+    pub label: String,
+    pub bytes: usize,
+    pub included: bool,
+}
+
+/// The `[context]` section: how much of the model's context window requests
+/// may fill, and what gives way when the conversation outgrows it.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ContextConfig {
+    /// Context window in tokens. Defaults to Ollama's default `num_ctx`, or
+    /// to the window of current Anthropic models.
+    #[serde(default)]
+    pub window: Option<usize>,
+    /// Tokens kept free for the answer.
+    #[serde(default = "default_reserve")]
+    pub reserve: usize,
+    #[serde(default)]
+    pub overflow: Overflow,
+}
+
+fn default_reserve() -> usize {
+    1024
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self { window: None, reserve: default_reserve(), overflow: Overflow::default() }
+    }
+}
+
+impl ContextConfig {
+    /// The window requests are fitted to. An Ollama `num_ctx` option is the
+    /// window the server actually uses, so it wins over `window`.
+    pub fn window(&self, provider: ProviderKind, num_ctx: Option<u32>) -> usize {
+        match (provider, num_ctx) {
+            (ProviderKind::Ollama, Some(num_ctx)) => num_ctx as usize,
+            (ProviderKind::Ollama, None) => self.window.unwrap_or(4096),
+            (ProviderKind::Anthropic, _) => self.window.unwrap_or(200_000),
+        }
+    }
+
+    /// Tokens the request itself may take.
+    pub fn budget(&self, provider: ProviderKind, num_ctx: Option<u32>) -> usize {
+        self.window(provider, num_ctx).saturating_sub(self.reserve)
+    }
+}
+
+/// What happens to the oldest turns once a request would not fit.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    /// Leave them out of the request.
+    #[default]
+    Drop,
+    /// Replace them with a summary written by the model.
+    Summarize,
+}
+
+/// Longest message preview shown in the panel.
+const PREVIEW_CHARS: usize = 48;
+
+/// Rough token count: about four characters per token for English text and
+/// code, which is close enough to compare items against each other.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// The first line of `message`, shortened for a one-line panel row.
+pub fn preview(message: &str) -> String {
+    let line = message.lines().next().unwrap_or_default();
+    if line.chars().count() > PREVIEW_CHARS {
+        format!("{}…", line.chars().take(PREVIEW_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Estimated size of `prompt`: its system prompt, messages and tool schemas.
+pub fn prompt_tokens(prompt: &Prompt) -> usize {
+    let system = prompt.system.as_deref().map_or(0, estimate_tokens);
+    let messages: usize = prompt.messages.iter().map(|msg| estimate_tokens(&msg.display_text())).sum();
+    let tools = if prompt.tools.is_empty() { 0 } else { estimate_tokens(&serde_json::to_string(&prompt.tools).unwrap_or_default()) };
+    system + messages + tools
+}
+
+/// Takes the oldest turns (a prompt and everything up to the next one) out of
+/// `prompt` until it fits in `budget` tokens, and returns them. The latest
+/// turn is always kept, even when it does not fit on its own.
+pub fn fit(prompt: &mut Prompt, budget: usize) -> Vec<ChatMessage> {
+    let mut dropped = Vec::new();
+    while prompt_tokens(prompt) > budget {
+        let Some(next_turn) = prompt.messages.iter().skip(1).position(|msg| msg.role == Role::User) else {
+            break;
+        };
+        dropped.extend(prompt.messages.drain(..=next_turn));
+    }
+    dropped
+}
+
+/// The summary sent in place of a conversation's oldest turns. Kept so the
+/// next request that overflows only summarizes the turns dropped since.
+#[derive(Debug, Clone)]
+pub struct EarlierSummary {
+    /// How many of the oldest messages it covers.
+    pub messages: usize,
+    fingerprint: u64,
+    pub text: String,
+}
+
+impl EarlierSummary {
+    pub fn new(messages: &[ChatMessage], text: String) -> Self {
+        Self { messages: messages.len(), fingerprint: fingerprint(messages), text }
+    }
+
+    /// Whether it still covers the start of `earlier`: the same messages,
+    /// none of them removed or edited since.
+    pub fn covers(&self, earlier: &[ChatMessage]) -> bool {
+        earlier.get(..self.messages).is_some_and(|covered| fingerprint(covered) == self.fingerprint)
+    }
+}
+
+fn fingerprint(messages: &[ChatMessage]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for msg in messages {
+        msg.display_text().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_turns_give_way_to_fit_the_budget() {
+        let turn = |n: usize| [ChatMessage::user(format!("question {} {}", n, "x".repeat(40))), ChatMessage::assistant("y".repeat(40))];
+        let mut prompt = Prompt { system: Some("s".repeat(40)), messages: (0..3).flat_map(turn).collect(), ..Prompt::default() };
+        let full = prompt_tokens(&prompt);
+
+        assert!(fit(&mut prompt, full).is_empty());
+        let dropped = fit(&mut prompt, full - 1);
+        assert_eq!(dropped.len(), 2);
+        assert!(dropped[0].content.starts_with("question 0"));
+        assert!(prompt.messages[0].content.starts_with("question 1"));
+
+        // The last turn stays however small the budget
+        assert_eq!(fit(&mut prompt, 0).len(), 2);
+        assert_eq!(prompt.messages.len(), 2);
+    }
+
+    #[test]
+    fn a_summary_covers_the_messages_it_was_written_for() {
+        let earlier = vec![ChatMessage::user("restart nginx"), ChatMessage::assistant("Done."), ChatMessage::user("and redis?")];
+        let summary = EarlierSummary::new(&earlier[..2], "Restarted nginx.".to_string());
+        assert!(summary.covers(&earlier) && summary.covers(&earlier[..2]));
+        assert!(!summary.covers(&earlier[..1]));
+        assert!(!summary.covers(&[ChatMessage::user("restart apache"), ChatMessage::assistant("Done.")]));
+    }
+}
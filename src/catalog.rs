@@ -0,0 +1,29 @@
+/// A model worth pulling, suggested when the Ollama server has none.
+pub struct CatalogEntry {
+    /// The name `ollama pull` takes, with its tag.
+    pub name: &'static str,
+    /// Download size, as the Ollama library lists it.
+    pub size: &'static str,
+    pub good_for: &'static [&'static str],
+}
+
+/// Small to mid-sized models that run on a laptop, smallest first within
+/// each use.
+pub const CATALOG: &[CatalogEntry] = &[
+    CatalogEntry { name: "llama3.2:3b", size: "2.0 GB", good_for: &["chat", "fast"] },
+    CatalogEntry { name: "llama3.1:8b", size: "4.9 GB", good_for: &["chat", "tools"] },
+    CatalogEntry { name: "qwen2.5:7b", size: "4.7 GB", good_for: &["chat", "tools", "multilingual"] },
+    CatalogEntry { name: "mistral:7b", size: "4.1 GB", good_for: &["chat", "tools"] },
+    CatalogEntry { name: "gemma2:2b", size: "1.6 GB", good_for: &["chat", "low memory"] },
+    CatalogEntry { name: "qwen2.5-coder:7b", size: "4.7 GB", good_for: &["code", "tools"] },
+    CatalogEntry { name: "deepseek-r1:8b", size: "4.9 GB", good_for: &["reasoning"] },
+    CatalogEntry { name: "phi3.5:3.8b", size: "2.2 GB", good_for: &["reasoning", "low memory"] },
+    CatalogEntry { name: "nomic-embed-text", size: "274 MB", good_for: &["embeddings", "[rag]"] },
+];
+
+impl CatalogEntry {
+    /// One row of the settings list: name, size and uses in columns.
+    pub fn row(&self) -> String {
+        format!("{:<18} {:>7}  {}", self.name, self.size, self.good_for.join(", "))
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// The `[chaos]` section: the odds, from 0 to 1, of each fault the hidden
+/// `--chaos` flag injects into tool results and streamed answers, so
+/// timeouts, retries and error handling can be exercised. Ignored without
+/// the flag.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChaosConfig {
+    /// Holding a tool result or a chunk back, for up to `max_delay_ms`.
+    #[serde(default = "default_delay")]
+    pub delay: f64,
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// Cutting a tool result or an answer short.
+    #[serde(default = "default_truncate")]
+    pub truncate: f64,
+    /// Failing a tool result or an answer outright.
+    #[serde(default = "default_fail")]
+    pub fail: f64,
+}
+
+fn default_delay() -> f64 {
+    0.2
+}
+
+fn default_max_delay_ms() -> u64 {
+    3000
+}
+
+fn default_truncate() -> f64 {
+    0.1
+}
+
+fn default_fail() -> f64 {
+    0.1
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self { delay: default_delay(), max_delay_ms: default_max_delay_ms(), truncate: default_truncate(), fail: default_fail() }
+    }
+}
+
+impl ChaosConfig {
+    /// Whether the section can be left out of a saved config.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+static CHAOS: OnceLock<Chaos> = OnceLock::new();
+
+/// Turns fault injection on for the rest of the run.
+pub fn enable(config: ChaosConfig) {
+    log::warn!("Chaos mode: injecting delays, truncation and failures ({:?})", config);
+    let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(1, |since| since.as_nanos() as u64);
+    let _ = CHAOS.set(Chaos::new(config, seed));
+}
+
+/// Sometimes waits a while, when chaos mode is on.
+pub async fn delay() {
+    if let Some(delay) = CHAOS.get().and_then(Chaos::delay) {
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Sometimes cuts `text` short, when chaos mode is on.
+pub fn truncate(text: String) -> String {
+    match CHAOS.get() {
+        Some(chaos) => chaos.truncate(text),
+        None => text,
+    }
+}
+
+/// Sometimes fails `what`, when chaos mode is on.
+pub fn fail(what: &str) -> Result<(), String> {
+    CHAOS.get().map_or(Ok(()), |chaos| chaos.fail(what))
+}
+
+/// The faults to inject and the dice they are rolled with.
+struct Chaos {
+    config: ChaosConfig,
+    /// xorshift64 state; never 0.
+    state: Mutex<u64>,
+}
+
+impl Chaos {
+    fn new(config: ChaosConfig, seed: u64) -> Self {
+        Self { config, state: Mutex::new(seed.max(1)) }
+    }
+
+    /// A number in `0..1`.
+    fn next(&self) -> f64 {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn roll(&self, odds: f64) -> bool {
+        self.next() < odds
+    }
+
+    fn delay(&self) -> Option<Duration> {
+        self.roll(self.config.delay).then(|| Duration::from_millis((self.next() * self.config.max_delay_ms as f64) as u64))
+    }
+
+    fn truncate(&self, text: String) -> String {
+        if !self.roll(self.config.truncate) {
+            return text;
+        }
+        let keep = (self.next() * text.chars().count() as f64) as usize;
+        log::warn!("Chaos mode: truncating {} characters to {}", text.chars().count(), keep);
+        text.chars().take(keep).collect()
+    }
+
+    fn fail(&self, what: &str) -> Result<(), String> {
+        if self.roll(self.config.fail) {
+            log::warn!("Chaos mode: failing {}", what);
+            return Err(format!("Chaos mode: injected {} failure.", what));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faults_follow_their_odds() {
+        let never = Chaos::new(ChaosConfig { delay: 0.0, max_delay_ms: 100, truncate: 0.0, fail: 0.0 }, 42);
+        assert_eq!(never.delay(), None);
+        assert_eq!(never.truncate("intact".to_string()), "intact");
+        assert!(never.fail("tool result").is_ok());
+
+        let always = Chaos::new(ChaosConfig { delay: 1.0, max_delay_ms: 100, truncate: 1.0, fail: 1.0 }, 42);
+        assert!(always.delay().is_some_and(|delay| delay <= Duration::from_millis(100)));
+        assert!("intact".starts_with(&always.truncate("intact".to_string())));
+        assert_eq!(always.fail("answer"), Err("Chaos mode: injected answer failure.".to_string()));
+    }
+
+    #[test]
+    fn default_odds_stay_out_of_the_saved_config() {
+        let saved = toml::to_string(&crate::config::Config::default()).unwrap();
+        assert!(!saved.contains("[chaos]"));
+        let config = crate::config::Config { chaos: ChaosConfig { fail: 0.5, ..ChaosConfig::default() }, ..Default::default() };
+        assert!(toml::to_string(&config).unwrap().contains("[chaos]"));
+    }
+}
@@ -1,7 +1,21 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
 use arboard::Clipboard;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use tokio::task;
 
-pub async fn copy_to_clipboard(text: String) {
+use crate::config::ClipboardBackend;
+
+/// Copies `text` through `backend`. OSC 52 goes out to the terminal right
+/// away, between frames; the system clipboard is set on a blocking thread.
+pub fn copy_to_clipboard(text: String, backend: ClipboardBackend) {
+    if resolve(backend) == ClipboardBackend::Osc52 {
+        if let Err(e) = write_osc52(&text) {
+            log::error!("Failed to write the OSC 52 sequence: {}", e);
+        }
+        return;
+    }
     task::spawn_blocking(move || {
         if let Ok(mut clipboard) = Clipboard::new() {
             if let Err(e) = clipboard.set_text(text) {
@@ -11,4 +25,73 @@ pub async fn copy_to_clipboard(text: String) {
             log::error!("Failed to initialize clipboard.");
         }
     });
-}
\ No newline at end of file
+}
+
+/// The backend `Auto` stands for: OSC 52 in an SSH session, where the
+/// system clipboard is on the wrong machine, the system one otherwise.
+pub fn resolve(backend: ClipboardBackend) -> ClipboardBackend {
+    match backend {
+        ClipboardBackend::Auto if std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some() => {
+            ClipboardBackend::Osc52
+        }
+        ClipboardBackend::Auto => ClipboardBackend::System,
+        backend => backend,
+    }
+}
+
+/// Asks the terminal to put `text` on its clipboard.
+pub fn write_osc52(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(osc52(text, std::env::var_os("TMUX").is_some()).as_bytes())?;
+    stdout.flush()
+}
+
+/// The OSC 52 sequence setting the clipboard to `text`. Inside tmux it is
+/// wrapped in a passthrough sequence so it reaches the outer terminal.
+fn osc52(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", BASE64.encode(text));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// Copies `text` through an external clipboard tool (`wl-copy`, `xclip` or
+/// `pbcopy`). Unlike `arboard`, these keep serving the selection after Lucius
+/// exits, which matters for one-shot modes.
+pub fn copy_with_external_tool(text: &str) -> Result<(), String> {
+    let candidates: [(&str, Vec<&str>); 3] = [
+        ("wl-copy", vec![]),
+        ("xclip", vec!["-selection", "clipboard"]),
+        ("pbcopy", vec![]),
+    ];
+    for (program, args) in candidates.iter() {
+        let spawned = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = spawned {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes()).map_err(|e| format!("Failed to write to {}: {}", program, e))?;
+            }
+            let _ = child.wait();
+            return Ok(());
+        }
+    }
+    Err("No clipboard tool found (install wl-clipboard, xclip or pbcopy).".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_encodes_the_text_and_passes_through_tmux() {
+        assert_eq!(osc52("ls -la\n", false), "\x1b]52;c;bHMgLWxhCg==\x07");
+        assert_eq!(osc52("hey", false), "\x1b]52;c;aGV5\x07");
+        assert_eq!(osc52("hey", true), "\x1bPtmux;\x1b\x1b]52;c;aGV5\x07\x1b\\");
+    }
+}
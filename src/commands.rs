@@ -0,0 +1,113 @@
+use crate::i18n;
+
+/// A slash command typed into the chat input instead of a prompt.
+pub struct SlashCommand {
+    pub name: &'static str,
+    pub usage: &'static str,
+    /// Translation key for the description shown on the help screen.
+    pub description: &'static str,
+}
+
+/// Every registered slash command.
+pub const COMMANDS: &[SlashCommand] = &[
+    SlashCommand { name: "help", usage: "/help", description: "cmd.help" },
+    SlashCommand { name: "settings", usage: "/settings", description: "cmd.settings" },
+    SlashCommand { name: "clear", usage: "/clear", description: "cmd.clear" },
+    SlashCommand { name: "new", usage: "/new", description: "cmd.new" },
+    SlashCommand { name: "model", usage: "/model <name>", description: "cmd.model" },
+    SlashCommand { name: "tab", usage: "/tab", description: "cmd.tab" },
+    SlashCommand { name: "closetab", usage: "/closetab", description: "cmd.closetab" },
+    SlashCommand { name: "sessions", usage: "/sessions [#tag|clean [now]]", description: "cmd.sessions" },
+    SlashCommand { name: "tag", usage: "/tag [#tag|-#tag ...]", description: "cmd.tag" },
+    SlashCommand { name: "rename", usage: "/rename <title>", description: "cmd.rename" },
+    SlashCommand { name: "export", usage: "/export script [file]", description: "cmd.export" },
+    SlashCommand { name: "search", usage: "/search [query]", description: "cmd.search" },
+    SlashCommand { name: "context", usage: "/context", description: "cmd.context" },
+    SlashCommand { name: "deadletters", usage: "/deadletters", description: "cmd.deadletters" },
+    SlashCommand { name: "requeue", usage: "/requeue <n|all>", description: "cmd.requeue" },
+    SlashCommand { name: "inventory", usage: "/inventory", description: "cmd.inventory" },
+    SlashCommand { name: "compact", usage: "/compact", description: "cmd.compact" },
+    SlashCommand { name: "verbosity", usage: "/verbosity [brief|normal|detailed]", description: "cmd.verbosity" },
+    SlashCommand { name: "prompt", usage: "/prompt", description: "cmd.prompt" },
+    SlashCommand { name: "set", usage: "/set [key=value ...]", description: "cmd.set" },
+    SlashCommand { name: "index", usage: "/index", description: "cmd.index" },
+    SlashCommand { name: "debug", usage: "/debug prompt", description: "cmd.debug" },
+    SlashCommand { name: "quit", usage: "/quit", description: "cmd.quit" },
+];
+
+/// The result of parsing a line of input that starts with `/`.
+#[derive(Debug, PartialEq)]
+pub enum ParsedCommand<'a> {
+    Known { name: &'static str, args: &'a str },
+    Unknown { typed: &'a str, suggestion: Option<&'static str> },
+}
+
+/// Parses `input` as a slash command. Returns `None` for ordinary prompts,
+/// including ones that merely start with an absolute path like `/etc/hosts`.
+pub fn parse(input: &str) -> Option<ParsedCommand<'_>> {
+    let rest = input.trim().strip_prefix('/')?;
+    let (typed, args) = match rest.split_once(char::is_whitespace) {
+        Some((typed, args)) => (typed, args.trim()),
+        None => (rest, ""),
+    };
+    if typed.is_empty() || typed.contains('/') {
+        return None;
+    }
+
+    if let Some(command) = COMMANDS.iter().find(|c| c.name == typed) {
+        return Some(ParsedCommand::Known { name: command.name, args });
+    }
+    Some(ParsedCommand::Unknown { typed, suggestion: suggest(typed) })
+}
+
+/// Finds the registered command closest to `typed`, if it is close enough to
+/// be a plausible typo.
+pub fn suggest(typed: &str) -> Option<&'static str> {
+    let max_distance = (typed.chars().count() / 2).clamp(1, 2);
+    COMMANDS
+        .iter()
+        .map(|c| (edit_distance(typed, c.name), c.name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between two strings.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// One line per command, for the help screen.
+pub fn help_lines() -> String {
+    COMMANDS
+        .iter()
+        .map(|c| format!("{}: {}", c.usage, i18n::t(c.description)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_closest_command() {
+        assert_eq!(
+            parse("/modl llama3"),
+            Some(ParsedCommand::Unknown { typed: "modl", suggestion: Some("model") })
+        );
+        assert_eq!(parse("/model llama3"), Some(ParsedCommand::Known { name: "model", args: "llama3" }));
+        assert_eq!(parse("/etc/hosts is empty"), None);
+        assert_eq!(suggest("xyzzy"), None);
+    }
+}
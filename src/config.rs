@@ -1,14 +1,287 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::postprocess::PostProcessConfig;
+use crate::templates::ConversationTemplate;
+use crate::i18n::Language;
+use crate::timefmt::TimeConfig;
+use crate::llm::ProviderKind;
+use crate::llm::ollama::KeepAlive;
+use crate::autocomplete::AutocompleteConfig;
+use crate::session::RetentionConfig;
+use crate::theme::ThemeConfig;
+use crate::verbosity::Verbosity;
+use crate::vim::InputConfig;
+use crate::context::ProjectProfile;
+use crate::rag::RagConfig;
+use crate::budget::ContextConfig;
+use crate::options::ModelOptions;
+use crate::sync::SyncConfig;
+use crate::syslog::SyslogConfig;
+use crate::resources::ResourcesConfig;
+use crate::chaos::ChaosConfig;
+use lucius::mcp::EncryptionConfig;
 
 const CONFIG_FILENAME: &str = "lucius_config.toml";
 
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct Config {
+    /// Which LLM backend to talk to.
+    #[serde(default)]
+    pub provider: ProviderKind,
     pub ollama_url: Option<String>,
+    /// How long Ollama keeps the model loaded after a request: a duration
+    /// like "30m", -1 to keep it loaded, 0 to unload it right away.
+    #[serde(default)]
+    pub keep_alive: Option<KeepAlive>,
+    #[serde(default)]
+    pub anthropic: AnthropicConfig,
     pub selected_model: Option<String>,
     pub mcp_redis_host: Option<String>,
+    /// UI language; detected from `LANG` when unset.
+    pub language: Option<Language>,
+    #[serde(default)]
+    pub postprocess: PostProcessConfig,
+    #[serde(default)]
+    pub templates: Vec<ConversationTemplate>,
+    /// Tools pinned to the top of the `Ctrl+P` palette.
+    #[serde(default)]
+    pub favorite_tools: Vec<String>,
+    #[serde(default)]
+    pub followups: FollowupConfig,
+    #[serde(default)]
+    pub time: TimeConfig,
+    #[serde(default)]
+    pub scroll: ScrollConfig,
+    #[serde(default)]
+    pub autocomplete: AutocompleteConfig,
+    #[serde(default)]
+    pub sessions: RetentionConfig,
+    #[serde(default)]
+    pub animation: AnimationConfig,
+    #[serde(default)]
+    pub layout: LayoutConfig,
+    #[serde(default)]
+    pub mouse: MouseConfig,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Answer length preset: `brief`, `normal` or `detailed`.
+    #[serde(default)]
+    pub verbosity: Verbosity,
+    #[serde(default)]
+    pub input: InputConfig,
+    #[serde(default)]
+    pub rag: RagConfig,
+    #[serde(default)]
+    pub requests: RequestsConfig,
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Sampling parameters sent with every chat request.
+    #[serde(default)]
+    pub options: ModelOptions,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub syslog: SyslogConfig,
+    #[serde(default)]
+    pub resources: ResourcesConfig,
+    /// Key shared with the workers for sealed task payloads.
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    /// Fault odds for the hidden `--chaos` flag.
+    #[serde(default, skip_serializing_if = "ChaosConfig::is_default")]
+    pub chaos: ChaosConfig,
+    /// Settings from the front matter of the project's LUCIUS.md. Never
+    /// written to the config file.
+    #[serde(skip)]
+    pub project: ProjectProfile,
+}
+
+/// The `[anthropic]` section, used when `provider = "anthropic"`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnthropicConfig {
+    /// Falls back to `ANTHROPIC_API_KEY` when unset.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Overrides the API base URL, e.g. for a proxy.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+}
+
+fn default_max_tokens() -> u32 {
+    4096
+}
+
+impl Default for AnthropicConfig {
+    fn default() -> Self {
+        Self { api_key: None, url: None, max_tokens: default_max_tokens() }
+    }
+}
+
+impl AnthropicConfig {
+    pub fn api_key(&self) -> String {
+        self.api_key
+            .clone()
+            .filter(|key| !key.is_empty())
+            .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The `[followups]` section: suggested follow-up prompts after each answer.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct FollowupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// A small, cheap model to generate suggestions with. Defaults to the chat model.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// The `[scroll]` section: how the conversation viewport reacts to new output.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct ScrollConfig {
+    #[serde(default)]
+    pub follow_stream: FollowStream,
+}
+
+/// Whether the view moves to new output while a response arrives.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FollowStream {
+    /// Always jump to the bottom.
+    Always,
+    /// Follow only if the view was already at the bottom.
+    #[default]
+    WhenAtBottom,
+    /// Stay where the user is reading.
+    Never,
+}
+
+/// The `[layout]` section: how densely the chat screen is drawn.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct LayoutConfig {
+    /// No borders, padding or banner, one-line separators between messages
+    /// and a single status line; toggled with `/compact`.
+    #[serde(default)]
+    pub compact: bool,
+}
+
+/// The `[animation]` section: how often spinners and the streaming cursor move.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnimationConfig {
+    /// Frames per second; `0` turns animations off, e.g. over slow SSH links.
+    #[serde(default = "default_fps")]
+    pub fps: u32,
+}
+
+fn default_fps() -> u32 {
+    10
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self { fps: default_fps() }
+    }
+}
+
+/// The `[mouse]` section.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MouseConfig {
+    /// Capture the mouse for wheel scrolling and selection in the chat.
+    /// `false` leaves clicks and drags to the terminal's own selection;
+    /// toggled with `Alt+M`.
+    #[serde(default = "default_mouse_enabled")]
+    pub enabled: bool,
+}
+
+fn default_mouse_enabled() -> bool {
+    true
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        Self { enabled: default_mouse_enabled() }
+    }
+}
+
+impl AnimationConfig {
+    /// Time between animation frames, or `None` when animations are off.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        (self.fps > 0).then(|| Duration::from_millis(1000 / u64::from(self.fps.min(60))))
+    }
+}
+
+/// The `[requests]` section: how many answers are generated at once across
+/// all conversations.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RequestsConfig {
+    /// Further prompts wait until one of these finishes.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+fn default_max_concurrent() -> usize {
+    2
+}
+
+impl Default for RequestsConfig {
+    fn default() -> Self {
+        Self { max_concurrent: default_max_concurrent() }
+    }
+}
+
+/// The `[confirmation]` section: how long a tool call waits for y/n.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConfirmationConfig {
+    /// Seconds before an unanswered call is declined; 0 waits forever.
+    #[serde(default = "default_confirmation_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_confirmation_timeout() -> u64 {
+    300
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self { timeout_secs: default_confirmation_timeout() }
+    }
+}
+
+impl ConfirmationConfig {
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_secs > 0).then(|| Duration::from_secs(self.timeout_secs))
+    }
+}
+
+/// The `[clipboard]` section: how copied text reaches the clipboard.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct ClipboardConfig {
+    #[serde(default)]
+    pub backend: ClipboardBackend,
+}
+
+/// Where copies go.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardBackend {
+    /// OSC 52 over SSH, the system clipboard otherwise.
+    #[default]
+    Auto,
+    /// The clipboard of the machine Lucius runs on.
+    System,
+    /// An OSC 52 escape sequence, which the terminal puts on the clipboard
+    /// of the machine it runs on, through SSH and tmux.
+    Osc52,
 }
 
 impl Config {
@@ -31,6 +304,22 @@ impl Config {
         }
     }
 
+    /// Applies a project's LUCIUS.md settings on top of the config: its
+    /// model is selected and the rest is kept for requests.
+    pub fn apply_project(&mut self, project: ProjectProfile) {
+        if project.model.is_some() {
+            self.selected_model = project.model.clone();
+        }
+        self.project = project;
+    }
+
+    /// The parameters to send: `[options]`, then the temperature of the
+    /// conversation's project `profile`, then its `/set` overrides.
+    pub fn model_options(&self, profile: &ProjectProfile, overrides: &ModelOptions) -> ModelOptions {
+        let project = ModelOptions { temperature: profile.temperature, ..ModelOptions::default() };
+        self.options.overlay(&project).overlay(overrides)
+    }
+
     pub fn save(&self) {
         let config_path = Self::get_config_path();
         log::info!("Saving config to: {}", config_path.display());
@@ -40,7 +329,9 @@ impl Config {
         }
     }
 
-    fn get_config_path() -> PathBuf {
+    /// The `lucius` directory under the platform config dir, where the config
+    /// file and other persistent state live. Created on first use.
+    pub fn data_dir() -> PathBuf {
         let mut path = match dirs::config_dir() {
             Some(dir) => dir,
             None => {
@@ -50,6 +341,11 @@ impl Config {
         };
         path.push("lucius"); // Create a lucius subdirectory in config_dir
         fs::create_dir_all(&path).ok(); // Ensure the directory exists
+        path
+    }
+
+    pub fn get_config_path() -> PathBuf {
+        let mut path = Self::data_dir();
         path.push(CONFIG_FILENAME);
         log::info!("Config path resolved to: {}", path.display());
         path
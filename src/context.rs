@@ -1,4 +1,8 @@
 use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
 
 const LUCIUS_CONTEXT_FILENAME: &str = "LUCIUS.md";
 const DEFAULT_LUCIUS_CONTEXT: &str = r#"
@@ -7,32 +11,253 @@ const DEFAULT_LUCIUS_CONTEXT: &str = r#"
 You are Lucius, a helpful AI assistant. Respond concisely and accurately.
 "#;
 
-/// Traverses parent directories starting from the current working directory
-/// to find a file named `LUCIUS.md`.
-/// If found, its content is read and returned as a String.
-/// If not found, a default `LUCIUS.md` is created in the current working directory,
-/// and its content is returned.
-/// Returns None if creation fails or cannot be read.
-pub fn load_lucius_context() -> Option<String> {
-    let mut current_path = std::path::PathBuf::from(std::env::current_dir().ok()?);
-    let initial_cwd = current_path.clone(); // Store initial CWD for default creation
-
-    loop {
-        let potential_path = current_path.join(LUCIUS_CONTEXT_FILENAME);
-        if potential_path.exists() && potential_path.is_file() {
-            return fs::read_to_string(potential_path).ok();
+/// Per-project settings from the YAML front matter at the top of LUCIUS.md:
+///
+/// ```text
+/// ---
+/// model: qwen2.5-coder:14b
+/// temperature: 0.2
+/// tools: [run_shell, read_file]
+/// deny_tools: [remote_exec]
+/// persona: Ops
+/// ---
+/// ```
+///
+/// Only these flat keys are understood; anything else is ignored.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectProfile {
+    /// Model selected when Lucius starts in this project.
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    /// Tools the model may call, unless a template or session sets its own.
+    pub tools: Option<Vec<String>>,
+    /// Tools that never run in this project, whatever a template, session or
+    /// the palette allows.
+    pub deny_tools: Vec<String>,
+    /// Name the assistant goes by in the system prompt.
+    pub persona: Option<String>,
+}
+
+impl ProjectProfile {
+    pub fn forbids(&self, tool: &str) -> bool {
+        self.deny_tools.iter().any(|t| t == tool)
+    }
+
+    /// Layers a more specific file's settings over these: its values win,
+    /// and denied tools add up.
+    fn merge(&mut self, nearer: ProjectProfile) {
+        self.model = nearer.model.or(self.model.take());
+        self.temperature = nearer.temperature.or(self.temperature);
+        self.tools = nearer.tools.or(self.tools.take());
+        self.persona = nearer.persona.or(self.persona.take());
+        for tool in nearer.deny_tools {
+            if !self.deny_tools.contains(&tool) {
+                self.deny_tools.push(tool);
+            }
+        }
+    }
+}
+
+/// The merged LUCIUS.md files that apply to the current directory.
+#[derive(Debug, Default)]
+pub struct ProjectContext {
+    pub profile: ProjectProfile,
+    /// The system prompt text, without front matter.
+    pub prompt: Option<String>,
+    /// The files it was built from, most general first.
+    pub files: Vec<PathBuf>,
+}
+
+/// Loads every LUCIUS.md that applies (see [`lucius_md_paths`]) and merges
+/// them, writing a default one first when none does yet.
+pub fn load_project_context() -> ProjectContext {
+    let mut files = lucius_md_paths();
+    if files.is_empty() {
+        files.extend(create_default_lucius_md());
+    }
+    read_project_context(files)
+}
+
+/// Like `load_project_context` after a LUCIUS.md changed on disk: deleting
+/// the last one leaves no project context rather than a new default file.
+pub fn reload_project_context() -> ProjectContext {
+    read_project_context(lucius_md_paths())
+}
+
+/// Merges `files`, skipping any that cannot be read. The persona, if any,
+/// is introduced at the top of the prompt.
+fn read_project_context(files: Vec<PathBuf>) -> ProjectContext {
+    let contents: Vec<String> = files
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).map_err(|e| log::warn!("Failed to read {}: {}", path.display(), e)).ok())
+        .collect();
+    if contents.is_empty() {
+        return ProjectContext { files, ..ProjectContext::default() };
+    }
+    let (profile, body) = merge_files(&contents);
+    let prompt = match &profile.persona {
+        Some(persona) => format!("Your name is {}.\n\n{}", persona, body),
+        None => body,
+    };
+    ProjectContext { profile, prompt: Some(prompt), files }
+}
+
+/// Merges LUCIUS.md contents, most general first: front matter is layered
+/// and bodies are concatenated in order.
+fn merge_files(contents: &[String]) -> (ProjectProfile, String) {
+    let mut profile = ProjectProfile::default();
+    let mut bodies = Vec::new();
+    for content in contents {
+        let (nearer, body) = split_front_matter(content);
+        profile.merge(nearer);
+        if !body.trim().is_empty() {
+            bodies.push(body.trim());
         }
+    }
+    (profile, bodies.join("\n\n"))
+}
+
+/// Separates a `---` delimited front matter block from the rest of
+/// `content`. Content without one comes back unchanged.
+pub fn split_front_matter(content: &str) -> (ProjectProfile, &str) {
+    let mut profile = ProjectProfile::default();
+    let Some(rest) = content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) else {
+        return (profile, content);
+    };
+    let Some(end) = rest.find("\n---") else {
+        return (profile, content);
+    };
+    let body = rest[end + 4..].split_once('\n').map_or("", |(_, body)| body);
 
-        // If we are at the root, stop
-        if !current_path.pop() {
-            // If we've reached the root and not found, create a default in initial CWD
-            let default_path = initial_cwd.join(LUCIUS_CONTEXT_FILENAME);
-            log::info!("LUCIUS.md not found. Creating default at: {}", default_path.display());
-            if let Err(e) = fs::write(&default_path, DEFAULT_LUCIUS_CONTEXT.trim()) {
-                log::error!("Failed to create default LUCIUS.md at {}: {}", default_path.display(), e);
-                return None; // Return None if creation fails
+    let mut list_key: Option<String> = None;
+    for line in rest[..end].lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        // Block list items belong to the last key without a value
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            let item = unquote(item).to_string();
+            match list_key.as_deref() {
+                Some("tools") => profile.tools.get_or_insert_with(Vec::new).push(item),
+                Some("deny_tools") => profile.deny_tools.push(item),
+                _ => {}
             }
-            return fs::read_to_string(default_path).ok(); // Read and return content of newly created file
+            continue;
         }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), unquote(value.trim()));
+        list_key = value.is_empty().then(|| key.to_string());
+        match key {
+            "model" if !value.is_empty() => profile.model = Some(value.to_string()),
+            "temperature" => profile.temperature = value.parse().ok(),
+            "persona" if !value.is_empty() => profile.persona = Some(value.to_string()),
+            "tools" => profile.tools = Some(inline_list(value)),
+            "deny_tools" => profile.deny_tools = inline_list(value),
+            _ => {}
+        }
+    }
+    (profile, body)
+}
+
+/// The items of a `[a, b]` list, or of a bare `a, b`.
+fn inline_list(value: &str) -> Vec<String> {
+    let items = value.trim_start_matches('[').trim_end_matches(']');
+    items.split(',').map(|item| unquote(item).to_string()).filter(|item| !item.is_empty()).collect()
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+/// Every `LUCIUS.md` that applies to the current directory, most general
+/// first: the global one in the Lucius config directory, then each one from
+/// the filesystem root down to the current directory.
+pub fn lucius_md_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let global = Config::data_dir().join(LUCIUS_CONTEXT_FILENAME);
+    if global.is_file() {
+        paths.push(global);
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        let mut project: Vec<PathBuf> = cwd
+            .ancestors()
+            .map(|dir| dir.join(LUCIUS_CONTEXT_FILENAME))
+            .filter(|path| path.is_file() && !paths.contains(path))
+            .collect();
+        project.reverse();
+        paths.extend(project);
+    }
+    paths
+}
+
+/// Writes the default `LUCIUS.md` to the current directory, for when none
+/// applies yet, and returns its path.
+fn create_default_lucius_md() -> Option<PathBuf> {
+    let default_path = std::env::current_dir().ok()?.join(LUCIUS_CONTEXT_FILENAME);
+    log::info!("LUCIUS.md not found. Creating default at: {}", default_path.display());
+    if let Err(e) = fs::write(&default_path, DEFAULT_LUCIUS_CONTEXT.trim()) {
+        log::error!("Failed to create default LUCIUS.md at {}: {}", default_path.display(), e);
+        return None;
+    }
+    Some(default_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_is_split_from_the_prompt() {
+        let content = "---\nmodel: \"llama3.1:8b\"\ntemperature: 0.2\npersona: Ops\ntools:\n  - run_shell\n  - read_file\n---\n# Notes\nBe terse.\n";
+        let (profile, body) = split_front_matter(content);
+        assert_eq!(profile.model.as_deref(), Some("llama3.1:8b"));
+        assert_eq!(profile.temperature, Some(0.2));
+        assert_eq!(profile.persona.as_deref(), Some("Ops"));
+        assert_eq!(profile.tools, Some(vec!["run_shell".to_string(), "read_file".to_string()]));
+        assert_eq!(body, "# Notes\nBe terse.\n");
+
+        let (profile, _) = split_front_matter("---\ntools: [a, 'b']\ndeny_tools:\n  - remote_exec\n---\n");
+        assert_eq!(profile.tools, Some(vec!["a".to_string(), "b".to_string()]));
+        assert!(profile.forbids("remote_exec") && !profile.forbids("a"));
+        assert_eq!(split_front_matter("# Plain\n---\n"), (ProjectProfile::default(), "# Plain\n---\n"));
+    }
+
+    #[test]
+    fn nearer_files_override_and_bodies_concatenate() {
+        let global = "---\nmodel: llama3\npersona: Lucius\ndeny_tools: [a]\n---\nGlobal notes.\n".to_string();
+        let repo = "---\nmodel: qwen\ndeny_tools: [b]\n---\n".to_string();
+        let subdir = "Subdir notes.\n".to_string();
+        let (profile, body) = merge_files(&[global, repo, subdir]);
+        assert_eq!(profile.model.as_deref(), Some("qwen"));
+        assert_eq!(profile.persona.as_deref(), Some("Lucius"));
+        assert_eq!(profile.deny_tools, ["a", "b"]);
+        assert_eq!(body, "Global notes.\n\nSubdir notes.");
+    }
+
+    #[test]
+    fn missing_files_leave_no_context() {
+        let dir = std::env::temp_dir().join(format!("lucius-context-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (global, project) = (dir.join("global.md"), dir.join("LUCIUS.md"));
+        fs::write(&global, "---\npersona: Ops\n---\nGlobal notes.\n").unwrap();
+        fs::write(&project, "Project notes.\n").unwrap();
+
+        let context = read_project_context(vec![global.clone(), project.clone()]);
+        assert_eq!(context.prompt.as_deref(), Some("Your name is Ops.\n\nGlobal notes.\n\nProject notes."));
+
+        fs::remove_file(&project).unwrap();
+        assert_eq!(read_project_context(vec![global, project]).prompt.as_deref(), Some("Your name is Ops.\n\nGlobal notes."));
+        let none = read_project_context(vec![]);
+        assert!(none.prompt.is_none() && none.files.is_empty() && none.profile == ProjectProfile::default());
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,70 @@
+/// One row of a side-by-side diff.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffRow {
+    /// A line both texts have.
+    Same(String),
+    /// A line of the old text replaced by one of the new, or only on one side.
+    Changed(Option<String>, Option<String>),
+}
+
+/// Lines of `old` and `new` side by side, matched on their longest common
+/// subsequence. Removed and added lines between two matches are paired up
+/// row by row, so an edited line sits next to what it became.
+pub fn side_by_side(old: &str, new: &str) -> Vec<DiffRow> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // lcs[i][j]: common lines of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut rows = Vec::new();
+    let (mut removed, mut added): (Vec<&str>, Vec<&str>) = (vec![], vec![]);
+    let flush = |rows: &mut Vec<DiffRow>, removed: &mut Vec<&str>, added: &mut Vec<&str>| {
+        for k in 0..removed.len().max(added.len()) {
+            rows.push(DiffRow::Changed(removed.get(k).map(|line| line.to_string()), added.get(k).map(|line| line.to_string())));
+        }
+        removed.clear();
+        added.clear();
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            flush(&mut rows, &mut removed, &mut added);
+            rows.push(DiffRow::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            removed.push(old[i]);
+            i += 1;
+        } else {
+            added.push(new[j]);
+            j += 1;
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edited_lines_sit_next_to_what_they_became() {
+        let old = "# Ops\nUse kubectl.\nBe brief.";
+        let new = "# Ops\nUse kubectl and helm.\nBe brief.\nCite runbooks.";
+        assert_eq!(
+            side_by_side(old, new),
+            vec![
+                DiffRow::Same("# Ops".into()),
+                DiffRow::Changed(Some("Use kubectl.".into()), Some("Use kubectl and helm.".into())),
+                DiffRow::Same("Be brief.".into()),
+                DiffRow::Changed(None, Some("Cite runbooks.".into())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+use crossterm::style::Stylize;
+use lucius::mcp;
+
+use crate::config::Config;
+use crate::i18n::{self, t, tf};
+use crate::llm::{self, ollama::OllamaProvider, ProviderKind};
+use crate::session;
+
+/// How long each network check may take before it counts as unreachable.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Collects check results and prints each one as it comes in.
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn print(&mut self, status: Status, name: &str, detail: impl AsRef<str>) {
+        let mark = match status {
+            Status::Ok => "✔".green(),
+            Status::Warn => {
+                self.warnings += 1;
+                "!".yellow()
+            }
+            Status::Fail => {
+                self.failures += 1;
+                "✘".red()
+            }
+        };
+        println!(" {} {:<14} {}", mark, name.bold(), detail.as_ref());
+    }
+
+    fn section(&self, title: &str) {
+        println!("\n{}", title.cyan().bold());
+    }
+
+    /// Prints the totals; any failure makes Lucius exit with status 1.
+    fn finish(&self) -> ExitCode {
+        println!();
+        let (failures, warnings) = (self.failures.to_string(), self.warnings.to_string());
+        let summary = tf("doctor.summary", &[("failures", failures.as_str()), ("warnings", warnings.as_str())]);
+        match (self.failures, self.warnings) {
+            (0, 0) => println!("{}", t("doctor.all_passed").green().bold()),
+            (0, _) => println!("{}", summary.yellow().bold()),
+            _ => println!("{}", summary.red().bold()),
+        }
+        if self.failures > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+    }
+}
+
+/// `lucius doctor`: checks everything Lucius depends on and prints a colored
+/// report, for setting up a machine or attaching to a bug report. Returns
+/// status 1 if any check failed.
+pub async fn run() -> io::Result<ExitCode> {
+    // Read first, so the whole report comes out in the configured language
+    let path = Config::get_config_path();
+    let (config, config_check) = read_config(&path);
+    i18n::set_language(config.language.unwrap_or_else(i18n::detect_language));
+
+    let mut report = Report::default();
+    println!("{} {}", t("doctor.title").bold(), env!("CARGO_PKG_VERSION"));
+
+    report.section(t("doctor.terminal"));
+    check_terminal(&mut report);
+
+    report.section(t("doctor.configuration"));
+    let (status, detail) = config_check;
+    report.print(status, t("doctor.config_file"), detail);
+    match &config.selected_model {
+        Some(model) => report.print(Status::Ok, t("doctor.model"), model),
+        None => report.print(Status::Warn, t("doctor.model"), t("doctor.model_none")),
+    }
+
+    report.section(t("doctor.provider"));
+    check_provider(&mut report, &config).await;
+
+    report.section("MCP");
+    check_mcp(&mut report, &config).await;
+
+    report.section(t("doctor.clipboard"));
+    check_clipboard(&mut report);
+
+    report.section(t("doctor.state_dirs"));
+    check_writable(&mut report, t("doctor.config_dir"), &Config::data_dir());
+    check_writable(&mut report, t("doctor.sessions"), &session::sessions_dir());
+    check_log(&mut report);
+
+    Ok(report.finish())
+}
+
+fn check_terminal(report: &mut Report) {
+    if io::stdout().is_terminal() {
+        report.print(Status::Ok, "tty", t("doctor.tty"));
+    } else {
+        report.print(Status::Warn, "tty", t("doctor.no_tty"));
+    }
+    match crossterm::terminal::size() {
+        Ok((cols, rows)) if cols >= 80 && rows >= 24 => report.print(Status::Ok, t("doctor.size"), format!("{}x{}", cols, rows)),
+        Ok((cols, rows)) => report.print(Status::Warn, t("doctor.size"), tf("doctor.size_small", &[("size", format!("{}x{}", cols, rows).as_str())])),
+        Err(e) => report.print(Status::Warn, t("doctor.size"), tf("doctor.size_unknown", &[("error", e.to_string().as_str())])),
+    }
+    match std::env::var("TERM") {
+        Ok(term) if term != "dumb" => report.print(Status::Ok, "TERM", term),
+        Ok(term) => report.print(Status::Fail, "TERM", tf("doctor.term_dumb", &[("term", term.as_str())])),
+        Err(_) => report.print(Status::Warn, "TERM", t("doctor.term_unset")),
+    }
+    let truecolor = std::env::var("COLORTERM").is_ok_and(|c| c == "truecolor" || c == "24bit");
+    if truecolor {
+        report.print(Status::Ok, t("doctor.colors"), "truecolor");
+    } else {
+        report.print(Status::Warn, t("doctor.colors"), t("doctor.colors_approximated"));
+    }
+}
+
+/// Parses the config file at `path` strictly, unlike `Config::load` which
+/// falls back to defaults. Returns the config the remaining checks should
+/// use, with how reading it went.
+fn read_config(path: &Path) -> (Config, (Status, String)) {
+    let shown = path.display().to_string();
+    match fs::read_to_string(path) {
+        Ok(content) => match toml::from_str::<Config>(&content) {
+            Ok(config) => (config, (Status::Ok, shown)),
+            Err(e) => (Config::default(), (Status::Fail, tf("doctor.config_invalid", &[("path", shown.as_str()), ("error", e.to_string().as_str())]))),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (Config::default(), (Status::Warn, tf("doctor.config_missing", &[("path", shown.as_str())]))),
+        Err(e) => (Config::default(), (Status::Fail, format!("{}: {}", shown, e))),
+    }
+}
+
+async fn check_provider(report: &mut Report, config: &Config) {
+    report.print(Status::Ok, t("doctor.provider_name"), config.provider.label());
+    match config.provider {
+        ProviderKind::Ollama => {
+            let url = config.ollama_url.clone().unwrap_or_default();
+            if url.is_empty() {
+                report.print(Status::Fail, "ollama", t("doctor.ollama_unset"));
+                return;
+            }
+            let ollama = OllamaProvider::new(url.clone());
+            match tokio::time::timeout(CHECK_TIMEOUT, ollama.version()).await {
+                Ok(Ok(version)) => report.print(Status::Ok, "ollama", tf("doctor.ollama_version", &[("url", url.as_str()), ("version", version.as_str())])),
+                Ok(Err(e)) => report.print(Status::Fail, "ollama", format!("{}: {}", url, e)),
+                Err(_) => report.print(Status::Fail, "ollama", format!("{}: {}", url, t("doctor.timed_out"))),
+            }
+        }
+        ProviderKind::Anthropic => {
+            if config.anthropic.api_key().is_empty() {
+                report.print(Status::Fail, t("doctor.api_key"), t("doctor.api_key_unset"));
+                return;
+            }
+            let provider = llm::provider(config);
+            match tokio::time::timeout(CHECK_TIMEOUT, provider.ping()).await {
+                Ok(true) => report.print(Status::Ok, "anthropic", t("doctor.anthropic_ok")),
+                Ok(false) => report.print(Status::Fail, "anthropic", t("doctor.anthropic_failed")),
+                Err(_) => report.print(Status::Fail, "anthropic", t("doctor.timed_out")),
+            }
+        }
+    }
+}
+
+/// Connects to Redis like the TUI does and checks that registered workers
+/// are alive.
+async fn check_mcp(report: &mut Report, config: &Config) {
+    match config.encryption.load() {
+        Ok(Some(_)) => report.print(Status::Ok, t("doctor.encryption"), t("doctor.encryption_on")),
+        Ok(None) => report.print(Status::Ok, t("doctor.encryption"), t("doctor.encryption_off")),
+        Err(e) => report.print(Status::Fail, t("doctor.encryption"), tf("doctor.encryption_failed", &[("error", e.to_string().as_str())])),
+    }
+    let host = config.mcp_redis_host.clone().unwrap_or_else(|| "127.0.0.1".to_string());
+    let connected = match redis::Client::open(format!("redis://{}/", host)) {
+        Ok(client) => tokio::time::timeout(CHECK_TIMEOUT, client.get_multiplexed_async_connection())
+            .await
+            .map_err(|_| "timed out".to_string())
+            .and_then(|conn| conn.map_err(|e| e.to_string())),
+        Err(e) => Err(e.to_string()),
+    };
+    let mut conn = match connected {
+        Ok(conn) => {
+            report.print(Status::Ok, "redis", &host);
+            conn
+        }
+        Err(e) => {
+            report.print(Status::Warn, "redis", tf("doctor.redis_failed", &[("host", host.as_str()), ("error", e.as_str())]));
+            return;
+        }
+    };
+    let workers = match mcp::fetch_workers(&mut conn).await {
+        Ok(workers) => workers,
+        Err(e) => {
+            report.print(Status::Fail, t("doctor.workers"), e);
+            return;
+        }
+    };
+    if workers.is_empty() {
+        report.print(Status::Warn, t("doctor.workers"), t("doctor.workers_none"));
+    }
+    for worker in &workers {
+        let host = [("host", worker.hostname.as_str())];
+        let mut tools = tf("doctor.worker_tools", &[("count", worker.tools.len().to_string().as_str())]);
+        if let Some(user) = &worker.user {
+            tools.push_str(&tf("doctor.worker_user", &[("user", user.as_str())]));
+        }
+        if (config.encryption.key.is_some() || config.encryption.key_command.is_some()) && !worker.encryption {
+            report.print(Status::Warn, t("doctor.worker"), tf("doctor.worker_no_key", &host));
+        }
+        if worker.user.as_deref() == Some("root") {
+            report.print(Status::Warn, t("doctor.worker"), tf("doctor.worker_root", &host));
+        }
+        match mcp::worker_alive(&mut conn, &worker.hostname).await {
+            Ok(true) => report.print(Status::Ok, t("doctor.worker"), tf("doctor.worker_alive", &[host[0], ("tools", tools.as_str())])),
+            Ok(false) => report.print(Status::Warn, t("doctor.worker"), tf("doctor.worker_silent", &host)),
+            Err(e) => report.print(Status::Fail, t("doctor.worker"), e),
+        }
+    }
+    match mcp::dead_letter_count(&mut conn).await {
+        Ok(0) => report.print(Status::Ok, t("doctor.dead_letters"), t("doctor.dead_letters_none")),
+        Ok(count) => report.print(Status::Warn, t("doctor.dead_letters"), tf("doctor.dead_letters_waiting", &[("count", count.to_string().as_str())])),
+        Err(e) => report.print(Status::Fail, t("doctor.dead_letters"), e),
+    }
+}
+
+fn check_clipboard(report: &mut Report) {
+    match arboard::Clipboard::new() {
+        Ok(_) => report.print(Status::Ok, "arboard", t("doctor.clipboard_ok")),
+        Err(e) => report.print(Status::Warn, "arboard", tf("doctor.clipboard_failed", &[("error", e.to_string().as_str())])),
+    }
+    let tools = ["wl-copy", "xclip", "pbcopy"];
+    match tools.iter().find(|tool| in_path(tool)) {
+        Some(tool) => report.print(Status::Ok, t("doctor.external"), *tool),
+        None => report.print(Status::Warn, t("doctor.external"), t("doctor.external_none")),
+    }
+}
+
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+fn check_writable(report: &mut Report, name: &str, dir: &Path) {
+    let probe = dir.join(".lucius-doctor");
+    match fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe)) {
+        Ok(()) => report.print(Status::Ok, name, dir.display().to_string()),
+        Err(e) => report.print(Status::Fail, name, not_writable(dir, &e)),
+    }
+}
+
+/// The log goes to `lucius.log` in the directory Lucius is started from,
+/// so this checks the file itself rather than a directory. Lucius runs on
+/// without a log, hence only a warning.
+fn check_log(report: &mut Report) {
+    let path = std::env::current_dir().unwrap_or_default().join(crate::LOG_FILE);
+    match fs::OpenOptions::new().append(true).create(true).open(&path) {
+        Ok(_) => report.print(Status::Ok, t("doctor.log_file"), path.display().to_string()),
+        Err(e) => report.print(Status::Warn, t("doctor.log_file"), not_writable(&path, &e)),
+    }
+}
+
+fn not_writable(path: &Path, error: &io::Error) -> String {
+    tf("doctor.not_writable", &[("path", path.display().to_string().as_str()), ("error", error.to_string().as_str())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_failures_fail_the_report() {
+        let mut report = Report::default();
+        report.print(Status::Ok, "a", "fine");
+        report.print(Status::Warn, "b", "meh");
+        assert_eq!((report.failures, report.warnings), (0, 1));
+        assert_eq!(report.finish(), ExitCode::SUCCESS);
+        report.print(Status::Fail, "c", "broken");
+        assert_eq!(report.finish(), ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn the_config_file_is_read_strictly() {
+        let dir = std::env::temp_dir().join(format!("lucius-doctor-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+
+        let (config, (status, _)) = read_config(&path);
+        assert!(status == Status::Warn && config.selected_model.is_none());
+        fs::write(&path, "selected_model = \"llama3\"\n").unwrap();
+        let (config, (status, detail)) = read_config(&path);
+        assert!(status == Status::Ok && detail == path.display().to_string());
+        assert_eq!(config.selected_model.as_deref(), Some("llama3"));
+        fs::write(&path, "selected_model = [\n").unwrap();
+        let (config, (status, detail)) = read_config(&path);
+        assert!(status == Status::Fail && detail.contains("does not parse"));
+        assert!(config.selected_model.is_none());
+
+        let mut report = Report::default();
+        check_writable(&mut report, "dir", &dir);
+        check_writable(&mut report, "gone", &dir.join("missing"));
+        assert_eq!((report.failures, report.warnings), (1, 0));
+        assert!(!dir.join(".lucius-doctor").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::Local;
+use lucius::mcp;
+
+use crate::hints::shell_quote;
+use crate::message::{ChatMessage, Role};
+use crate::session;
+
+/// The tools whose results `/export script` replays.
+const SCRIPT_TOOLS: &[&str] = &["exec", "remote_exec"];
+
+/// The commands that ran in a conversation as a shell script, in the order
+/// they ran. Only calls that produced a result are included, so declined
+/// ones are left out; `remote_exec` calls become `ssh` invocations. Each
+/// command is preceded by the prompt that led to it and, if it failed, a
+/// note saying so.
+pub fn shell_script(history: &[ChatMessage], title: Option<&str>, tags: &[String]) -> String {
+    let mut script = String::from("#!/bin/sh\n");
+    // A title is one comment line, whatever line breaks it was given
+    let title = title.map(|title| title.split(['\n', '\r']).map(str::trim).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" "));
+    script.push_str(&format!("# {}\n", title.as_deref().unwrap_or("Lucius session")));
+    if !tags.is_empty() {
+        script.push_str(&format!("# Tags: {}\n", session::format_tags(tags)));
+    }
+
+    let mut prompt = None;
+    let mut commented_prompt = None;
+    for msg in history {
+        if msg.role == Role::User {
+            prompt = Some(msg.content.as_str());
+            continue;
+        }
+        let call = match (&msg.role, &msg.tool_call) {
+            (Role::ToolResult, Some(call)) if SCRIPT_TOOLS.contains(&call.tool.as_str()) => call,
+            _ => continue,
+        };
+        let command = match call.params["command"].as_str() {
+            Some(command) => command,
+            None => continue,
+        };
+
+        script.push('\n');
+        if let Some(text) = prompt.filter(|_| prompt != commented_prompt) {
+            for line in text.lines() {
+                script.push_str(format!("# > {}", line).trim_end());
+                script.push('\n');
+            }
+            commented_prompt = prompt;
+        }
+        let ran_at = msg.timestamp.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+        match call.params["host"].as_str() {
+            Some(host) => script.push_str(&format!("# {} on {}\n", ran_at, host)),
+            None => script.push_str(&format!("# {}\n", ran_at)),
+        }
+        if mcp::result_is_error(&msg.content) {
+            script.push_str("# This failed in the session.\n");
+        }
+        match call.params["host"].as_str() {
+            Some(host) if call.tool == "remote_exec" => script.push_str(&format!("ssh {} {}\n", shell_quote(host), shell_quote(command))),
+            _ => script.push_str(&format!("{}\n", command)),
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucius::mcp::ToolCall;
+
+    fn call(tool: &str, params: serde_json::Value) -> ToolCall {
+        ToolCall { tool: tool.to_string(), params, expect: vec![] }
+    }
+
+    #[test]
+    fn executed_commands_become_script_lines() {
+        let disk = call("exec", serde_json::json!({ "command": "df -h" }));
+        let declined = call("exec", serde_json::json!({ "command": "rm -rf /tmp/cache" }));
+        let remote = call("remote_exec", serde_json::json!({ "host": "root@db1", "command": "systemctl restart postgresql" }));
+        let history = vec![
+            ChatMessage::user("Why is db1 slow?"),
+            ChatMessage::tool_call(&disk),
+            ChatMessage::tool_result(&disk, r#"{"exit_code": 0}"#),
+            ChatMessage::tool_call(&declined),
+            ChatMessage::tool_error(&declined, "Declined"),
+            ChatMessage::tool_call(&remote),
+            ChatMessage::tool_result(&remote, r#"{"exit_code": 1}"#),
+        ];
+        let script = shell_script(&history, Some("db1"), &["postgres".to_string()]);
+        let lines: Vec<&str> = script.lines().filter(|line| !line.starts_with("# 20")).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "#!/bin/sh",
+                "# db1",
+                "# Tags: #postgres",
+                "",
+                "# > Why is db1 slow?",
+                "df -h",
+                "",
+                "# This failed in the session.",
+                "ssh 'root@db1' 'systemctl restart postgresql'",
+            ]
+        );
+    }
+
+    #[test]
+    fn titles_cannot_add_lines_to_the_script() {
+        let script = shell_script(&[], Some("db1\nrm -rf ~\r\n"), &[]);
+        assert_eq!(script, "#!/bin/sh\n# db1 rm -rf ~\n");
+    }
+}
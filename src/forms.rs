@@ -0,0 +1,170 @@
+use serde_json::{Map, Value};
+
+/// One parameter of a tool call, as shown in the confirmation form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    pub name: String,
+    /// The schema `type`, or "any" for parameters the schema doesn't list.
+    pub kind: String,
+    pub required: bool,
+    pub description: Option<String>,
+    /// Strings are shown as-is, everything else as compact JSON.
+    pub value: String,
+}
+
+/// The confirmation modal's view of a tool call's params, generated from the
+/// tool's JSON schema so each field can be checked and edited on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolForm {
+    pub fields: Vec<FormField>,
+    pub selected: usize,
+    /// Whether keystrokes currently go to the selected field.
+    pub editing: bool,
+    /// Why the last attempt to confirm was rejected.
+    pub error: Option<String>,
+}
+
+impl ToolForm {
+    /// Builds a form for `params` when `schema` describes an object with
+    /// properties; plain or missing schemas keep the raw JSON view.
+    pub fn from_schema(schema: &Value, params: &Value) -> Option<ToolForm> {
+        let properties = schema.get("properties")?.as_object()?;
+        if properties.is_empty() {
+            return None;
+        }
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        let mut fields: Vec<FormField> = properties
+            .iter()
+            .map(|(name, property)| FormField {
+                name: name.clone(),
+                kind: property["type"].as_str().unwrap_or("any").to_string(),
+                required: required.contains(&name.as_str()),
+                description: property["description"].as_str().map(str::to_string),
+                value: params.get(name).map(display_value).unwrap_or_default(),
+            })
+            .collect();
+        // Keep anything the model sent that the schema doesn't know about
+        if let Some(extra) = params.as_object() {
+            for (name, value) in extra.iter().filter(|(name, _)| !properties.contains_key(*name)) {
+                fields.push(FormField {
+                    name: name.clone(),
+                    kind: "any".to_string(),
+                    required: false,
+                    description: None,
+                    value: display_value(value),
+                });
+            }
+        }
+        Some(ToolForm { fields, selected: 0, editing: false, error: None })
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.fields.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.fields.len() - 1) % self.fields.len();
+    }
+
+    pub fn selected_value(&mut self) -> &mut String {
+        &mut self.fields[self.selected].value
+    }
+
+    /// Converts the fields back to params, typed according to the schema.
+    /// Empty optional fields are left out.
+    pub fn to_params(&self) -> Result<Value, String> {
+        let mut params = Map::new();
+        for field in &self.fields {
+            if field.value.trim().is_empty() {
+                if field.required {
+                    return Err(format!("'{}' is required", field.name));
+                }
+                continue;
+            }
+            params.insert(field.name.clone(), parse_value(field)?);
+        }
+        Ok(Value::Object(params))
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_value(field: &FormField) -> Result<Value, String> {
+    let text = field.value.trim();
+    let invalid = || format!("'{}' must be {} {}", field.name, article(&field.kind), field.kind);
+    match field.kind.as_str() {
+        "string" => Ok(Value::String(field.value.clone())),
+        "integer" => text.parse::<i64>().map(Value::from).map_err(|_| invalid()),
+        "number" => text.parse::<f64>().map(Value::from).map_err(|_| invalid()),
+        "boolean" => text.parse::<bool>().map(Value::from).map_err(|_| invalid()),
+        "array" | "object" => serde_json::from_str(text).map_err(|_| invalid()),
+        // Untyped: take valid JSON as such, anything else as a string
+        _ => Ok(serde_json::from_str(text).unwrap_or_else(|_| Value::String(field.value.clone()))),
+    }
+}
+
+fn article(kind: &str) -> &'static str {
+    if kind.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": {"type": "string", "description": "Shell command"},
+                "retries": {"type": "integer"},
+                "verbose": {"type": "boolean"},
+            },
+            "required": ["command"],
+        })
+    }
+
+    #[test]
+    fn fields_come_from_the_schema_and_keep_unknown_params() {
+        let form = ToolForm::from_schema(&schema(), &json!({"command": "uptime", "retries": 2, "host": "db01"})).unwrap();
+        let fields: Vec<_> = form.fields.iter().map(|f| (f.name.as_str(), f.kind.as_str(), f.required, f.value.as_str())).collect();
+        assert_eq!(
+            fields,
+            [
+                ("command", "string", true, "uptime"),
+                ("retries", "integer", false, "2"),
+                ("verbose", "boolean", false, ""),
+                ("host", "any", false, "db01"),
+            ]
+        );
+        assert_eq!(form.fields[0].description.as_deref(), Some("Shell command"));
+
+        assert_eq!(ToolForm::from_schema(&json!({"type": "object"}), &json!({})), None);
+        assert_eq!(ToolForm::from_schema(&json!({"properties": {}}), &json!({})), None);
+    }
+
+    #[test]
+    fn params_are_typed_and_checked_against_the_schema() {
+        let mut form = ToolForm::from_schema(&schema(), &json!({"command": "uptime", "host": "{\"port\": 22}"})).unwrap();
+        form.fields[1].value = "3".into();
+        assert_eq!(form.to_params(), Ok(json!({"command": "uptime", "retries": 3, "host": {"port": 22}})));
+
+        form.fields[2].value = "yes".into();
+        assert_eq!(form.to_params(), Err("'verbose' must be a boolean".to_string()));
+        form.fields[2].value.clear();
+        form.fields[1].value = "1.5".into();
+        assert_eq!(form.to_params(), Err("'retries' must be an integer".to_string()));
+        form.fields[1].value.clear();
+        form.fields[0].value = " ".into();
+        assert_eq!(form.to_params(), Err("'command' is required".to_string()));
+    }
+}
@@ -1,31 +1,231 @@
 use std::time::Instant;
-use crossterm::event::{Event, KeyCode, KeyModifiers, MouseEventKind};
+use std::io;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::ExecutableCommand;
+use ratatui::layout::Position;
 use tui_textarea::{Input, TextArea};
-use ratatui::widgets::{Block, Borders};
-use crate::app::{App, SharedState};
+use crate::app::{App, ChatSearch, SharedState};
 use crate::ui::{AppMode, Focus, ConfirmationModal, Action};
+use crate::keymap::{InputContext, UiCommand};
 // use crate::clipboard;
+use crate::budget::AttachedSource;
 use crate::mouse;
+use crate::hints::{self, HintKind};
+use crate::attachments;
+use crate::templates;
+use crate::clipboard;
+use crate::markdown;
+use crate::export;
+use crate::postprocess;
+use crate::syslog;
+use crate::catalog::CATALOG;
+use crate::resources;
+use crate::session::{self, SessionMeta};
+use crate::message::{ChatMessage, CopyFormat, Role};
+use crate::commands::{self, ParsedCommand};
+use crate::i18n::{t, tf};
+use crate::llm::{self, ProviderKind};
+use crate::llm::ollama::KeepAlive;
+use crate::verbosity::Verbosity;
+use crate::options::ModelOptions;
+use lucius::mcp::ToolCall;
+
+/// Writes the settings screen's text fields to the config.
+fn save_settings(app: &App, state: &mut SharedState) {
+    state.config.ollama_url = Some(app.url_editor.lines().join(""));
+    state.config.mcp_redis_host = Some(app.mcp_url_editor.lines().join(""));
+    let api_key = app.api_key_editor.lines().join("");
+    state.config.anthropic.api_key = Some(api_key).filter(|key| !key.is_empty());
+    if state.config.provider == ProviderKind::Anthropic {
+        let model = app.model_name_editor.lines().join("");
+        if !model.trim().is_empty() {
+            state.config.selected_model = Some(model.trim().to_string());
+        }
+    }
+    let mut options = ModelOptions::default();
+    match options.apply(&app.options_editor.lines().join(" ")) {
+        Ok(()) => state.config.options = options,
+        Err(e) => state.status_message = Some((tf("status.options_invalid", &[("error", e.as_str())]), Instant::now())),
+    }
+    state.config.save();
+}
+
+/// The settings field after `focus`; the Ollama URL and keep-alive are only
+/// shown for Ollama and the API key and model name only for Anthropic.
+fn next_settings_focus(focus: Focus, provider: ProviderKind) -> Focus {
+    match (focus, provider) {
+        (Focus::Provider, ProviderKind::Ollama) => Focus::Url,
+        (Focus::Provider, ProviderKind::Anthropic) => Focus::ApiKey,
+        (Focus::ApiKey, _) => Focus::ModelName,
+        (Focus::Url, _) => Focus::KeepAlive,
+        (Focus::KeepAlive, _) | (Focus::ModelName, _) => Focus::McpUrl,
+        (Focus::McpUrl, _) => Focus::Parameters,
+        (Focus::Parameters, _) => Focus::Models,
+        (Focus::Models, _) => Focus::Provider,
+    }
+}
+
+/// Keys on the settings model list: Up/Down select, `i` shows details of the
+/// selected model, `p` asks for a model to pull and `d` twice deletes the
+/// selected one.
+fn handle_model_list(app: &mut App<'_>, state: &mut SharedState, key: KeyEvent) {
+    // With nothing installed the list is the catalog, and `p` pulls its selected entry
+    let command = app.keymap.map(InputContext::ModelList, key);
+    if state.shows_catalog() {
+        match command {
+            Some(UiCommand::SelectNext) => app.models_next(CATALOG.len()),
+            Some(UiCommand::SelectPrevious) => app.models_previous(CATALOG.len()),
+            Some(UiCommand::PullModel) => match app.model_list_state.selected().and_then(|i| CATALOG.get(i)) {
+                Some(entry) => app.dispatch(state, Action::PullModel(entry.name.to_string())),
+                None => app.model_pull = Some(TextArea::default()),
+            },
+            _ => {}
+        }
+        return;
+    }
+    let delete_armed = std::mem::take(&mut app.model_delete_armed);
+    app.model_fit_armed = false;
+    let Some(command) = command else {
+        return;
+    };
+    match command {
+        UiCommand::SelectNext => app.models_next(state.models.len()),
+        UiCommand::SelectPrevious => app.models_previous(state.models.len()),
+        UiCommand::ShowModelInfo | UiCommand::PullModel | UiCommand::DeleteItem | UiCommand::UnloadModel
+            if state.config.provider != ProviderKind::Ollama =>
+        {
+            state.status_message = Some((t("status.models_ollama_only").to_string(), Instant::now()));
+        }
+        UiCommand::ShowModelInfo => {
+            if let Some(model) = app.model_list_state.selected().and_then(|i| state.models.get(i)).map(|model| model.name.clone()) {
+                app.model_info_scroll = 0;
+                app.dispatch(state, Action::ShowModelInfo(model));
+            }
+        }
+        UiCommand::PullModel => app.model_pull = Some(TextArea::default()),
+        UiCommand::UnloadModel => {
+            let Some(model) = app.model_list_state.selected().and_then(|i| state.models.get(i)).map(|model| model.name.clone()) else {
+                return;
+            };
+            if state.loaded_models.iter().any(|loaded| loaded.name == model) {
+                app.dispatch(state, Action::UnloadModel(model));
+            } else {
+                state.status_message = Some((tf("status.model_not_loaded", &[("model", model.as_str())]), Instant::now()));
+            }
+        }
+        UiCommand::DeleteItem => {
+            let Some(model) = app.model_list_state.selected().and_then(|i| state.models.get(i)).map(|model| model.name.clone()) else {
+                return;
+            };
+            if delete_armed {
+                app.dispatch(state, Action::DeleteModel(model));
+            } else {
+                app.model_delete_armed = true;
+                state.status_message = Some((tf("status.model_delete_confirm", &[("model", model.as_str())]), Instant::now()));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The warning shown before picking the selected model when it would not fit
+/// in the Ollama host's memory.
+fn selected_fit_warning(app: &App<'_>, state: &SharedState) -> Option<String> {
+    let model = app.model_list_state.selected().and_then(|i| state.models.get(i))?;
+    let size = model.size?;
+    let memory = resources::host_memory(&state.config)?;
+    (!resources::fits(size, memory)).then(|| {
+        let (size, memory) = (resources::format_bytes(size), resources::format_bytes(memory));
+        tf("status.model_too_big", &[("model", model.name.as_str()), ("size", size.as_str()), ("memory", memory.as_str())])
+    })
+}
+
+/// Keys while typing the name of a model to pull.
+fn handle_model_pull(app: &mut App<'_>, state: &mut SharedState, key: KeyEvent) {
+    let command = app.keymap.map(InputContext::ModelList, key);
+    let Some(editor) = &mut app.model_pull else {
+        return;
+    };
+    match command {
+        Some(UiCommand::Choose) => {
+            let model = editor.lines().join("").trim().to_string();
+            app.model_pull = None;
+            if !model.is_empty() {
+                app.dispatch(state, Action::PullModel(model));
+            }
+        }
+        Some(UiCommand::Close) => app.model_pull = None,
+        _ => { editor.input(Input::from(key)); }
+    }
+}
+
+/// Columns moved per Alt+Left/Right press.
+const HSCROLL_STEP: u16 = 4;
+/// How long typing on the search screen pauses before the query runs.
+const SEARCH_DELAY: std::time::Duration = std::time::Duration::from_millis(300);
 
 pub async fn handle_event(app: &mut App<'_>, state: &mut SharedState, event: Event, should_quit: &mut bool) {
     log::info!("Handling event: {:?}", event);
-    
-    if let AppMode::Confirmation(ConfirmationModal::ExecuteTool { tool_call: _, confirm_tx }) = &mut state.mode {
+
+    if let Event::Paste(text) = &event {
+        handle_paste(app, state, text);
+        return;
+    }
+
+    if let AppMode::Confirmation(ConfirmationModal::ExecuteTool { tool_call, form, confirm_tx, .. }) = &mut state.mode {
         if let Event::Key(key) = event {
             if key.kind == crossterm::event::KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                if let Some(form) = form.as_mut().filter(|form| form.editing) {
+                    // Inline editing of the selected field
+                    match key.code {
+                        KeyCode::Enter | KeyCode::Esc => form.editing = false,
+                        KeyCode::Backspace => {
+                            form.selected_value().pop();
+                        }
+                        KeyCode::Char(c) => form.selected_value().push(c),
+                        _ => {}
+                    }
+                    return;
+                }
+                match app.keymap.map(InputContext::Confirmation, key) {
+                    Some(UiCommand::Confirm) => {
+                        let params = match form {
+                            Some(form) => match form.to_params() {
+                                Ok(params) => params,
+                                Err(e) => {
+                                    form.error = Some(e);
+                                    return;
+                                }
+                            },
+                            None => tool_call.params.clone(),
+                        };
                         if let Some(tx) = confirm_tx.take() {
-                            let _ = tx.send(true);
+                            let _ = tx.send(Some(params));
                         }
                         state.mode = AppMode::Chat; // Exit modal
                     }
-                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    Some(UiCommand::Decline) => {
                         if let Some(tx) = confirm_tx.take() {
-                            let _ = tx.send(false);
+                            let _ = tx.send(None);
                         }
                         state.mode = AppMode::Chat; // Exit modal
                     }
+                    Some(UiCommand::NextField) => {
+                        if let Some(form) = form {
+                            form.select_next();
+                        }
+                    }
+                    Some(UiCommand::PreviousField) => {
+                        if let Some(form) = form {
+                            form.select_previous();
+                        }
+                    }
+                    Some(UiCommand::EditField) => {
+                        if let Some(form) = form {
+                            form.editing = true;
+                            form.error = None;
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -33,156 +233,379 @@ pub async fn handle_event(app: &mut App<'_>, state: &mut SharedState, event: Eve
         return; // Don't process other events while in confirmation mode
     }
 
+    // The editor takes every key, Ctrl ones included
+    if state.mode == AppMode::PromptEditor {
+        if let Event::Key(key) = event {
+            if key.kind == crossterm::event::KeyEventKind::Press {
+                handle_prompt_editor(app, state, key);
+            }
+        }
+        return;
+    }
+
     match event {
         Event::Key(key) => {
             log::info!("Key event: {:?}", key);
             if key.kind == crossterm::event::KeyEventKind::Press {
-                if key.modifiers == KeyModifiers::CONTROL {
-                    match key.code {
-                        KeyCode::Char('h') => {
-                            state.mode = match state.mode {
-                                AppMode::Help => AppMode::Chat,
-                                _ => AppMode::Help,
-                            };
-                        }
-                        KeyCode::Char('q') => *should_quit = true,
-                        KeyCode::Char('s') => {
-                            state.mode = AppMode::Settings;
-                            let _ = app.action_tx.try_send(Action::RefreshModelsAndStatus);
-                        }
-                        KeyCode::Char('l') => {
-                            state.chat_history.clear();
-                            app.scroll = 0;
-                        }
-                        KeyCode::Char('c') | KeyCode::Char('y') => {
-                            // if app.selection_range.is_none() {
-                            //     if let Some(last_response) = state.chat_history.iter().rev().find(|m| m.starts_with("Lucius:")) {
-                            //         let content_to_copy = last_response.strip_prefix("Lucius: ").unwrap_or(last_response).trim();
-                            //         clipboard::copy_to_clipboard(content_to_copy.to_string()).await;
-                            //         state.status_message = Some(("Copied last response to clipboard!".to_string(), Instant::now()));
-                            //     } else {
-                            //         log::warn!("Ctrl+C pressed, but no previous response from Lucius found to copy.");
-                            //     }
-                            // }
-                        }
-                        KeyCode::Char('r') if matches!(state.mode, AppMode::Settings) => {
-                            state.config.ollama_url = Some(app.url_editor.lines().join(""));
-                            state.config.save();
-                            let _ = app.action_tx.try_send(Action::RefreshModelsAndStatus);
-                        }
-                        KeyCode::Char('t') => {
-                            state.status_message = if state.redis_conn.is_some() {
-                                Some(("MCP is connected via Redis.".to_string(), Instant::now()))
-                            } else {
-                                Some(("MCP Redis client not connected.".to_string(), Instant::now()))
-                            };
-                        }
-                        _ => {}
-                    }
+                let command = app.keymap.map(InputContext::of(&state.mode), key);
+                if let Some(command) = command.filter(|command| command.is_global()) {
+                    run_global_command(app, state, command, should_quit);
+                } else if key.modifiers == KeyModifiers::CONTROL && command.is_none() {
+                    // Unbound Ctrl keys do nothing rather than reach the inputs
+                } else if matches!(state.mode, AppMode::Chat) && app.chat_search.is_some() && handle_chat_search(app, key, command) {
+                    // Handled by the search bar
+                } else if matches!(state.mode, AppMode::Chat) && app.focused_message.is_some() && handle_focused_message(app, state, key) {
+                    // Handled by the focused message
                 } else {
+                    let history_len = state.chat_history.len();
                     match &mut state.mode {
-                        AppMode::Chat => match key.code {
-                            KeyCode::Enter => {
+                        AppMode::Chat => match command {
+                            Some(UiCommand::Submit) => {
                                 let input = app.textarea.lines().join("\n");
                                 if !input.trim().is_empty() {
-                                    state.chat_history.push(format!("You: {}", input));
-                                    app.scroll = u16::MAX;
-                                    let _ = app.action_tx.try_send(Action::SendMessage(input));
-
-                                    let mut textarea = TextArea::default();
-                                    textarea.set_placeholder_text("Ask me anything...");
-                                    textarea.set_block(
-                                        Block::default().borders(Borders::ALL).title("Input").border_type(ratatui::widgets::BorderType::Rounded),
-                                    );
-                                    app.textarea = textarea;
+                                    match commands::parse(&input) {
+                                        Some(command) => run_command(app, state, command, should_quit),
+                                        None => send_message(app, state, input.clone()),
+                                    }
                                 }
                             }
-                            _ => { app.textarea.input(Input::from(key)); }
-                        },
-                        AppMode::Settings => match app.focus {
-                            Focus::Url => match key.code {
-                                KeyCode::Tab => {
-                                    state.config.ollama_url = Some(app.url_editor.lines().join(""));
-                                    state.config.save();
-                                    app.focus = Focus::McpUrl;
+                            Some(UiCommand::GoToTab(index)) => switch_tab(app, state, index),
+                            Some(UiCommand::NextVerbosity) => set_verbosity(state, state.config.verbosity.next()),
+                            Some(UiCommand::ToggleMessageStamps) => app.show_stamps = !app.show_stamps,
+                            Some(UiCommand::OpenAttachedPanel) => open_attached_panel(app, state),
+                            Some(UiCommand::ToggleMouse) => toggle_mouse(app, state),
+                            // Alt+Up/Down step through messages to scroll or wrap their code blocks
+                            Some(UiCommand::FocusPreviousMessage) => move_message_focus(app, history_len, -1),
+                            Some(UiCommand::FocusNextMessage) => move_message_focus(app, history_len, 1),
+                            // Alt+Left/Right scroll wide code blocks and tables sideways
+                            Some(UiCommand::ScrollLeft) => app.hscroll = app.hscroll.saturating_sub(HSCROLL_STEP),
+                            Some(UiCommand::ScrollRight) => app.hscroll = app.hscroll.saturating_add(HSCROLL_STEP),
+                            Some(UiCommand::Cancel) if state.generating() => cancel_generation(app, state),
+                            Some(UiCommand::PageUp) => {
+                                app.focused_message = None;
+                                app.scroll = app.scroll.saturating_sub(app.page_height());
+                            }
+                            Some(UiCommand::PageDown) => {
+                                app.focused_message = None;
+                                app.scroll = app.scroll.saturating_add(app.page_height());
+                            }
+                            Some(UiCommand::ScrollToTop) => {
+                                app.focused_message = None;
+                                app.scroll = 0;
+                            }
+                            // Clamped to the last page when drawn
+                            Some(UiCommand::ScrollToBottom) => {
+                                app.focused_message = None;
+                                app.scroll = u16::MAX;
+                            }
+                            Some(UiCommand::FindInChat) => {
+                                app.focused_message = None;
+                                app.chat_search = Some(ChatSearch::new());
+                            }
+                            // Only over an empty input, so nothing typed is lost
+                            Some(UiCommand::SendFollowUp(index)) => {
+                                if let Some(followup) = state.active_followups().get(index).cloned().filter(|_| input_is_empty(app)) {
+                                    send_message(app, state, followup);
                                 }
-                                KeyCode::Enter | KeyCode::Esc => {
-                                    state.config.ollama_url = Some(app.url_editor.lines().join(""));
-                                    state.config.save();
-                                    state.mode = AppMode::Chat;
+                            }
+                            _ => match key.code {
+                                // Without the mouse wheel, Up/Down over an empty input scroll instead
+                                KeyCode::Up if !app.mouse_capture && input_is_empty(app) => app.scroll_up(),
+                                KeyCode::Down if !app.mouse_capture && input_is_empty(app) => app.scroll_down(),
+                                // Vim mode takes every key outside insert mode
+                                _ if state.config.input.vim && app.vim.handle_key(&mut app.textarea, key) => {}
+                                // Right/Tab accept the autocomplete ghost text
+                                KeyCode::Right | KeyCode::Tab if app.input_completion(&state.config).is_some() => {
+                                    if let Some(completion) = app.input_completion(&state.config) {
+                                        app.textarea.insert_str(completion);
+                                    }
                                 }
-                                _ => { app.url_editor.input(Input::from(key)); }
+                                _ => { app.textarea.input(Input::from(key)); }
                             },
-                            Focus::McpUrl => match key.code {
-                                KeyCode::Tab => {
-                                    state.config.mcp_redis_host = Some(app.mcp_url_editor.lines().join(""));
-                                    state.config.save();
-                                    app.focus = Focus::Models;
+                        },
+                        AppMode::Settings if app.model_pull.is_some() => handle_model_pull(app, state, key),
+                        AppMode::Settings if state.model_info.is_some() => match app.keymap.map(InputContext::ModelList, key) {
+                            Some(UiCommand::SelectNext) => app.model_info_scroll = app.model_info_scroll.saturating_add(1),
+                            Some(UiCommand::SelectPrevious) => app.model_info_scroll = app.model_info_scroll.saturating_sub(1),
+                            Some(UiCommand::Close | UiCommand::Choose | UiCommand::ShowModelInfo) => state.model_info = None,
+                            _ => {}
+                        },
+                        AppMode::Settings => match key.code {
+                            KeyCode::Tab => {
+                                save_settings(app, state);
+                                app.focus = next_settings_focus(app.focus, state.config.provider);
+                            }
+                            // A model too big for the host is only picked with a second Enter
+                            KeyCode::Enter if app.focus == Focus::Models && !app.model_fit_armed && selected_fit_warning(app, state).is_some() => {
+                                state.status_message = selected_fit_warning(app, state).map(|warning| (warning, Instant::now()));
+                                app.model_fit_armed = true;
+                            }
+                            KeyCode::Esc | KeyCode::Enter => {
+                                app.model_fit_armed = false;
+                                let previous = state.config.selected_model.clone();
+                                if let (Focus::Models, Some(selected_index)) = (app.focus, app.model_list_state.selected().filter(|_| !state.shows_catalog())) {
+                                    state.config.selected_model = state.models.get(selected_index).map(|m| m.name.clone());
+                                    app.model_name_editor = TextArea::new(vec![state.config.selected_model.clone().unwrap_or_default()]);
                                 }
-                                KeyCode::Enter | KeyCode::Esc => {
-                                    state.config.mcp_redis_host = Some(app.mcp_url_editor.lines().join(""));
-                                    state.config.save();
-                                    state.mode = AppMode::Chat;
+                                save_settings(app, state);
+                                if let Some(model) = state.config.selected_model.clone().filter(|model| previous.as_ref() != Some(model)) {
+                                    app.dispatch(state, Action::WarmUpModel(model));
                                 }
-                                _ => { app.mcp_url_editor.input(Input::from(key)); }
-                            },
-                            Focus::Models => match key.code {
-                                KeyCode::Esc | KeyCode::Enter => {
-                                    if let Some(selected_index) = app.model_list_state.selected() {
-                                        state.config.selected_model = state.models.get(selected_index).map(|m| m.name.clone());
+                                state.mode = AppMode::Chat;
+                            }
+                            _ => match app.focus {
+                                Focus::Provider => {
+                                    if matches!(key.code, KeyCode::Left | KeyCode::Right | KeyCode::Char(' ')) {
+                                        state.config.provider = state.config.provider.next();
                                         state.config.save();
+                                        app.dispatch(state, Action::RefreshModelsAndStatus { scheduled: false });
                                     }
-                                    state.mode = AppMode::Chat;
                                 }
-                                KeyCode::Down => app.models_next(state.models.len()),
-                                KeyCode::Up => app.models_previous(state.models.len()),
-                                KeyCode::Tab => { app.focus = Focus::Url; }
-                                _ => {}
+                                Focus::Url => { app.url_editor.input(Input::from(key)); }
+                                Focus::KeepAlive => {
+                                    if matches!(key.code, KeyCode::Left | KeyCode::Right | KeyCode::Char(' ')) {
+                                        state.config.keep_alive = KeepAlive::cycle(&state.config.keep_alive);
+                                        state.config.save();
+                                    }
+                                }
+                                Focus::ApiKey => { app.api_key_editor.input(Input::from(key)); }
+                                Focus::ModelName => { app.model_name_editor.input(Input::from(key)); }
+                                Focus::McpUrl => { app.mcp_url_editor.input(Input::from(key)); }
+                                Focus::Parameters => { app.options_editor.input(Input::from(key)); }
+                                Focus::Models => handle_model_list(app, state, key),
                             },
                         },
                         AppMode::Help => {
-                            if key.code == KeyCode::Esc {
+                            if command == Some(UiCommand::Close) {
                                 state.mode = AppMode::Chat;
                             }
                         }
                         AppMode::Confirmation(_) => {}
+                        AppMode::Hints(found) => match key.code {
+                            _ if command == Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                            // The hint labels are letters, whatever they are bound to elsewhere
+                            KeyCode::Char(c) => {
+                                if let Some(hint) = hints::hint_index(c).and_then(|i| found.get(i)).cloned() {
+                                    match hint.kind {
+                                        HintKind::Url => {
+                                            let msg = match hints::open_url(&hint.target) {
+                                                Ok(()) => tf("status.opened", &[("target", hint.target.as_str())]),
+                                                Err(e) => e,
+                                            };
+                                            state.status_message = Some((msg, Instant::now()));
+                                            state.mode = AppMode::Chat;
+                                        }
+                                        HintKind::Path => state.mode = AppMode::PathActions(hint.target),
+                                    }
+                                }
+                            }
+                            _ => {}
+                        },
+                        AppMode::TemplatePicker(choices) => match command {
+                            Some(UiCommand::SelectNext) => {
+                                let next = app.template_list_state.selected().map_or(0, |i| (i + 1) % choices.len().max(1));
+                                app.template_list_state.select(Some(next));
+                            }
+                            Some(UiCommand::SelectPrevious) => {
+                                let count = choices.len().max(1);
+                                let prev = app.template_list_state.selected().map_or(0, |i| (i + count - 1) % count);
+                                app.template_list_state.select(Some(prev));
+                            }
+                            Some(UiCommand::Choose) => {
+                                if let Some(template) = app.template_list_state.selected().and_then(|i| choices.get(i)).cloned() {
+                                    state.apply_template(&template);
+                                    app.reset_message_view();
+                                    app.attachments.clear();
+                                    app.reset_input(template.first_message.as_deref().unwrap_or(""));
+                                    state.status_message = Some((tf("status.template_started", &[("name", template.name.as_str())]), Instant::now()));
+                                    state.mode = AppMode::Chat;
+                                }
+                            }
+                            Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                            _ => {}
+                        },
+                        AppMode::ToolPalette(tools) => match command {
+                            Some(UiCommand::SelectNext) => {
+                                let next = app.tool_list_state.selected().map_or(0, |i| (i + 1) % tools.len().max(1));
+                                app.tool_list_state.select(Some(next));
+                            }
+                            Some(UiCommand::SelectPrevious) => {
+                                let count = tools.len().max(1);
+                                let prev = app.tool_list_state.selected().map_or(0, |i| (i + count - 1) % count);
+                                app.tool_list_state.select(Some(prev));
+                            }
+                            Some(UiCommand::ToggleFavorite) => {
+                                if let Some(name) = app.tool_list_state.selected().and_then(|i| tools.get(i)).map(|tool| tool.name.clone()) {
+                                    let favorites = &mut state.config.favorite_tools;
+                                    match favorites.iter().position(|favorite| *favorite == name) {
+                                        Some(index) => { favorites.remove(index); }
+                                        None => favorites.push(name.clone()),
+                                    }
+                                    state.config.save();
+                                    // Re-sort, keeping the same tool selected
+                                    open_tool_palette(app, state);
+                                    if let AppMode::ToolPalette(tools) = &state.mode {
+                                        app.tool_list_state.select(tools.iter().position(|tool| tool.name == name));
+                                    }
+                                }
+                            }
+                            Some(UiCommand::Choose) => {
+                                if let Some(tool) = app.tool_list_state.selected().and_then(|i| tools.get(i)) {
+                                    let tool_call = ToolCall { tool: tool.name.clone(), params: serde_json::json!({}), expect: vec![] };
+                                    app.dispatch(state, Action::ConfirmTool(tool_call));
+                                    state.mode = AppMode::Chat;
+                                }
+                            }
+                            Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                            _ => {}
+                        },
+                        AppMode::PromptEditor => {}
+                        AppMode::ContextDiff => match command {
+                            Some(UiCommand::SelectNext) => app.context_diff_scroll = app.context_diff_scroll.saturating_add(1),
+                            Some(UiCommand::SelectPrevious) => app.context_diff_scroll = app.context_diff_scroll.saturating_sub(1),
+                            Some(UiCommand::PageDown) => app.context_diff_scroll = app.context_diff_scroll.saturating_add(app.page_height()),
+                            Some(UiCommand::PageUp) => app.context_diff_scroll = app.context_diff_scroll.saturating_sub(app.page_height()),
+                            // Esc leaves this conversation as it is, like `n`
+                            Some(UiCommand::ApplyContextChange | UiCommand::KeepContext | UiCommand::Close) => {
+                                let this_conversation = command == Some(UiCommand::ApplyContextChange);
+                                state.resolve_context_change(this_conversation);
+                                let key = if this_conversation { "status.lucius_md_applied" } else { "status.lucius_md_new_only" };
+                                state.status_message = Some((t(key).to_string(), Instant::now()));
+                                state.mode = AppMode::Chat;
+                            }
+                            _ => {}
+                        },
+                        AppMode::SessionBrowser(_) => handle_session_browser(app, state, key, command),
+                        AppMode::Search(_) => handle_search(app, state, key, command),
+                        AppMode::ContextPanel => {
+                            let count = state.context_items().len().max(1);
+                            match command {
+                                Some(UiCommand::SelectNext) => {
+                                    let next = app.context_list_state.selected().map_or(0, |i| (i + 1) % count);
+                                    app.context_list_state.select(Some(next));
+                                }
+                                Some(UiCommand::SelectPrevious) => {
+                                    let prev = app.context_list_state.selected().map_or(0, |i| (i + count - 1) % count);
+                                    app.context_list_state.select(Some(prev));
+                                }
+                                Some(UiCommand::ToggleItem | UiCommand::Choose) => {
+                                    if let Some(item) = app.context_list_state.selected().and_then(|i| state.context_items().into_iter().nth(i)) {
+                                        state.toggle_context(item.section);
+                                    }
+                                }
+                                Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                                _ => {}
+                            }
+                        }
+                        AppMode::AttachedPanel => {
+                            let items = app.attached_items(state);
+                            let count = items.len().max(1);
+                            match command {
+                                Some(UiCommand::SelectNext) => {
+                                    let next = app.attached_list_state.selected().map_or(0, |i| (i + 1) % count);
+                                    app.attached_list_state.select(Some(next));
+                                }
+                                Some(UiCommand::SelectPrevious) => {
+                                    let prev = app.attached_list_state.selected().map_or(0, |i| (i + count - 1) % count);
+                                    app.attached_list_state.select(Some(prev));
+                                }
+                                Some(UiCommand::RemoveItem) => {
+                                    if let Some(item) = app.attached_list_state.selected().and_then(|i| items.into_iter().nth(i)) {
+                                        remove_attached(app, state, item.source);
+                                    }
+                                }
+                                Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                                _ => {}
+                            }
+                        }
+                        AppMode::CopyMenu(index) => {
+                            let index = *index;
+                            match command {
+                                Some(UiCommand::CopyAs(format)) => copy_message(state, index, format),
+                                Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                                _ => {}
+                            }
+                        }
+                        AppMode::PathActions(path) => {
+                            let path = path.clone();
+                            let quoted = hints::shell_quote(&path);
+                            match command {
+                                Some(UiCommand::EditPath) => {
+                                    app.pending_editor = Some(path);
+                                    state.mode = AppMode::Chat;
+                                }
+                                Some(UiCommand::AttachPath) => {
+                                    if !app.attachments.contains(&path) {
+                                        app.attachments.push(path.clone());
+                                    }
+                                    state.status_message = Some((tf("status.attached", &[("path", path.as_str())]), Instant::now()));
+                                    state.mode = AppMode::Chat;
+                                }
+                                Some(UiCommand::ListPath | UiCommand::HeadPath) => {
+                                    let command = if command == Some(UiCommand::ListPath) {
+                                        format!("ls -la {}", quoted)
+                                    } else {
+                                        format!("head -n 40 {}", quoted)
+                                    };
+                                    let tool = ToolCall {
+                                        tool: "exec".to_string(),
+                                        params: serde_json::json!({ "command": command }),
+                                        expect: vec![],
+                                    };
+                                    app.dispatch(state, Action::RunTool(tool));
+                                    app.scroll = u16::MAX;
+                                    state.mode = AppMode::Chat;
+                                }
+                                Some(UiCommand::Close) => state.mode = AppMode::Chat,
+                                _ => {}
+                            }
+                        }
                     }
                 }
             }
         }
+        // Events read before capture was turned off
+        Event::Mouse(_) if !app.mouse_capture => {}
         Event::Mouse(mouse_event) => {
             match mouse_event.kind {
                 MouseEventKind::ScrollUp => app.scroll_up(),
                 MouseEventKind::ScrollDown => app.scroll_down(),
-                MouseEventKind::Down(_) => {
+                // The status line's count of attached context opens its panel
+                MouseEventKind::Down(MouseButton::Left)
+                    if state.mode == AppMode::Chat
+                        && app.status_area.contains(Position::new(mouse_event.column, mouse_event.row)) =>
+                {
+                    open_attached_panel(app, state);
+                }
+                // Dragging over the conversation selects its text; letting go copies it
+                MouseEventKind::Down(MouseButton::Left) if state.mode == AppMode::Chat => {
                     let (x, y) = (mouse_event.column, mouse_event.row);
-                    if let Some(coords) = mouse::get_text_coordinates(app.conversation_area, x, y) {
-                        app.selection_range = Some((coords, coords));
-                    }
+                    app.selection_range = mouse::get_text_coordinates(app.conversation_area, app.scroll, x, y).map(|cell| (cell, cell));
                 }
-                MouseEventKind::Drag(_) => {
-                    if let Some((start, _)) = app.selection_range {
-                        let (x, y) = (mouse_event.column, mouse_event.row);
-                        if let Some(end) = mouse::get_text_coordinates(app.conversation_area, x, y) {
-                            app.selection_range = Some((start, end));
+                MouseEventKind::Drag(MouseButton::Left) => {
+                    if let Some((anchor, _)) = app.selection_range {
+                        let area = app.conversation_area;
+                        // Dragging past the top or bottom edge scrolls
+                        if mouse_event.row < area.y {
+                            app.scroll_up();
+                        } else if mouse_event.row >= area.bottom() {
+                            app.scroll_down();
+                        }
+                        let (x, y) = mouse::clamp_to(area, mouse_event.column, mouse_event.row);
+                        if let Some(head) = mouse::get_text_coordinates(area, app.scroll, x, y) {
+                            app.selection_range = Some((anchor, head));
                         }
                     }
                 }
-                MouseEventKind::Up(_) => {
-                    // if let Some(((start_line, _), _)) = app.selection_range {
-                    //     // Reconstruct the rendered text to find the clicked line.
-                    //     // This is a temporary fix for the broken selection logic.
-                    //     let history_text: String = state.chat_history.join("\n");
-                    //     let markdown_text = termimad::MadSkin::default().term_text(&history_text).to_string();
-                    //     let rendered_lines: Vec<&str> = markdown_text.lines().collect();
-
-                    //     // The start_line is the screen line index.
-                    //     if let Some(line_to_copy) = rendered_lines.get(start_line) {
-                    //         clipboard::copy_to_clipboard(line_to_copy.to_string()).await;
-                    //         state.status_message = Some(("Copied line to clipboard!".to_string(), Instant::now()));
-                    //     }
-                    // }
-                    app.selection_range = None;
+                MouseEventKind::Up(MouseButton::Left) => {
+                    if let Some((anchor, head)) = app.selection_range.take().filter(|(anchor, head)| anchor != head) {
+                        let text = mouse::selected_text(&app.conversation_rows, anchor, head);
+                        if !text.is_empty() {
+                            let count = text.chars().count().to_string();
+                            clipboard::copy_to_clipboard(text, state.config.clipboard.backend);
+                            state.status_message = Some((tf("status.selection_copied", &[("count", count.as_str())]), Instant::now()));
+                        }
+                    }
                 }
                 _ => {}
             }
@@ -190,3 +613,1175 @@ pub async fn handle_event(app: &mut App<'_>, state: &mut SharedState, event: Eve
         _ => {}
     }
 }
+
+/// Inserts pasted text into the input that has the keyboard in one go, so
+/// its line breaks never reach the Enter handling. Single-line inputs get
+/// the lines joined with spaces.
+fn handle_paste(app: &mut App<'_>, state: &mut SharedState, text: &str) {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let single_line = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join(" ");
+    match &mut state.mode {
+        AppMode::Chat if app.chat_search.as_ref().is_some_and(|search| search.editing) => {
+            if let Some(search) = &mut app.chat_search {
+                search.query.push_str(&single_line);
+                search.restart();
+            }
+        }
+        AppMode::Chat => {
+            app.textarea.insert_str(&text);
+            // The input shows one line, so say how much went in
+            let lines = text.trim_end_matches('\n').lines().count();
+            if lines > 1 {
+                state.status_message = Some((tf("status.pasted_lines", &[("count", lines.to_string().as_str())]), Instant::now()));
+            }
+        }
+        AppMode::PromptEditor => {
+            app.prompt_editor.insert_str(&text);
+        }
+        AppMode::Settings => {
+            let editor = match (&mut app.model_pull, app.focus) {
+                (Some(editor), _) => Some(editor),
+                (None, Focus::Url) => Some(&mut app.url_editor),
+                (None, Focus::ApiKey) => Some(&mut app.api_key_editor),
+                (None, Focus::ModelName) => Some(&mut app.model_name_editor),
+                (None, Focus::McpUrl) => Some(&mut app.mcp_url_editor),
+                (None, Focus::Parameters) => Some(&mut app.options_editor),
+                (None, _) => None,
+            };
+            if let Some(editor) = editor {
+                editor.insert_str(&single_line);
+            }
+        }
+        AppMode::Confirmation(ConfirmationModal::ExecuteTool { form: Some(form), .. }) if form.editing => {
+            form.selected_value().push_str(&single_line);
+        }
+        AppMode::SessionBrowser(_) => {
+            if let Some(editor) = &mut app.session_rename {
+                editor.insert_str(&single_line);
+            }
+        }
+        AppMode::Search(_) => {
+            app.search_input.insert_str(&single_line);
+            app.search_edited = Some(Instant::now());
+        }
+        _ => {}
+    }
+}
+
+/// Runs a command bound on every screen, whatever the input has focus.
+fn run_global_command(app: &mut App<'_>, state: &mut SharedState, command: UiCommand, should_quit: &mut bool) {
+    match command {
+        UiCommand::ToggleHelp => {
+            state.mode = match state.mode {
+                AppMode::Help => AppMode::Chat,
+                _ => AppMode::Help,
+            };
+        }
+        UiCommand::Quit => *should_quit = true,
+        UiCommand::OpenSettings => {
+            state.mode = AppMode::Settings;
+            app.dispatch(state, Action::RefreshModelsAndStatus { scheduled: false });
+        }
+        UiCommand::ReloadSettings if matches!(state.mode, AppMode::Settings) => {
+            save_settings(app, state);
+            app.dispatch(state, Action::RefreshModelsAndStatus { scheduled: false });
+        }
+        UiCommand::ClearChat => clear_chat(app, state),
+        UiCommand::NewFromTemplate => open_template_picker(app, state),
+        UiCommand::OpenToolPalette => open_tool_palette(app, state),
+        UiCommand::OpenContextPanel => open_context_panel(app, state),
+        UiCommand::OpenSessionBrowser => {
+            app.session_tag_filter = None;
+            open_session_browser(app, state);
+        }
+        UiCommand::SearchSessions => open_search(app, state, ""),
+        UiCommand::NextTab => switch_tab(app, state, (state.active_tab + 1) % state.tabs.len().max(1)),
+        UiCommand::PreviousTab => {
+            let count = state.tabs.len().max(1);
+            switch_tab(app, state, (state.active_tab + count - 1) % count);
+        }
+        UiCommand::ToggleTimestamps => app.expand_timestamps = !app.expand_timestamps,
+        UiCommand::CopyCodeBlock => copy_next_code_block(app, state),
+        UiCommand::CopyAnswer => copy_last_answer(state),
+        UiCommand::JumpToUnread => {
+            if let Some(line) = app.unread_line {
+                app.focused_message = None;
+                app.scroll = line;
+            }
+        }
+        UiCommand::OpenHints => open_hints(state),
+        UiCommand::ShowMcpStatus => {
+            state.status_message = if state.redis_conn.is_some() && state.dead_letter_count > 0 {
+                let count = state.dead_letter_count.to_string();
+                Some((tf("status.mcp_connected_dead_letters", &[("count", count.as_str())]), Instant::now()))
+            } else if state.redis_conn.is_some() {
+                Some((t("status.mcp_connected").to_string(), Instant::now()))
+            } else {
+                Some((t("status.mcp_disconnected").to_string(), Instant::now()))
+            };
+        }
+        _ => {}
+    }
+}
+
+/// Ctrl+Y: copies the last answer to the clipboard.
+fn copy_last_answer(state: &mut SharedState) {
+    match state.chat_history.iter().rev().find(|msg| msg.role == Role::Assistant) {
+        Some(answer) => {
+            clipboard::copy_to_clipboard(answer.content.trim().to_string(), state.config.clipboard.backend);
+            state.status_message = Some((t("status.answer_copied").to_string(), Instant::now()));
+        }
+        None => state.status_message = Some((t("status.no_answer").to_string(), Instant::now())),
+    }
+}
+
+/// Copies message `index` in the format picked from the copy menu, and
+/// closes the menu.
+fn copy_message(state: &mut SharedState, index: usize, format: CopyFormat) {
+    let message = match state.chat_history.get(index).and_then(|msg| msg.copy_text(format)) {
+        Some(text) => {
+            clipboard::copy_to_clipboard(text, state.config.clipboard.backend);
+            t("status.message_copied")
+        }
+        None => t("status.message_no_code"),
+    };
+    state.status_message = Some((message.to_string(), Instant::now()));
+    state.mode = AppMode::Chat;
+}
+
+/// Ctrl+K: copies the next code block of the last answer to the clipboard,
+/// starting over at the first one after the last.
+fn copy_next_code_block(app: &mut App<'_>, state: &mut SharedState) {
+    let last_answer = state.chat_history.iter().rposition(|msg| msg.role == Role::Assistant);
+    let (index, blocks) = match last_answer.map(|index| (index, markdown::code_blocks(&state.chat_history[index].display_text()))) {
+        Some((index, blocks)) if !blocks.is_empty() => (index, blocks),
+        _ => {
+            state.status_message = Some((t("status.no_code_blocks").to_string(), Instant::now()));
+            return;
+        }
+    };
+    let block = match app.code_block {
+        Some((message, block)) if message == index => (block + 1) % blocks.len(),
+        _ => 0,
+    };
+    app.code_block = Some((index, block));
+    clipboard::copy_to_clipboard(blocks[block].clone(), state.config.clipboard.backend);
+    let (number, count) = ((block + 1).to_string(), blocks.len().to_string());
+    state.status_message = Some((tf("status.code_copied", &[("number", number.as_str()), ("count", count.as_str())]), Instant::now()));
+}
+
+/// Pushes the user's message (with any attached files) to the history and
+/// hands it to the background worker.
+fn send_message(app: &mut App<'_>, state: &mut SharedState, input: String) {
+    // One answer at a time per conversation; the prompt stays in the input box
+    if state.generating() || state.busy_since().is_some() {
+        state.status_message = Some((t("status.answer_in_progress").to_string(), Instant::now()));
+        return;
+    }
+    if app.prompt_history.last() != Some(&input) {
+        app.prompt_history.push(input.clone());
+    }
+    if state.config.sync.enabled {
+        app.dispatch(state, Action::SharePrompt(input.clone()));
+    }
+    syslog::prompt(&state.config.syslog, &state.conversation_id, &input);
+    let input = attachments::append_attachments(&input, &app.attached_paths(&input));
+    app.attachments.clear();
+    state.followups.remove(&state.conversation_id);
+    state.push_message(ChatMessage::user(input.clone()));
+    state.busy.insert(state.conversation_id.clone(), Instant::now());
+    app.scroll = u16::MAX;
+    app.dispatch(state, Action::SendMessage(input));
+    app.reset_input("");
+}
+
+fn input_is_empty(app: &App<'_>) -> bool {
+    app.textarea.lines().iter().all(|line| line.is_empty())
+}
+
+/// Runs a slash command typed into the input box. Unknown commands are never
+/// sent to the model; the input is kept so the typo can be fixed.
+fn run_command(app: &mut App<'_>, state: &mut SharedState, command: ParsedCommand<'_>, should_quit: &mut bool) {
+    match command {
+        ParsedCommand::Unknown { typed, suggestion } => {
+            let msg = match suggestion {
+                Some(name) => tf("status.unknown_command_suggest", &[("typed", typed), ("suggestion", name)]),
+                None => tf("status.unknown_command", &[("typed", typed)]),
+            };
+            state.status_message = Some((msg, Instant::now()));
+            return;
+        }
+        ParsedCommand::Known { name, args } => match name {
+            "help" => state.mode = AppMode::Help,
+            "settings" => {
+                state.mode = AppMode::Settings;
+                app.dispatch(state, Action::RefreshModelsAndStatus { scheduled: false });
+            }
+            "clear" => clear_chat(app, state),
+            "new" => open_template_picker(app, state),
+            "model" => select_model(app, state, args),
+            "context" => open_context_panel(app, state),
+            "sessions" => match args {
+                "clean" => clean_sessions(app, state, false),
+                "clean now" => clean_sessions(app, state, true),
+                "" => {
+                    app.session_tag_filter = None;
+                    open_session_browser(app, state);
+                }
+                tag => match session::normalize_tag(tag) {
+                    Some(tag) => {
+                        app.session_tag_filter = Some(tag);
+                        open_session_browser(app, state);
+                    }
+                    None => state.status_message = Some((tf("status.tag_invalid", &[("tag", tag)]), Instant::now())),
+                },
+            },
+            "tag" => tag_session(state, args),
+            "rename" => rename_session(state, args),
+            "export" => match args.split_once(char::is_whitespace).unwrap_or((args, "")) {
+                ("script", path) => export_script(state, path.trim()),
+                _ => state.status_message = Some((t("status.export_usage").to_string(), Instant::now())),
+            },
+            "search" => open_search(app, state, args),
+            "tab" => {
+                state.new_tab(app.scroll);
+                app.reset_message_view();
+            }
+            "closetab" => {
+                if let Some(scroll) = state.close_tab() {
+                    app.restore_message_view(scroll);
+                }
+            }
+            "deadletters" => {
+                app.dispatch(state, Action::ListDeadLetters);
+            }
+            "index" => {
+                state.status_message = Some((t("status.indexing_start").to_string(), Instant::now()));
+                app.dispatch(state, Action::IndexProject);
+            }
+            "inventory" => {
+                state.status_message = Some((t("status.inventory_refreshing").to_string(), Instant::now()));
+                app.dispatch(state, Action::RefreshInventory);
+            }
+            "verbosity" => match args {
+                "" => set_verbosity(state, state.config.verbosity.next()),
+                name => match Verbosity::parse(name) {
+                    Some(verbosity) => set_verbosity(state, verbosity),
+                    None => {
+                        state.status_message = Some((t("status.verbosity_usage").to_string(), Instant::now()));
+                        return;
+                    }
+                },
+            },
+            "prompt" => open_prompt_editor(app, state),
+            "set" => {
+                if let Err(e) = state.session_options.apply(args) {
+                    state.status_message = Some((tf("status.options_invalid", &[("error", e.as_str())]), Instant::now()));
+                    return;
+                }
+                if !args.is_empty() {
+                    state.save_session();
+                }
+                show_model_options(state);
+            }
+            "debug" => match args {
+                "prompt" => show_request_payload(app, state),
+                _ => state.status_message = Some((t("status.debug_usage").to_string(), Instant::now())),
+            },
+            "compact" => {
+                state.config.layout.compact = !state.config.layout.compact;
+                state.config.save();
+                let key = if state.config.layout.compact { "status.compact_on" } else { "status.compact_off" };
+                state.status_message = Some((t(key).to_string(), Instant::now()));
+            }
+            "requeue" => {
+                let target = match args {
+                    "all" => Some(None),
+                    n => n.parse::<usize>().ok().filter(|n| *n > 0).map(|n| Some(n - 1)),
+                };
+                match target {
+                    Some(index) => {
+                        app.dispatch(state, Action::RequeueDeadLetters(index));
+                    }
+                    None => {
+                        state.status_message = Some((t("status.requeue_usage").to_string(), Instant::now()));
+                        return;
+                    }
+                }
+            }
+            "quit" => *should_quit = true,
+            _ => {}
+        },
+    }
+    app.reset_input("");
+}
+
+/// Switches to tab `index`, each tab keeping its own scroll position.
+fn switch_tab(app: &mut App<'_>, state: &mut SharedState, index: usize) {
+    if index == state.active_tab || index >= state.tabs.len() {
+        return;
+    }
+    if let Some(scroll) = state.switch_tab(index, app.scroll) {
+        app.restore_message_view(scroll);
+    }
+}
+
+/// Turns mouse capture on or off for this run and the next ones. Without it
+/// the terminal selects and copies text itself, and the keyboard scrolls.
+/// Terminals that report no mouse are never sent the capture sequences.
+fn toggle_mouse(app: &mut App<'_>, state: &mut SharedState) {
+    if !app.mouse_capture && !mouse::terminal_reports_mouse() {
+        let term = std::env::var("TERM").unwrap_or_default();
+        state.status_message = Some((tf("status.mouse_unsupported", &[("term", term.as_str())]), Instant::now()));
+        return;
+    }
+    app.mouse_capture = !app.mouse_capture;
+    app.selection_range = None;
+    let mut out = io::stdout();
+    let switched = if app.mouse_capture {
+        out.execute(crossterm::event::EnableMouseCapture).map(|_| ())
+    } else {
+        out.execute(crossterm::event::DisableMouseCapture).map(|_| ())
+    };
+    if let Err(e) = switched {
+        log::warn!("Failed to switch mouse capture: {}", e);
+    }
+    state.config.mouse.enabled = app.mouse_capture;
+    state.config.save();
+    let key = if app.mouse_capture { "status.mouse_on" } else { "status.mouse_off" };
+    state.status_message = Some((t(key).to_string(), Instant::now()));
+}
+
+/// Switches the answer length preset for the following requests and saves it.
+fn set_verbosity(state: &mut SharedState, verbosity: Verbosity) {
+    state.config.verbosity = verbosity;
+    state.config.save();
+    state.status_message = Some((tf("status.verbosity", &[("verbosity", verbosity.label())]), Instant::now()));
+}
+
+/// Shows the parameters the next request goes out with.
+fn show_model_options(state: &mut SharedState) {
+    let options = state.model_options();
+    let message = if options.is_empty() {
+        t("status.options_default").to_string()
+    } else {
+        tf("status.options", &[("options", options.describe().as_str())])
+    };
+    state.status_message = Some((message, Instant::now()));
+}
+
+fn clear_chat(app: &mut App<'_>, state: &mut SharedState) {
+    state.note_closing();
+    state.clear_history();
+    app.reset_message_view();
+}
+
+/// Moves the message focus by `delta`, starting from the newest message.
+/// Moving past the newest message hands focus back to the input box.
+fn move_message_focus(app: &mut App<'_>, history_len: usize, delta: isize) {
+    if history_len == 0 {
+        return;
+    }
+    let next = match app.focused_message {
+        None if delta < 0 => Some(history_len - 1),
+        None => None,
+        Some(index) => {
+            let target = index as isize + delta;
+            if target < 0 {
+                Some(0)
+            } else if target as usize >= history_len {
+                None
+            } else {
+                Some(target as usize)
+            }
+        }
+    };
+    if next != app.focused_message {
+        app.hscroll = 0;
+    }
+    app.focused_message = next;
+}
+
+/// Keys of the Ctrl+F search bar: the query is typed, then Enter leaves n
+/// and N to step up and down through the matches. Returns false for a key
+/// that closes the search and goes on to the chat.
+fn handle_chat_search(app: &mut App<'_>, key: KeyEvent, command: Option<UiCommand>) -> bool {
+    let Some(search) = &mut app.chat_search else {
+        return false;
+    };
+    if command == Some(UiCommand::FindInChat) {
+        search.editing = true;
+        return true;
+    }
+    if key.code == KeyCode::Esc {
+        app.chat_search = None;
+        return true;
+    }
+    if search.editing {
+        match key.code {
+            KeyCode::Enter => search.editing = false,
+            KeyCode::Backspace => {
+                search.query.pop();
+                search.restart();
+            }
+            KeyCode::Up => search.step(-1),
+            KeyCode::Down => search.step(1),
+            KeyCode::Char(c) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                search.query.push(c);
+                search.restart();
+            }
+            _ => {}
+        }
+        return true;
+    }
+    match key.code {
+        // Upwards first, as answers being looked for are usually earlier ones
+        KeyCode::Char('n') => search.step(-1),
+        KeyCode::Char('N') => search.step(1),
+        _ => {
+            app.chat_search = None;
+            return false;
+        }
+    }
+    true
+}
+
+/// Keys while a message is focused. Returns false for keys meant for the input
+/// box, which also gives the focus back to it.
+fn handle_focused_message(app: &mut App<'_>, state: &mut SharedState, key: KeyEvent) -> bool {
+    let history_len = state.chat_history.len();
+    let delete_armed = std::mem::take(&mut app.message_delete_armed);
+    match app.keymap.map(InputContext::FocusedMessage, key) {
+        Some(UiCommand::FocusPreviousMessage) => move_message_focus(app, history_len, -1),
+        Some(UiCommand::FocusNextMessage) => move_message_focus(app, history_len, 1),
+        Some(UiCommand::ScrollLeft) => app.hscroll = app.hscroll.saturating_sub(HSCROLL_STEP),
+        Some(UiCommand::ScrollRight) => app.hscroll = app.hscroll.saturating_add(HSCROLL_STEP),
+        // Vimium's `f`, away from the input where it would be typed
+        Some(UiCommand::OpenHints) => open_hints(state),
+        Some(UiCommand::ToggleWrap) => {
+            if let Some(index) = app.focused_message {
+                if !app.wrapped_messages.remove(&index) {
+                    app.wrapped_messages.insert(index);
+                }
+            }
+        }
+        // Take the message out of the conversation and what is sent to the model
+        Some(UiCommand::DeleteItem) => {
+            let Some(index) = app.focused_message else {
+                return true;
+            };
+            if delete_armed {
+                state.remove_message(index);
+                state.save_session();
+                app.forget_message(index, state.chat_history.len());
+                state.status_message = Some((t("status.message_deleted").to_string(), Instant::now()));
+            } else {
+                app.message_delete_armed = true;
+                state.status_message = Some((t("status.message_delete_confirm").to_string(), Instant::now()));
+            }
+        }
+        // Fork the conversation here to try another follow-up in a new tab
+        Some(UiCommand::BranchConversation) => {
+            let Some(index) = app.focused_message else {
+                return true;
+            };
+            state.branch_tab(index, app.scroll);
+            app.reset_message_view();
+            state.status_message = Some((tf("status.branched", &[("count", (index + 1).to_string().as_str())]), Instant::now()));
+        }
+        // Finish reason, token counts and the raw final chunk of an answer
+        Some(UiCommand::ToggleDebugInfo) => {
+            if let Some(index) = app.focused_message.filter(|&index| state.chat_history[index].debug.is_some()) {
+                if !app.debug_messages.remove(&index) {
+                    app.debug_messages.insert(index);
+                }
+            }
+        }
+        // Fold the focused message's turn down to its prompt and answer
+        Some(UiCommand::FoldTurn) => {
+            if let Some(start) = app.focused_message.and_then(|index| state.turn_start(index)) {
+                if !app.folded_turns.remove(&start) {
+                    app.folded_turns.insert(start);
+                }
+            }
+        }
+        // Fold every turn with tool steps, or unfold all if any is folded
+        Some(UiCommand::FoldAllTurns) => {
+            if app.folded_turns.is_empty() {
+                app.folded_turns = (0..state.chat_history.len())
+                    .filter(|&index| state.chat_history[index].role == Role::User && !state.turn_tool_steps(index).is_empty())
+                    .collect();
+            } else {
+                app.folded_turns.clear();
+            }
+        }
+        // Re-run a failed tool task as is, without asking the model again
+        Some(UiCommand::RetryTool) => {
+            if let Some(tool) = app.focused_message.and_then(|index| state.failed_tool_call(index)) {
+                app.dispatch(state, Action::RunTool(tool));
+                app.focused_message = None;
+                app.scroll = u16::MAX;
+            }
+        }
+        // Copy the message as shown, as plain text, its code or a quote
+        Some(UiCommand::OpenCopyMenu) => {
+            if let Some(index) = app.focused_message {
+                state.mode = AppMode::CopyMenu(index);
+            }
+        }
+        Some(UiCommand::Close) => app.focused_message = None,
+        _ => {
+            app.focused_message = None;
+            return false;
+        }
+    }
+    true
+}
+
+fn open_template_picker(app: &mut App<'_>, state: &mut SharedState) {
+    app.template_list_state.select(Some(0));
+    state.mode = AppMode::TemplatePicker(templates::all_templates(&state.config, &state.shared_templates));
+}
+
+/// Aborts the answer being generated in the active conversation, keeping
+/// what has streamed so far.
+fn cancel_generation(app: &mut App<'_>, state: &mut SharedState) {
+    state.busy.remove(&state.conversation_id);
+    let Some(generation) = state.generations.remove(&state.conversation_id) else {
+        return;
+    };
+    if generation.is_finished() {
+        return;
+    }
+    generation.abort();
+    // Filtered like a finished answer
+    let partial = app.streaming.remove(&state.conversation_id).map(|(_, partial)| partial).unwrap_or_default();
+    let partial = postprocess::apply(&state.config.postprocess, partial.trim_end());
+    state.push_message(ChatMessage::cancelled(partial.trim_end()));
+}
+
+/// Opens the session browser with the current conversation saved, so it is
+/// listed with its latest messages.
+fn open_session_browser(app: &mut App<'_>, state: &mut SharedState) {
+    state.save_session();
+    let sessions = browser_sessions(app);
+    let current = sessions.iter().position(|meta| meta.id == state.conversation_id);
+    app.session_list_state.select(current.or(Some(0)));
+    app.session_rename = None;
+    app.session_delete_armed = false;
+    state.mode = AppMode::SessionBrowser(sessions);
+}
+
+/// The saved sessions the browser lists: all of them, or those with the
+/// tag it is filtered by.
+fn browser_sessions(app: &App<'_>) -> Vec<SessionMeta> {
+    let mut sessions = session::list();
+    if let Some(tag) = &app.session_tag_filter {
+        sessions.retain(|meta| meta.tags.contains(tag));
+    }
+    sessions
+}
+
+/// `/tag`: shows the conversation's tags, or adds and removes some.
+fn tag_session(state: &mut SharedState, args: &str) {
+    if let Err(word) = session::edit_tags(&mut state.session_tags, args) {
+        state.status_message = Some((tf("status.tag_invalid", &[("tag", word.as_str())]), Instant::now()));
+        return;
+    }
+    if !args.is_empty() {
+        state.save_session();
+    }
+    let msg = if state.session_tags.is_empty() {
+        t("status.tags_none").to_string()
+    } else {
+        tf("status.tags", &[("tags", session::format_tags(&state.session_tags).as_str())])
+    };
+    state.status_message = Some((msg, Instant::now()));
+}
+
+/// `/rename <title>`: titles the conversation, replacing a generated title.
+fn rename_session(state: &mut SharedState, title: &str) {
+    if title.is_empty() {
+        state.status_message = Some((t("status.rename_usage").to_string(), Instant::now()));
+        return;
+    }
+    state.save_session();
+    let id = state.conversation_id.clone();
+    state.rename_session(&id, title);
+    state.status_message = Some((tf("status.renamed", &[("title", title)]), Instant::now()));
+}
+
+/// `/export script`: writes the conversation's `exec` and `remote_exec`
+/// commands to an executable script, `lucius-<id>.sh` in the working
+/// directory unless a file is given. An existing file is left alone.
+fn export_script(state: &mut SharedState, path: &str) {
+    let script = export::shell_script(&state.chat_history, state.session_title.as_deref(), &state.session_tags);
+    if !script.lines().any(|line| !line.is_empty() && !line.starts_with('#')) {
+        state.status_message = Some((t("status.export_empty").to_string(), Instant::now()));
+        return;
+    }
+    let path = match path {
+        "" => format!("lucius-{}.sh", state.conversation_id.get(..8).unwrap_or(&state.conversation_id)),
+        path => hints::expand_path(path).to_string_lossy().into_owned(),
+    };
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| io::Write::write_all(&mut file, script.as_bytes()))
+        .and_then(|_| make_executable(&path));
+    let msg = match written {
+        Ok(()) => tf("status.exported", &[("path", path.as_str())]),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => tf("status.export_exists", &[("path", path.as_str())]),
+        Err(e) => tf("status.export_failed", &[("path", path.as_str()), ("error", e.to_string().as_str())]),
+    };
+    state.status_message = Some((msg, Instant::now()));
+}
+
+#[cfg(unix)]
+fn make_executable(path: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// `/sessions clean`: lists the sessions the `[sessions]` retention limits
+/// would remove, or removes them with `now`.
+fn clean_sessions(app: &mut App<'_>, state: &mut SharedState, now: bool) {
+    state.save_session();
+    let retention = state.config.sessions.clone();
+    let (plan, bytes) = session::prune_candidates(&retention);
+    if plan.is_empty() {
+        state.status_message = Some((t("status.sessions_clean_nothing").to_string(), Instant::now()));
+        return;
+    }
+    let count = plan.len().to_string();
+    let size = format!("{:.1}", bytes as f64 / (1024.0 * 1024.0));
+    let args = [("count", count.as_str()), ("size", size.as_str())];
+    if now {
+        session::prune(&retention);
+        state.status_message = Some((tf("status.sessions_cleaned", &args), Instant::now()));
+        return;
+    }
+    // Listed in the session browser rather than the conversation, which
+    // would save the preview with it
+    app.session_tag_filter = None;
+    app.session_list_state.select(Some(0));
+    app.session_rename = None;
+    app.session_delete_armed = false;
+    state.mode = AppMode::SessionBrowser(plan);
+    state.status_message = Some((tf("sessions.clean_summary", &args), Instant::now()));
+}
+
+/// Keys in the session browser: Enter opens, `r` renames, `s` stars and `d`
+/// twice deletes.
+fn handle_session_browser(app: &mut App<'_>, state: &mut SharedState, key: KeyEvent, command: Option<UiCommand>) {
+    let AppMode::SessionBrowser(sessions) = &state.mode else {
+        return;
+    };
+    let selected = app.session_list_state.selected().and_then(|i| sessions.get(i)).cloned();
+    let count = sessions.len().max(1);
+
+    if let Some(editor) = &mut app.session_rename {
+        match command {
+            Some(UiCommand::Choose) => {
+                let title = editor.lines().join(" ").trim().to_string();
+                app.session_rename = None;
+                if let (Some(meta), false) = (selected, title.is_empty()) {
+                    state.rename_session(&meta.id, &title);
+                    open_session_browser(app, state);
+                    app.session_list_state.select(browser_sessions(app).iter().position(|other| other.id == meta.id));
+                }
+            }
+            Some(UiCommand::Close) => app.session_rename = None,
+            _ => { editor.input(Input::from(key)); }
+        }
+        return;
+    }
+
+    let delete_armed = std::mem::take(&mut app.session_delete_armed);
+    let Some(command) = command else {
+        return;
+    };
+    match command {
+        UiCommand::SelectNext => app.session_list_state.select(Some(app.session_list_state.selected().map_or(0, |i| (i + 1) % count))),
+        UiCommand::SelectPrevious => app.session_list_state.select(Some(app.session_list_state.selected().map_or(0, |i| (i + count - 1) % count))),
+        UiCommand::Choose => {
+            let Some(meta) = selected else {
+                return;
+            };
+            match session::load(&meta) {
+                Some(saved) => {
+                    let scroll = state.open_session(saved, app.scroll);
+                    app.restore_message_view(scroll);
+                    state.mode = AppMode::Chat;
+                }
+                None => state.status_message = Some((tf("status.session_unreadable", &[("title", meta.title.as_str())]), Instant::now())),
+            }
+        }
+        UiCommand::RenameSession => {
+            if let Some(meta) = selected {
+                app.session_rename = Some(TextArea::new(vec![meta.title]));
+            }
+        }
+        UiCommand::FilterByTag => {
+            // Cycle through every tag in use, then back to all sessions
+            let mut tags: Vec<String> = session::list().into_iter().flat_map(|meta| meta.tags).collect();
+            tags.sort();
+            tags.dedup();
+            let next = match &app.session_tag_filter {
+                Some(current) => tags.iter().position(|tag| tag == current).and_then(|i| tags.get(i + 1)).cloned(),
+                None => tags.first().cloned(),
+            };
+            app.session_tag_filter = next;
+            open_session_browser(app, state);
+        }
+        UiCommand::ToggleStar => {
+            if let Some(meta) = selected {
+                let index = app.session_list_state.selected();
+                state.star_session(&meta.id, !meta.starred);
+                open_session_browser(app, state);
+                app.session_list_state.select(index);
+            }
+        }
+        UiCommand::DeleteItem => {
+            let Some(meta) = selected else {
+                return;
+            };
+            let was_current = meta.id == state.conversation_id;
+            if !delete_armed {
+                app.session_delete_armed = true;
+                state.status_message = Some((tf("status.session_delete_confirm", &[("title", meta.title.as_str())]), Instant::now()));
+            } else if state.delete_session(&meta) {
+                if was_current {
+                    app.reset_message_view();
+                }
+                let index = app.session_list_state.selected().unwrap_or(0);
+                open_session_browser(app, state);
+                app.session_list_state.select(Some(index.min(browser_sessions(app).len().saturating_sub(1))));
+                state.status_message = Some((tf("status.session_deleted", &[("title", meta.title.as_str())]), Instant::now()));
+            } else {
+                state.status_message = Some((t("status.session_open_in_tab").to_string(), Instant::now()));
+            }
+        }
+        UiCommand::Close => state.mode = AppMode::Chat,
+        _ => {}
+    }
+}
+
+/// Opens the search screen over all saved sessions, with the current one
+/// saved first so its latest messages are found too.
+fn open_search(app: &mut App<'_>, state: &mut SharedState, query: &str) {
+    state.save_session();
+    app.search_input = TextArea::new(vec![query.to_string()]);
+    app.search_input.move_cursor(tui_textarea::CursorMove::End);
+    app.search_list_state.select(Some(0));
+    app.search_edited = None;
+    state.mode = AppMode::Search(session::search(query));
+}
+
+/// Searches for the edited query once typing has paused for `SEARCH_DELAY`,
+/// or right away with `now`, since every search reads all saved sessions.
+/// Called on every tick of the UI loop.
+pub fn search_if_due(app: &mut App<'_>, state: &mut SharedState, now: bool) {
+    let Some(edited) = app.search_edited else {
+        return;
+    };
+    if !matches!(state.mode, AppMode::Search(_)) {
+        app.search_edited = None;
+    } else if now || edited.elapsed() >= SEARCH_DELAY {
+        let query = app.search_input.lines().join(" ");
+        app.search_list_state.select(Some(0));
+        app.search_edited = None;
+        state.mode = AppMode::Search(session::search(&query));
+    }
+}
+
+/// Keys on the search screen: typing refines the query, Up/Down pick a match
+/// and Enter opens its session at the matching message.
+fn handle_search(app: &mut App<'_>, state: &mut SharedState, key: KeyEvent, command: Option<UiCommand>) {
+    // Picking a match goes by the query as typed so far
+    if matches!(command, Some(UiCommand::SelectNext | UiCommand::SelectPrevious | UiCommand::Choose)) {
+        search_if_due(app, state, true);
+    }
+    let AppMode::Search(hits) = &state.mode else {
+        return;
+    };
+    let count = hits.len().max(1);
+    match command {
+        Some(UiCommand::SelectNext) => app.search_list_state.select(Some(app.search_list_state.selected().map_or(0, |i| (i + 1) % count))),
+        Some(UiCommand::SelectPrevious) => app.search_list_state.select(Some(app.search_list_state.selected().map_or(0, |i| (i + count - 1) % count))),
+        Some(UiCommand::Choose) => {
+            let Some(hit) = app.search_list_state.selected().and_then(|i| hits.get(i)).cloned() else {
+                return;
+            };
+            match session::load(&hit.session) {
+                Some(saved) => {
+                    let scroll = state.open_session(saved, app.scroll);
+                    app.restore_message_view(scroll);
+                    app.focused_message = hit.message.filter(|&index| index < state.chat_history.len());
+                    state.mode = AppMode::Chat;
+                }
+                None => state.status_message = Some((tf("status.session_unreadable", &[("title", hit.session.title.as_str())]), Instant::now())),
+            }
+        }
+        Some(UiCommand::Close) => state.mode = AppMode::Chat,
+        _ => {
+            if app.search_input.input(Input::from(key)) {
+                app.search_edited = Some(Instant::now());
+            }
+        }
+    }
+}
+
+/// Opens the editor on the conversation's system prompt.
+fn open_prompt_editor(app: &mut App<'_>, state: &mut SharedState) {
+    let text = state.session_system_prompt.clone().unwrap_or_default();
+    app.prompt_editor = TextArea::new(text.lines().map(str::to_string).collect());
+    app.prompt_editor.move_cursor(tui_textarea::CursorMove::Bottom);
+    app.prompt_editor.move_cursor(tui_textarea::CursorMove::End);
+    state.mode = AppMode::PromptEditor;
+}
+
+/// Keys in the system prompt editor: Ctrl+S keeps the text for this
+/// conversation (an empty one removes the prompt) and Esc drops the edits.
+fn handle_prompt_editor(app: &mut App<'_>, state: &mut SharedState, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => {
+            let text = app.prompt_editor.lines().join("\n");
+            state.session_system_prompt = Some(text.trim().to_string()).filter(|text| !text.is_empty());
+            state.save_session();
+            let key = if state.session_system_prompt.is_some() { "status.prompt_saved" } else { "status.prompt_removed" };
+            state.status_message = Some((t(key).to_string(), Instant::now()));
+            state.mode = AppMode::Chat;
+        }
+        KeyCode::Esc => state.mode = AppMode::Chat,
+        _ => {
+            app.prompt_editor.input(Input::from(key));
+        }
+    }
+}
+
+/// Opens the diff of a pending LUCIUS.md edit once the chat is back with an
+/// empty input box, so keys typed for a prompt never answer it.
+pub fn show_context_change(app: &mut App<'_>, state: &mut SharedState) {
+    if state.context_change.is_some() && state.mode == AppMode::Chat && input_is_empty(app) {
+        state.mode = AppMode::ContextDiff;
+        app.context_diff_scroll = 0;
+    }
+}
+
+fn open_context_panel(app: &mut App<'_>, state: &mut SharedState) {
+    app.context_list_state.select(Some(0));
+    state.mode = AppMode::ContextPanel;
+}
+
+/// Opens the panel listing the context attached to the next request, from
+/// files and `@path` mentions to excerpts sent before.
+fn open_attached_panel(app: &mut App<'_>, state: &mut SharedState) {
+    app.attached_list_state.select(Some(0));
+    state.mode = AppMode::AttachedPanel;
+}
+
+/// Takes an attached file or excerpt out of the next request, or puts back
+/// one taken out before. Files not sent yet are dropped outright, and
+/// `@path` mentions of them become plain text.
+fn remove_attached(app: &mut App<'_>, state: &mut SharedState, source: AttachedSource) {
+    match source {
+        AttachedSource::Pending(path) => {
+            app.attachments.retain(|attached| *attached != path);
+            let input = app.textarea.lines().join("\n");
+            if attachments::mentions(&input).contains(&path) {
+                app.reset_input(&attachments::unmention(&input, &path));
+            }
+            let count = app.attached_items(state).len();
+            if app.attached_list_state.selected().is_some_and(|i| i >= count) {
+                app.attached_list_state.select(count.checked_sub(1));
+            }
+        }
+        AttachedSource::Sent(section) => state.toggle_context(section),
+        AttachedSource::Excerpt(path, line) => {
+            if !state.skipped_excerpts.remove(&(path.clone(), line)) {
+                state.skipped_excerpts.insert((path, line));
+            }
+        }
+    }
+}
+
+/// Labels the links and file paths of the conversation for hint mode, or
+/// says there are none.
+fn open_hints(state: &mut SharedState) {
+    let found = hints::collect_hints(&state.chat_history);
+    if found.is_empty() {
+        state.status_message = Some((t("status.no_hints").to_string(), Instant::now()));
+    } else {
+        state.mode = AppMode::Hints(found);
+    }
+}
+
+/// Opens the tool palette with favorites first, or explains why it is empty.
+fn open_tool_palette(app: &mut App<'_>, state: &mut SharedState) {
+    if state.worker_tools.is_empty() {
+        state.status_message = Some((t("status.no_tools").to_string(), Instant::now()));
+        return;
+    }
+    let mut tools: Vec<_> = state.worker_tools.iter().filter(|tool| !state.project_profile().forbids(&tool.name)).cloned().collect();
+    let favorites = &state.config.favorite_tools;
+    tools.sort_by_key(|tool| !favorites.contains(&tool.name));
+    app.tool_list_state.select(Some(0));
+    state.mode = AppMode::ToolPalette(tools);
+}
+
+/// Shows the exact JSON the next request would send, as a notice that never
+/// reaches the model itself.
+fn show_request_payload(app: &mut App<'_>, state: &mut SharedState) {
+    let body = llm::provider_with(&state.config, &state.model_options()).request_body(&state.prompt());
+    let json = serde_json::to_string_pretty(&body).unwrap_or_default();
+    let title = tf("chat.debug_prompt", &[("provider", state.config.provider.label())]);
+    state.push_message(ChatMessage::notice(format!("{}\n```json\n{}\n```", title, json)));
+    app.scroll = u16::MAX;
+}
+
+fn select_model(app: &mut App<'_>, state: &mut SharedState, name: &str) {
+    let msg = if name.is_empty() {
+        t("status.model_usage").to_string()
+    } else if let Some(index) = state.models.iter().position(|m| m.name == name) {
+        app.model_list_state.select(Some(index));
+        if state.config.selected_model.as_deref() != Some(name) {
+            app.dispatch(state, Action::WarmUpModel(name.to_string()));
+        }
+        state.config.selected_model = Some(name.to_string());
+        state.config.save();
+        tf("status.model_switched", &[("model", name)])
+    } else {
+        tf("status.model_missing", &[("model", name)])
+    };
+    state.status_message = Some((msg, Instant::now()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+    use crate::config::Config;
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[tokio::test]
+    async fn cancelled_answers_are_filtered_like_finished_ones() {
+        let mut app = App::new(mpsc::channel(8).0, &Config::default());
+        let mut state = SharedState::for_tests();
+        let mut quit = false;
+        state.config.postprocess.filters.push(postprocess::Filter::RedactPii);
+        let generation = tokio::spawn(std::future::pending::<()>());
+        state.generations.insert(state.conversation_id.clone(), generation.abort_handle());
+        app.streaming.insert(state.conversation_id.clone(), (1, "Mail admin@example.com about   \n```bash\nls".to_string()));
+
+        handle_event(&mut app, &mut state, key(KeyCode::Esc), &mut quit).await;
+        let answer = state.chat_history.last().unwrap();
+        assert!(answer.cancelled && !state.generating());
+        assert!(answer.content.starts_with("Mail [email redacted] about\n```bash\nls"));
+        assert!(generation.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn follow_ups_are_sent_with_function_keys_only() {
+        let (action_tx, mut action_rx) = mpsc::channel(8);
+        let mut app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        let mut quit = false;
+        state.followups.insert(state.conversation_id.clone(), vec!["Which mount?".to_string(), "Since when?".to_string()]);
+
+        handle_event(&mut app, &mut state, key(KeyCode::Char('2')), &mut quit).await;
+        assert_eq!(app.textarea.lines(), ["2"]);
+        handle_event(&mut app, &mut state, key(KeyCode::F(2)), &mut quit).await;
+        assert!(action_rx.try_recv().is_err());
+
+        app.reset_input("");
+        handle_event(&mut app, &mut state, key(KeyCode::F(3)), &mut quit).await;
+        assert!(action_rx.try_recv().is_err());
+        handle_event(&mut app, &mut state, key(KeyCode::F(2)), &mut quit).await;
+        assert!(matches!(action_rx.try_recv(), Ok(Action::SendMessage(prompt)) if prompt == "Since when?"));
+    }
+
+    #[tokio::test]
+    async fn f_on_a_focused_message_starts_hint_mode() {
+        let mut app = App::new(mpsc::channel(8).0, &Config::default());
+        let mut state = SharedState::for_tests();
+        let mut quit = false;
+        state.chat_history.push(ChatMessage::assistant("See https://example.com/status"));
+
+        // Typed into the input as usual
+        handle_event(&mut app, &mut state, key(KeyCode::Char('f')), &mut quit).await;
+        assert_eq!(app.textarea.lines(), ["f"]);
+        assert!(state.mode == AppMode::Chat);
+
+        app.focused_message = Some(0);
+        handle_event(&mut app, &mut state, key(KeyCode::Char('f')), &mut quit).await;
+        assert!(matches!(&state.mode, AppMode::Hints(found) if found.len() == 1));
+        assert_eq!(app.textarea.lines(), ["f"]);
+    }
+
+    #[tokio::test]
+    async fn pastes_never_send_and_enter_always_does() {
+        let (action_tx, mut action_rx) = mpsc::channel(8);
+        let mut app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        let mut quit = false;
+
+        handle_event(&mut app, &mut state, Event::Paste("df -h\r\nfree -m".to_string()), &mut quit).await;
+        assert_eq!(app.textarea.lines(), ["df -h", "free -m"]);
+        assert!(action_rx.try_recv().is_err());
+
+        // Keys handled back to back, as after the UI waited on the state
+        app.reset_input("");
+        for c in "uptime".chars() {
+            handle_event(&mut app, &mut state, key(KeyCode::Char(c)), &mut quit).await;
+        }
+        handle_event(&mut app, &mut state, key(KeyCode::Enter), &mut quit).await;
+        assert!(matches!(action_rx.try_recv(), Ok(Action::SendMessage(prompt)) if prompt == "uptime"));
+        assert!(input_is_empty(&app));
+    }
+
+    #[test]
+    fn lucius_md_edits_are_shown_once_the_input_is_empty() {
+        let (action_tx, _action_rx) = mpsc::channel(1);
+        let mut app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        state.push_message(ChatMessage::user("hi"));
+        state.reload_lucius_context(crate::context::ProjectContext { prompt: Some("Be brief.".into()), ..Default::default() });
+
+        app.reset_input("and now");
+        show_context_change(&mut app, &mut state);
+        assert!(state.mode == AppMode::Chat);
+
+        app.reset_input("");
+        app.context_diff_scroll = 7;
+        show_context_change(&mut app, &mut state);
+        assert!(state.mode == AppMode::ContextDiff);
+        assert_eq!(app.context_diff_scroll, 0);
+    }
+
+    #[test]
+    fn i_toggles_how_a_focused_answer_ended() {
+        let mut app = App::new(mpsc::channel(1).0, &Config::default());
+        let mut state = SharedState::for_tests();
+        let debug = crate::llm::ResponseDebug { finish_reason: Some("length".into()), prompt_tokens: None, output_tokens: None, generation_ms: None, raw: "{}".into() };
+        state.push_message(ChatMessage::user("hi"));
+        state.push_message(ChatMessage { debug: Some(debug), ..ChatMessage::assistant("Hel") });
+        let i = KeyEvent::new(KeyCode::Char('i'), KeyModifiers::NONE);
+
+        app.focused_message = Some(1);
+        handle_focused_message(&mut app, &mut state, i);
+        assert_eq!(app.debug_messages, [1].into());
+        handle_focused_message(&mut app, &mut state, i);
+        assert!(app.debug_messages.is_empty());
+
+        // Prompts have nothing to show
+        app.focused_message = Some(0);
+        handle_focused_message(&mut app, &mut state, i);
+        assert!(app.debug_messages.is_empty());
+    }
+
+    #[test]
+    fn r_runs_a_failed_tool_call_again() {
+        let (action_tx, mut action_rx) = mpsc::channel(2);
+        let mut app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        let call = ToolCall { tool: "docker".to_string(), params: serde_json::json!({ "action": "ps" }), expect: vec![] };
+        state.push_message(ChatMessage::tool_call(&call));
+        state.push_message(ChatMessage::tool_result(&call, r#"{"exit_code": 0}"#));
+        state.push_message(ChatMessage::tool_result(&call, r#"{"exit_code": 1}"#));
+        let r = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::NONE);
+
+        // Only failed steps are retried
+        app.focused_message = Some(1);
+        handle_focused_message(&mut app, &mut state, r);
+        assert!(action_rx.try_recv().is_err());
+        app.focused_message = Some(2);
+        handle_focused_message(&mut app, &mut state, r);
+        assert!(matches!(action_rx.try_recv(), Ok(Action::RunTool(tool)) if tool == call));
+        assert!(app.focused_message.is_none());
+    }
+
+    #[tokio::test]
+    async fn session_search_waits_for_typing_to_pause() {
+        let (action_tx, _action_rx) = mpsc::channel(1);
+        let mut app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        let mut quit = false;
+        let meta = SessionMeta {
+            id: "old".to_string(),
+            title: "old".to_string(),
+            model: None,
+            updated: chrono::Utc::now(),
+            message_count: 1,
+            starred: false,
+            tags: vec![],
+            notes: None,
+            branch_of: None,
+            file: None,
+        };
+        let stale = session::SearchHit { session: meta, message: None, snippet: "old".to_string() };
+        state.mode = AppMode::Search(vec![stale]);
+
+        for c in "qx".chars() {
+            handle_event(&mut app, &mut state, key(KeyCode::Char(c)), &mut quit).await;
+        }
+        search_if_due(&mut app, &mut state, false);
+        assert!(matches!(&state.mode, AppMode::Search(hits) if hits.len() == 1));
+
+        app.search_edited = Some(Instant::now() - SEARCH_DELAY);
+        search_if_due(&mut app, &mut state, false);
+        assert!(app.search_edited.is_none());
+        assert!(matches!(&state.mode, AppMode::Search(hits) if hits.iter().all(|hit| hit.snippet != "old")));
+
+        // Picking a match searches for what was typed first
+        handle_event(&mut app, &mut state, key(KeyCode::Char('z')), &mut quit).await;
+        assert!(app.search_edited.is_some());
+        handle_event(&mut app, &mut state, key(KeyCode::Down), &mut quit).await;
+        assert!(app.search_edited.is_none());
+    }
+
+    #[test]
+    fn models_are_deleted_on_the_second_d_only() {
+        let (action_tx, mut action_rx) = mpsc::channel(2);
+        let mut app = App::new(action_tx, &Config::default());
+        let mut state = SharedState::for_tests();
+        state.models = vec![crate::llm::Model { name: "llama3".to_string(), size: None }];
+        app.model_list_state.select(Some(0));
+        let d = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::NONE);
+
+        handle_model_list(&mut app, &mut state, d);
+        assert!(app.model_delete_armed && action_rx.try_recv().is_err());
+        // Any other key disarms it
+        handle_model_list(&mut app, &mut state, KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        handle_model_list(&mut app, &mut state, d);
+        assert!(action_rx.try_recv().is_err());
+        handle_model_list(&mut app, &mut state, d);
+        assert!(matches!(action_rx.try_recv(), Ok(Action::DeleteModel(model)) if model == "llama3"));
+    }
+
+    #[test]
+    fn exported_scripts_never_replace_a_file() {
+        let mut state = SharedState::for_tests();
+        let call = lucius::mcp::ToolCall { tool: "exec".into(), params: serde_json::json!({"command": "df -h"}), expect: vec![] };
+        state.chat_history = vec![ChatMessage::tool_call(&call), ChatMessage::tool_result(&call, r#"{"exit_code": 0}"#)];
+        let path = std::env::temp_dir().join(format!("lucius-export-{}.sh", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        export_script(&mut state, path.to_str().unwrap());
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("df -h\n"));
+        std::fs::write(&path, "keep me").unwrap();
+        export_script(&mut state, path.to_str().unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+        assert!(state.status_message.as_ref().is_some_and(|(msg, _)| msg.contains("already exists")));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use lucius::mcp::{self, ApprovalDecision};
+
+use crate::app::SharedState;
+use crate::config::Config;
+use crate::llm::{self, LLMResponse};
+use crate::message::{ChatMessage, Role};
+use crate::postprocess;
+
+/// How many tool calls one run may make before it gives up on an answer.
+const MAX_TOOL_STEPS: usize = 8;
+
+/// `lucius -p "prompt"`: runs one prompt with the `LUCIUS.md` context and the
+/// registered MCP tools, prints the answer to stdout and exits, without
+/// touching the terminal. Input piped on stdin is appended to the prompt, so
+/// `git diff | lucius -p "review this"` works. Nobody can confirm tool calls
+/// here, so they only run with `--yes`; otherwise, or when a step fails, the
+/// reason goes to stderr and Lucius exits with status 1.
+pub async fn run(args: &[String]) -> io::Result<()> {
+    let Options { mut prompt, allow_tools, chaos } = match parse_args(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if !io::stdin().is_terminal() {
+        let mut piped = String::new();
+        io::stdin().read_to_string(&mut piped)?;
+        if !piped.trim().is_empty() {
+            prompt = if prompt.trim().is_empty() { piped } else { format!("{}\n\n{}", prompt, piped.trim_end()) };
+        }
+    }
+    if prompt.trim().is_empty() {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    }
+
+    let config = Config::load();
+    if chaos {
+        crate::chaos::enable(config.chaos.clone());
+    }
+    let state = Arc::new(Mutex::new(SharedState::headless(config).await));
+    {
+        let mut state_lock = state.lock().await;
+        if let Some(mut redis_conn) = state_lock.redis_conn.clone() {
+            match mcp::fetch_workers(&mut redis_conn).await {
+                Ok(workers) => {
+                    state_lock.worker_tools = mcp::aggregate_tools(&workers);
+                    state_lock.workers = workers;
+                }
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+        state_lock.push_message(ChatMessage::user(prompt));
+    }
+
+    for _ in 0..=MAX_TOOL_STEPS {
+        let (provider, request, postprocess) = {
+            let state_lock = state.lock().await;
+            (llm::provider(&state_lock.config), state_lock.prompt(), state_lock.config.postprocess.clone())
+        };
+        let tool = match provider.chat_stream(request, None).await {
+            Ok(LLMResponse::FinalResponse(text, debug)) => {
+                let text = postprocess::apply(&postprocess, &text);
+                state.lock().await.push_message(ChatMessage { debug, ..ChatMessage::assistant(text.clone()) });
+                let mut stdout = io::stdout();
+                writeln!(stdout, "{}", text.trim_end())?;
+                return stdout.flush();
+            }
+            Ok(LLMResponse::ToolCallDetected(tool)) => tool,
+            Err(e) => fail(format!("Error: {}", e)),
+        };
+
+        if !state.lock().await.tool_enabled(&tool.tool) {
+            fail(format!("Tool '{}' is not enabled.", tool.tool));
+        }
+        if !allow_tools {
+            crate::execute_tool(&state, &tool, ApprovalDecision::Declined).await;
+            fail(format!(
+                "The model asked to run '{}' with {}; pass --yes to allow tool calls.",
+                tool.tool, tool.params
+            ));
+        }
+        eprintln!("[tool] {} {}", tool.tool, tool.params);
+        crate::execute_tool(&state, &tool, ApprovalDecision::Approved).await;
+        // A result goes back to the model for the answer; a failed step ends the run
+        let state_lock = state.lock().await;
+        if let Some(error) = state_lock.chat_history.last().filter(|msg| msg.role == Role::Error) {
+            fail(error.content.clone());
+        }
+    }
+    fail(format!("No answer after {} tool calls.", MAX_TOOL_STEPS))
+}
+
+const USAGE: &str = "Usage: lucius -p \"prompt\" [--yes]";
+
+struct Options {
+    prompt: String,
+    allow_tools: bool,
+    chaos: bool,
+}
+
+/// Splits the flags from the prompt words. Anything else that looks like a
+/// flag is refused rather than sent to the model; after `--` every word
+/// belongs to the prompt.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { prompt: String::new(), allow_tools: false, chaos: false };
+    let mut words = Vec::new();
+    let mut flags_done = false;
+    for arg in args {
+        if flags_done || !arg.starts_with('-') || arg == "-" || arg.contains(char::is_whitespace) {
+            words.push(arg.as_str());
+            continue;
+        }
+        match arg.as_str() {
+            "-p" | "--print" => {}
+            "-y" | "--yes" => options.allow_tools = true,
+            "--chaos" => options.chaos = true,
+            "--" => flags_done = true,
+            _ => return Err(format!("Unknown option '{}'.", arg)),
+        }
+    }
+    options.prompt = words.join(" ");
+    Ok(options)
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_stay_out_of_the_prompt() {
+        let options = parse_args(&args(&["-p", "review", "this", "--yes", "--chaos"])).unwrap();
+        assert_eq!(options.prompt, "review this");
+        assert!(options.allow_tools && options.chaos);
+
+        let options = parse_args(&args(&["--print", "- list items", "--", "--verbose"])).unwrap();
+        assert_eq!(options.prompt, "- list items --verbose");
+        assert!(!options.allow_tools && !options.chaos);
+
+        assert!(parse_args(&args(&["-p", "hi", "--verbose"])).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::message::{ChatMessage, Role};
+
+/// Maximum number of hints shown at once; one per lowercase letter.
+pub const MAX_HINTS: usize = 26;
+
+lazy_static! {
+    pub static ref URL_REGEX: Regex = Regex::new(r#"https?://[^\s<>()\[\]"'`]+"#).unwrap();
+    static ref PATH_REGEX: Regex = Regex::new(r#"(?:^|[\s"'`(\[])((?:~/|\.{1,2}/|/)?(?:[\w.-]+/)+[\w.-]*|/[\w.-]+)"#).unwrap();
+}
+
+/// What a hint points at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintKind {
+    Url,
+    Path,
+}
+
+/// A labelled target in hint mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub kind: HintKind,
+    pub target: String,
+}
+
+/// Strips punctuation that commonly trails a URL in prose ("see https://x.org.").
+pub fn trim_url(url: &str) -> &str {
+    url.trim_end_matches(['.', ',', ';', ':', '!', '?'])
+}
+
+/// Returns the byte ranges of URLs and file paths in a single line, sorted and
+/// non-overlapping.
+pub fn find_spans(line: &str) -> Vec<(usize, usize, HintKind)> {
+    let mut spans: Vec<(usize, usize, HintKind)> = URL_REGEX
+        .find_iter(line)
+        .map(|m| (m.start(), m.start() + trim_url(m.as_str()).len(), HintKind::Url))
+        .collect();
+
+    for caps in PATH_REGEX.captures_iter(line) {
+        let m = match caps.get(1) {
+            Some(m) => m,
+            None => continue,
+        };
+        let path = m.as_str().trim_end_matches(['.', ',', ':']);
+        let end = m.start() + path.len();
+        let inside_url = spans.iter().any(|(s, e, kind)| *kind == HintKind::Url && m.start() < *e && end > *s);
+        if !inside_url && looks_like_path(path) {
+            spans.push((m.start(), end, HintKind::Path));
+        }
+    }
+    spans.sort_by_key(|(start, _, _)| *start);
+    spans
+}
+
+/// Filters out slash-separated prose like "and/or" or "TCP/IP": a path must be
+/// anchored (`/`, `~/`, `./`) or end in a file name with an extension.
+fn looks_like_path(candidate: &str) -> bool {
+    if candidate.len() < 2 {
+        return false;
+    }
+    let anchored = candidate.starts_with('/') || candidate.starts_with("~/") || candidate.starts_with('.');
+    let file_name = candidate.rsplit('/').next().unwrap_or("");
+    anchored || (file_name.contains('.') && !file_name.starts_with('.') && !file_name.ends_with('.'))
+}
+
+/// Collects the unique URLs mentioned anywhere in the chat history, plus file
+/// paths from assistant output and tool results. Keeps the most recent ones
+/// when there are more than can be labelled.
+pub fn collect_hints(history: &[ChatMessage]) -> Vec<Hint> {
+    let mut hints: Vec<Hint> = Vec::new();
+    for msg in history {
+        let paths_allowed = matches!(msg.role, Role::Assistant | Role::ToolResult);
+        for line in msg.content.lines() {
+            for (start, end, kind) in find_spans(line) {
+                if kind == HintKind::Path && !paths_allowed {
+                    continue;
+                }
+                let hint = Hint { kind, target: line[start..end].to_string() };
+                if let Some(pos) = hints.iter().position(|h| *h == hint) {
+                    hints.remove(pos);
+                }
+                hints.push(hint);
+            }
+        }
+    }
+    let skip = hints.len().saturating_sub(MAX_HINTS);
+    hints.split_off(skip)
+}
+
+/// The key that selects the hint at `index`.
+pub fn hint_label(index: usize) -> char {
+    (b'a' + (index % MAX_HINTS) as u8) as char
+}
+
+/// Maps a pressed key back to a hint index.
+pub fn hint_index(key: char) -> Option<usize> {
+    if key.is_ascii_lowercase() {
+        Some((key as u8 - b'a') as usize)
+    } else {
+        None
+    }
+}
+
+/// Opens `url` with `$BROWSER` if set, otherwise the platform opener.
+pub fn open_url(url: &str) -> Result<(), String> {
+    let opener = std::env::var("BROWSER")
+        .ok()
+        .and_then(|b| b.split(':').next().map(|s| s.to_string()))
+        .filter(|b| !b.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(target_os = "macos") { "open".to_string() } else { "xdg-open".to_string() }
+        });
+
+    log::info!("Opening {} with {}", url, opener);
+    Command::new(&opener)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to run {}: {}", opener, e))
+}
+
+/// Resolves a leading `~/` against the home directory.
+pub fn expand_path(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Runs `$EDITOR` (falling back to `vi`) on `path` and waits for it to exit.
+/// The caller is responsible for handing the terminal over first.
+pub fn edit_path(path: &str) -> Result<(), String> {
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    log::info!("Opening {} in {}", path, editor);
+    Command::new(&editor)
+        .arg(expand_path(path))
+        .status()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to run {}: {}", editor, e))
+}
+
+/// Quotes `s` for safe use as a single POSIX shell word.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
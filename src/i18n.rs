@@ -0,0 +1,774 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use serde::{Deserialize, Serialize};
+
+/// A UI language. Strings missing from a locale fall back to English.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Es,
+}
+
+static CURRENT_LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+/// Sets the language used by `t` and `tf` for the rest of the process.
+pub fn set_language(language: Language) {
+    let code = match language {
+        Language::En => 0,
+        Language::Es => 1,
+    };
+    CURRENT_LANGUAGE.store(code, Ordering::Relaxed);
+}
+
+pub fn current_language() -> Language {
+    match CURRENT_LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::Es,
+        _ => Language::En,
+    }
+}
+
+/// Picks a language from the `LANG`/`LC_ALL` environment when the config does not set one.
+pub fn detect_language() -> Language {
+    let locale = std::env::var("LC_ALL")
+        .or_else(|_| std::env::var("LANG"))
+        .unwrap_or_default();
+    if locale.starts_with("es") {
+        Language::Es
+    } else {
+        Language::En
+    }
+}
+
+/// Looks up the UI string for `key` in the current language.
+pub fn t(key: &str) -> &'static str {
+    let localized = match current_language() {
+        Language::En => None,
+        Language::Es => spanish(key),
+    };
+    localized.or_else(|| english(key)).unwrap_or_else(|| {
+        log::warn!("Missing translation key: {}", key);
+        "???"
+    })
+}
+
+/// Like `t`, replacing `{name}` placeholders with the given values.
+pub fn tf(key: &str, args: &[(&str, &str)]) -> String {
+    let mut text = t(key).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "help.body" => HELP_EN,
+        "help.title" => "Help",
+        "help.slash_commands" => "Slash commands:",
+        "help.mouse" => "Mouse Scroll: Scroll chat history\nMouse Drag: Select text and copy it (Shift + Drag: the terminal's own selection)\nClick the status line: Attached context panel",
+        "help.no_mouse" => "Up/Down: Scroll the conversation a line (empty input, while the mouse is not captured)",
+
+        "cmd.help" => "Show the help screen",
+        "cmd.settings" => "Open the settings screen",
+        "cmd.clear" => "Clear the conversation",
+        "cmd.new" => "Start a new conversation from a template",
+        "cmd.model" => "Switch to another installed model",
+        "cmd.deadletters" => "List MCP tasks that failed to parse or run",
+        "cmd.requeue" => "Requeue a dead-lettered task by number, or all of them",
+        "cmd.context" => "Show what the next request will send, with token estimates",
+        "cmd.inventory" => "Refresh the hosts, containers and ports the workers report",
+        "cmd.compact" => "Toggle the compact layout (no borders, dense spacing)",
+        "cmd.index" => "Embed the project's files so relevant excerpts join each prompt",
+        "cmd.debug" => "Show the exact JSON payload the next request would send",
+        "cmd.verbosity" => "Set the answer length, or cycle it without an argument (also Alt+V)",
+        "cmd.prompt" => "Edit this conversation's system prompt, sent after LUCIUS.md",
+        "cmd.set" => "Override model parameters for this conversation, e.g. temperature=0.2 (empty value resets)",
+        "cmd.tab" => "Open a new conversation in a tab",
+        "cmd.closetab" => "Close the current tab (its session stays saved)",
+        "cmd.sessions" => "Browse saved sessions, optionally only those with a tag; /sessions clean previews pruning",
+        "cmd.tag" => "Show, add or remove (-#tag) tags of this conversation",
+        "cmd.rename" => "Give this conversation a title",
+        "cmd.export" => "Save the commands run in this conversation as a shell script",
+        "cmd.search" => "Search all saved sessions",
+        "cmd.quit" => "Quit Lucius",
+
+        "context.title" => "Next request: ~{tokens} of {window} tokens",
+        "context.keys" => " Space: include/exclude · Esc: close ",
+        "context.tokens" => "{tokens} tok",
+        "context.lucius_md" => "System prompt: LUCIUS.md",
+        "context.session_prompt" => "System prompt: conversation template",
+        "context.tools" => "System prompt: tool descriptions",
+        "context.inventory" => "System prompt: inventory",
+        "context.verbosity" => "System prompt: answer length",
+        "context.excerpt" => "System prompt: excerpt {path}:{line}",
+        "attached.title" => "Attached context",
+        "attached.keys" => " x: remove/restore · Esc: close ",
+        "attached.empty" => "No files or excerpts attached",
+        "attached.pending" => "next",
+        "attached.file" => "file",
+        "attached.excerpt" => "excerpt",
+
+        "chat.title" => "Conversation",
+        "chat.input_title" => "Input",
+        "chat.placeholder" => "Ask me anything...",
+        "chat.dir" => "Dir: {dir}",
+        "chat.dir_error" => "Error getting dir",
+        "chat.model" => "Model: {model}",
+        "chat.no_model" => "No model selected",
+        "chat.using" => "using: {lucius_md} LUCIUS.md | {mcp} MCP server | context ~{tokens}/{window} tokens",
+        "chat.using_attached" => " | attached: {files} files, {excerpts} excerpts",
+        "chat.scroll_keys" => " PgUp/PgDn · Up/Down (empty input): scroll · Alt+M: mouse ",
+        "chat.debug_prompt" => "Request payload for {provider} (the next prompt is appended to the messages):",
+        "chat.new_divider" => "— new —",
+        "chat.retry" => " retry (r) ",
+        "chat.response_debug" => "finish: {reason} · prompt tokens: {prompt} · output tokens: {output} · raw final chunk:",
+        "chat.verified" => " ✔ verified ",
+        "chat.language_detected" => "(detected)",
+        "chat.code_copied" => "copied",
+        "chat.folded_steps" => "▸ {count} tool step(s) folded: {tools} (z to expand)",
+        "chat.folded_failed" => "  {count} failed",
+        "chat.verify_failed" => " ✘ failed: {expect} ",
+        "chat.task_running" => "⚙ {tool} on {worker} ({secs}s)",
+        "chat.task_queued" => "⚙ {tool} waiting for a worker ({secs}s)",
+        "chat.thinking" => "Lucius is thinking… ({secs}s)",
+        "chat.search" => "Find: {query}  {position}  {keys}",
+        "chat.search_none" => "no matches",
+        "chat.search_keys_editing" => "Enter: done · ↑/↓: previous/next · Esc: close",
+        "chat.search_keys" => "n: previous · N: next · Esc: close",
+        "chat.new_tab" => "new",
+
+        "settings.provider" => "Provider",
+        "settings.provider_value" => "{provider}  (←/→ to change)",
+        "settings.api_key" => "Anthropic API Key",
+        "settings.model_name" => "Model Name",
+        "settings.ollama_url" => "Ollama URL",
+        "settings.keep_alive" => "Keep model loaded",
+        "settings.keep_alive_value" => "{keep_alive}  (←/→ to change)",
+        "settings.keep_alive_default" => "server default (5 minutes)",
+        "settings.keep_alive_forever" => "always",
+        "settings.keep_alive_unload" => "unload after each answer",
+        "settings.mcp_host" => "MCP Redis Host",
+        "settings.parameters" => "Model parameters (temperature, top_p, num_ctx, seed as key=value)",
+        "settings.status" => "Status",
+        "settings.connected" => "Status: Connected",
+        "settings.disconnected" => "Status: Disconnected",
+        "settings.models" => "Models",
+        "settings.models_cached" => "Models (cached {age}, refreshing)",
+        "settings.models_keys" => " i: info · p: pull · u: unload · d d: delete ",
+        "settings.loaded" => "in memory: {models}",
+        "settings.host_memory" => "host {memory}",
+        "settings.model_loaded" => "● loaded",
+        "settings.model_too_big" => "⚠ may not fit",
+        "settings.models_catalog" => "No models installed · suggested models",
+        "settings.catalog_keys" => " p: pull the selected model ",
+        "settings.info_keys" => " Up/Down: scroll · Esc: close ",
+        "settings.info_parameters" => "Parameters",
+        "settings.info_quantization" => "Quantization",
+        "settings.info_family" => "Family",
+        "settings.info_context" => "Context length",
+        "settings.info_template" => "Template",
+        "settings.info_license" => "License",
+        "settings.info_unknown" => "unknown",
+        "settings.pull_name" => "Model to pull (e.g. llama3.1:8b)",
+        "settings.pulling" => "Pulling {model}: {status}",
+
+        "modal.confirm_title" => "CONFIRM ACTION",
+        "modal.confirm_question" => "Execute Command?",
+        "modal.tool" => "Tool: {tool}",
+        "modal.params" => "Params: {params}",
+        "modal.run_as" => "Runs as: {identity}",
+        "modal.confirm_keys" => "Press 'y' to confirm, 'n' to cancel.",
+        "modal.form_keys" => "Up/Down: field · Enter: edit · y: run · n/Esc: cancel",
+        "modal.auto_deny" => "Declined automatically in {seconds}s",
+        "modal.templates_title" => "New Conversation",
+        "modal.tools_title" => "Tools",
+        "modal.tools_keys" => " Enter: fill in and run · f: favorite · Esc: close ",
+        "modal.file_actions_title" => "File Actions",
+        "modal.file_edit" => "e: Open in $EDITOR",
+        "modal.file_attach" => "a: Attach to next prompt",
+        "modal.file_ls" => "l: ls -la (shell tool)",
+        "modal.file_head" => "h: head (shell tool)",
+        "modal.cancel" => "Esc: Cancel",
+        "modal.copy_title" => "Copy Message",
+        "modal.copy_raw" => "r: As shown",
+        "modal.context_diff_title" => "LUCIUS.md changed during this conversation",
+        "modal.context_diff_keys" => " a: apply to this conversation too · n/Esc: new conversations only · ↑/↓: scroll ",
+        "modal.context_diff_old" => "In use",
+        "modal.context_diff_new" => "Edited",
+        "modal.copy_text" => "t: Text without the prefix",
+        "modal.copy_code" => "c: Code blocks only",
+        "modal.copy_quoted" => "q: As a markdown quote",
+        "modal.hint_line" => "Press a highlighted letter to open a link or pick a file path, Esc to cancel.",
+        "modal.search_title" => "Matches: {count}",
+        "modal.search_query" => "Search all sessions",
+        "modal.search_keys" => " Up/Down: select · Enter: open at match · Esc: close ",
+        "modal.prompt_title" => "System prompt for this conversation",
+        "modal.prompt_keys" => " Ctrl+S: save · Esc: discard ",
+        "modal.prompt_lucius" => "LUCIUS.md (sent first, read-only)",
+        "modal.sessions_title" => "Saved sessions",
+        "modal.sessions_title_tag" => "Saved sessions tagged #{tag}",
+        "modal.sessions_keys" => " Enter: open · r: rename · s: star · t: filter by tag · d: delete · Esc: close ",
+        "modal.sessions_rename" => "New title (Enter: save · Esc: cancel)",
+        "modal.sessions_messages" => "{count} messages",
+        "modal.sessions_branch" => "branched from {title} at message {count}",
+
+        "status.connecting" => "Connecting to Ollama...",
+        "status.ollama_online" => "Ollama is online.",
+        "status.ollama_offline" => "Ollama is offline.",
+        "status.models_updated" => "Models updated.",
+        "status.models_failed" => "Could not list models: {error}",
+        "status.mcp_connected" => "MCP is connected via Redis.",
+        "status.mcp_disconnected" => "MCP Redis client not connected.",
+        "status.mcp_connected_dead_letters" => "MCP is connected via Redis. {count} dead-lettered task(s), see /deadletters.",
+        "status.requeued" => "Requeued {count} task(s).",
+        "status.requeued_partly" => "Requeued {count} task(s); {failed} could not be: {error}",
+        "status.requeue_usage" => "Usage: /requeue <n|all>",
+
+        "mcp.dead_letters_title" => "Dead-lettered tasks (requeue with /requeue <n|all>):",
+        "mcp.dead_letters_empty" => "The dead-letter queue is empty.",
+        "mcp.inventory_title" => "Inventory of {count} host(s), now part of the context:",
+        "mcp.inventory_empty" => "No worker has reported an inventory yet.",
+        "mcp.inventory_stale" => "(no answer, snapshot from {time})",
+        "status.inventory_refreshing" => "Asking the workers for an inventory...",
+        "status.compact_on" => "Compact layout on",
+        "status.compact_off" => "Compact layout off",
+        "status.indexing_start" => "Indexing the project...",
+        "status.indexing" => "Indexing the project: {done}/{total} chunks",
+        "status.indexed" => "Indexed {chunks} chunks from {files} files.",
+        "status.index_failed" => "Indexing failed: {error}",
+        "status.verbosity" => "Answer length: {verbosity}",
+        "status.verbosity_usage" => "Usage: /verbosity [brief|normal|detailed]",
+        "status.options" => "Model parameters: {options}",
+        "status.options_default" => "Model parameters: the model's defaults",
+        "status.options_invalid" => "Invalid parameters: {error}",
+        "status.prompt_saved" => "System prompt saved for this conversation.",
+        "status.prompt_removed" => "This conversation no longer has its own system prompt.",
+        "status.no_hints" => "No links or file paths found in the conversation.",
+        "status.no_code_blocks" => "The last answer has no code blocks.",
+        "status.code_copied" => "Copied code block {number}/{count} (Ctrl+K for the next)",
+        "status.selection_copied" => "Copied {count} selected characters",
+        "status.answer_copied" => "Copied the last answer",
+        "status.no_answer" => "There is no answer to copy yet.",
+        "status.message_copied" => "Copied the message",
+        "status.message_no_code" => "The message has no code blocks.",
+        "status.mouse_on" => "Mouse capture on: the wheel scrolls and dragging copies.",
+        "status.mouse_off" => "Mouse capture off: the terminal selects text; PgUp/PgDn and Up/Down scroll.",
+        "status.mouse_unsupported" => "This terminal ($TERM={term}) reports no mouse, so capture stays off.",
+        "status.no_mouse" => "No mouse on this terminal ($TERM={term}): Up/Down and PgUp/PgDn scroll, Alt+Up/Down picks a message to copy, Alt+A shows attachments.",
+        "status.pasted_lines" => "Pasted {count} lines into the input; Enter sends them together",
+        "status.no_tools" => "No tools discovered. Is a worker connected to MCP?",
+        "status.opened" => "Opened {target}",
+        "status.attached" => "Attached {path} to the next prompt.",
+        "status.template_started" => "Started: {name}",
+        "status.unknown_command_suggest" => "Unknown command /{typed}. Did you mean /{suggestion}?",
+        "status.unknown_command" => "Unknown command /{typed}. Type /help for the list of commands.",
+        "status.model_usage" => "Usage: /model <name>",
+        "status.debug_usage" => "Usage: /debug prompt",
+        "status.model_switched" => "Switched to {model}.",
+        "status.lucius_md_reloaded" => "LUCIUS.md changed; context reloaded.",
+        "status.lucius_md_changed" => "LUCIUS.md changed; the changes are shown back in the chat once the input is empty.",
+        "status.lucius_md_applied" => "LUCIUS.md changes applied to this conversation.",
+        "status.lucius_md_new_only" => "LUCIUS.md changes apply to new conversations; this one keeps the previous context.",
+        "status.context_dropped" => "Context window full: left out the {turns} oldest turn(s).",
+        "status.context_summarized" => "Context window full: summarizing the {turns} oldest turn(s).",
+        "status.warming" => "Loading {model} ({secs}s)",
+        "status.warmed" => "{model} is loaded and ready ({secs}s).",
+        "status.warm_failed" => "Could not load {model}: {error}",
+        "status.models_ollama_only" => "Model details, pulls and deletes are only available with Ollama.",
+        "status.model_info_failed" => "Could not read the details of {model}: {error}",
+        "status.pull_busy" => "Another model is still being pulled.",
+        "status.model_pulled" => "Pulled {model}.",
+        "status.model_pull_failed" => "Could not pull {model}: {error}",
+        "status.model_delete_confirm" => "Press d again to delete {model} from the server.",
+        "status.model_deleted" => "Deleted {model}.",
+        "status.model_delete_failed" => "Could not delete {model}: {error}",
+        "status.model_unloaded" => "Unloaded {model} from memory.",
+        "status.message_delete_confirm" => "Press d again to delete this message from the conversation.",
+        "status.message_deleted" => "Message deleted; it is no longer sent to the model.",
+        "status.branched" => "Branched after message {count} into a new tab; the original is unchanged.",
+        "status.model_unload_failed" => "Could not unload {model}: {error}",
+        "status.model_not_loaded" => "{model} is not loaded.",
+        "status.model_too_big" => "{model} ({size}) may not fit in the host's {memory} of memory; press Enter again to use it anyway.",
+        "status.model_missing" => "Model {model} is not installed.",
+        "sessions.clean_summary" => "{count} session(s), {size} MB, listed here would be removed by the retention limits. Run /sessions clean now to remove them.",
+        "status.sessions_clean_nothing" => "Nothing to clean: no unstarred session is past the [sessions] limits.",
+        "status.sessions_cleaned" => "Removed {count} session(s), {size} MB.",
+        "status.session_delete_confirm" => "Press d again to delete \"{title}\".",
+        "status.session_deleted" => "Deleted \"{title}\".",
+        "status.tags" => "Tags: {tags}",
+        "status.writing_notes" => "Writing notes for this conversation...",
+        "status.tags_none" => "This conversation has no tags. Add some with /tag #name.",
+        "status.rename_usage" => "Usage: /rename <title>",
+        "status.renamed" => "Conversation renamed to \"{title}\"",
+        "status.tag_invalid" => "Not a tag: {tag} (use letters, digits, - _ or /)",
+        "status.confirmation_timed_out" => "No answer in time; {tool} was not run",
+        "status.confirmation_queued" => "{tool} will be confirmed after the call on screen",
+        "status.export_usage" => "Usage: /export script [file]",
+        "status.export_empty" => "No exec or remote_exec commands ran in this conversation",
+        "status.exported" => "Commands exported to {path}",
+        "status.export_failed" => "Could not write {path}: {error}",
+        "status.export_exists" => "{path} already exists; give /export script another file",
+        "status.session_open_in_tab" => "That session is open in another tab; close it first.",
+        "status.session_unreadable" => "Could not read \"{title}\".",
+        "status.answer_in_progress" => "An answer is still coming in this conversation; wait for it or press Esc.",
+        "status.request_queued" => "Other answers are being generated; this one starts when one finishes.",
+        "status.queue_full_waiting" => "The background worker is busy; your request is queued and will run when it catches up.",
+        "status.queue_full_skipped" => "The background worker is busy; skipped a refresh.",
+        "status.worker_stopped" => "The background worker has stopped; restart Lucius.",
+
+        "doctor.title" => "Lucius doctor",
+        "doctor.terminal" => "Terminal",
+        "doctor.configuration" => "Configuration",
+        "doctor.provider" => "LLM provider",
+        "doctor.clipboard" => "Clipboard",
+        "doctor.state_dirs" => "State directories",
+        "doctor.summary" => "{failures} failure(s), {warnings} warning(s)",
+        "doctor.all_passed" => "All checks passed.",
+        "doctor.timed_out" => "timed out",
+        "doctor.tty" => "stdout is a terminal",
+        "doctor.no_tty" => "stdout is not a terminal; the TUI needs one",
+        "doctor.size" => "size",
+        "doctor.size_small" => "{size}, smaller than 80x24",
+        "doctor.size_unknown" => "unknown ({error})",
+        "doctor.term_dumb" => "{term} cannot draw the TUI",
+        "doctor.term_unset" => "not set",
+        "doctor.colors" => "colors",
+        "doctor.colors_approximated" => "COLORTERM is not truecolor; colors may be approximated",
+        "doctor.config_file" => "config file",
+        "doctor.config_invalid" => "{path} does not parse: {error}",
+        "doctor.config_missing" => "{path} not found, using defaults",
+        "doctor.model" => "model",
+        "doctor.model_none" => "none selected; pick one in Settings (Ctrl+S)",
+        "doctor.provider_name" => "provider",
+        "doctor.ollama_unset" => "ollama_url is not set",
+        "doctor.ollama_version" => "{url} (version {version})",
+        "doctor.api_key" => "api key",
+        "doctor.api_key_unset" => "set anthropic.api_key or ANTHROPIC_API_KEY",
+        "doctor.anthropic_ok" => "API reachable, key accepted",
+        "doctor.anthropic_failed" => "API unreachable or key rejected",
+        "doctor.encryption" => "encryption",
+        "doctor.encryption_on" => "task payloads are sealed",
+        "doctor.encryption_off" => "off, task payloads cross Redis as plain JSON",
+        "doctor.encryption_failed" => "{error} (tools will not run)",
+        "doctor.redis_failed" => "{host}: {error} (tools are disabled without it)",
+        "doctor.workers" => "workers",
+        "doctor.workers_none" => "none registered in mcp::workers",
+        "doctor.worker" => "worker",
+        "doctor.worker_tools" => "{count} tool(s)",
+        "doctor.worker_user" => ", runs as {user}",
+        "doctor.worker_no_key" => "{host}: has no LUCIUS_TASK_KEY and cannot run encrypted tasks",
+        "doctor.worker_root" => "{host}: runs as root; use a dedicated user",
+        "doctor.worker_alive" => "{host}: alive, {tools}",
+        "doctor.worker_silent" => "{host}: registered but no heartbeat",
+        "doctor.dead_letters" => "dead letters",
+        "doctor.dead_letters_none" => "none",
+        "doctor.dead_letters_waiting" => "{count} waiting, see /deadletters",
+        "doctor.clipboard_ok" => "system clipboard available",
+        "doctor.clipboard_failed" => "unavailable: {error}",
+        "doctor.external" => "external",
+        "doctor.external_none" => "none of wl-copy, xclip or pbcopy; `lucius quick` cannot copy",
+        "doctor.config_dir" => "config dir",
+        "doctor.sessions" => "sessions",
+        "doctor.log_file" => "log file",
+        "doctor.not_writable" => "{path} is not writable: {error}",
+        _ => return None,
+    })
+}
+
+fn spanish(key: &str) -> Option<&'static str> {
+    Some(match key {
+        "help.body" => HELP_ES,
+        "help.title" => "Ayuda",
+        "help.slash_commands" => "Comandos:",
+        "help.mouse" => "Rueda del ratón: Desplazar el historial\nArrastrar: Seleccionar texto y copiarlo (Shift + arrastrar: la selección del terminal)\nClic en la línea de estado: Panel de contexto adjunto",
+        "help.no_mouse" => "Arriba/Abajo: Desplazar la conversación una línea (entrada vacía, sin captura del ratón)",
+
+        "cmd.help" => "Mostrar la pantalla de ayuda",
+        "cmd.settings" => "Abrir la configuración",
+        "cmd.clear" => "Borrar la conversación",
+        "cmd.new" => "Nueva conversación a partir de una plantilla",
+        "cmd.model" => "Cambiar a otro modelo instalado",
+        "cmd.deadletters" => "Listar las tareas MCP que no se pudieron leer o ejecutar",
+        "cmd.requeue" => "Reencolar una tarea fallida por número, o todas",
+        "cmd.context" => "Mostrar lo que enviará la próxima petición, con tokens estimados",
+        "cmd.inventory" => "Actualizar los hosts, contenedores y puertos que informan los workers",
+        "cmd.compact" => "Alternar el diseño compacto (sin bordes, espaciado denso)",
+        "cmd.index" => "Indexar los archivos del proyecto para sumar extractos relevantes a cada mensaje",
+        "cmd.debug" => "Mostrar el JSON exacto que enviaría la próxima petición",
+        "cmd.verbosity" => "Fijar la longitud de las respuestas, o alternarla sin argumento (también Alt+V)",
+        "cmd.prompt" => "Editar el prompt de sistema de esta conversación, enviado tras LUCIUS.md",
+        "cmd.set" => "Cambiar parámetros del modelo en esta conversación, p. ej. temperature=0.2 (valor vacío restablece)",
+        "cmd.tab" => "Abrir una conversación nueva en una pestaña",
+        "cmd.closetab" => "Cerrar la pestaña actual (su sesión sigue guardada)",
+        "cmd.sessions" => "Explorar las sesiones guardadas, opcionalmente solo las de una etiqueta; /sessions clean muestra qué se eliminaría",
+        "cmd.tag" => "Mostrar, añadir o quitar (-#etiqueta) etiquetas de esta conversación",
+        "cmd.rename" => "Dar un título a esta conversación",
+        "cmd.export" => "Guardar los comandos ejecutados en esta conversación como script de shell",
+        "cmd.search" => "Buscar en todas las sesiones guardadas",
+        "cmd.quit" => "Salir de Lucius",
+
+        "context.title" => "Próxima petición: ~{tokens} de {window} tokens",
+        "context.keys" => " Espacio: incluir/excluir · Esc: cerrar ",
+        "context.tokens" => "{tokens} tok",
+        "context.lucius_md" => "Prompt de sistema: LUCIUS.md",
+        "context.session_prompt" => "Prompt de sistema: plantilla de conversación",
+        "context.tools" => "Prompt de sistema: descripción de herramientas",
+        "context.inventory" => "Prompt de sistema: inventario",
+        "context.verbosity" => "Prompt de sistema: longitud de las respuestas",
+        "context.excerpt" => "Prompt de sistema: extracto {path}:{line}",
+        "attached.title" => "Contexto adjunto",
+        "attached.keys" => " x: quitar/restaurar · Esc: cerrar ",
+        "attached.empty" => "No hay archivos ni extractos adjuntos",
+        "attached.pending" => "próximo",
+        "attached.file" => "archivo",
+        "attached.excerpt" => "extracto",
+
+        "chat.title" => "Conversación",
+        "chat.input_title" => "Entrada",
+        "chat.placeholder" => "Pregúntame lo que quieras...",
+        "chat.dir" => "Dir: {dir}",
+        "chat.dir_error" => "Error al obtener el directorio",
+        "chat.model" => "Modelo: {model}",
+        "chat.no_model" => "Ningún modelo seleccionado",
+        "chat.using" => "usando: {lucius_md} LUCIUS.md | {mcp} servidor MCP | contexto ~{tokens}/{window} tokens",
+        "chat.using_attached" => " | adjuntos: {files} archivos, {excerpts} extractos",
+        "chat.scroll_keys" => " RePág/AvPág · Arriba/Abajo (entrada vacía): desplazar · Alt+M: ratón ",
+        "chat.debug_prompt" => "Petición para {provider} (el próximo mensaje se añade a los mensajes):",
+        "chat.new_divider" => "— nuevo —",
+        "chat.retry" => " reintentar (r) ",
+        "chat.response_debug" => "fin: {reason} · tokens del prompt: {prompt} · tokens generados: {output} · último fragmento:",
+        "chat.verified" => " ✔ verificado ",
+        "chat.language_detected" => "(detectado)",
+        "chat.code_copied" => "copiado",
+        "chat.folded_steps" => "▸ {count} paso(s) de herramientas plegados: {tools} (z para desplegar)",
+        "chat.folded_failed" => "  {count} fallido(s)",
+        "chat.verify_failed" => " ✘ falló: {expect} ",
+        "chat.task_running" => "⚙ {tool} en {worker} ({secs}s)",
+        "chat.task_queued" => "⚙ {tool} esperando a un worker ({secs}s)",
+        "chat.thinking" => "Lucius está pensando… ({secs}s)",
+        "chat.search" => "Buscar: {query}  {position}  {keys}",
+        "chat.search_none" => "sin coincidencias",
+        "chat.search_keys_editing" => "Enter: listo · ↑/↓: anterior/siguiente · Esc: cerrar",
+        "chat.search_keys" => "n: anterior · N: siguiente · Esc: cerrar",
+        "chat.new_tab" => "nueva",
+
+        "settings.provider" => "Proveedor",
+        "settings.provider_value" => "{provider}  (←/→ para cambiar)",
+        "settings.api_key" => "Clave de API de Anthropic",
+        "settings.model_name" => "Nombre del modelo",
+        "settings.ollama_url" => "URL de Ollama",
+        "settings.keep_alive" => "Mantener el modelo cargado",
+        "settings.keep_alive_value" => "{keep_alive}  (←/→ para cambiar)",
+        "settings.keep_alive_default" => "lo predeterminado del servidor (5 minutos)",
+        "settings.keep_alive_forever" => "siempre",
+        "settings.keep_alive_unload" => "descargar tras cada respuesta",
+        "settings.mcp_host" => "Host Redis de MCP",
+        "settings.parameters" => "Parámetros del modelo (temperature, top_p, num_ctx, seed como clave=valor)",
+        "settings.status" => "Estado",
+        "settings.connected" => "Estado: Conectado",
+        "settings.disconnected" => "Estado: Desconectado",
+        "settings.models" => "Modelos",
+        "settings.models_cached" => "Modelos (en caché {age}, actualizando)",
+        "settings.models_keys" => " i: info · p: descargar · u: liberar · d d: borrar ",
+        "settings.loaded" => "en memoria: {models}",
+        "settings.host_memory" => "servidor {memory}",
+        "settings.model_loaded" => "● cargado",
+        "settings.model_too_big" => "⚠ puede no caber",
+        "settings.models_catalog" => "No hay modelos instalados · modelos sugeridos",
+        "settings.catalog_keys" => " p: descargar el modelo seleccionado ",
+        "settings.info_keys" => " Arriba/Abajo: desplazar · Esc: cerrar ",
+        "settings.info_parameters" => "Parámetros",
+        "settings.info_quantization" => "Cuantización",
+        "settings.info_family" => "Familia",
+        "settings.info_context" => "Longitud de contexto",
+        "settings.info_template" => "Plantilla",
+        "settings.info_license" => "Licencia",
+        "settings.info_unknown" => "desconocido",
+        "settings.pull_name" => "Modelo a descargar (p. ej. llama3.1:8b)",
+        "settings.pulling" => "Descargando {model}: {status}",
+
+        "modal.confirm_title" => "CONFIRMAR ACCIÓN",
+        "modal.confirm_question" => "¿Ejecutar el comando?",
+        "modal.tool" => "Herramienta: {tool}",
+        "modal.params" => "Parámetros: {params}",
+        "modal.run_as" => "Se ejecuta como: {identity}",
+        "modal.confirm_keys" => "Pulsa 'y' para confirmar, 'n' para cancelar.",
+        "modal.form_keys" => "Arriba/Abajo: campo · Enter: editar · y: ejecutar · n/Esc: cancelar",
+        "modal.auto_deny" => "Se rechaza automáticamente en {seconds}s",
+        "modal.templates_title" => "Nueva conversación",
+        "modal.tools_title" => "Herramientas",
+        "modal.tools_keys" => " Enter: rellenar y ejecutar · f: favorita · Esc: cerrar ",
+        "modal.file_actions_title" => "Acciones de archivo",
+        "modal.file_edit" => "e: Abrir en $EDITOR",
+        "modal.file_attach" => "a: Adjuntar al siguiente mensaje",
+        "modal.file_ls" => "l: ls -la (herramienta shell)",
+        "modal.file_head" => "h: head (herramienta shell)",
+        "modal.cancel" => "Esc: Cancelar",
+        "modal.copy_title" => "Copiar mensaje",
+        "modal.copy_raw" => "r: Tal como se muestra",
+        "modal.context_diff_title" => "LUCIUS.md cambió durante esta conversación",
+        "modal.context_diff_keys" => " a: aplicar también a esta conversación · n/Esc: solo conversaciones nuevas · ↑/↓: desplazar ",
+        "modal.context_diff_old" => "En uso",
+        "modal.context_diff_new" => "Editado",
+        "modal.copy_text" => "t: Texto sin el prefijo",
+        "modal.copy_code" => "c: Solo los bloques de código",
+        "modal.copy_quoted" => "q: Como cita en markdown",
+        "modal.hint_line" => "Pulsa una letra resaltada para abrir un enlace o elegir una ruta, Esc para cancelar.",
+        "modal.search_title" => "Coincidencias: {count}",
+        "modal.search_query" => "Buscar en todas las sesiones",
+        "modal.search_keys" => " Arriba/Abajo: elegir · Enter: abrir en la coincidencia · Esc: cerrar ",
+        "modal.prompt_title" => "Prompt de sistema de esta conversación",
+        "modal.prompt_keys" => " Ctrl+S: guardar · Esc: descartar ",
+        "modal.prompt_lucius" => "LUCIUS.md (se envía primero, solo lectura)",
+        "modal.sessions_title" => "Sesiones guardadas",
+        "modal.sessions_title_tag" => "Sesiones guardadas con #{tag}",
+        "modal.sessions_keys" => " Enter: abrir · r: renombrar · s: destacar · t: filtrar por etiqueta · d: borrar · Esc: cerrar ",
+        "modal.sessions_rename" => "Nuevo título (Enter: guardar · Esc: cancelar)",
+        "modal.sessions_messages" => "{count} mensajes",
+        "modal.sessions_branch" => "rama de {title} en el mensaje {count}",
+
+        "status.connecting" => "Conectando con Ollama...",
+        "status.ollama_online" => "Ollama está en línea.",
+        "status.ollama_offline" => "Ollama está desconectado.",
+        "status.models_updated" => "Modelos actualizados.",
+        "status.models_failed" => "No se pudieron listar los modelos: {error}",
+        "status.mcp_connected" => "MCP conectado vía Redis.",
+        "status.mcp_disconnected" => "Cliente Redis de MCP no conectado.",
+        "status.mcp_connected_dead_letters" => "MCP conectado vía Redis. {count} tarea(s) en la cola de fallidas, ver /deadletters.",
+        "status.requeued" => "{count} tarea(s) reencolada(s).",
+        "status.requeued_partly" => "{count} tarea(s) reencolada(s); {failed} no se pudieron: {error}",
+        "status.requeue_usage" => "Uso: /requeue <n|all>",
+
+        "mcp.dead_letters_title" => "Tareas fallidas (reencolar con /requeue <n|all>):",
+        "mcp.dead_letters_empty" => "La cola de tareas fallidas está vacía.",
+        "mcp.inventory_title" => "Inventario de {count} host(s), ahora parte del contexto:",
+        "mcp.inventory_empty" => "Ningún worker ha enviado un inventario todavía.",
+        "mcp.inventory_stale" => "(sin respuesta, instantánea de {time})",
+        "status.inventory_refreshing" => "Pidiendo el inventario a los workers...",
+        "status.compact_on" => "Diseño compacto activado",
+        "status.compact_off" => "Diseño compacto desactivado",
+        "status.indexing_start" => "Indexando el proyecto...",
+        "status.indexing" => "Indexando el proyecto: {done}/{total} fragmentos",
+        "status.indexed" => "Indexados {chunks} fragmentos de {files} archivos.",
+        "status.index_failed" => "Falló la indexación: {error}",
+        "status.verbosity" => "Longitud de las respuestas: {verbosity}",
+        "status.verbosity_usage" => "Uso: /verbosity [brief|normal|detailed]",
+        "status.options" => "Parámetros del modelo: {options}",
+        "status.options_default" => "Parámetros del modelo: los predeterminados del modelo",
+        "status.options_invalid" => "Parámetros no válidos: {error}",
+        "status.prompt_saved" => "Prompt de sistema guardado para esta conversación.",
+        "status.prompt_removed" => "Esta conversación ya no tiene prompt de sistema propio.",
+        "status.no_hints" => "No hay enlaces ni rutas de archivo en la conversación.",
+        "status.no_code_blocks" => "La última respuesta no tiene bloques de código.",
+        "status.code_copied" => "Bloque de código {number}/{count} copiado (Ctrl+K para el siguiente)",
+        "status.selection_copied" => "{count} caracteres seleccionados copiados",
+        "status.answer_copied" => "Última respuesta copiada",
+        "status.no_answer" => "Todavía no hay ninguna respuesta que copiar.",
+        "status.message_copied" => "Mensaje copiado",
+        "status.message_no_code" => "El mensaje no tiene bloques de código.",
+        "status.mouse_on" => "Captura del ratón activada: la rueda desplaza y arrastrar copia.",
+        "status.mouse_off" => "Captura del ratón desactivada: el terminal selecciona el texto; RePág/AvPág y Arriba/Abajo desplazan.",
+        "status.mouse_unsupported" => "Este terminal ($TERM={term}) no informa del ratón, así que la captura sigue desactivada.",
+        "status.no_mouse" => "Sin ratón en este terminal ($TERM={term}): Arriba/Abajo y RePág/AvPág desplazan, Alt+Arriba/Abajo elige un mensaje para copiar, Alt+A muestra los adjuntos.",
+        "status.pasted_lines" => "{count} líneas pegadas en la entrada; Enter las envía juntas",
+        "status.no_tools" => "No se encontraron herramientas. ¿Hay algún worker conectado a MCP?",
+        "status.opened" => "Abierto {target}",
+        "status.attached" => "{path} se adjuntará al siguiente mensaje.",
+        "status.template_started" => "Iniciada: {name}",
+        "status.unknown_command_suggest" => "Comando desconocido /{typed}. ¿Quisiste decir /{suggestion}?",
+        "status.unknown_command" => "Comando desconocido /{typed}. Escribe /help para ver los comandos.",
+        "status.model_usage" => "Uso: /model <nombre>",
+        "status.debug_usage" => "Uso: /debug prompt",
+        "status.model_switched" => "Cambiado a {model}.",
+        "status.lucius_md_reloaded" => "LUCIUS.md cambió; contexto recargado.",
+        "status.lucius_md_changed" => "LUCIUS.md cambió; los cambios se mostrarán en el chat cuando la entrada esté vacía.",
+        "status.lucius_md_applied" => "Cambios de LUCIUS.md aplicados a esta conversación.",
+        "status.lucius_md_new_only" => "Los cambios de LUCIUS.md se aplican a las conversaciones nuevas; esta mantiene el contexto anterior.",
+        "status.context_dropped" => "Ventana de contexto llena: se omiten los {turns} turnos más antiguos.",
+        "status.context_summarized" => "Ventana de contexto llena: resumiendo los {turns} turnos más antiguos.",
+        "status.warming" => "Cargando {model} ({secs}s)",
+        "status.warmed" => "{model} está cargado y listo ({secs}s).",
+        "status.warm_failed" => "No se pudo cargar {model}: {error}",
+        "status.models_ollama_only" => "Los detalles, descargas y borrados de modelos solo están disponibles con Ollama.",
+        "status.model_info_failed" => "No se pudieron leer los detalles de {model}: {error}",
+        "status.pull_busy" => "Todavía se está descargando otro modelo.",
+        "status.model_pulled" => "{model} descargado.",
+        "status.model_pull_failed" => "No se pudo descargar {model}: {error}",
+        "status.model_delete_confirm" => "Pulsa d otra vez para borrar {model} del servidor.",
+        "status.model_deleted" => "{model} borrado.",
+        "status.model_delete_failed" => "No se pudo borrar {model}: {error}",
+        "status.model_unloaded" => "{model} descargado de la memoria.",
+        "status.message_delete_confirm" => "Pulsa d otra vez para borrar este mensaje de la conversación.",
+        "status.message_deleted" => "Mensaje borrado; ya no se envía al modelo.",
+        "status.branched" => "Rama creada tras el mensaje {count} en una pestaña nueva; la original no cambia.",
+        "status.model_unload_failed" => "No se pudo descargar {model} de la memoria: {error}",
+        "status.model_not_loaded" => "{model} no está cargado.",
+        "status.model_too_big" => "{model} ({size}) puede no caber en los {memory} de memoria del servidor; pulsa Enter otra vez para usarlo de todos modos.",
+        "status.model_missing" => "El modelo {model} no está instalado.",
+        "sessions.clean_summary" => "Los límites de retención eliminarían las {count} sesión(es) listadas, {size} MB. Ejecuta /sessions clean now para eliminarlas.",
+        "status.sessions_clean_nothing" => "Nada que limpiar: ninguna sesión sin destacar supera los límites de [sessions].",
+        "status.sessions_cleaned" => "{count} sesión(es) eliminada(s), {size} MB.",
+        "status.session_delete_confirm" => "Pulsa d otra vez para borrar \"{title}\".",
+        "status.session_deleted" => "\"{title}\" borrada.",
+        "status.tags" => "Etiquetas: {tags}",
+        "status.writing_notes" => "Escribiendo las notas de esta conversación...",
+        "status.tags_none" => "Esta conversación no tiene etiquetas. Añade alguna con /tag #nombre.",
+        "status.rename_usage" => "Uso: /rename <título>",
+        "status.renamed" => "Conversación renombrada a \"{title}\"",
+        "status.tag_invalid" => "No es una etiqueta: {tag} (usa letras, dígitos, - _ o /)",
+        "status.confirmation_timed_out" => "Sin respuesta a tiempo; {tool} no se ejecutó",
+        "status.confirmation_queued" => "{tool} se confirmará después de la llamada en pantalla",
+        "status.export_usage" => "Uso: /export script [archivo]",
+        "status.export_empty" => "En esta conversación no se ejecutó ningún comando exec ni remote_exec",
+        "status.exported" => "Comandos exportados a {path}",
+        "status.export_failed" => "No se pudo escribir {path}: {error}",
+        "status.export_exists" => "{path} ya existe; indica otro archivo a /export script",
+        "status.session_open_in_tab" => "Esa sesión está abierta en otra pestaña; ciérrala primero.",
+        "status.session_unreadable" => "No se pudo leer \"{title}\".",
+        "status.answer_in_progress" => "Aún llega una respuesta en esta conversación; espérala o pulsa Esc.",
+        "status.request_queued" => "Se están generando otras respuestas; esta empieza cuando termine una.",
+        "status.queue_full_waiting" => "El proceso en segundo plano está ocupado; tu petición queda en cola y se ejecutará cuando se libere.",
+        "status.queue_full_skipped" => "El proceso en segundo plano está ocupado; se omitió una actualización.",
+        "status.worker_stopped" => "El proceso en segundo plano se detuvo; reinicia Lucius.",
+
+        "doctor.title" => "Diagnóstico de Lucius",
+        "doctor.terminal" => "Terminal",
+        "doctor.configuration" => "Configuración",
+        "doctor.provider" => "Proveedor de LLM",
+        "doctor.clipboard" => "Portapapeles",
+        "doctor.state_dirs" => "Directorios de estado",
+        "doctor.summary" => "{failures} fallo(s), {warnings} aviso(s)",
+        "doctor.all_passed" => "Todas las comprobaciones pasaron.",
+        "doctor.timed_out" => "tiempo agotado",
+        "doctor.tty" => "stdout es un terminal",
+        "doctor.no_tty" => "stdout no es un terminal; la TUI necesita uno",
+        "doctor.size" => "tamaño",
+        "doctor.size_small" => "{size}, menor que 80x24",
+        "doctor.size_unknown" => "desconocido ({error})",
+        "doctor.term_dumb" => "{term} no puede dibujar la TUI",
+        "doctor.term_unset" => "sin definir",
+        "doctor.colors" => "colores",
+        "doctor.colors_approximated" => "COLORTERM no es truecolor; los colores pueden aproximarse",
+        "doctor.config_file" => "archivo config",
+        "doctor.config_invalid" => "{path} no se puede analizar: {error}",
+        "doctor.config_missing" => "{path} no existe, se usan los valores por defecto",
+        "doctor.model" => "modelo",
+        "doctor.model_none" => "ninguno elegido; elige uno en Configuración (Ctrl+S)",
+        "doctor.provider_name" => "proveedor",
+        "doctor.ollama_unset" => "ollama_url no está definido",
+        "doctor.ollama_version" => "{url} (versión {version})",
+        "doctor.api_key" => "clave API",
+        "doctor.api_key_unset" => "define anthropic.api_key o ANTHROPIC_API_KEY",
+        "doctor.anthropic_ok" => "API accesible, clave aceptada",
+        "doctor.anthropic_failed" => "API inaccesible o clave rechazada",
+        "doctor.encryption" => "cifrado",
+        "doctor.encryption_on" => "las tareas viajan selladas",
+        "doctor.encryption_off" => "desactivado, las tareas cruzan Redis como JSON plano",
+        "doctor.encryption_failed" => "{error} (las herramientas no se ejecutarán)",
+        "doctor.redis_failed" => "{host}: {error} (sin él las herramientas están desactivadas)",
+        "doctor.workers" => "workers",
+        "doctor.workers_none" => "ninguno registrado en mcp::workers",
+        "doctor.worker" => "worker",
+        "doctor.worker_tools" => "{count} herramienta(s)",
+        "doctor.worker_user" => ", se ejecuta como {user}",
+        "doctor.worker_no_key" => "{host}: no tiene LUCIUS_TASK_KEY y no puede ejecutar tareas cifradas",
+        "doctor.worker_root" => "{host}: se ejecuta como root; usa un usuario dedicado",
+        "doctor.worker_alive" => "{host}: activo, {tools}",
+        "doctor.worker_silent" => "{host}: registrado pero sin latido",
+        "doctor.dead_letters" => "cartas muertas",
+        "doctor.dead_letters_none" => "ninguna",
+        "doctor.dead_letters_waiting" => "{count} en espera, ver /deadletters",
+        "doctor.clipboard_ok" => "portapapeles del sistema disponible",
+        "doctor.clipboard_failed" => "no disponible: {error}",
+        "doctor.external" => "externo",
+        "doctor.external_none" => "no hay wl-copy, xclip ni pbcopy; `lucius quick` no puede copiar",
+        "doctor.config_dir" => "dir. config",
+        "doctor.sessions" => "sesiones",
+        "doctor.log_file" => "archivo log",
+        "doctor.not_writable" => "no se puede escribir en {path}: {error}",
+        _ => return None,
+    })
+}
+
+const HELP_EN: &str = r#"
+--- Help ---
+Ctrl+H: Toggle Help
+Ctrl+S: Toggle Settings
+Ctrl+Q: Quit
+Ctrl+L: Clear Chat (starts a new session)
+Ctrl+Tab / Alt+1-9: Switch tabs (/tab opens one, /closetab closes it)
+Ctrl+N: New conversation from a template
+Ctrl+P: Tool palette (run an MCP tool without the LLM, f to favorite)
+Ctrl+B: Context budget panel (what the next request sends, Space to exclude items)
+Ctrl+G: Session browser (open, rename or delete saved sessions)
+Ctrl+Shift+F: Search all saved sessions
+Right/Tab: Accept the grey autocomplete suggestion
+@path: Attach a file to the prompt (Right/Tab completes the path)
+Ctrl+Y: Yank (Copy) Last Response
+Ctrl+T: MCP Status
+Ctrl+E: Toggle absolute/relative message times
+Alt+T: Show/hide message times and generation durations
+Alt+A: Attached files and excerpts (x removes one from the next request)
+Alt+M: Turn mouse capture on/off (off leaves selection to the terminal)
+Ctrl+K: Copy the next code block of the last answer
+Ctrl+U: Jump to the first unread message
+Ctrl+O: Hint mode (f on a focused message) - open a link, or pick a file path to edit, attach, ls or head
+Alt+Left/Right: Scroll wide code blocks and tables horizontally
+Alt+Up/Down: Focus a message (Left/Right scroll its code, w toggles wrapping, i shows how an answer ended, d d deletes it, b branches the conversation there, r retries a failed tool task, z folds its turn, Z folds/unfolds all turns, y opens the copy menu, Esc returns to input)
+Alt+V: Cycle the answer length (brief, normal, detailed)
+Esc: With [input] vim = true, switch the input box to normal mode (i/a/o to insert again)
+Esc: Interrupt current stream (if any)
+PageUp/PageDown: Scroll the conversation a page
+Ctrl+Home/End: Jump to the top/bottom of the conversation
+Ctrl+F: Find in the conversation (n/N: previous/next match)
+{mouse}
+Enter: Send message (Chat mode), Select model (Settings mode)
+F1-F3: Send a suggested follow-up (when the input is empty)
+Tab: Switch focus (Settings mode)
+Left/Right: Change LLM provider (Settings mode)
+Ctrl+R: Refresh models (Settings mode)
+Esc: Go to Chat (Settings mode)
+-----------------
+"#;
+
+const HELP_ES: &str = r#"
+--- Ayuda ---
+Ctrl+H: Mostrar/ocultar ayuda
+Ctrl+S: Mostrar/ocultar configuración
+Ctrl+Q: Salir
+Ctrl+L: Borrar el chat (empieza una sesión nueva)
+Ctrl+Tab / Alt+1-9: Cambiar de pestaña (/tab abre una, /closetab la cierra)
+Ctrl+N: Nueva conversación a partir de una plantilla
+Ctrl+P: Paleta de herramientas (ejecutar una herramienta MCP sin el LLM, f para favorita)
+Ctrl+B: Panel de contexto (lo que envía la próxima petición, Espacio para excluir elementos)
+Ctrl+G: Explorador de sesiones (abrir, renombrar o borrar sesiones guardadas)
+Ctrl+Shift+F: Buscar en todas las sesiones guardadas
+Derecha/Tab: Aceptar la sugerencia de autocompletado en gris
+@ruta: Adjuntar un archivo al mensaje (Derecha/Tab completa la ruta)
+Ctrl+Y: Copiar la última respuesta
+Ctrl+T: Estado de MCP
+Ctrl+E: Alternar hora absoluta/relativa de los mensajes
+Alt+T: Mostrar/ocultar la hora de los mensajes y el tiempo de generación
+Alt+A: Archivos y extractos adjuntos (x quita uno de la próxima petición)
+Alt+M: Activar/desactivar la captura del ratón (desactivada, la selección es del terminal)
+Ctrl+K: Copiar el siguiente bloque de código de la última respuesta
+Ctrl+U: Saltar al primer mensaje no leído
+Ctrl+O: Modo de pistas (f en un mensaje enfocado) - abrir un enlace o elegir una ruta para editar, adjuntar, ls o head
+Alt+Izquierda/Derecha: Desplazar código y tablas anchas horizontalmente
+Alt+Arriba/Abajo: Enfocar un mensaje (Izquierda/Derecha desplazan su código, w alterna el ajuste de línea, i muestra cómo terminó una respuesta, d d lo borra, b crea una rama de la conversación ahí, r reintenta una tarea fallida, z pliega su turno, Z pliega/despliega todos, y abre el menú de copia, Esc vuelve a la entrada)
+Alt+V: Alternar la longitud de las respuestas (brief, normal, detailed)
+Esc: Con [input] vim = true, pasar la entrada a modo normal (i/a/o para volver a insertar)
+Esc: Interrumpir la respuesta en curso (si la hay)
+RePág/AvPág: Desplazar la conversación una página
+Ctrl+Inicio/Fin: Ir al principio/final de la conversación
+Ctrl+F: Buscar en la conversación (n/N: coincidencia anterior/siguiente)
+{mouse}
+Enter: Enviar mensaje (Chat), Elegir modelo (Configuración)
+F1-F3: Enviar una pregunta sugerida (con la entrada vacía)
+Tab: Cambiar el foco (Configuración)
+Izquierda/Derecha: Cambiar el proveedor de LLM (Configuración)
+Ctrl+R: Refrescar modelos (Configuración)
+Esc: Volver al chat (Configuración)
+-----------------
+"#;
@@ -0,0 +1,427 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::message::CopyFormat;
+use crate::ui::AppMode;
+
+/// What a key press asks for, whatever key it came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiCommand {
+    ToggleHelp,
+    Quit,
+    OpenSettings,
+    /// Save the settings and refresh the model list and status.
+    ReloadSettings,
+    ClearChat,
+    NewFromTemplate,
+    OpenToolPalette,
+    OpenContextPanel,
+    /// Show the files and excerpts the next request carries.
+    OpenAttachedPanel,
+    OpenSessionBrowser,
+    SearchSessions,
+    NextTab,
+    PreviousTab,
+    /// A tab by its index, from 0.
+    GoToTab(usize),
+    ToggleTimestamps,
+    /// Show or hide the timestamps and generation times after messages.
+    ToggleMessageStamps,
+    /// Capture the mouse, or leave it to the terminal's own selection.
+    ToggleMouse,
+    JumpToUnread,
+    OpenHints,
+    CopyCodeBlock,
+    CopyAnswer,
+    ShowMcpStatus,
+    /// Send the input, or run it as a slash command.
+    Submit,
+    /// Stop the answer being generated.
+    Cancel,
+    NextVerbosity,
+    /// Send a suggested follow-up, by its index from 0.
+    SendFollowUp(usize),
+    FocusPreviousMessage,
+    FocusNextMessage,
+    ScrollLeft,
+    ScrollRight,
+    PageUp,
+    PageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    /// Search the conversation.
+    FindInChat,
+    /// Run the tool call being confirmed.
+    Confirm,
+    Decline,
+    NextField,
+    PreviousField,
+    EditField,
+    /// Move the selection of a list or popup.
+    SelectNext,
+    SelectPrevious,
+    /// Act on the selected entry: open it, pick it or run it.
+    Choose,
+    /// Leave the popup, input or focused message.
+    Close,
+    /// Include or exclude the selected row of the context budget panel.
+    ToggleItem,
+    /// Take the selected attached file or excerpt out, or put it back.
+    RemoveItem,
+    ToggleFavorite,
+    RenameSession,
+    /// Show only the sessions with the next tag in use.
+    FilterByTag,
+    ToggleStar,
+    /// Delete the selected session, message or model, on a second press.
+    DeleteItem,
+    /// Wrap the focused message's code blocks instead of scrolling them.
+    ToggleWrap,
+    /// Fork the conversation at the focused message into a new tab.
+    BranchConversation,
+    ToggleDebugInfo,
+    /// Fold the focused message's turn down to its prompt and answer.
+    FoldTurn,
+    /// Fold every turn with tool steps, or unfold all if any is folded.
+    FoldAllTurns,
+    /// Run the focused failed tool call again.
+    RetryTool,
+    OpenCopyMenu,
+    CopyAs(CopyFormat),
+    EditPath,
+    AttachPath,
+    ListPath,
+    /// Show the first lines of the path.
+    HeadPath,
+    /// Apply an edited LUCIUS.md to this conversation too.
+    ApplyContextChange,
+    /// Leave this conversation on the LUCIUS.md it started with.
+    KeepContext,
+    ShowModelInfo,
+    PullModel,
+    UnloadModel,
+}
+
+impl UiCommand {
+    /// Commands that work on every screen but the confirmation dialog and
+    /// the prompt editor, handled before the keys of the screen itself.
+    pub fn is_global(self) -> bool {
+        use UiCommand::*;
+        matches!(
+            self,
+            ToggleHelp
+                | Quit
+                | OpenSettings
+                | ReloadSettings
+                | ClearChat
+                | NewFromTemplate
+                | OpenToolPalette
+                | OpenContextPanel
+                | OpenSessionBrowser
+                | SearchSessions
+                | NextTab
+                | PreviousTab
+                | ToggleTimestamps
+                | JumpToUnread
+                | OpenHints
+                | CopyCodeBlock
+                | CopyAnswer
+                | ShowMcpStatus
+        )
+    }
+}
+
+/// Which set of bindings applies, from the mode the UI is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputContext {
+    Chat,
+    /// A message focused with Alt+Up/Down.
+    FocusedMessage,
+    Settings,
+    /// The model list of the settings, with its info popup and pull input.
+    ModelList,
+    Confirmation,
+    /// The list popups: templates, tools, the context panels, help and hints.
+    Popup,
+    SessionBrowser,
+    Search,
+    CopyMenu,
+    PathActions,
+    ContextDiff,
+    /// The prompt editor, which takes every key itself.
+    Other,
+}
+
+impl InputContext {
+    /// The context of `mode`. The focused message and the model list are
+    /// parts of the chat and the settings, mapped where they are handled.
+    pub fn of(mode: &AppMode) -> Self {
+        match mode {
+            AppMode::Chat => InputContext::Chat,
+            AppMode::Settings => InputContext::Settings,
+            AppMode::Confirmation(_) => InputContext::Confirmation,
+            AppMode::Help
+            | AppMode::Hints(_)
+            | AppMode::TemplatePicker(_)
+            | AppMode::ToolPalette(_)
+            | AppMode::ContextPanel
+            | AppMode::AttachedPanel => InputContext::Popup,
+            AppMode::SessionBrowser(_) => InputContext::SessionBrowser,
+            AppMode::Search(_) => InputContext::Search,
+            AppMode::CopyMenu(_) => InputContext::CopyMenu,
+            AppMode::PathActions(_) => InputContext::PathActions,
+            AppMode::ContextDiff => InputContext::ContextDiff,
+            AppMode::PromptEditor => InputContext::Other,
+        }
+    }
+}
+
+/// A key with its modifiers, bound to a command in one context or, without
+/// one, wherever no context binding takes the key.
+#[derive(Debug, Clone, PartialEq)]
+struct Binding {
+    context: Option<InputContext>,
+    code: KeyCode,
+    modifiers: KeyModifiers,
+    command: UiCommand,
+}
+
+/// Turns key events into `UiCommand`s. Keys without a binding are left to
+/// the text inputs and lists of the mode.
+#[derive(Debug, Clone)]
+pub struct InputMapper {
+    bindings: Vec<Binding>,
+}
+
+impl Default for InputMapper {
+    fn default() -> Self {
+        use InputContext::*;
+        use UiCommand::*;
+
+        let ctrl = KeyModifiers::CONTROL;
+        let alt = KeyModifiers::ALT;
+        let none = KeyModifiers::NONE;
+        let mut mapper = InputMapper { bindings: vec![] };
+        for (key, command) in [
+            ('h', ToggleHelp),
+            ('q', Quit),
+            ('s', OpenSettings),
+            ('l', ClearChat),
+            ('n', NewFromTemplate),
+            ('p', OpenToolPalette),
+            ('b', OpenContextPanel),
+            ('g', OpenSessionBrowser),
+            ('e', ToggleTimestamps),
+            ('u', JumpToUnread),
+            ('o', OpenHints),
+            ('k', CopyCodeBlock),
+            ('y', CopyAnswer),
+            ('t', ShowMcpStatus),
+        ] {
+            mapper.bind(None, KeyCode::Char(key), ctrl, command);
+        }
+        mapper.bind(None, KeyCode::Char('f'), ctrl | KeyModifiers::SHIFT, SearchSessions);
+        mapper.bind(None, KeyCode::Tab, ctrl, NextTab);
+        mapper.bind(None, KeyCode::BackTab, ctrl, PreviousTab);
+        mapper.bind(Some(Settings), KeyCode::Char('r'), ctrl, ReloadSettings);
+
+        mapper.bind(Some(Chat), KeyCode::Enter, none, Submit);
+        mapper.bind(Some(Chat), KeyCode::Esc, none, Cancel);
+        for tab in 1..=9 {
+            mapper.bind(Some(Chat), KeyCode::Char(char::from(b'0' + tab as u8)), alt, GoToTab(tab - 1));
+        }
+        // Not bare digits, which start prompts too
+        for followup in 1..=3 {
+            mapper.bind(Some(Chat), KeyCode::F(followup), none, SendFollowUp(followup as usize - 1));
+        }
+        mapper.bind(Some(Chat), KeyCode::Char('v'), alt, NextVerbosity);
+        mapper.bind(Some(Chat), KeyCode::Char('t'), alt, ToggleMessageStamps);
+        mapper.bind(Some(Chat), KeyCode::Char('a'), alt, OpenAttachedPanel);
+        mapper.bind(Some(Chat), KeyCode::Char('m'), alt, ToggleMouse);
+        mapper.bind(Some(Chat), KeyCode::Up, alt, FocusPreviousMessage);
+        mapper.bind(Some(Chat), KeyCode::Down, alt, FocusNextMessage);
+        mapper.bind(Some(Chat), KeyCode::Left, alt, ScrollLeft);
+        mapper.bind(Some(Chat), KeyCode::Right, alt, ScrollRight);
+        mapper.bind(Some(Chat), KeyCode::PageUp, none, PageUp);
+        mapper.bind(Some(Chat), KeyCode::PageDown, none, PageDown);
+        mapper.bind(Some(Chat), KeyCode::Home, ctrl, ScrollToTop);
+        mapper.bind(Some(Chat), KeyCode::End, ctrl, ScrollToBottom);
+        mapper.bind(Some(Chat), KeyCode::Char('f'), ctrl, FindInChat);
+
+        for (code, command) in [
+            (KeyCode::Char('y'), Confirm),
+            (KeyCode::Char('n'), Decline),
+            (KeyCode::Esc, Decline),
+            (KeyCode::Down, NextField),
+            (KeyCode::Tab, NextField),
+            (KeyCode::Up, PreviousField),
+            (KeyCode::BackTab, PreviousField),
+            (KeyCode::Enter, EditField),
+        ] {
+            mapper.bind(Some(Confirmation), code, none, command);
+        }
+
+        mapper.bind(Some(FocusedMessage), KeyCode::Up, none, FocusPreviousMessage);
+        mapper.bind(Some(FocusedMessage), KeyCode::Down, none, FocusNextMessage);
+        mapper.bind(Some(FocusedMessage), KeyCode::Left, none, ScrollLeft);
+        mapper.bind(Some(FocusedMessage), KeyCode::Right, none, ScrollRight);
+        mapper.bind(Some(FocusedMessage), KeyCode::Esc, none, Close);
+        mapper.bind(Some(FocusedMessage), KeyCode::Char('z'), KeyModifiers::SHIFT, FoldAllTurns);
+        for (key, command) in [
+            ('w', ToggleWrap),
+            ('d', DeleteItem),
+            ('b', BranchConversation),
+            ('i', ToggleDebugInfo),
+            ('z', FoldTurn),
+            ('r', RetryTool),
+            ('y', OpenCopyMenu),
+            ('f', OpenHints),
+        ] {
+            mapper.bind(Some(FocusedMessage), KeyCode::Char(key), none, command);
+        }
+
+        // Every list moves with Up/Down, picks with Enter and closes with Esc
+        for context in [ModelList, Popup, SessionBrowser, Search, ContextDiff] {
+            mapper.bind(Some(context), KeyCode::Down, none, SelectNext);
+            mapper.bind(Some(context), KeyCode::Up, none, SelectPrevious);
+            mapper.bind(Some(context), KeyCode::Enter, none, Choose);
+            mapper.bind(Some(context), KeyCode::Esc, none, Close);
+        }
+        for (key, command) in [('i', ShowModelInfo), ('p', PullModel), ('d', DeleteItem), ('u', UnloadModel)] {
+            mapper.bind(Some(ModelList), KeyCode::Char(key), none, command);
+        }
+        mapper.bind(Some(Popup), KeyCode::Char(' '), none, ToggleItem);
+        mapper.bind(Some(Popup), KeyCode::Char('x'), none, RemoveItem);
+        mapper.bind(Some(Popup), KeyCode::Char('f'), none, ToggleFavorite);
+        for (key, command) in [('r', RenameSession), ('t', FilterByTag), ('s', ToggleStar), ('d', DeleteItem)] {
+            mapper.bind(Some(SessionBrowser), KeyCode::Char(key), none, command);
+        }
+        mapper.bind(Some(ContextDiff), KeyCode::PageDown, none, PageDown);
+        mapper.bind(Some(ContextDiff), KeyCode::PageUp, none, PageUp);
+        mapper.bind(Some(ContextDiff), KeyCode::Char('a'), none, ApplyContextChange);
+        mapper.bind(Some(ContextDiff), KeyCode::Char('n'), none, KeepContext);
+
+        for (key, format) in [('r', CopyFormat::Raw), ('t', CopyFormat::Text), ('c', CopyFormat::Code), ('q', CopyFormat::Quoted)] {
+            mapper.bind(Some(CopyMenu), KeyCode::Char(key), none, CopyAs(format));
+        }
+        mapper.bind(Some(CopyMenu), KeyCode::Esc, none, Close);
+        for (key, command) in [('e', EditPath), ('a', AttachPath), ('l', ListPath), ('h', HeadPath)] {
+            mapper.bind(Some(PathActions), KeyCode::Char(key), none, command);
+        }
+        mapper.bind(Some(PathActions), KeyCode::Esc, none, Close);
+        mapper
+    }
+}
+
+impl InputMapper {
+    /// Binds `code` with exactly `modifiers` to `command`, ahead of any
+    /// earlier binding of the same key.
+    pub fn bind(&mut self, context: Option<InputContext>, code: KeyCode, modifiers: KeyModifiers, command: UiCommand) {
+        self.bindings.insert(0, Binding { context, code: normalize(code), modifiers, command });
+    }
+
+    /// The command `key` stands for in `context`. Bindings of the context
+    /// come before global ones; the confirmation dialog has no global ones,
+    /// so nothing happens behind it. A shifted key without a binding of its
+    /// own takes the unshifted key's.
+    pub fn map(&self, context: InputContext, key: KeyEvent) -> Option<UiCommand> {
+        let (code, modifiers) = normalize_key(key);
+        let scopes: &[Option<InputContext>] = if context == InputContext::Confirmation { &[Some(context)] } else { &[Some(context), None] };
+        [modifiers, modifiers.difference(KeyModifiers::SHIFT)]
+            .into_iter()
+            .flat_map(|modifiers| scopes.iter().map(move |scope| (scope, modifiers)))
+            .find_map(|(scope, modifiers)| {
+                self.bindings.iter().find(|binding| binding.context == *scope && binding.code == code && binding.modifiers == modifiers)
+            })
+            .map(|binding| binding.command)
+    }
+}
+
+/// Letters are bound lowercase, so Caps Lock and Shift+letter presses match.
+fn normalize(code: KeyCode) -> KeyCode {
+    match code {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        code => code,
+    }
+}
+
+/// Terminals report Shift+Tab as `BackTab`, some with Shift still set, and
+/// Shift+letter as the uppercase letter, some without Shift. Shift is kept
+/// only on letters, as the lowercase letter with Shift.
+fn normalize_key(key: KeyEvent) -> (KeyCode, KeyModifiers) {
+    let modifiers = key.modifiers.difference(KeyModifiers::SHIFT);
+    match key.code {
+        KeyCode::Char(c) if c.is_ascii_uppercase() || key.modifiers.contains(KeyModifiers::SHIFT) && c.is_ascii_alphabetic() => {
+            (KeyCode::Char(c.to_ascii_lowercase()), modifiers | KeyModifiers::SHIFT)
+        }
+        code => (normalize(code), modifiers),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn maps_keys_per_context() {
+        let mapper = InputMapper::default();
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('q'), ctrl)), Some(UiCommand::Quit));
+        assert_eq!(mapper.map(InputContext::Other, key(KeyCode::Char('g'), ctrl)), Some(UiCommand::OpenSessionBrowser));
+        assert_eq!(mapper.map(InputContext::Settings, key(KeyCode::Char('r'), ctrl)), Some(UiCommand::ReloadSettings));
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('r'), ctrl)), None);
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('3'), KeyModifiers::ALT)), Some(UiCommand::GoToTab(2)));
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('x'), KeyModifiers::NONE)), None);
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::End, ctrl)), Some(UiCommand::ScrollToBottom));
+        assert_eq!(mapper.map(InputContext::Other, key(KeyCode::End, ctrl)), None);
+
+        // Global shortcuts stay out of the confirmation dialog
+        assert_eq!(mapper.map(InputContext::Confirmation, key(KeyCode::Char('Y'), KeyModifiers::SHIFT)), Some(UiCommand::Confirm));
+        assert_eq!(mapper.map(InputContext::Confirmation, key(KeyCode::Char('q'), ctrl)), None);
+    }
+
+    #[test]
+    fn shift_variants_match_their_bindings() {
+        let mapper = InputMapper::default();
+        let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('F'), ctrl_shift)), Some(UiCommand::SearchSessions));
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('F'), KeyModifiers::CONTROL)), Some(UiCommand::SearchSessions));
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('f'), KeyModifiers::CONTROL)), Some(UiCommand::FindInChat));
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::BackTab, ctrl_shift)), Some(UiCommand::PreviousTab));
+        assert_eq!(mapper.map(InputContext::Confirmation, key(KeyCode::BackTab, KeyModifiers::SHIFT)), Some(UiCommand::PreviousField));
+    }
+
+    #[test]
+    fn popups_and_lists_have_bindings_of_their_own() {
+        let mapper = InputMapper::default();
+        let none = KeyModifiers::NONE;
+        assert_eq!(InputContext::of(&AppMode::ContextPanel), InputContext::Popup);
+        assert_eq!(mapper.map(InputContext::Popup, key(KeyCode::Down, none)), Some(UiCommand::SelectNext));
+        assert_eq!(mapper.map(InputContext::Popup, key(KeyCode::Char(' '), none)), Some(UiCommand::ToggleItem));
+        assert_eq!(mapper.map(InputContext::SessionBrowser, key(KeyCode::Char('d'), none)), Some(UiCommand::DeleteItem));
+        assert_eq!(mapper.map(InputContext::ModelList, key(KeyCode::Char('d'), none)), Some(UiCommand::DeleteItem));
+        assert_eq!(mapper.map(InputContext::ModelList, key(KeyCode::Char('u'), none)), Some(UiCommand::UnloadModel));
+        assert_eq!(mapper.map(InputContext::CopyMenu, key(KeyCode::Char('q'), none)), Some(UiCommand::CopyAs(CopyFormat::Quoted)));
+        assert_eq!(mapper.map(InputContext::PathActions, key(KeyCode::Char('a'), none)), Some(UiCommand::AttachPath));
+        assert_eq!(mapper.map(InputContext::ContextDiff, key(KeyCode::Char('a'), none)), Some(UiCommand::ApplyContextChange));
+        // Typing in the search popup reaches its input
+        assert_eq!(mapper.map(InputContext::Search, key(KeyCode::Char('d'), none)), None);
+        assert_eq!(mapper.map(InputContext::Search, key(KeyCode::Esc, none)), Some(UiCommand::Close));
+
+        // Shift+letter has a binding of its own only where one is bound
+        assert_eq!(mapper.map(InputContext::FocusedMessage, key(KeyCode::Char('z'), none)), Some(UiCommand::FoldTurn));
+        assert_eq!(mapper.map(InputContext::FocusedMessage, key(KeyCode::Char('Z'), KeyModifiers::SHIFT)), Some(UiCommand::FoldAllTurns));
+        assert_eq!(mapper.map(InputContext::FocusedMessage, key(KeyCode::Char('Z'), none)), Some(UiCommand::FoldAllTurns));
+        assert_eq!(mapper.map(InputContext::FocusedMessage, key(KeyCode::Char('W'), none)), Some(UiCommand::ToggleWrap));
+    }
+
+    #[test]
+    fn later_bindings_take_precedence() {
+        let mut mapper = InputMapper::default();
+        mapper.bind(None, KeyCode::Char('q'), KeyModifiers::CONTROL, UiCommand::ToggleHelp);
+        assert_eq!(mapper.map(InputContext::Chat, key(KeyCode::Char('q'), KeyModifiers::CONTROL)), Some(UiCommand::ToggleHelp));
+    }
+}
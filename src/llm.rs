@@ -1,102 +1,345 @@
-use serde::Deserialize;
-use lucius::mcp::{parse_tool_call, ToolCall};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use lucius::mcp::ToolCall;
+
+use tokio::sync::mpsc;
+
+use crate::config::Config;
+use crate::message::ChatMessage;
+use crate::options::ModelOptions;
+
+pub mod anthropic;
+pub mod ollama;
+pub mod prompt;
+
+pub use prompt::{Prompt, PromptBuilder};
 
 #[derive(Deserialize, Clone)]
 pub struct Model {
     pub name: String,
-}
-
-#[derive(Deserialize)]
-pub struct TagsResponse {
-    pub models: Vec<Model>,
+    /// Bytes on disk, as `/api/tags` lists them.
+    #[serde(default)]
+    pub size: Option<u64>,
 }
 
 #[derive(PartialEq)] // Added for comparison in ConfirmationModal
 pub enum LLMResponse {
-    FinalResponse(String),
+    /// The full answer, and how the backend says it ended when it did.
+    FinalResponse(String, Option<ResponseDebug>),
     ToolCallDetected(ToolCall),
 }
 
-pub async fn ping_ollama(url: String) -> bool {
-    let client = reqwest::Client::new();
-    let res = client.get(url).send().await;
-    res.is_ok()
+/// How a streamed answer ended, for telling truncation and stop-token
+/// problems apart. Counts are only present when the backend reports them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseDebug {
+    /// e.g. `stop` or `length` from Ollama, `end_turn` or `max_tokens` from
+    /// Anthropic.
+    pub finish_reason: Option<String>,
+    pub prompt_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    /// Time spent generating the output, when the backend measures it
+    /// (Ollama's `eval_duration`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_ms: Option<u64>,
+    /// The stream chunk carrying the above, as received.
+    pub raw: String,
 }
 
-pub async fn fetch_models(url: String) -> Result<Vec<Model>, reqwest::Error> {
-    let client = reqwest::Client::new();
-    let res = client.get(format!("{}/api/tags", url)).send().await?;
-    let tags_response: TagsResponse = res.json().await?;
-    Ok(tags_response.models)
+/// How long `Provider::ping` waits for the backend to answer.
+pub const PING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Errors from any provider, shown to the user as-is.
+pub type ProviderError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An LLM backend. The UI and the background worker only talk to this trait,
+/// so a new backend only needs an implementation and a `ProviderKind`.
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// Whether the backend is reachable, answering within `PING_TIMEOUT`.
+    async fn ping(&self) -> bool;
+
+    async fn list_models(&self) -> Result<Vec<Model>, ProviderError>;
+
+    /// The JSON body `chat_stream` sends for `prompt`, as shown by
+    /// `/debug prompt`.
+    fn request_body(&self, prompt: &Prompt) -> serde_json::Value;
+
+    /// Sends `prompt` and returns the full answer, or the first tool call
+    /// found in it. Its tools are offered through the backend's native tool
+    /// API where it has one; the `[TOOL_CALL]` markers described in the
+    /// system prompt are the fallback. Text is also sent to `chunks` as it
+    /// arrives, waiting for room when the receiver falls behind.
+    async fn chat_stream(&self, prompt: Prompt, chunks: Option<&mpsc::Sender<String>>) -> Result<LLMResponse, ProviderError>;
+
+    /// Runs a single completion outside the conversation.
+    async fn generate(&self, model: String, prompt: String) -> Result<String, ProviderError>;
+}
+
+/// The `provider` config setting.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Ollama,
+    Anthropic,
+}
+
+impl ProviderKind {
+    /// The next backend, for cycling through them in the settings screen.
+    pub fn next(self) -> Self {
+        match self {
+            ProviderKind::Ollama => ProviderKind::Anthropic,
+            ProviderKind::Anthropic => ProviderKind::Ollama,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::Anthropic => "anthropic",
+        }
+    }
+}
+
+/// Takes the first complete line off `buffer`. Lines are only decoded once
+/// whole, as a streamed chunk may end in the middle of a multibyte character.
+fn next_line(buffer: &mut Vec<u8>) -> Option<String> {
+    let newline = buffer.iter().position(|&byte| byte == b'\n')?;
+    let line: Vec<u8> = buffer.drain(..=newline).collect();
+    Some(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Builds the provider selected in `config`. Cheap enough to call per request,
+/// which keeps it in step with URL changes made in the settings screen.
+pub fn provider(config: &Config) -> Box<dyn Provider> {
+    provider_with(config, &config.model_options(&config.project, &ModelOptions::default()))
+}
+
+/// Like `provider`, sending a conversation's parameters (see
+/// `SharedState::model_options`).
+pub fn provider_with(config: &Config, options: &ModelOptions) -> Box<dyn Provider> {
+    let options = options.clone();
+    match config.provider {
+        ProviderKind::Ollama => Box::new(
+            ollama::OllamaProvider::new(config.ollama_url.clone().unwrap_or_default())
+                .with_num_predict(config.verbosity.num_predict())
+                .with_options(options)
+                .with_keep_alive(config.keep_alive.clone()),
+        ),
+        ProviderKind::Anthropic => Box::new(
+            anthropic::AnthropicProvider::new(
+                config.anthropic.url.clone().unwrap_or_else(|| anthropic::DEFAULT_URL.to_string()),
+                config.anthropic.api_key(),
+                config.verbosity.max_tokens(config.anthropic.max_tokens),
+            )
+            .with_options(options),
+        ),
+    }
+}
+
+/// Most characters of conversation sent to be summarized; the latest
+/// messages are the ones kept.
+const MAX_TRANSCRIPT_CHARS: usize = 24_000;
+/// Most characters of a single message in such a transcript.
+const MAX_TRANSCRIPT_MESSAGE_CHARS: usize = 4_000;
+
+/// `messages` as plain text to be summarized or noted, capped: long
+/// messages are cut short and the oldest ones left out once it grows too
+/// long.
+fn transcript(messages: &[ChatMessage]) -> String {
+    let mut parts = Vec::new();
+    let mut total = 0;
+    for msg in messages.iter().rev() {
+        let text = msg.display_text();
+        let text = match text.char_indices().nth(MAX_TRANSCRIPT_MESSAGE_CHARS) {
+            Some((end, _)) => format!("{}…", &text[..end]),
+            None => text,
+        };
+        total += text.chars().count();
+        if total > MAX_TRANSCRIPT_CHARS {
+            break;
+        }
+        parts.push(text);
+    }
+    parts.reverse();
+    parts.join("\n\n")
 }
 
+/// Condenses turns that no longer fit the context window into a short
+/// summary to send in their place. With the `previous` summary, only the
+/// turns dropped since are sent, to extend it.
+pub async fn summarize(provider: &dyn Provider, model: String, previous: Option<&str>, messages: &[ChatMessage]) -> Option<String> {
+    let prompt = match previous {
+        Some(previous) => format!(
+            "Here is a summary of the earlier part of a conversation:\n{}\n\nRewrite it in a few sentences to also \
+             cover the turns that followed, keeping facts, decisions, names, commands and open questions:\n\n{}",
+            previous,
+            transcript(messages)
+        ),
+        None => format!(
+            "Summarize this earlier part of a conversation in a few sentences, keeping facts, \
+             decisions, names, commands and open questions:\n\n{}",
+            transcript(messages)
+        ),
+    };
+    match provider.generate(model, prompt).await {
+        Ok(summary) if !summary.trim().is_empty() => Some(summary.trim().to_string()),
+        Ok(_) => None,
+        Err(e) => {
+            log::warn!("Failed to summarize earlier turns: {}", e);
+            None
+        }
+    }
+}
 
-pub async fn chat_stream(
-    messages: Vec<String>,
-    model: String,
-    url: String,
-    system_message: Option<String>,
-) -> Result<LLMResponse, reqwest::Error> {
-    let client = reqwest::Client::new();
-    
-    let mut ollama_messages = Vec::new();
+/// Notes on a finished conversation for the session browser: what was
+/// asked, which tools ran and how it ended.
+pub async fn session_notes(provider: &dyn Provider, model: String, messages: &[ChatMessage]) -> Option<String> {
+    let prompt = format!(
+        "Write notes on this conversation in at most three short sentences: what was asked, \
+         which tools or commands ran, and the outcome. Reply with the notes only.\n\n{}",
+        transcript(messages)
+    );
+    match provider.generate(model, prompt).await {
+        Ok(notes) if !notes.trim().is_empty() => Some(notes.split_whitespace().collect::<Vec<_>>().join(" ")),
+        Ok(_) => None,
+        Err(e) => {
+            log::warn!("Failed to write session notes: {}", e);
+            None
+        }
+    }
+}
 
-    if let Some(sys_msg) = system_message {
-        ollama_messages.push(serde_json::json!({"role": "system", "content": sys_msg}));
+/// A title of a few words for a conversation, from its first exchange.
+pub async fn session_title(provider: &dyn Provider, model: String, question: String, answer: String) -> Option<String> {
+    let prompt = format!(
+        "A user asked:\n{}\n\nThe assistant answered:\n{}\n\n\
+         Write a title for this conversation of at most six words. \
+         Reply with the title only, without quotes or punctuation at the end.",
+        question, answer
+    );
+    match provider.generate(model, prompt).await {
+        Ok(text) => {
+            let title = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+            let title = title.trim_start_matches("Title:").trim().trim_matches(|c: char| matches!(c, '"' | '\'' | '*' | '#' | '.')).trim();
+            (!title.is_empty()).then(|| title.chars().take(60).collect())
+        }
+        Err(e) => {
+            log::warn!("Failed to generate a session title: {}", e);
+            None
+        }
     }
+}
 
-    for msg in messages {
-        if let Some(content) = msg.strip_prefix("You: ") {
-            ollama_messages.push(serde_json::json!({"role": "user", "content": content}));
-        } else if let Some(content) = msg.strip_prefix("Lucius: ") {
-            ollama_messages.push(serde_json::json!({"role": "assistant", "content": content}));
-        } else if let Some(content) = msg.strip_prefix("Tool Result: ") {
-            ollama_messages.push(serde_json::json!({"role": "tool", "content": content}));
-        } else if msg.starts_with("Tool Call: ") {
-            ollama_messages.push(serde_json::json!({"role": "assistant", "content": msg}));
+/// Asks the model for up to three short follow-up prompts to the last exchange.
+pub async fn suggest_followups(provider: &dyn Provider, model: String, question: String, answer: String) -> Vec<String> {
+    let prompt = format!(
+        "A user asked:\n{}\n\nThe assistant answered:\n{}\n\n\
+         Suggest up to 3 short follow-up questions the user might ask next. \
+         Reply with one question per line and nothing else.",
+        question, answer
+    );
+    match provider.generate(model, prompt).await {
+        Ok(text) => text
+            .lines()
+            .map(|line| line.trim().trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*')).trim())
+            .filter(|line| !line.is_empty())
+            .take(3)
+            .map(|line| line.to_string())
+            .collect(),
+        Err(e) => {
+            log::warn!("Failed to generate follow-up suggestions: {}", e);
+            vec![]
         }
     }
-    
-    let req_body = serde_json::json!({
-        "model": model,
-        "stream": true,
-        "messages": ollama_messages,
-    });
-    
-    let mut res = client
-        .post(format!("{}/api/chat", url))
-        .json(&req_body)
-        .send()
-        .await?;
-
-    let mut full_response = String::new();
-    while let Ok(Some(chunk)) = res.chunk().await {
-        let text = String::from_utf8_lossy(&chunk);
-        for line in text.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(chat_res) = serde_json::from_str::<serde_json::Value>(line) {
-                if let Some(message) = chat_res["message"].as_object() {
-                    if let Some(content) = message["content"].as_str() {
-                        full_response.push_str(content);
-                        if let Some(tool_call) = parse_tool_call(&full_response) {
-                            return Ok(LLMResponse::ToolCallDetected(tool_call));
-                        }
-                    }
-                }
-                if chat_res["done"].as_bool().unwrap_or(false) {
-                    log::info!("Full response from LLM: {}", full_response);
-                    return Ok(LLMResponse::FinalResponse(full_response));
-                }
-            } else {
-                log::error!("Failed to parse stream chunk from /api/chat: {}", line);
-            }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcripts_keep_the_latest_messages_within_the_cap() {
+        let long = ChatMessage::user("x".repeat(MAX_TRANSCRIPT_MESSAGE_CHARS + 10));
+        let text = transcript(&[ChatMessage::user("oldest"), long, ChatMessage::assistant("latest")]);
+        assert!(text.contains('…') && text.ends_with("latest") && text.contains("oldest"));
+
+        let many: Vec<ChatMessage> = (0..10).map(|n| ChatMessage::user(format!("{} {}", n, "y".repeat(MAX_TRANSCRIPT_MESSAGE_CHARS)))).collect();
+        let text = transcript(&many);
+        assert!(text.chars().count() <= MAX_TRANSCRIPT_CHARS + 20);
+        assert!(text.starts_with("You: 5"));
+        assert!(text.ends_with('…') && text.contains("You: 9 y"));
+    }
+
+    #[test]
+    fn lines_split_inside_a_character_decode_whole() {
+        let line = "data: {\"text\": \"señal\"}\n".as_bytes();
+        let split = line.iter().position(|&byte| byte == 0xc3).unwrap() + 1;
+        let mut buffer = line[..split].to_vec();
+        assert_eq!(next_line(&mut buffer), None);
+        buffer.extend_from_slice(&line[split..]);
+        assert_eq!(next_line(&mut buffer).as_deref(), Some("data: {\"text\": \"señal\"}\n"));
+        assert!(buffer.is_empty());
+    }
+
+    /// Answers every completion with `reply`, or fails when there is none.
+    struct Canned(Option<&'static str>);
+
+    #[async_trait]
+    impl Provider for Canned {
+        async fn ping(&self) -> bool {
+            true
+        }
+
+        async fn list_models(&self) -> Result<Vec<Model>, ProviderError> {
+            Ok(vec![])
+        }
+
+        fn request_body(&self, prompt: &Prompt) -> serde_json::Value {
+            serde_json::json!({"model": prompt.model})
+        }
+
+        async fn chat_stream(&self, _prompt: Prompt, _chunks: Option<&mpsc::Sender<String>>) -> Result<LLMResponse, ProviderError> {
+            Ok(LLMResponse::FinalResponse(self.0.unwrap_or_default().to_string(), None))
+        }
+
+        async fn generate(&self, _model: String, _prompt: String) -> Result<String, ProviderError> {
+            self.0.map(str::to_string).ok_or_else(|| "connection refused".into())
         }
     }
-    if let Some(tool_call) = parse_tool_call(&full_response) {
-        Ok(LLMResponse::ToolCallDetected(tool_call))
-    } else {
-        Ok(LLMResponse::FinalResponse(full_response))
+
+    #[tokio::test]
+    async fn titles_and_follow_ups_are_cleaned_up_from_any_provider() {
+        let ask = |reply| async move {
+            let provider = Canned(reply);
+            let title = session_title(&provider, "m".into(), "q".into(), "a".into()).await;
+            let followups = suggest_followups(&provider, "m".into(), "q".into(), "a".into()).await;
+            (title, followups)
+        };
+
+        let (title, _) = ask(Some("\nTitle: \"Disk usage on db01\".\n")).await;
+        assert_eq!(title.as_deref(), Some("Disk usage on db01"));
+        let (_, followups) = ask(Some("1. Which mount?\n\n2) Why?\n- How big?\n* Extra?")).await;
+        assert_eq!(followups, ["Which mount?", "Why?", "How big?"]);
+        assert_eq!(ask(Some("  \n")).await, (None, vec![]));
+        assert_eq!(ask(None).await, (None, vec![]));
+    }
+
+    #[test]
+    fn the_configured_backend_gets_the_conversation_options() {
+        let prompt = PromptBuilder::new("llama3").system(Some("Be terse.".into())).message(ChatMessage::user("Hi")).build();
+        let options = ModelOptions { temperature: Some(0.5), ..ModelOptions::default() };
+
+        let mut config = Config::default();
+        let body = provider_with(&config, &options).request_body(&prompt);
+        assert_eq!(body["options"]["temperature"], 0.5);
+        assert!(body.get("system").is_none());
+
+        config.provider = config.provider.next();
+        assert_eq!(config.provider.label(), "anthropic");
+        let body = provider_with(&config, &options).request_body(&prompt);
+        assert_eq!((body["system"].as_str(), body["temperature"].as_f64()), (Some("Be terse."), Some(0.5)));
+        assert_eq!(config.provider.next(), ProviderKind::Ollama);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,239 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use lucius::mcp::parse_tool_call;
+
+use tokio::sync::mpsc;
+
+use super::{next_line, LLMResponse, Model, Prompt, Provider, ProviderError, ResponseDebug, PING_TIMEOUT};
+use crate::message::{ChatMessage, Role};
+use crate::options::ModelOptions;
+
+pub const DEFAULT_URL: &str = "https://api.anthropic.com";
+const API_VERSION: &str = "2023-06-01";
+
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// The Anthropic Messages API.
+pub struct AnthropicProvider {
+    url: String,
+    api_key: String,
+    max_tokens: u32,
+    /// Only `temperature` and `top_p` have an equivalent in the API.
+    options: ModelOptions,
+    client: reqwest::Client,
+}
+
+impl AnthropicProvider {
+    pub fn new(url: String, api_key: String, max_tokens: u32) -> Self {
+        Self {
+            url,
+            api_key,
+            max_tokens,
+            options: ModelOptions::default(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ModelOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, format!("{}{}", self.url, path))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+    }
+
+    /// Sends a Messages request and returns the response, turning API errors
+    /// into their message.
+    async fn post_messages(&self, body: serde_json::Value) -> Result<reqwest::Response, ProviderError> {
+        let res = self.request(reqwest::Method::POST, "/v1/messages").json(&body).send().await?;
+        if res.status().is_success() {
+            Ok(res)
+        } else {
+            let status = res.status();
+            let body: serde_json::Value = res.json().await.unwrap_or_default();
+            let message = body["error"]["message"].as_str().unwrap_or("no details").to_string();
+            Err(format!("Anthropic API error ({}): {}", status, message).into())
+        }
+    }
+}
+
+/// Converts chat history to Messages API turns. The API has no tool role and
+/// requires alternating user/assistant turns starting with the user, so tool
+/// results are sent as user text and consecutive turns of one role are merged.
+fn to_messages(history: &[ChatMessage]) -> Vec<serde_json::Value> {
+    let mut turns: Vec<(&str, String)> = Vec::new();
+    for msg in history {
+        let turn = match msg.role {
+            Role::User => ("user", msg.content.clone()),
+            Role::Assistant => ("assistant", msg.content.clone()),
+            Role::ToolResult => ("user", msg.display_text()),
+            Role::ToolCall => ("assistant", msg.display_text()),
+            Role::Error | Role::Notice => continue,
+        };
+        match turns.last_mut() {
+            Some((role, content)) if *role == turn.0 => {
+                content.push_str("\n\n");
+                content.push_str(&turn.1);
+            }
+            _ => turns.push(turn),
+        }
+    }
+    if turns.first().is_some_and(|(role, _)| *role == "assistant") {
+        turns.remove(0);
+    }
+    turns
+        .into_iter()
+        .map(|(role, content)| serde_json::json!({"role": role, "content": content}))
+        .collect()
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    async fn ping(&self) -> bool {
+        match self.request(reqwest::Method::GET, "/v1/models").timeout(PING_TIMEOUT).send().await {
+            Ok(res) => res.status().is_success(),
+            Err(_) => false,
+        }
+    }
+
+    async fn list_models(&self) -> Result<Vec<Model>, ProviderError> {
+        let res = self.request(reqwest::Method::GET, "/v1/models").send().await?.error_for_status()?;
+        let models: ModelsResponse = res.json().await?;
+        Ok(models.data.into_iter().map(|model| Model { name: model.id, size: None }).collect())
+    }
+
+    fn request_body(&self, prompt: &Prompt) -> serde_json::Value {
+        // Tool calls come back as [TOOL_CALL] markers in the text, so tools
+        // are only described in the system prompt
+        let mut req_body = serde_json::json!({
+            "model": prompt.model,
+            "max_tokens": self.max_tokens,
+            "stream": true,
+            "messages": to_messages(&prompt.messages),
+        });
+        if let Some(temperature) = self.options.temperature {
+            req_body["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(top_p) = self.options.top_p {
+            req_body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(sys_msg) = &prompt.system {
+            req_body["system"] = serde_json::Value::String(sys_msg.clone());
+        }
+        req_body
+    }
+
+    async fn chat_stream(&self, prompt: Prompt, chunks: Option<&mpsc::Sender<String>>) -> Result<LLMResponse, ProviderError> {
+        let mut res = self.post_messages(self.request_body(&prompt)).await?;
+
+        // Server-sent events; a chunk may end in the middle of a line
+        let mut full_response = String::new();
+        let mut buffer = Vec::new();
+        // Input tokens come with `message_start`, the rest with `message_delta`
+        let mut prompt_tokens = None;
+        let mut debug = None;
+        while let Some(chunk) = res.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(line) = next_line(&mut buffer) {
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let Ok(event) = serde_json::from_str::<serde_json::Value>(data.trim()) else {
+                    log::error!("Failed to parse stream event from /v1/messages: {}", data);
+                    continue;
+                };
+                match event["type"].as_str() {
+                    Some("message_start") => prompt_tokens = event["message"]["usage"]["input_tokens"].as_u64(),
+                    Some("message_delta") => {
+                        debug = Some(ResponseDebug {
+                            finish_reason: event["delta"]["stop_reason"].as_str().map(str::to_string),
+                            prompt_tokens,
+                            output_tokens: event["usage"]["output_tokens"].as_u64(),
+                            generation_ms: None,
+                            raw: data.trim().to_string(),
+                        });
+                    }
+                    Some("content_block_delta") => {
+                        if let Some(text) = event["delta"]["text"].as_str() {
+                            full_response.push_str(text);
+                            if let Some(tx) = chunks {
+                                let _ = tx.send(text.to_string()).await;
+                            }
+                            if let Some(tool_call) = parse_tool_call(&full_response) {
+                                return Ok(LLMResponse::ToolCallDetected(tool_call));
+                            }
+                        }
+                    }
+                    Some("message_stop") => {
+                        log::info!("Full response from LLM: {}", full_response);
+                        return Ok(LLMResponse::FinalResponse(full_response, debug));
+                    }
+                    Some("error") => {
+                        let message = event["error"]["message"].as_str().unwrap_or("unknown error");
+                        return Err(format!("Anthropic API error: {}", message).into());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(tool_call) = parse_tool_call(&full_response) {
+            Ok(LLMResponse::ToolCallDetected(tool_call))
+        } else {
+            Ok(LLMResponse::FinalResponse(full_response, debug))
+        }
+    }
+
+    async fn generate(&self, model: String, prompt: String) -> Result<String, ProviderError> {
+        let req_body = serde_json::json!({
+            "model": model,
+            "max_tokens": self.max_tokens,
+            "messages": [{"role": "user", "content": prompt}],
+        });
+        let res: serde_json::Value = self.post_messages(req_body).await?.json().await?;
+        let text = res["content"]
+            .as_array()
+            .map(|blocks| blocks.iter().filter_map(|block| block["text"].as_str()).collect::<String>())
+            .unwrap_or_default();
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucius::mcp::ToolCall;
+
+    #[test]
+    fn messages_alternate_and_start_with_the_user() {
+        let call = ToolCall { tool: "exec".into(), params: serde_json::json!({"command": "uptime"}), expect: vec![] };
+        let history = vec![
+            ChatMessage::assistant("Hello"),
+            ChatMessage::user("How long has it been up?"),
+            ChatMessage::tool_call(&call),
+            ChatMessage::tool_result(&call, "up 3 days"),
+            ChatMessage::error("connection reset"),
+            ChatMessage::user("And the load?"),
+            ChatMessage::notice("Sessions: nothing to clean"),
+            ChatMessage::assistant("Low."),
+        ];
+        let messages = to_messages(&history);
+        let roles: Vec<_> = messages.iter().map(|msg| msg["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "user", "assistant"]);
+        assert_eq!(messages[0]["content"], "How long has it been up?");
+        let merged = messages[2]["content"].as_str().unwrap();
+        assert!(merged.contains("up 3 days") && merged.ends_with("And the load?"));
+        assert!(!merged.contains("connection reset"));
+    }
+}
@@ -0,0 +1,444 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use lucius::mcp::{parse_tool_call, ToolCall, ToolSchema};
+
+use tokio::sync::mpsc;
+
+use super::{next_line, LLMResponse, Model, Prompt, Provider, ProviderError, ResponseDebug, PING_TIMEOUT};
+use crate::message::Role;
+use crate::options::ModelOptions;
+
+#[derive(Deserialize)]
+pub struct TagsResponse {
+    pub models: Vec<Model>,
+}
+
+/// Ollama's `keep_alive`: seconds, where -1 keeps the model loaded and 0
+/// unloads it after each request, or a duration such as "30m".
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Seconds(i64),
+    Duration(String),
+}
+
+impl KeepAlive {
+    /// The choices the settings screen cycles through, after the server's
+    /// default.
+    pub const PRESETS: [KeepAlive; 2] = [KeepAlive::Seconds(-1), KeepAlive::Seconds(0)];
+
+    /// The next setting after `current`: the server default, then the presets.
+    /// A custom value from the config moves on to the server default.
+    pub fn cycle(current: &Option<KeepAlive>) -> Option<KeepAlive> {
+        match current {
+            None => Some(Self::PRESETS[0].clone()),
+            Some(value) => Self::PRESETS.iter().position(|preset| preset == value).and_then(|i| Self::PRESETS.get(i + 1)).cloned(),
+        }
+    }
+}
+
+/// What `/api/show` says about a model, for the settings info popup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub name: String,
+    /// e.g. "8.0B".
+    pub parameter_size: Option<String>,
+    /// e.g. "Q4_K_M".
+    pub quantization: Option<String>,
+    pub family: Option<String>,
+    /// The context length the model was trained for.
+    pub context_length: Option<u64>,
+    pub template: Option<String>,
+    pub license: Option<String>,
+}
+
+impl ModelInfo {
+    fn from_show(name: &str, show: &serde_json::Value) -> Self {
+        let text = |value: &serde_json::Value| value.as_str().map(str::trim).filter(|text| !text.is_empty()).map(str::to_string);
+        // Architecture-specific keys, e.g. "llama.context_length"
+        let context_length = show["model_info"]
+            .as_object()
+            .and_then(|info| info.iter().find(|(key, _)| key.ends_with(".context_length")))
+            .and_then(|(_, value)| value.as_u64());
+        ModelInfo {
+            name: name.to_string(),
+            parameter_size: text(&show["details"]["parameter_size"]),
+            quantization: text(&show["details"]["quantization_level"]),
+            family: text(&show["details"]["family"]),
+            context_length,
+            template: text(&show["template"]),
+            license: text(&show["license"]),
+        }
+    }
+}
+
+/// A model Ollama has in memory, from `/api/ps`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LoadedModel {
+    pub name: String,
+    /// Bytes of memory it takes, of which `size_vram` are on the GPU.
+    pub size: u64,
+    #[serde(default)]
+    pub size_vram: u64,
+}
+
+/// A local or remote Ollama server.
+pub struct OllamaProvider {
+    url: String,
+    /// `num_predict` for chat requests; `None` keeps the model's default.
+    num_predict: Option<i32>,
+    /// Sent as the request's `options`; unset ones keep the model's defaults.
+    options: ModelOptions,
+    /// How long the model stays loaded after a request; `None` keeps the
+    /// server's default.
+    keep_alive: Option<KeepAlive>,
+    client: reqwest::Client,
+}
+
+impl OllamaProvider {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            num_predict: None,
+            options: ModelOptions::default(),
+            keep_alive: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn with_num_predict(mut self, num_predict: Option<i32>) -> Self {
+        self.num_predict = num_predict;
+        self
+    }
+
+    pub fn with_options(mut self, options: ModelOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_keep_alive(mut self, keep_alive: Option<KeepAlive>) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Adds `keep_alive` to a request that loads the model. Every such request
+    /// sets it anew, so leaving it out of one would reset it to the default.
+    fn keep_alive_in(&self, req_body: &mut serde_json::Value) {
+        if let Some(keep_alive) = &self.keep_alive {
+            req_body["keep_alive"] = serde_json::to_value(keep_alive).unwrap_or_default();
+        }
+    }
+
+    /// The server's version from `/api/version`.
+    pub async fn version(&self) -> Result<String, ProviderError> {
+        let res: serde_json::Value = self.client.get(format!("{}/api/version", self.url)).send().await?.error_for_status()?.json().await?;
+        Ok(res["version"].as_str().unwrap_or("unknown").to_string())
+    }
+
+    /// Loads `model` into memory with a one-token generation.
+    pub async fn warm_up(&self, model: &str) -> Result<(), ProviderError> {
+        let mut req_body = serde_json::json!({
+            "model": model,
+            "prompt": "Hi",
+            "stream": false,
+            "options": {"num_predict": 1},
+        });
+        self.keep_alive_in(&mut req_body);
+        self.client.post(format!("{}/api/generate", self.url)).json(&req_body).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Downloads `model` with `/api/pull`, calling `progress` with each
+    /// status line and, while a layer downloads, its completed and total bytes.
+    pub async fn pull(&self, model: &str, progress: impl Fn(&str, Option<(u64, u64)>)) -> Result<(), ProviderError> {
+        let req_body = serde_json::json!({"model": model, "stream": true});
+        let mut res = self.client.post(format!("{}/api/pull", self.url)).json(&req_body).send().await?;
+        if !res.status().is_success() {
+            return Err(api_error("pull", res).await.into());
+        }
+        // A status line may be split across chunks
+        let mut buffer = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(line) = next_line(&mut buffer) {
+                let Ok(status) = serde_json::from_str::<serde_json::Value>(line.trim()) else {
+                    continue;
+                };
+                if let Some(error) = status["error"].as_str() {
+                    return Err(format!("Ollama pull error: {}", error).into());
+                }
+                let bytes = status["total"].as_u64().map(|total| (status["completed"].as_u64().unwrap_or(0), total));
+                progress(status["status"].as_str().unwrap_or_default(), bytes);
+            }
+        }
+        Ok(())
+    }
+
+    /// Details of `model` from `/api/show`.
+    pub async fn show(&self, model: &str) -> Result<ModelInfo, ProviderError> {
+        let req_body = serde_json::json!({"model": model});
+        let res = self.client.post(format!("{}/api/show", self.url)).json(&req_body).send().await?;
+        if !res.status().is_success() {
+            return Err(api_error("show", res).await.into());
+        }
+        let show: serde_json::Value = res.json().await?;
+        Ok(ModelInfo::from_show(model, &show))
+    }
+
+    /// Removes `model` from the server with `/api/delete`.
+    pub async fn delete(&self, model: &str) -> Result<(), ProviderError> {
+        let req_body = serde_json::json!({"model": model});
+        let res = self.client.delete(format!("{}/api/delete", self.url)).json(&req_body).send().await?;
+        if !res.status().is_success() {
+            return Err(api_error("delete", res).await.into());
+        }
+        Ok(())
+    }
+
+    /// The models loaded in memory, from `/api/ps`.
+    pub async fn running(&self) -> Result<Vec<LoadedModel>, ProviderError> {
+        let res = self.client.get(format!("{}/api/ps", self.url)).send().await?;
+        if !res.status().is_success() {
+            return Err(api_error("ps", res).await.into());
+        }
+        let body: serde_json::Value = res.json().await?;
+        let models = serde_json::from_value(body["models"].clone())?;
+        Ok(models)
+    }
+
+    /// Frees the memory of `model` with an empty generation whose
+    /// `keep_alive` is 0.
+    pub async fn unload(&self, model: &str) -> Result<(), ProviderError> {
+        let req_body = serde_json::json!({"model": model, "keep_alive": 0});
+        let res = self.client.post(format!("{}/api/generate", self.url)).json(&req_body).send().await?;
+        if !res.status().is_success() {
+            return Err(api_error("unload", res).await.into());
+        }
+        Ok(())
+    }
+
+    /// Embeddings of `inputs` from `/api/embed`, in the same order.
+    pub async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let req_body = serde_json::json!({"model": model, "input": inputs});
+        let res = self.client.post(format!("{}/api/embed", self.url)).json(&req_body).send().await?;
+        if !res.status().is_success() {
+            return Err(api_error("embeddings", res).await.into());
+        }
+        let body: serde_json::Value = res.json().await?;
+        let embeddings = serde_json::from_value(body["embeddings"].clone())?;
+        Ok(embeddings)
+    }
+
+    async fn post_chat(&self, req_body: &serde_json::Value) -> Result<reqwest::Response, reqwest::Error> {
+        self.client.post(format!("{}/api/chat", self.url)).json(req_body).send().await
+    }
+}
+
+/// The `error` Ollama sent with a failed response, with its status.
+async fn api_error(endpoint: &str, res: reqwest::Response) -> String {
+    let status = res.status();
+    let body: serde_json::Value = res.json().await.unwrap_or_default();
+    format!("Ollama {} error ({}): {}", endpoint, status, body["error"].as_str().unwrap_or("no details"))
+}
+
+/// Tool schemas in the `tools` format of `/api/chat`.
+fn native_tools(tools: &[ToolSchema]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                },
+            })
+        })
+        .collect()
+}
+
+/// The first entry of a streamed message's `tool_calls` array. Arguments
+/// arrive as an object, or from some models as a JSON string.
+fn native_tool_call(message: &serde_json::Value) -> Option<ToolCall> {
+    let function = &message["tool_calls"].as_array()?.first()?["function"];
+    let params = match &function["arguments"] {
+        serde_json::Value::String(text) => serde_json::from_str(text).ok()?,
+        serde_json::Value::Null => serde_json::json!({}),
+        arguments => arguments.clone(),
+    };
+    Some(ToolCall { tool: function["name"].as_str()?.to_string(), params, expect: vec![] })
+}
+
+/// What the final `done` chunk of `/api/chat` says about the answer.
+fn response_debug(chunk: &serde_json::Value, raw: &str) -> ResponseDebug {
+    ResponseDebug {
+        finish_reason: chunk["done_reason"].as_str().map(str::to_string),
+        prompt_tokens: chunk["prompt_eval_count"].as_u64(),
+        output_tokens: chunk["eval_count"].as_u64(),
+        // Reported in nanoseconds
+        generation_ms: chunk["eval_duration"].as_u64().map(|ns| ns / 1_000_000),
+        raw: raw.to_string(),
+    }
+}
+
+#[async_trait]
+impl Provider for OllamaProvider {
+    async fn ping(&self) -> bool {
+        let res = self.client.get(&self.url).timeout(PING_TIMEOUT).send().await;
+        res.is_ok()
+    }
+
+    async fn list_models(&self) -> Result<Vec<Model>, ProviderError> {
+        let res = self.client.get(format!("{}/api/tags", self.url)).send().await?;
+        let tags_response: TagsResponse = res.json().await?;
+        Ok(tags_response.models)
+    }
+
+    fn request_body(&self, prompt: &Prompt) -> serde_json::Value {
+        let mut ollama_messages = Vec::new();
+
+        if let Some(sys_msg) = &prompt.system {
+            ollama_messages.push(serde_json::json!({"role": "system", "content": sys_msg}));
+        }
+
+        for msg in &prompt.messages {
+            let (role, content) = match msg.role {
+                Role::User => ("user", msg.content.clone()),
+                Role::Assistant => ("assistant", msg.content.clone()),
+                Role::ToolResult => ("tool", msg.content.clone()),
+                Role::ToolCall => ("assistant", msg.display_text()),
+                Role::Error | Role::Notice => continue,
+            };
+            ollama_messages.push(serde_json::json!({"role": role, "content": content}));
+        }
+
+        let mut req_body = serde_json::json!({
+            "model": prompt.model,
+            "stream": true,
+            "messages": ollama_messages,
+        });
+        if !prompt.tools.is_empty() {
+            req_body["tools"] = serde_json::Value::Array(native_tools(&prompt.tools));
+        }
+        if let Some(num_predict) = self.num_predict {
+            req_body["options"]["num_predict"] = serde_json::json!(num_predict);
+        }
+        for (key, value) in self.options.to_json() {
+            req_body["options"][key] = value;
+        }
+        self.keep_alive_in(&mut req_body);
+        req_body
+    }
+
+    async fn chat_stream(&self, prompt: Prompt, chunks: Option<&mpsc::Sender<String>>) -> Result<LLMResponse, ProviderError> {
+        let mut req_body = self.request_body(&prompt);
+        let model = prompt.model;
+
+        let mut res = self.post_chat(&req_body).await?;
+        if !prompt.tools.is_empty() && res.status() == reqwest::StatusCode::BAD_REQUEST {
+            // Models without tool support reject `tools`; they fall back to
+            // the [TOOL_CALL] markers described in the system prompt
+            let error = res.text().await.unwrap_or_default();
+            log::warn!("Model {} rejected native tools, retrying without them: {}", model, error);
+            if let Some(body) = req_body.as_object_mut() {
+                body.remove("tools");
+            }
+            res = self.post_chat(&req_body).await?;
+        }
+
+        // One JSON object per line; a chunk may end in the middle of one
+        let mut full_response = String::new();
+        let mut buffer = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(line) = next_line(&mut buffer) {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                if let Ok(chat_res) = serde_json::from_str::<serde_json::Value>(line) {
+                    if let Some(tool_call) = native_tool_call(&chat_res["message"]) {
+                        return Ok(LLMResponse::ToolCallDetected(tool_call));
+                    }
+                    if let Some(message) = chat_res["message"].as_object() {
+                        if let Some(content) = message["content"].as_str() {
+                            full_response.push_str(content);
+                            if let (Some(tx), false) = (chunks, content.is_empty()) {
+                                let _ = tx.send(content.to_string()).await;
+                            }
+                            if let Some(tool_call) = parse_tool_call(&full_response) {
+                                return Ok(LLMResponse::ToolCallDetected(tool_call));
+                            }
+                        }
+                    }
+                    if chat_res["done"].as_bool().unwrap_or(false) {
+                        log::info!("Full response from LLM: {}", full_response);
+                        return Ok(LLMResponse::FinalResponse(full_response, Some(response_debug(&chat_res, line))));
+                    }
+                } else {
+                    log::error!("Failed to parse stream chunk from /api/chat: {}", line);
+                }
+            }
+        }
+        if let Some(tool_call) = parse_tool_call(&full_response) {
+            Ok(LLMResponse::ToolCallDetected(tool_call))
+        } else {
+            Ok(LLMResponse::FinalResponse(full_response, None))
+        }
+    }
+
+    /// Runs a single non-streaming completion and returns the generated text.
+    async fn generate(&self, model: String, prompt: String) -> Result<String, ProviderError> {
+        let mut req_body = serde_json::json!({
+            "model": model,
+            "prompt": prompt,
+            "stream": false,
+        });
+        self.keep_alive_in(&mut req_body);
+        let res: serde_json::Value = self
+            .client
+            .post(format!("{}/api/generate", self.url))
+            .json(&req_body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(res["response"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_alive_cycles_from_the_default_through_the_presets() {
+        let mut keep_alive = None;
+        let mut seen = vec![];
+        for _ in 0..3 {
+            keep_alive = KeepAlive::cycle(&keep_alive);
+            seen.push(keep_alive.clone());
+        }
+        assert_eq!(seen, vec![Some(KeepAlive::Seconds(-1)), Some(KeepAlive::Seconds(0)), None]);
+        assert_eq!(KeepAlive::cycle(&Some(KeepAlive::Duration("30m".into()))), None);
+        let parsed: KeepAlive = serde_json::from_str("\"30m\"").unwrap();
+        assert_eq!(serde_json::to_value(parsed).unwrap(), serde_json::json!("30m"));
+    }
+
+    #[test]
+    fn model_info_is_read_from_show() {
+        let show = serde_json::json!({
+            "license": "LLAMA 3.1 COMMUNITY LICENSE",
+            "template": "{{ .Prompt }}",
+            "details": {"family": "llama", "parameter_size": "8.0B", "quantization_level": "Q4_K_M"},
+            "model_info": {"general.architecture": "llama", "llama.context_length": 131072},
+        });
+        let info = ModelInfo::from_show("llama3.1:8b", &show);
+        assert_eq!(info.parameter_size.as_deref(), Some("8.0B"));
+        assert_eq!(info.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(info.context_length, Some(131072));
+        assert_eq!(info.license.as_deref(), Some("LLAMA 3.1 COMMUNITY LICENSE"));
+        assert_eq!(ModelInfo::from_show("x", &serde_json::json!({})).template, None);
+    }
+}
@@ -0,0 +1,135 @@
+use lucius::mcp::ToolSchema;
+
+use crate::message::ChatMessage;
+
+/// Everything one chat request carries, before a provider turns it into
+/// its own JSON.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Prompt {
+    pub model: String,
+    pub system: Option<String>,
+    /// The conversation, already without UI-only entries.
+    pub messages: Vec<ChatMessage>,
+    /// Tools offered natively where the backend supports it.
+    pub tools: Vec<ToolSchema>,
+}
+
+impl Prompt {
+    /// Adds a section after the rest of the system prompt, the way
+    /// [`PromptBuilder::system`] would have.
+    pub fn append_system(&mut self, section: Option<String>) {
+        if let Some(section) = section.filter(|section| !section.trim().is_empty()) {
+            self.system = Some(match self.system.take() {
+                Some(system) => format!("{}\n\n{}", system, section),
+                None => section,
+            });
+        }
+    }
+}
+
+/// Assembles a [`Prompt`]: system prompt sections in order, the history the
+/// model may see and the tools it may call.
+#[derive(Debug, Default)]
+pub struct PromptBuilder {
+    model: String,
+    system: Vec<String>,
+    messages: Vec<ChatMessage>,
+    tools: Vec<ToolSchema>,
+}
+
+impl PromptBuilder {
+    pub fn new(model: impl Into<String>) -> Self {
+        Self { model: model.into(), ..Self::default() }
+    }
+
+    /// Appends a section to the system prompt; absent or blank ones are
+    /// skipped.
+    pub fn system(mut self, section: Option<String>) -> Self {
+        if let Some(section) = section.filter(|section| !section.trim().is_empty()) {
+            self.system.push(section);
+        }
+        self
+    }
+
+    /// Appends history entries, dropping the ones that only live in the UI.
+    pub fn history(mut self, messages: impl IntoIterator<Item = ChatMessage>) -> Self {
+        self.messages.extend(messages.into_iter().filter(ChatMessage::is_sent));
+        self
+    }
+
+    pub fn message(self, message: ChatMessage) -> Self {
+        self.history([message])
+    }
+
+    pub fn tools(mut self, tools: Vec<ToolSchema>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn build(self) -> Prompt {
+        Prompt {
+            model: self.model,
+            system: (!self.system.is_empty()).then(|| self.system.join("\n\n")),
+            messages: self.messages,
+            tools: self.tools,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{anthropic::AnthropicProvider, ollama::OllamaProvider, Provider};
+    use serde_json::json;
+
+    fn prompt() -> Prompt {
+        PromptBuilder::new("m")
+            .system(Some("LUCIUS.md".to_string()))
+            .system(None)
+            .system(Some("  ".to_string()))
+            .system(Some("Be brief.".to_string()))
+            .history([
+                ChatMessage::assistant("Hello"),
+                ChatMessage::user("hi"),
+                ChatMessage::error("connection refused"),
+                ChatMessage::cancelled(""),
+                ChatMessage::user("again"),
+            ])
+            .build()
+    }
+
+    #[test]
+    fn builds_system_prompt_and_drops_ui_only_entries() {
+        let prompt = prompt();
+        assert_eq!(prompt.system.as_deref(), Some("LUCIUS.md\n\nBe brief."));
+        let contents: Vec<&str> = prompt.messages.iter().map(|msg| msg.content.as_str()).collect();
+        assert_eq!(contents, ["Hello", "hi", "again"]);
+        assert_eq!(PromptBuilder::new("m").build().system, None);
+    }
+
+    #[test]
+    fn appended_sections_match_built_ones() {
+        let mut prompt = prompt();
+        prompt.append_system(Some(" ".to_string()));
+        prompt.append_system(Some("Excerpts".to_string()));
+        assert_eq!(prompt.system.as_deref(), Some("LUCIUS.md\n\nBe brief.\n\nExcerpts"));
+        let mut empty = PromptBuilder::new("m").build();
+        empty.append_system(Some("Excerpts".to_string()));
+        assert_eq!(empty.system.as_deref(), Some("Excerpts"));
+    }
+
+    #[test]
+    fn providers_map_the_prompt_to_their_payloads() {
+        let ollama = OllamaProvider::new(String::new()).with_num_predict(Some(400)).request_body(&prompt());
+        assert_eq!(ollama["messages"][0], json!({"role": "system", "content": "LUCIUS.md\n\nBe brief."}));
+        assert_eq!(ollama["messages"].as_array().map(Vec::len), Some(4));
+        assert_eq!(ollama["options"]["num_predict"], json!(400));
+        assert!(ollama.get("tools").is_none());
+
+        // Anthropic needs alternating turns starting with the user
+        let anthropic = AnthropicProvider::new(String::new(), String::new(), 1024).request_body(&prompt());
+        assert_eq!(anthropic["system"], json!("LUCIUS.md\n\nBe brief."));
+        assert_eq!(anthropic["messages"], json!([{"role": "user", "content": "hi\n\nagain"}]));
+        assert_eq!(anthropic["max_tokens"], json!(1024));
+    }
+}
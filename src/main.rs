@@ -1,8 +1,10 @@
 use std::io::{self, stdout};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use crossterm::{
-    event::{self},
+    event::{self, EventStream},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -12,36 +14,107 @@ use ratatui::{
 };
 use simplelog::{LevelFilter, WriteLogger};
 use std::fs::File;
-use tokio::sync::{mpsc, Mutex};
+use futures::{FutureExt, StreamExt};
+use tokio::sync::{mpsc, oneshot, Mutex, Semaphore};
+use tokio::time::MissedTickBehavior;
 
 mod app;
 mod context;
 mod config;
 mod ui;
 mod handlers;
-mod renderer;
+mod keymap;
+mod views;
 mod llm;
 mod mouse;
 mod clipboard;
-
-use app::{App, SharedState};
-
-use ui::Action;
-
-use llm::{ping_ollama, fetch_models, chat_stream, LLMResponse};
-
-use lucius::mcp;
-
-
-
+mod postprocess;
+mod hints;
+mod attachments;
+mod templates;
+mod commands;
+mod i18n;
+mod timefmt;
+mod quick;
+mod headless;
+mod doctor;
+mod markdown;
+mod audit;
+mod forms;
+mod autocomplete;
+mod budget;
+mod session;
+mod message;
+mod theme;
+mod verbosity;
+mod vim;
+mod rag;
+mod options;
+mod sync;
+mod export;
+mod model_cache;
+mod syslog;
+mod catalog;
+mod resources;
+mod diff;
+mod chaos;
+
+use app::{App, ModelPull, PendingTask, SharedState};
+
+use ui::{Action, AppMode, ConfirmationModal, Update};
+
+use llm::{ollama::OllamaProvider, LLMResponse, Prompt, Provider};
+use budget::{ContextSection, Overflow};
+use session::{Session, SessionNotes};
+use model_cache::ModelCache;
+
+use lucius::mcp::{self, ApprovalDecision, ApprovalRecord, TaskContext, ToolCall};
+
+use i18n::{t, tf};
+
+use message::{ChatMessage, Role};
+
+/// How long workers get to answer `/inventory` before their snapshots are read.
+const INVENTORY_WAIT_SECS: u64 = 3;
+/// Redraw interval with animations off, enough to pick up background changes
+/// and expire status messages.
+const IDLE_TICK: Duration = Duration::from_secs(1);
+/// Chunks of one answer waiting to be forwarded to the UI; when full, the
+/// provider stops reading its stream until there is room.
+const CHUNK_QUEUE: usize = 64;
+/// Numbers each generation, so the chunks of one cancelled in a conversation
+/// never mix with those of the next.
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+/// Held while a tool confirmation is open. Replacing an open modal would
+/// drop its answer and record that call as declined, so others wait their turn.
+static CONFIRMATION: Mutex<()> = Mutex::const_new(());
+/// How often the connection status and model list are refreshed unasked.
+const MODELS_REFRESH: Duration = Duration::from_secs(60);
+/// How often LUCIUS.md is checked for changes.
+const LUCIUS_MD_POLL: Duration = Duration::from_secs(2);
+/// How long quitting waits for the notes of the open conversation.
+const EXIT_NOTES_TIMEOUT: Duration = Duration::from_secs(20);
+/// The log, in the directory Lucius is started from.
+const LOG_FILE: &str = "lucius.log";
+
+
+
+/// Supervises the work the UI asks for. Quick actions run here in order;
+/// answers and tool tasks get tasks of their own, so a long generation in one
+/// conversation never holds up another, with at most `[requests]
+/// max_concurrent` answers generated at once.
 async fn background_worker(
 
     state: Arc<Mutex<SharedState>>,
 
     mut action_rx: mpsc::Receiver<Action>,
 
+    update_tx: mpsc::Sender<Update>,
+
 ) {
 
+    let limit = Arc::new(Semaphore::new(state.lock().await.config.requests.max_concurrent.max(1)));
+
     loop {
 
         tokio::select! {
@@ -52,144 +125,392 @@ async fn background_worker(
 
                 match action {
 
-                    Action::RefreshModelsAndStatus => {
+                    Action::RefreshModelsAndStatus { scheduled } => {
+
+                        // Asked without holding the state, so a slow or unreachable backend
+                        // never stalls the UI
+                        let config = state_lock.config.clone();
+                        let redis_conn = state_lock.redis_conn.clone();
+                        drop(state_lock);
+
+                        let provider = llm::provider(&config);
+                        let online = provider.ping().await;
+                        let models = if online { Some(provider.list_models().await) } else { None };
+                        // Pick up tools from workers that registered since the last refresh
+                        let (workers, dead_letters) = match redis_conn {
+                            Some(mut redis_conn) => (
+                                mcp::fetch_workers(&mut redis_conn).await.map_err(|e| log::warn!("{}", e)).ok(),
+                                mcp::dead_letter_count(&mut redis_conn).await.map_err(|e| log::warn!("{}", e)).ok(),
+                            ),
+                            None => (None, None),
+                        };
+
+                        state_lock = state.lock().await;
+                        let was_online = state_lock.status;
+                        state_lock.status = online;
+
+                        // The scheduled refresh only speaks up when the connection came or went
+                        if !scheduled || online != was_online {
+                            let msg = if online { t("status.ollama_online") } else { t("status.ollama_offline") };
+                            state_lock.status_message = Some((msg.to_string(), std::time::Instant::now()));
+                        }
+
+                        match models {
+                            Some(Ok(models)) => {
+                                ModelCache::new(&config, &models).save();
+                                state_lock.models = models;
+                                if config.provider == llm::ProviderKind::Ollama {
+                                    let ollama = OllamaProvider::new(config.ollama_url.clone().unwrap_or_default());
+                                    spawn_loaded_refresh(state.clone(), ollama);
+                                }
+                                state_lock.models_cached = None;
+                                if !scheduled {
+                                    state_lock.status_message = Some((t("status.models_updated").to_string(), std::time::Instant::now()));
+                                }
+                            }
+                            // The cached list would otherwise stay up as "refreshing"
+                            Some(Err(e)) => {
+                                log::warn!("Failed to list models: {}", e);
+                                state_lock.models = vec![];
+                                state_lock.models_cached = None;
+                                state_lock.status_message = Some((tf("status.models_failed", &[("error", e.to_string().as_str())]), std::time::Instant::now()));
+                            }
+                            None => {
+                                state_lock.models = vec![];
+                                state_lock.models_cached = None;
+                                state_lock.loaded_models = vec![];
+                            }
+                        }
+
+                        if let Some(workers) = workers {
+                            state_lock.worker_tools = mcp::aggregate_tools(&workers);
+                            state_lock.workers = workers;
+                        }
+                        if let Some(count) = dead_letters {
+                            state_lock.dead_letter_count = count;
+                        }
 
-                        let url = state_lock.config.ollama_url.clone().unwrap_or_default();
+                    }
 
-                        state_lock.status = ping_ollama(url.clone()).await;
+                    Action::SendMessage(input) => {
 
-                        let msg = if state_lock.status { "Ollama is online." } else { "Ollama is offline." };
+                        let conversation = state_lock.conversation_id.clone();
 
-                        state_lock.status_message = Some((msg.to_string(), std::time::Instant::now()));
+                        // Everything but the excerpts is taken now, so switching tabs during retrieval
+                        // cannot change or drop the request. The embeddings come from Ollama, which
+                        // need not be running for other providers
+                        let index = state_lock.rag_index.clone().filter(|_| state_lock.config.provider == llm::ProviderKind::Ollama);
+                        if index.is_some() {
+                            state_lock.retrieved.remove(&conversation);
+                        }
+                        let mut prompt = state_lock.prompt();
+                        let num_ctx = state_lock.model_options().num_ctx;
+                        let budget = state_lock.config.context.budget(state_lock.config.provider, num_ctx);
+                        let overflow = state_lock.config.context.overflow;
+                        let provider = llm::provider_with(&state_lock.config, &state_lock.model_options());
+                        let with_excerpts = !state_lock.excluded_context.contains(&ContextSection::Retrieved);
+
+                        // Project excerpts for this prompt, embedded without holding the lock
+                        if let Some(index) = index {
+                            let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default());
+                            let top_k = state_lock.config.rag.top_k;
+                            let skip = state_lock.skipped_excerpts.clone();
+                            drop(state_lock);
+                            let retrieved = rag::retrieve(&index, &ollama, top_k, &input, &skip).await.unwrap_or_else(|e| {
+                                log::warn!("Retrieval from the project index failed: {}", e);
+                                vec![]
+                            });
+                            state_lock = state.lock().await;
+                            if with_excerpts {
+                                prompt.append_system(rag::excerpts_prompt(&retrieved));
+                            }
+                            state_lock.retrieved.insert(conversation.clone(), retrieved);
+                        }
 
+                        if limit.available_permits() == 0 {
+                            state_lock.status_message = Some((t("status.request_queued").to_string(), std::time::Instant::now()));
+                        }
+                        let earlier = budget::fit(&mut prompt, budget);
+                        if !earlier.is_empty() {
+                            let turns = earlier.iter().filter(|msg| msg.role == Role::User).count();
+                            let key = match overflow {
+                                Overflow::Drop => "status.context_dropped",
+                                Overflow::Summarize => "status.context_summarized",
+                            };
+                            state_lock.status_message = Some((tf(key, &[("turns", turns.to_string().as_str())]), std::time::Instant::now()));
+                        }
+                        let earlier = if overflow == Overflow::Summarize { earlier } else { vec![] };
+                        let generation = spawn_generation(state.clone(), limit.clone(), update_tx.clone(), provider, conversation.clone(), prompt, earlier);
+                        state_lock.generations.insert(conversation, generation);
 
+                    }
 
-                        if state_lock.status {
+                    Action::RunTool(tool) => {
 
-                            if let Ok(models) = fetch_models(url).await {
+                        let state = state.clone();
 
-                                state_lock.models = models;
+                        tokio::spawn(async move { execute_tool(&state, &tool, ApprovalDecision::UserInitiated).await });
 
-                                state_lock.status_message = Some(("Models updated.".to_string(), std::time::Instant::now()));
+                    }
+
+                    Action::ConfirmTool(tool) => {
+
+                        let state = state.clone();
+
+                        tokio::spawn(async move { confirm_and_execute(&state, tool).await });
+
+                    }
+
+                    Action::ListDeadLetters => {
+
+                        if let Some(mut redis_conn) = state_lock.redis_conn.clone() {
+
+                            match mcp::dead_letters(&mut redis_conn).await {
+
+                                Ok(letters) => {
+
+                                    state_lock.dead_letter_count = letters.len();
+
+                                    state_lock.push_message(ChatMessage::notice(format_dead_letters(&letters)));
+
+                                }
+
+                                Err(e) => state_lock.push_message(ChatMessage::error(e)),
 
                             }
 
                         } else {
 
-                            state_lock.models = vec![];
+                            state_lock.push_message(ChatMessage::error("Not connected to MCP."));
 
                         }
 
                     }
 
-                    Action::SendMessage(input) => {
+                    Action::WarmUpModel(model) => {
+
+                        // Hosted models have no load time to hide
+                        if state_lock.config.provider == llm::ProviderKind::Ollama {
 
-                        let history = state_lock.chat_history.clone();
+                            let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default()).with_keep_alive(state_lock.config.keep_alive.clone());
 
-                        let model = state_lock.config.selected_model.clone().unwrap_or_default();
+                            state_lock.warming_up = Some((model.clone(), std::time::Instant::now()));
 
-                        let url = state_lock.config.ollama_url.clone().unwrap_or_default();
+                            spawn_warm_up(state.clone(), ollama, model);
 
-                        let context = state_lock.lucius_context.clone();
+                        }
+
+                    }
 
-                        
+                    Action::PullModel(model) => {
+
+                        if state_lock.pulling.is_some() {
+
+                            state_lock.status_message = Some((t("status.pull_busy").to_string(), std::time::Instant::now()));
+
+                        } else {
 
-                        // Drop the lock so the UI can update while the LLM is thinking
+                            let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default());
+
+                            state_lock.pulling = Some(ModelPull { model: model.clone(), status: String::new(), bytes: None });
+
+                            spawn_pull(state.clone(), ollama, model);
+
+                        }
+
+                    }
+
+                    Action::ShowModelInfo(model) => {
+
+                        let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default());
 
                         drop(state_lock);
 
+                        let result = ollama.show(&model).await;
 
+                        state_lock = state.lock().await;
 
-                        // This part needs its own state management for multi-turn tool use
+                        match result {
 
-                        let mut messages_for_llm = history;
+                            Ok(info) => state_lock.model_info = Some(info),
 
-                        messages_for_llm.push(format!("You: {}", input));
+                            Err(e) => {
 
+                                let msg = tf("status.model_info_failed", &[("model", model.as_str()), ("error", e.to_string().as_str())]);
 
+                                state_lock.status_message = Some((msg, std::time::Instant::now()));
 
-                        // The actual stream handling needs to be done here
+                            }
 
-                        match chat_stream(messages_for_llm, model, url, context).await {
+                        }
 
-                            Ok(llm_response) => {
+                    }
 
-                                let mut state_lock = state.lock().await;
+                    Action::DeleteModel(model) => {
 
-                                match llm_response {
+                        let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default());
 
-                                    LLMResponse::FinalResponse(text) => {
+                        drop(state_lock);
 
-                                        state_lock.chat_history.push(format!("Lucius: {}", text));
+                        let result = ollama.delete(&model).await;
 
-                                    },
+                        state_lock = state.lock().await;
 
-                                    LLMResponse::ToolCallDetected(tool) => {
+                        let msg = match result {
 
-                                        let tool_text = serde_json::to_string_pretty(&tool).unwrap_or_default();
+                            Ok(()) => {
 
-                                        state_lock.chat_history.push(format!("Tool Call: {}", tool_text));
+                                if state_lock.forget_model(&model) {
 
+                                    state_lock.config.save();
 
+                                }
 
-                                        if let Some(ref mut redis_conn) = state_lock.redis_conn {
+                                tf("status.model_deleted", &[("model", model.as_str())])
 
-                                            match mcp::submit_task(redis_conn, &tool).await {
+                            }
 
-                                                Ok(task_id) => {
+                            Err(e) => tf("status.model_delete_failed", &[("model", model.as_str()), ("error", e.to_string().as_str())]),
 
-                                                    match mcp::poll_result(redis_conn, &task_id).await {
+                        };
 
-                                                        Ok(result) => {
+                        state_lock.status_message = Some((msg, std::time::Instant::now()));
 
-                                                            state_lock.chat_history.push(format!("Tool Result: {}", result));
+                    }
 
-                                                            
+                    Action::UnloadModel(model) => {
 
-                                                            // TODO: Send the result back to the LLM for a final response.
+                        let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default());
 
-                                                            // For now, just display the raw result.
+                        drop(state_lock);
 
-                                                        },
+                        let result = ollama.unload(&model).await;
 
-                                                        Err(e) => {
+                        let loaded = ollama.running().await;
 
-                                                            state_lock.chat_history.push(format!("Error polling result: {}", e));
+                        state_lock = state.lock().await;
 
-                                                        }
+                        if let Ok(loaded) = loaded {
 
-                                                    }
+                            state_lock.loaded_models = loaded;
 
-                                                },
+                        }
 
-                                                Err(e) => {
+                        let msg = match result {
 
-                                                    state_lock.chat_history.push(format!("Error submitting task: {}", e));
+                            Ok(()) => tf("status.model_unloaded", &[("model", model.as_str())]),
 
-                                                }
+                            Err(e) => tf("status.model_unload_failed", &[("model", model.as_str()), ("error", e.to_string().as_str())]),
 
-                                            }
+                        };
 
-                                        } else {
+                        state_lock.status_message = Some((msg, std::time::Instant::now()));
 
-                                            state_lock.chat_history.push("Error: Not connected to MCP.".to_string());
+                    }
+
+                    Action::WriteNotes(session) => {
+
+                        let provider = llm::provider(&state_lock.config);
+
+                        let model = session.model.clone().or_else(|| state_lock.config.selected_model.clone()).unwrap_or_default();
+
+                        spawn_notes(state.clone(), provider, model, *session);
 
-                                        }
+                    }
+
+                    Action::SyncHistory => {
+
+                        // Like tool calls, nothing is shared in the clear when the key failed to load
+                        if let (Some(redis_conn), Ok(key)) = (state_lock.redis_conn.clone(), state_lock.task_key.clone()) {
 
-                                    }
+                            spawn_sync(state.clone(), redis_conn, update_tx.clone(), state_lock.config.sync.clone(), key);
+
+                        }
+
+                    }
+
+                    Action::SharePrompt(prompt) => {
+
+                        if let (Some(mut redis_conn), Ok(key)) = (state_lock.redis_conn.clone(), state_lock.task_key.clone()) {
+
+                            let config = state_lock.config.sync.clone();
+
+                            tokio::spawn(async move {
+
+                                if let Err(e) = sync::push_prompt(&mut redis_conn, &config, key.as_ref(), &prompt).await {
+
+                                    log::warn!("{}", e);
 
                                 }
 
-                            }
+                            });
 
-                            Err(e) => {
+                        }
+
+                    }
+
+                    Action::IndexProject => {
+
+                        let ollama = OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default());
+
+                        spawn_indexing(state.clone(), ollama, state_lock.config.rag.clone());
+
+                    }
+
+                    Action::RefreshInventory => {
+
+                        if let Some(redis_conn) = state_lock.redis_conn.clone() {
+
+                            spawn_inventory_refresh(state.clone(), redis_conn);
+
+                        } else {
+
+                            state_lock.push_message(ChatMessage::error("Not connected to MCP."));
+
+                        }
+
+                    }
+
+                    Action::RequeueDeadLetters(index) => {
+
+                        if let Some(mut redis_conn) = state_lock.redis_conn.clone() {
+
+                            let result = match index {
+
+                                Some(index) => mcp::requeue_dead_letter(&mut redis_conn, index).await.map(|_| (1, vec![])),
+
+                                None => requeue_all_dead_letters(&mut redis_conn).await,
+
+                            };
+
+                            match result {
+
+                                Ok((count, failed)) => {
+
+                                    state_lock.dead_letter_count = state_lock.dead_letter_count.saturating_sub(count);
 
-                                let mut state_lock = state.lock().await;
+                                    let count = count.to_string();
 
-                                state_lock.chat_history.push(format!("Error: {}", e));
+                                    let msg = match failed.first() {
+
+                                        Some(error) => tf("status.requeued_partly", &[("count", count.as_str()), ("failed", failed.len().to_string().as_str()), ("error", error.as_str())]),
+
+                                        None => tf("status.requeued", &[("count", count.as_str())]),
+
+                                    };
+
+                                    state_lock.status_message = Some((msg, std::time::Instant::now()));
+
+                                }
+
+                                Err(e) => state_lock.push_message(ChatMessage::error(e)),
 
                             }
 
+                        } else {
+
+                            state_lock.push_message(ChatMessage::error("Not connected to MCP."));
+
                         }
 
                     }
@@ -206,75 +527,915 @@ async fn background_worker(
 
 
 
+/// Generates the answer to `prompt` in conversation `id` once `limit` allows,
+/// streaming it to the UI and adding it to that conversation wherever it is
+/// by then. `earlier` turns, left out to fit the context window, are first
+/// summarized into the system prompt. Returns the handle Esc aborts it with.
+fn spawn_generation(
+    state: Arc<Mutex<SharedState>>,
+    limit: Arc<Semaphore>,
+    update_tx: mpsc::Sender<Update>,
+    provider: Box<dyn Provider>,
+    id: String,
+    mut prompt: Prompt,
+    earlier: Vec<ChatMessage>,
+) -> tokio::task::AbortHandle {
+    let model = prompt.model.clone();
+    let question = prompt.messages.last().map(|msg| msg.content.clone()).unwrap_or_default();
+    let serial = GENERATIONS.fetch_add(1, Ordering::Relaxed);
+    let (chunk_tx, mut chunk_rx) = mpsc::channel(CHUNK_QUEUE);
+    let (chunk_id, chunk_update_tx) = (id.clone(), update_tx.clone());
+    let forward = tokio::spawn(async move {
+        while let Some(chunk) = chunk_rx.recv().await {
+            chaos::delay().await;
+            let _ = chunk_update_tx.send(Update::LLMChunk(chunk_id.clone(), serial, chunk)).await;
+        }
+    });
+    // Its own task, so Esc can abort it without touching the rest
+    let summaries = state.clone();
+    let summary_id = id.clone();
+    let generation = tokio::spawn(async move {
+        let _permit = limit.acquire_owned().await;
+        if !earlier.is_empty() {
+            // Only the turns dropped since the last summary are summarized
+            let cached = summaries.lock().await.earlier_summaries.get(&summary_id).filter(|cached| cached.covers(&earlier)).cloned();
+            let summary = match cached {
+                Some(cached) if cached.messages == earlier.len() => Some(cached.text),
+                Some(cached) => llm::summarize(provider.as_ref(), prompt.model.clone(), Some(&cached.text), &earlier[cached.messages..]).await,
+                None => llm::summarize(provider.as_ref(), prompt.model.clone(), None, &earlier).await,
+            };
+            if let Some(summary) = summary {
+                summaries.lock().await.earlier_summaries.insert(summary_id, budget::EarlierSummary::new(&earlier, summary.clone()));
+                let section = format!("Summary of the earlier conversation, left out to fit the context window:\n{}", summary);
+                prompt.system = Some(match prompt.system.take() {
+                    Some(system) => format!("{}\n\n{}", system, section),
+                    None => section,
+                });
+            }
+        }
+        let started = std::time::Instant::now();
+        let response = match chaos::fail("answer") {
+            Ok(()) => provider.chat_stream(prompt, Some(&chunk_tx)).await,
+            Err(e) => Err(e.into()),
+        };
+        (provider, response, started.elapsed())
+    });
+    let abort = generation.abort_handle();
+    tokio::spawn(async move {
+        let joined = generation.await;
+        // Every chunk is forwarded before the answer takes their place
+        let _ = forward.await;
+        async {
+            let mut state_lock = state.lock().await;
+            // Unless Esc already removed it and a new prompt was sent since
+            if state_lock.generations.get(&id).is_some_and(|generation| generation.is_finished()) {
+                state_lock.generations.remove(&id);
+                state_lock.busy.remove(&id);
+            }
+            let (provider, response, elapsed) = match joined {
+                // Cancelled with Esc; the handler already kept the partial answer
+                Err(e) if e.is_cancelled() => return,
+                Err(e) => return state_lock.push_to(&id, ChatMessage::error(e)),
+                Ok(joined) => joined,
+            };
+            // Answering loads the model into memory if it was not already
+            if state_lock.config.provider == llm::ProviderKind::Ollama {
+                spawn_loaded_refresh(state.clone(), OllamaProvider::new(state_lock.config.ollama_url.clone().unwrap_or_default()));
+            }
+            match response {
+                Ok(LLMResponse::FinalResponse(text, debug)) => {
+                    // Cut here rather than in the stream, so the answer kept is the one cut short
+                    let text = postprocess::apply(&state_lock.config.postprocess, &chaos::truncate(text));
+                    let duration_ms = debug.as_ref().and_then(|debug| debug.generation_ms).unwrap_or(elapsed.as_millis() as u64);
+                    state_lock.push_to(&id, ChatMessage { debug, duration_ms: Some(duration_ms), ..ChatMessage::assistant(text.clone()) });
+                    if state_lock.config.sessions.auto_title && state_lock.needs_title(&id) {
+                        let title_provider = llm::provider(&state_lock.config);
+                        spawn_title(state.clone(), title_provider, id.clone(), model.clone(), question.clone(), text.clone());
+                    }
+                    if let Some(history_len) = state_lock.history_len(&id).filter(|_| state_lock.config.followups.enabled) {
+                        let followup_model = state_lock.config.followups.model.clone().unwrap_or(model);
+                        spawn_followup_suggestions(state.clone(), provider, id.clone(), followup_model, question, text, history_len);
+                    }
+                }
+                Ok(LLMResponse::ToolCallDetected(tool)) => {
+                    if !state_lock.tool_enabled(&tool.tool) {
+                        state_lock.push_to(&id, ChatMessage::error(format!("Tool '{}' is not enabled for this conversation.", tool.tool)));
+                    } else if state_lock.conversation_id == id {
+                        drop(state_lock);
+                        confirm_and_execute(&state, tool).await;
+                    } else {
+                        // Tool results go to the active conversation, so this one waits for the user to come back
+                        let note = format!("Tool call '{}' was not run while the conversation was in the background; press r on it to run it.", tool.tool);
+                        state_lock.push_to(&id, ChatMessage::tool_error(&tool, note));
+                    }
+                }
+                Err(e) => state_lock.push_to(&id, ChatMessage::error(e)),
+            }
+        }
+        .await;
+        // Only now, so the streamed text neither vanishes before the answer nor shows beside it
+        let _ = update_tx.send(Update::LLMDone(id, serial)).await;
+    });
+    abort
+}
+
+/// Asks the model for follow-up prompts in the background and keeps them for
+/// the latest answer of conversation `conversation`, unless it has moved on in
+/// the meantime.
+fn spawn_followup_suggestions(
+    state: Arc<Mutex<SharedState>>,
+    provider: Box<dyn Provider>,
+    conversation: String,
+    model: String,
+    question: String,
+    answer: String,
+    history_len: usize,
+) {
+    tokio::spawn(async move {
+        let suggestions = llm::suggest_followups(provider.as_ref(), model, question, answer).await;
+        let mut state_lock = state.lock().await;
+        if state_lock.history_len(&conversation) == Some(history_len) {
+            state_lock.followups.insert(conversation, suggestions);
+        }
+    });
+}
+
+/// Asks the model for a title after a conversation's first exchange, for its
+/// tab and the session browser.
+fn spawn_title(state: Arc<Mutex<SharedState>>, provider: Box<dyn Provider>, conversation: String, model: String, question: String, answer: String) {
+    tokio::spawn(async move {
+        if let Some(title) = llm::session_title(provider.as_ref(), model, question, answer).await {
+            state.lock().await.set_generated_title(&conversation, &title);
+        }
+    });
+}
+
+/// Lists dead-lettered tasks as a numbered chat entry for `/requeue <n>`.
+fn format_dead_letters(letters: &[mcp::DeadLetter]) -> String {
+    if letters.is_empty() {
+        return format!("MCP: {}", t("mcp.dead_letters_empty"));
+    }
+    let mut text = format!("MCP: {}\n", t("mcp.dead_letters_title"));
+    for (i, letter) in letters.iter().enumerate() {
+        text.push_str(&format!(
+            "\n{}. [{}] {} ({} {})\n   {}",
+            i + 1,
+            letter.queue,
+            letter.error,
+            letter.worker,
+            letter.failed_at,
+            letter.payload
+        ));
+    }
+    text
+}
+
+/// Polls every LUCIUS.md that applies and reloads the context whenever one
+/// is modified, created or removed, without a restart.
+fn spawn_lucius_md_watcher(state: Arc<Mutex<SharedState>>) {
+    tokio::spawn(async move {
+        let stamp = || -> Vec<_> {
+            context::lucius_md_paths()
+                .into_iter()
+                .map(|path| {
+                    let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+                    (path, modified)
+                })
+                .collect()
+        };
+        let mut last = stamp();
+        let mut interval = tokio::time::interval(LUCIUS_MD_POLL);
+        loop {
+            interval.tick().await;
+            let current = stamp();
+            if current == last {
+                continue;
+            }
+            last = current;
+            log::info!("LUCIUS.md changed, reloading the context");
+            let project = context::reload_project_context();
+            let mut state_lock = state.lock().await;
+            // An edit to a conversation under way is asked about by the UI loop
+            let key = if state_lock.reload_lucius_context(project) { "status.lucius_md_reloaded" } else { "status.lucius_md_changed" };
+            state_lock.status_message = Some((t(key).to_string(), std::time::Instant::now()));
+        }
+    });
+}
+
+/// Asks Ollama which models it holds in memory, without holding the state
+/// lock while it answers.
+fn spawn_loaded_refresh(state: Arc<Mutex<SharedState>>, ollama: OllamaProvider) {
+    tokio::spawn(async move {
+        match ollama.running().await {
+            Ok(loaded) => state.lock().await.loaded_models = loaded,
+            Err(e) => log::warn!("{}", e),
+        }
+    });
+}
+
+/// Loads `model` in the background; the status line shows it loading until
+/// it is ready.
+fn spawn_warm_up(state: Arc<Mutex<SharedState>>, ollama: OllamaProvider, model: String) {
+    tokio::spawn(async move {
+        let started = std::time::Instant::now();
+        let result = ollama.warm_up(&model).await;
+        let loaded = if result.is_ok() { ollama.running().await.ok() } else { None };
+        let mut state_lock = state.lock().await;
+        if let Some(loaded) = loaded {
+            state_lock.loaded_models = loaded;
+        }
+        // A later selection owns the indicator now
+        if state_lock.warming_up.as_ref().is_none_or(|(warming, _)| *warming != model) {
+            return;
+        }
+        state_lock.warming_up = None;
+        let secs = format!("{:.1}", started.elapsed().as_secs_f32());
+        let msg = match result {
+            Ok(()) => tf("status.warmed", &[("model", model.as_str()), ("secs", secs.as_str())]),
+            Err(e) => {
+                log::warn!("Warm-up of {} failed: {}", model, e);
+                tf("status.warm_failed", &[("model", model.as_str()), ("error", e.to_string().as_str())])
+            }
+        };
+        state_lock.status_message = Some((msg, std::time::Instant::now()));
+    });
+}
+
+/// Writes notes for a closed conversation and stores them with its session.
+fn spawn_notes(state: Arc<Mutex<SharedState>>, provider: Box<dyn Provider>, model: String, session: Session) {
+    tokio::spawn(async move {
+        if let Some(text) = llm::session_notes(provider.as_ref(), model, &session.messages).await {
+            let notes = SessionNotes { text, messages: session.messages.len() };
+            state.lock().await.set_session_notes(&session.id, notes);
+        }
+    });
+}
+
+/// Downloads a model, updating the settings gauge as Ollama reports
+/// progress, and lists it once it is there.
+fn spawn_pull(state: Arc<Mutex<SharedState>>, ollama: OllamaProvider, model: String) {
+    tokio::spawn(async move {
+        let progress_state = state.clone();
+        let progress = move |status: &str, bytes: Option<(u64, u64)>| {
+            // Progress is best effort; skip an update rather than wait for the UI
+            if let Ok(mut state_lock) = progress_state.try_lock() {
+                if let Some(pull) = state_lock.pulling.as_mut() {
+                    pull.status = status.to_string();
+                    pull.bytes = bytes;
+                }
+            }
+        };
+        let result = ollama.pull(&model, progress).await;
+        let models = ollama.list_models().await;
+        let mut state_lock = state.lock().await;
+        state_lock.pulling = None;
+        if let Ok(models) = models {
+            state_lock.models = models;
+        }
+        let msg = match result {
+            Ok(()) => tf("status.model_pulled", &[("model", model.as_str())]),
+            Err(e) => tf("status.model_pull_failed", &[("model", model.as_str()), ("error", e.to_string().as_str())]),
+        };
+        state_lock.status_message = Some((msg, std::time::Instant::now()));
+    });
+}
+
+/// Pulls the prompt history and the templates other machines share under
+/// `[sync]`, and publishes this machine's own templates.
+fn spawn_sync(state: Arc<Mutex<SharedState>>, mut redis_conn: redis::aio::MultiplexedConnection, update_tx: mpsc::Sender<Update>, config: sync::SyncConfig, key: Option<mcp::PayloadKey>) {
+    tokio::spawn(async move {
+        match sync::load_prompts(&mut redis_conn, &config, key.as_ref()).await {
+            Ok(prompts) => {
+                let _ = update_tx.send(Update::PromptHistory(prompts)).await;
+            }
+            Err(e) => log::warn!("{}", e),
+        }
+        match sync::load_templates(&mut redis_conn, &config, key.as_ref()).await {
+            Ok(shared) => state.lock().await.shared_templates = shared,
+            Err(e) => log::warn!("{}", e),
+        }
+        let own = state.lock().await.config.templates.clone();
+        if let Err(e) = sync::save_templates(&mut redis_conn, &config, key.as_ref(), &own).await {
+            log::warn!("{}", e);
+        }
+    });
+}
+
+/// Embeds the current directory's files in the background, reporting
+/// progress in the status line, and swaps in the new index when done.
+fn spawn_indexing(state: Arc<Mutex<SharedState>>, ollama: OllamaProvider, config: rag::RagConfig) {
+    tokio::spawn(async move {
+        let root = match std::env::current_dir() {
+            Ok(root) => root,
+            Err(e) => {
+                state.lock().await.status_message = Some((tf("status.index_failed", &[("error", e.to_string().as_str())]), std::time::Instant::now()));
+                return;
+            }
+        };
+        let progress_state = state.clone();
+        let progress = move |done: usize, total: usize| {
+            // Progress is best effort; skip an update rather than wait for the UI
+            if let Ok(mut state_lock) = progress_state.try_lock() {
+                let text = tf("status.indexing", &[("done", done.to_string().as_str()), ("total", total.to_string().as_str())]);
+                state_lock.status_message = Some((text, std::time::Instant::now()));
+            }
+        };
+        let result = rag::build(&root, &ollama, &config, progress).await;
+        let mut state_lock = state.lock().await;
+        let msg = match result {
+            Ok(index) => {
+                let index = Arc::new(index);
+                let saved = index.clone();
+                let saved = tokio::task::spawn_blocking(move || saved.save()).await.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                if let Err(e) = saved {
+                    log::error!("Failed to save the project index: {}", e);
+                }
+                let msg = tf("status.indexed", &[("chunks", index.chunks.len().to_string().as_str()), ("files", index.files.to_string().as_str())]);
+                state_lock.rag_index = Some(index);
+                msg
+            }
+            Err(e) => tf("status.index_failed", &[("error", e.to_string().as_str())]),
+        };
+        state_lock.status_message = Some((msg, std::time::Instant::now()));
+    });
+}
+
+/// Loads the current directory's project index, if `/index` was run there,
+/// without holding up startup. An `/index` finished meanwhile is kept.
+fn spawn_index_load(state: Arc<Mutex<SharedState>>) {
+    tokio::spawn(async move {
+        let Ok(root) = std::env::current_dir() else {
+            return;
+        };
+        let index = match tokio::task::spawn_blocking(move || rag::Index::load(&root)).await {
+            Ok(index) => index,
+            Err(e) => {
+                log::warn!("Loading the project index failed: {}", e);
+                return;
+            }
+        };
+        let mut state_lock = state.lock().await;
+        if state_lock.rag_index.is_none() {
+            state_lock.rag_index = index.map(Arc::new);
+        }
+    });
+}
+
+/// Asks the workers for inventory snapshots, gives them `INVENTORY_WAIT_SECS`
+/// to answer, then loads whatever is in the registry as context. Runs in the
+/// background so the UI stays responsive while waiting.
+fn spawn_inventory_refresh(state: Arc<Mutex<SharedState>>, mut redis_conn: redis::aio::MultiplexedConnection) {
+    tokio::spawn(async move {
+        let result = match mcp::request_inventory(&mut redis_conn).await {
+            Ok(request) => {
+                tokio::time::sleep(Duration::from_secs(INVENTORY_WAIT_SECS)).await;
+                mcp::fetch_inventory(&mut redis_conn).await.map(|hosts| (request, hosts))
+            }
+            Err(e) => Err(e),
+        };
+        let mut state_lock = state.lock().await;
+        match result {
+            Ok((request, hosts)) => {
+                state_lock.push_message(ChatMessage::notice(format_inventory(&hosts, request)));
+                state_lock.inventory = hosts;
+            }
+            Err(e) => state_lock.push_message(ChatMessage::error(e)),
+        }
+    });
+}
+
+/// Summarizes inventory snapshots as a chat entry, flagging hosts whose worker
+/// did not answer request `request` in time.
+fn format_inventory(hosts: &[mcp::Inventory], request: u64) -> String {
+    if hosts.is_empty() {
+        return format!("MCP: {}", t("mcp.inventory_empty"));
+    }
+    let mut text = format!("MCP: {}\n", tf("mcp.inventory_title", &[("count", hosts.len().to_string().as_str())]));
+    for host in hosts {
+        let ports: Vec<String> = host.listening_ports.iter().map(u16::to_string).collect();
+        text.push_str(&format!(
+            "\n- {} ({}): {} container(s), ports {}",
+            host.hostname,
+            host.os,
+            host.containers.len(),
+            if ports.is_empty() { "-".to_string() } else { ports.join(", ") },
+        ));
+        if host.request < request {
+            text.push_str(&format!(" {}", tf("mcp.inventory_stale", &[("time", host.collected_at.as_str())])));
+        }
+    }
+    text
+}
+
+/// Requeues every dead letter that can be, returning how many moved and why
+/// the others could not.
+async fn requeue_all_dead_letters(redis_conn: &mut redis::aio::MultiplexedConnection) -> Result<(usize, Vec<String>), String> {
+    let total = mcp::dead_letter_count(redis_conn).await?;
+    Ok(requeue_each(total, |index| {
+        let mut redis_conn = redis_conn.clone();
+        async move { mcp::requeue_dead_letter(&mut redis_conn, index).await.map(|_| ()) }
+    })
+    .await)
+}
+
+/// Requeues the `total` entries of a queue one at a time. A requeued entry
+/// leaves the queue and a failed one stays, so the next entry is at the
+/// number of failures so far.
+async fn requeue_each<F, Fut>(total: usize, mut requeue: F) -> (usize, Vec<String>)
+where
+    F: FnMut(usize) -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    let (mut moved, mut failed) = (0, Vec::new());
+    for _ in 0..total {
+        match requeue(failed.len()).await {
+            Ok(()) => moved += 1,
+            Err(e) => failed.push(e),
+        }
+    }
+    (moved, failed)
+}
+
+/// Shows the confirmation modal for `tool`, as a form when its worker
+/// registered a schema, and runs it with the params the user confirmed.
+/// While another call is being confirmed, this one waits behind it.
+async fn confirm_and_execute(state: &Arc<Mutex<SharedState>>, mut tool: ToolCall) {
+    let turn = match CONFIRMATION.try_lock() {
+        Ok(turn) => turn,
+        Err(_) => {
+            state.lock().await.status_message = Some((tf("status.confirmation_queued", &[("tool", tool.tool.as_str())]), std::time::Instant::now()));
+            CONFIRMATION.lock().await
+        }
+    };
+    let (confirm_tx, confirm_rx) = oneshot::channel();
+    let timeout = {
+        let mut state_lock = state.lock().await;
+        let form = state_lock
+            .worker_tools
+            .iter()
+            .find(|schema| schema.name == tool.tool)
+            .and_then(|schema| forms::ToolForm::from_schema(&schema.parameters, &tool.params));
+        let timeout = state_lock.config.confirmation.timeout();
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+        state_lock.mode = AppMode::Confirmation(ConfirmationModal::ExecuteTool { tool_call: tool.clone(), form, confirm_tx: Some(confirm_tx), deadline });
+        timeout
+    };
+
+    // Wait for y/n in the confirmation modal; the params may have been edited there
+    let answer = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, confirm_rx).await.ok(),
+        None => Some(confirm_rx.await),
+    };
+    let decision = match answer {
+        Some(answer) => match answer.ok().flatten() {
+            Some(params) => {
+                tool.params = params;
+                ApprovalDecision::Approved
+            }
+            None => ApprovalDecision::Declined,
+        },
+        None => {
+            // Nobody answered; take the modal down so the turn can go on
+            let mut state_lock = state.lock().await;
+            if matches!(&state_lock.mode, AppMode::Confirmation(ConfirmationModal::ExecuteTool { tool_call, .. }) if *tool_call == tool) {
+                state_lock.mode = AppMode::Chat;
+            }
+            state_lock.status_message = Some((tf("status.confirmation_timed_out", &[("tool", tool.tool.as_str())]), std::time::Instant::now()));
+            ApprovalDecision::TimedOut
+        }
+    };
+    drop(turn);
+    execute_tool(state, &tool, decision).await;
+}
+
+/// Submits `tool` to the MCP workers over Redis and records the call and its
+/// result in the conversation it was made in and the audit log. The state
+/// lock is released while waiting so the UI can show the task as pending.
+async fn execute_tool(state: &Arc<Mutex<SharedState>>, tool: &ToolCall, decision: ApprovalDecision) {
+    let (redis_conn, (task_type, priority), context, syslog, key) = {
+        let mut state_lock = state.lock().await;
+        let context = TaskContext {
+            conversation_id: state_lock.conversation_id.clone(),
+            user: audit::current_user(),
+            approval: Some(ApprovalRecord::new(&state_lock.conversation_id, tool, decision)),
+            run_as: mcp::run_identity(&state_lock.workers, tool),
+        };
+        if decision == ApprovalDecision::Declined {
+            audit::record(&state_lock.config.syslog, None, tool, &context);
+            state_lock.push_to(&context.conversation_id, ChatMessage::error(format!("Tool call '{}' was declined.", tool.tool)));
+            return;
+        }
+        if decision == ApprovalDecision::TimedOut {
+            audit::record(&state_lock.config.syslog, None, tool, &context);
+            // A tool result rather than an error, so the model learns the command never ran
+            let result = serde_json::json!({ "status": "error", "error": "Not run: the user did not confirm the call in time." });
+            state_lock.push_to(&context.conversation_id, ChatMessage::tool_result(tool, result.to_string()));
+            return;
+        }
+        // Retries and quick actions skip the model, so the project's deny list is checked here too
+        if state_lock.project_profile().forbids(&tool.tool) {
+            state_lock.push_to(&context.conversation_id, ChatMessage::error(format!("Tool '{}' is not allowed in this project (LUCIUS.md deny_tools).", tool.tool)));
+            return;
+        }
+        // A configured key that failed to load must not mean sending in the clear
+        let key = match state_lock.task_key.clone() {
+            Ok(key) => key,
+            Err(e) => {
+                state_lock.push_to(&context.conversation_id, ChatMessage::error(format!("Tool '{}' was not run: {}", tool.tool, e)));
+                return;
+            }
+        };
+        state_lock.push_to(&context.conversation_id, ChatMessage::tool_call(tool));
+        (state_lock.redis_conn.clone(), (state_lock.tool_task_type(&tool.tool), state_lock.task_priority()), context, state_lock.config.syslog.clone(), key)
+    };
+
+    let mut redis_conn = match redis_conn {
+        Some(conn) => conn,
+        None => {
+            state.lock().await.push_to(&context.conversation_id, ChatMessage::error("Not connected to MCP."));
+            return;
+        }
+    };
+
+    let task_id = match mcp::submit_task(&mut redis_conn, tool, task_type, priority, context.clone(), key.as_ref()).await {
+        Ok(task_id) => {
+            audit::record(&syslog, Some(&task_id), tool, &context);
+            task_id
+        }
+        Err(e) => {
+            state.lock().await.push_to(&context.conversation_id, ChatMessage::tool_error(tool, format!("Error submitting task: {}", e)));
+            return;
+        }
+    };
+
+    state.lock().await.pending_tasks.push(PendingTask {
+        id: task_id.clone(),
+        conversation: context.conversation_id.clone(),
+        tool: tool.tool.clone(),
+        worker: None,
+        started: std::time::Instant::now(),
+    });
+    let result = wait_for_result(state, &mut redis_conn, &task_id, key.as_ref()).await;
+
+    let mut state_lock = state.lock().await;
+    state_lock.pending_tasks.retain(|task| task.id != task_id);
+    match result {
+        // TODO: Send the result back to the LLM for a final response.
+        // For now, just display the raw result.
+        Ok(result) => match mcp::result_error_message(&result) {
+            Some(error) => state_lock.push_to(&context.conversation_id, ChatMessage::tool_error(tool, format!("Error from worker: {}", error))),
+            None => state_lock.push_to(&context.conversation_id, ChatMessage::tool_result(tool, result)),
+        },
+        Err(e) => state_lock.push_to(&context.conversation_id, ChatMessage::tool_error(tool, format!("Error polling result: {}", e))),
+    }
+}
+
+/// Waits for a task's result a second at a time, noting which worker has
+/// picked it up so the pending indicator can name it. Sealed results are
+/// opened with `key`.
+async fn wait_for_result(state: &Arc<Mutex<SharedState>>, redis_conn: &mut redis::aio::MultiplexedConnection, task_id: &str, key: Option<&mcp::PayloadKey>) -> Result<String, String> {
+    for _ in 0..mcp::RESULT_TIMEOUT_SECS {
+        if let Some(result) = mcp::wait_result(redis_conn, task_id, 1.0).await? {
+            let result = mcp::open_result(key, task_id, &result)?;
+            chaos::delay().await;
+            chaos::fail("tool result")?;
+            return Ok(chaos::truncate(result));
+        }
+        if let Some(worker) = mcp::task_worker(redis_conn, task_id).await {
+            if let Some(task) = state.lock().await.pending_tasks.iter_mut().find(|task| task.id == task_id) {
+                task.worker = Some(worker);
+            }
+        }
+    }
+    Err(format!("No result for task {} after {}s.", task_id, mcp::RESULT_TIMEOUT_SECS))
+}
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
+async fn main() -> io::Result<ExitCode> {
     // 1. Initialize Logger
-    if let Ok(log_file) = File::create("lucius.log") {
+    if let Ok(log_file) = File::create(LOG_FILE) {
         WriteLogger::init(LevelFilter::Info, simplelog::Config::default(), log_file).unwrap();
         log::info!("Lucius TUI application starting...");
     } else {
         eprintln!("Failed to create log file. Continuing without logging.");
     }
 
-    // 2. Setup Terminal
+    // Quick-capture mode: one exchange without entering the TUI
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("quick") {
+        return quick::run(args[1..].join(" ")).await.map(|()| ExitCode::SUCCESS);
+    }
+    if args.first().map(String::as_str) == Some("doctor") {
+        return doctor::run().await;
+    }
+    // Headless mode: one prompt, the answer on stdout, for scripts and CI
+    if matches!(args.first().map(String::as_str), Some("-p" | "--print")) {
+        return headless::run(&args).await.map(|()| ExitCode::SUCCESS);
+    }
+
+    // 2. Load Config and Setup Terminal. Capture sequences would only garble consoles without mouse reporting
+    log::info!("Loading configuration...");
+    let config = config::Config::load();
+    let mouse_reported = mouse::terminal_reports_mouse();
+    let mouse_capture = config.mouse.enabled && mouse_reported;
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
-    stdout().execute(event::EnableMouseCapture)?;
+    if mouse_capture {
+        stdout().execute(event::EnableMouseCapture)?;
+    }
+    stdout().execute(event::EnableBracketedPaste)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
-    // 3. Load Config and Create Shared State
-    log::info!("Loading configuration...");
-    let config = config::Config::load();
+    // 3. Create Shared State
+    i18n::set_language(config.language.unwrap_or_else(i18n::detect_language));
+    // Hidden on purpose: for exercising timeouts, retries and error paths
+    if args.iter().any(|arg| arg == "--chaos") {
+        chaos::enable(config.chaos.clone());
+    }
     let state = Arc::new(Mutex::new(SharedState::new(config.clone()).await));
     log::info!("Shared state created.");
 
-    // 4. Create channels for UI actions
-    let (action_tx, action_rx) = mpsc::channel(100);
+    // 4. Create channels for UI actions and streamed updates
+    let (action_tx, action_rx) = mpsc::channel(ui::ACTION_QUEUE);
+    let (update_tx, mut update_rx) = mpsc::channel(ui::UPDATE_QUEUE);
 
     // 5. Spawn background worker
-    tokio::spawn(background_worker(state.clone(), action_rx));
+    tokio::spawn(background_worker(state.clone(), action_rx, update_tx));
+    spawn_lucius_md_watcher(state.clone());
+    spawn_index_load(state.clone());
 
     // 6. Initialize App
     log::info!("Initializing App state...");
     let mut app = App::new(action_tx.clone(), &config);
+    // The bottom bar shows the configured model from the cached list right away
+    let cached = state.lock().await.models.iter().position(|model| config.selected_model.as_ref() == Some(&model.name));
+    if let Some(index) = cached {
+        app.model_list_state.select(Some(index));
+    }
+    app.mouse_capture = mouse_capture;
+    // Turned off in the config it needs no explaining
+    if !mouse_reported && config.mouse.enabled {
+        let term = std::env::var("TERM").unwrap_or_default();
+        log::warn!("Terminal {:?} reports no mouse events; mouse capture is off.", term);
+        state.lock().await.status_message = Some((tf("status.no_mouse", &[("term", term.as_str())]), std::time::Instant::now()));
+    }
     log::info!("App state initialized.");
     
     // 7. Trigger initial model and status refresh
-    if let Err(e) = action_tx.send(Action::RefreshModelsAndStatus).await {
+    if let Err(e) = action_tx.send(Action::RefreshModelsAndStatus { scheduled: false }).await {
         log::error!("Failed to send initial model and status refresh action: {}", e);
     }
+    if config.sync.enabled {
+        if let Err(e) = action_tx.send(Action::SyncHistory).await {
+            log::error!("Failed to send the history sync action: {}", e);
+        }
+    }
 
     // 8. Main Event Loop
+    // Redraws happen on input, on streamed output and on a steady tick, so
+    // spinners, the streaming cursor and status expiry move without input.
+    let mut ticker = tokio::time::interval(config.animation.frame_interval().unwrap_or(IDLE_TICK));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut refresher = tokio::time::interval_at(tokio::time::Instant::now() + MODELS_REFRESH, MODELS_REFRESH);
+    refresher.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut events = EventStream::new();
     let mut should_quit = false;
     while !should_quit {
+        if let Ok(mut state_lock) = state.try_lock() {
+            handlers::show_context_change(&mut app, &mut state_lock);
+        }
+
         // Draw UI
         terminal.draw(|frame| {
             if let Ok(state_lock) = state.try_lock() {
-                renderer::draw_ui(frame, &mut app, &state_lock);
+                views::draw_ui(frame, &mut app, &state_lock);
             }
         })?;
 
-        // Handle UI events
-        if event::poll(Duration::from_millis(50))? {
-            let event = event::read()?;
+        tokio::select! {
+            // Handle UI events
+            event = events.next() => match event {
+                Some(Ok(event)) => {
+                    let mut state_lock = state.lock().await;
+                    // Events that arrived together are handled before the next draw
+                    let mut next = Some(event);
+                    while let Some(event) = next.take() {
+                        handlers::handle_event(&mut app, &mut state_lock, event, &mut should_quit).await;
+                        if should_quit || app.pending_editor.is_some() {
+                            break;
+                        }
+                        next = match events.next().now_or_never() {
+                            Some(Some(Ok(event))) => Some(event),
+                            Some(Some(Err(e))) => return Err(e),
+                            _ => None,
+                        };
+                    }
+                    for closed in std::mem::take(&mut state_lock.closed_sessions) {
+                        app.dispatch(&mut state_lock, Action::WriteNotes(Box::new(closed)));
+                    }
+                }
+                Some(Err(e)) => return Err(e),
+                None => should_quit = true,
+            },
+            // Show the response as it streams in
+            Some(update) = update_rx.recv() => {
+                apply_update(&mut app, update);
+                while let Ok(update) = update_rx.try_recv() {
+                    apply_update(&mut app, update);
+                }
+            }
+            _ = ticker.tick() => {
+                if let Ok(mut state_lock) = state.try_lock() {
+                    state_lock.expire_status_message();
+                    state_lock.save_session_if_due();
+                    handlers::search_if_due(&mut app, &mut state_lock, false);
+                }
+            }
+            // Skipped while the worker is busy with the state; the next one comes in a minute
+            _ = refresher.tick() => {
+                if let Ok(mut state_lock) = state.try_lock() {
+                    app.dispatch(&mut state_lock, Action::RefreshModelsAndStatus { scheduled: true });
+                }
+            }
+        }
+
+        // Hand the terminal over to $EDITOR for quick actions picked in hint mode
+        if let Some(path) = app.pending_editor.take() {
+            // Stop reading input so the editor gets the keyboard to itself
+            drop(std::mem::replace(&mut events, EventStream::new()));
+            stdout().execute(LeaveAlternateScreen)?;
+            disable_raw_mode()?;
+            if let Err(e) = hints::edit_path(&path) {
+                log::error!("{}", e);
+            }
+            enable_raw_mode()?;
+            stdout().execute(EnterAlternateScreen)?;
+            // Editors turn bracketed paste and mouse reporting off when they exit
+            stdout().execute(event::EnableBracketedPaste)?;
+            if app.mouse_capture {
+                stdout().execute(event::EnableMouseCapture)?;
+            }
+            terminal.clear()?;
+        }
+    }
+
+    // Notes for the conversation open at exit, written while the UI still shows why it waits
+    let closing = {
+        let mut state_lock = state.lock().await;
+        state_lock.closed_sessions.clear();
+        state_lock.note_closing();
+        std::mem::take(&mut state_lock.closed_sessions)
+    };
+    for session in closing {
+        let (provider, model) = {
             let mut state_lock = state.lock().await;
-            handlers::handle_event(&mut app, &mut state_lock, event, &mut should_quit).await;
+            state_lock.status_message = Some((t("status.writing_notes").to_string(), std::time::Instant::now()));
+            terminal.draw(|frame| views::draw_ui(frame, &mut app, &state_lock))?;
+            (llm::provider(&state_lock.config), session.model.clone().or_else(|| state_lock.config.selected_model.clone()).unwrap_or_default())
+        };
+        match tokio::time::timeout(EXIT_NOTES_TIMEOUT, llm::session_notes(provider.as_ref(), model, &session.messages)).await {
+            Ok(Some(text)) => state.lock().await.set_session_notes(&session.id, SessionNotes { text, messages: session.messages.len() }),
+            Ok(None) => {}
+            Err(_) => log::warn!("Gave up on the notes of session {} after {:?}", session.id, EXIT_NOTES_TIMEOUT),
+        }
+    }
+
+    {
+        let mut state_lock = state.lock().await;
+        state_lock.save_session();
+        // Tasks still running report to the workers' webhooks instead
+        if let Some(mut redis_conn) = state_lock.redis_conn.clone() {
+            for task in &state_lock.pending_tasks {
+                if let Err(e) = mcp::request_notification(&mut redis_conn, &task.id).await {
+                    log::warn!("{}", e);
+                }
+            }
         }
     }
 
     // 9. Restore Terminal
     log::info!("Lucius TUI application shutting down.");
     stdout().execute(LeaveAlternateScreen)?;
-    stdout().execute(event::DisableMouseCapture)?;
+    // Alt+M may have switched it since startup
+    if app.mouse_capture {
+        stdout().execute(event::DisableMouseCapture)?;
+    }
+    stdout().execute(event::DisableBracketedPaste)?;
     disable_raw_mode()?;
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+fn apply_update(app: &mut App, update: Update) {
+    match update {
+        Update::LLMChunk(id, serial, chunk) => {
+            let stream = app.streaming.entry(id).or_insert_with(|| (serial, String::new()));
+            // A newer answer replaces what is left of an older one, whose late chunks are dropped
+            if stream.0 < serial {
+                *stream = (serial, String::new());
+            }
+            if stream.0 == serial {
+                stream.1.push_str(&chunk);
+            }
+        }
+        Update::LLMDone(id, serial) => {
+            if app.streaming.get(&id).is_some_and(|(current, _)| *current == serial) {
+                app.streaming.remove(&id);
+            }
+        }
+        Update::PromptHistory(shared) => app.prompt_history = sync::merge_prompts(shared, &app.prompt_history),
+        Update::Models(_) | Update::Status(_) => {}
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn simple_test() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[tokio::test]
+    async fn requeue_all_skips_entries_that_fail() {
+        // The second and fourth entries have no queue recorded
+        let queue = std::cell::RefCell::new(vec!["a", "", "b", "", "c"]);
+        let (moved, failed) = requeue_each(5, |index| {
+            let mut queue = queue.borrow_mut();
+            let result = match queue[index] {
+                "" => Err(format!("Dead letter #{} does not record its queue", index + 1)),
+                _ => {
+                    queue.remove(index);
+                    Ok(())
+                }
+            };
+            async move { result }
+        })
+        .await;
+        assert_eq!(moved, 3);
+        assert_eq!(failed, vec!["Dead letter #1 does not record its queue", "Dead letter #2 does not record its queue"]);
+        assert_eq!(*queue.borrow(), vec!["", ""]);
+    }
+
+    #[test]
+    fn late_chunks_of_a_cancelled_answer_stay_out_of_the_next() {
+        let mut app = App::new(mpsc::channel(1).0, &config::Config::default());
+        let chunk = |serial, text: &str| Update::LLMChunk("c".to_string(), serial, text.to_string());
+        apply_update(&mut app, chunk(1, "Old "));
+        // Cancelled: the handler takes the partial answer, then the next one starts
+        app.streaming.remove("c");
+        apply_update(&mut app, chunk(1, "late"));
+        apply_update(&mut app, chunk(2, "New"));
+        apply_update(&mut app, chunk(1, "later"));
+        apply_update(&mut app, Update::LLMDone("c".to_string(), 1));
+        assert_eq!(app.streaming.get("c"), Some(&(2, "New".to_string())));
+        apply_update(&mut app, Update::LLMDone("c".to_string(), 2));
+        assert!(app.streaming.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_second_confirmation_waits_for_the_open_one() {
+        let state = Arc::new(Mutex::new(SharedState::for_tests()));
+        state.lock().await.config.confirmation.timeout_secs = 0;
+        let call = |tool: &str| ToolCall { tool: tool.to_string(), params: serde_json::json!({}), expect: vec![] };
+        // The tool on screen once the modal is up, and the sender answering it
+        let open = |state: Arc<Mutex<SharedState>>| async move {
+            loop {
+                if let AppMode::Confirmation(ConfirmationModal::ExecuteTool { tool_call, confirm_tx, .. }) = &mut state.lock().await.mode {
+                    return (tool_call.tool.clone(), confirm_tx.take().unwrap());
+                }
+                tokio::task::yield_now().await;
+            }
+        };
+
+        let first = tokio::spawn({
+            let state = state.clone();
+            async move { confirm_and_execute(&state, call("exec")).await }
+        });
+        let (tool, answer) = open(state.clone()).await;
+        assert_eq!(tool, "exec");
+        let second = tokio::spawn({
+            let state = state.clone();
+            async move { confirm_and_execute(&state, call("docker")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        {
+            let state = state.lock().await;
+            assert!(matches!(&state.mode, AppMode::Confirmation(ConfirmationModal::ExecuteTool { tool_call, .. }) if tool_call.tool == "exec"));
+            assert!(state.status_message.as_ref().is_some_and(|(msg, _)| msg.contains("docker")));
+        }
+
+        state.lock().await.mode = AppMode::Chat;
+        answer.send(Some(serde_json::json!({}))).unwrap();
+        first.await.unwrap();
+        let (tool, answer) = open(state.clone()).await;
+        assert_eq!(tool, "docker");
+        state.lock().await.mode = AppMode::Chat;
+        answer.send(None).unwrap();
+        second.await.unwrap();
+
+        let history: Vec<String> = state.lock().await.chat_history.iter().map(|msg| msg.content.clone()).collect();
+        assert!(history.last().is_some_and(|msg| msg.ends_with("Tool call 'docker' was declined.")));
+        assert!(history.iter().any(|msg| msg.ends_with("Not connected to MCP.")));
+    }
 }
@@ -0,0 +1,507 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+/// Columns are never shrunk below this many characters.
+const MIN_COLUMN_WIDTH: usize = 5;
+
+lazy_static! {
+    static ref TABLE_SEPARATOR: Regex = Regex::new(r"^\s*\|?\s*:?-{3,}:?\s*(\|\s*:?-{3,}:?\s*)*\|?\s*$").unwrap();
+    static ref DISPLAY_MATH: Regex = Regex::new(r"\$\$(.+?)\$\$").unwrap();
+    static ref INLINE_MATH: Regex = Regex::new(r"\$([^\s$](?:[^$\n]*[^\s$])?)\$").unwrap();
+    static ref FRAC: Regex = Regex::new(r"\\frac\{([^{}]*)\}\{([^{}]*)\}").unwrap();
+    static ref SQRT: Regex = Regex::new(r"\\sqrt\{([^{}]*)\}").unwrap();
+    static ref TEXT: Regex = Regex::new(r"\\(?:text|mathrm|mathbf|operatorname)\{([^{}]*)\}").unwrap();
+    static ref SUPERSCRIPT: Regex = Regex::new(r"\^(\{[^{}]*\}|.)").unwrap();
+    static ref SUBSCRIPT: Regex = Regex::new(r"_(\{[^{}]*\}|.)").unwrap();
+    static ref COMMAND: Regex = Regex::new(r"\\([a-zA-Z]+)").unwrap();
+    static ref HEADING: Regex = Regex::new(r"^(#{1,6})\s+(.*?)(?:\s+#+)?\s*$").unwrap();
+    static ref LIST_ITEM: Regex = Regex::new(r"^(\s*)([-*+]|\d{1,9}[.)])\s+(.*)$").unwrap();
+    static ref RULE: Regex = Regex::new(r"^\s{0,3}(?:(?:-\s*){3,}|(?:\*\s*){3,}|(?:_\s*){3,})$").unwrap();
+    /// Telltale patterns per language, each scoring one point per matching line.
+    static ref LANGUAGE_HINTS: Vec<(&'static str, Regex)> = [
+        ("rust", r"^\s*(pub\s+)?(fn|impl|struct|enum|mod|use)\s|let\s+mut\s|::\w+\(|println!|\)\s*->\s*\w"),
+        ("python", r"^\s*(def|class)\s+\w+.*:\s*$|^\s*(import\s+\w+$|from\s+\S+\s+import\s)|^\s*(elif|except)\b|\bself\.|print\("),
+        ("go", r"^\s*(package|func)\s|:=|\bfmt\.\w+\("),
+        ("javascript", r"^\s*(const|let|var)\s+\w+\s*=|=>|\bfunction\b|console\.log|\brequire\("),
+        ("sql", r"(?i)^\s*(select|insert\s+into|update|delete\s+from|create\s+(table|index)|alter\s+table)\b"),
+        ("yaml", r"^\s*(apiVersion|kind|metadata|services|image|name):(\s|$)|^\s*- \w+:\s"),
+        ("toml", r"^\s*\[[\w.-]+\]\s*$|^\s*[\w-]+\s*=\s*(\x22|\d|true|false|\[)"),
+        ("dockerfile", r"^(FROM|RUN|COPY|ADD|CMD|ENTRYPOINT|WORKDIR|EXPOSE|ENV)\s"),
+        ("nginx", r"^\s*(server|location|upstream)\b.*\{\s*$|^\s*(listen|proxy_pass|server_name|root)\s+\S+;"),
+        ("bash", r"^\s*(\$\s|sudo\s|apt(-get)?\s|yum\s|dnf\s|docker\s|kubectl\s|systemctl\s|journalctl\s|cd\s|ls\b|grep\s|echo\s|export\s|curl\s|if\s+\[)|\|\s*(grep|awk|sed|xargs)\b"),
+    ]
+    .into_iter()
+    .map(|(language, pattern)| (language, Regex::new(pattern).unwrap()))
+    .collect();
+}
+
+/// A markdown table, split into cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// A piece of a message: markdown prose, a table or a fenced code block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Markdown(String),
+    Table(Table),
+    /// A fenced code block; `info` is the text after the opening fence.
+    Code { info: String, lines: Vec<String> },
+}
+
+/// Splits a message into markdown, table and code blocks. An unclosed fence
+/// runs to the end of the message, as while a response is still streaming.
+pub fn split_blocks(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut blocks = Vec::new();
+    let mut markdown: Vec<&str> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let fence = line.trim_start().strip_prefix("```");
+        let starts_table = line.trim_start().starts_with('|')
+            && lines.get(i + 1).is_some_and(|next| TABLE_SEPARATOR.is_match(next));
+        if fence.is_none() && !starts_table {
+            markdown.push(line);
+            i += 1;
+            continue;
+        }
+
+        if !markdown.is_empty() {
+            blocks.push(Block::Markdown(markdown.join("\n")));
+            markdown.clear();
+        }
+
+        if let Some(info) = fence {
+            let mut code = Vec::new();
+            i += 1;
+            while i < lines.len() && !lines[i].trim_start().starts_with("```") {
+                code.push(lines[i].to_string());
+                i += 1;
+            }
+            // Skip the closing fence
+            i += 1;
+            blocks.push(Block::Code { info: info.trim().to_string(), lines: code });
+        } else {
+            let header = parse_row(line);
+            let mut rows = Vec::new();
+            i += 2;
+            while i < lines.len() && lines[i].trim_start().starts_with('|') {
+                rows.push(parse_row(lines[i]));
+                i += 1;
+            }
+            blocks.push(Block::Table(Table { header, rows }));
+        }
+    }
+
+    if !markdown.is_empty() {
+        blocks.push(Block::Markdown(markdown.join("\n")));
+    }
+    blocks
+}
+
+/// The contents of the fenced code blocks of a message, in order.
+pub fn code_blocks(text: &str) -> Vec<String> {
+    split_blocks(text)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Code { lines, .. } => Some(lines.join("\n")),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_row(line: &str) -> Vec<String> {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix('|').unwrap_or(trimmed);
+    trimmed.split('|').map(|cell| cell.trim().to_string()).collect()
+}
+
+/// How a line of markdown prose is laid out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineKind {
+    Paragraph,
+    /// A `#` heading and its level, 1 to 6.
+    Heading(usize),
+    /// A list item, with its indentation and the marker to show: a bullet,
+    /// or the number of an ordered item.
+    ListItem { indent: usize, marker: String },
+    /// A `>` block quote line; nesting depth is not kept.
+    Quote,
+    /// A horizontal rule.
+    Rule,
+}
+
+/// Inline emphasis of a run of text.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Emphasis {
+    pub bold: bool,
+    pub italic: bool,
+    pub strike: bool,
+    pub code: bool,
+}
+
+/// A parsed line of markdown prose: its kind and its text split into runs
+/// of equal emphasis, with the markup removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownLine {
+    pub kind: LineKind,
+    pub runs: Vec<(String, Emphasis)>,
+}
+
+/// Parses the prose of a `Block::Markdown` line by line. Tables and fenced
+/// code never get here; `split_blocks` has taken them out already.
+pub fn parse_lines(text: &str) -> Vec<MarkdownLine> {
+    text.lines()
+        .map(|line| {
+            let (kind, rest) = if RULE.is_match(line) {
+                (LineKind::Rule, "")
+            } else if let Some(caps) = HEADING.captures(line) {
+                (LineKind::Heading(caps[1].len()), caps.get(2).map_or("", |m| m.as_str()))
+            } else if let Some(caps) = LIST_ITEM.captures(line) {
+                let marker = &caps[2];
+                let marker = if marker.len() == 1 && "-*+".contains(marker) { "•".to_string() } else { marker.to_string() };
+                let indent = caps[1].chars().count();
+                (LineKind::ListItem { indent, marker }, caps.get(3).map_or("", |m| m.as_str()))
+            } else if let Some(rest) = line.trim_start().strip_prefix('>') {
+                (LineKind::Quote, rest.trim_start_matches(['>', ' ']))
+            } else {
+                (LineKind::Paragraph, line)
+            };
+            MarkdownLine { kind, runs: parse_inline(rest) }
+        })
+        .collect()
+}
+
+/// Splits a line into runs of `**bold**`, `*italic*`, `~~strike~~` and
+/// `` `code` ``. Markers only open when they are closed later on the line,
+/// and `_` only at word boundaries, so `snake_case` and `2 * 3` stay as typed.
+pub fn parse_inline(text: &str) -> Vec<(String, Emphasis)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut runs = Vec::new();
+    let mut current = String::new();
+    let mut emphasis = Emphasis::default();
+    // The marker that opened the current bold or italic run
+    let mut bold_marker = None;
+    let mut italic_marker = None;
+    let closes_later = |from: usize, marker: &[char]| {
+        (from..chars.len().saturating_sub(marker.len() - 1)).any(|j| chars[j..j + marker.len()] == *marker)
+    };
+    fn flush(current: &mut String, emphasis: Emphasis, runs: &mut Vec<(String, Emphasis)>) {
+        if !current.is_empty() {
+            runs.push((std::mem::take(current), emphasis));
+        }
+    }
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let prev_is_word = i > 0 && chars[i - 1].is_alphanumeric();
+
+        if c == '\\' && next.is_some_and(|n| n.is_ascii_punctuation()) {
+            current.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if c == '`' {
+            if let Some(end) = (i + 1..chars.len()).find(|&j| chars[j] == '`') {
+                flush(&mut current, emphasis, &mut runs);
+                let code: String = chars[i + 1..end].iter().collect();
+                runs.push((code, Emphasis { code: true, ..emphasis }));
+                i = end + 1;
+                continue;
+            }
+        }
+        if c == '~' && next == Some('~') && (emphasis.strike || closes_later(i + 2, &['~', '~'])) {
+            flush(&mut current, emphasis, &mut runs);
+            emphasis.strike = !emphasis.strike;
+            i += 2;
+            continue;
+        }
+        if (c == '*' || c == '_') && next == Some(c) {
+            let marker = [c, c];
+            let opens = bold_marker.is_none() && !(c == '_' && prev_is_word) && closes_later(i + 2, &marker);
+            if bold_marker == Some(c) || opens {
+                flush(&mut current, emphasis, &mut runs);
+                emphasis.bold = !emphasis.bold;
+                bold_marker = if emphasis.bold { Some(c) } else { None };
+                i += 2;
+                continue;
+            }
+            // A pair that neither opens nor closes bold is literal text
+            current.push_str(&format!("{}{}", c, c));
+            i += 2;
+            continue;
+        }
+        if c == '*' || c == '_' {
+            let closes = italic_marker == Some(c) && !(c == '_' && next.is_some_and(char::is_alphanumeric));
+            let opens = italic_marker.is_none()
+                && next.is_some_and(|n| !n.is_whitespace())
+                && !(c == '_' && prev_is_word)
+                && closes_later(i + 1, &[c]);
+            if closes || opens {
+                flush(&mut current, emphasis, &mut runs);
+                emphasis.italic = !emphasis.italic;
+                italic_marker = if emphasis.italic { Some(c) } else { None };
+                i += 1;
+                continue;
+            }
+        }
+        current.push(c);
+        i += 1;
+    }
+    flush(&mut current, emphasis, &mut runs);
+    runs
+}
+
+/// Lays a table out with box-drawing borders, shrinking the widest columns
+/// until it fits `width`. Lines can still be wider than `width` when every
+/// column is already at its minimum; the caller scrolls them horizontally.
+/// The header is always the second line.
+pub fn layout_table(table: &Table, width: usize) -> Vec<String> {
+    let columns = table.rows.iter().map(|r| r.len()).chain(std::iter::once(table.header.len())).max().unwrap_or(0);
+    if columns == 0 {
+        return vec![];
+    }
+
+    let mut widths = vec![0; columns];
+    for row in std::iter::once(&table.header).chain(&table.rows) {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let available = width.saturating_sub(columns * 3 + 1);
+    while widths.iter().sum::<usize>() > available {
+        let (widest, &w) = widths.iter().enumerate().max_by_key(|(_, w)| **w).unwrap_or((0, &0));
+        if w <= MIN_COLUMN_WIDTH {
+            break;
+        }
+        widths[widest] -= 1;
+    }
+
+    let border = |left: &str, mid: &str, right: &str| {
+        let parts: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{}{}{}", left, parts.join(mid), right)
+    };
+    let row_line = |row: &Vec<String>| {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, w)| format!(" {} ", fit_cell(row.get(i).map(String::as_str).unwrap_or(""), *w)))
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+
+    let mut lines = vec![border("┌", "┬", "┐"), row_line(&table.header), border("├", "┼", "┤")];
+    lines.extend(table.rows.iter().map(row_line));
+    lines.push(border("└", "┴", "┘"));
+    lines
+}
+
+/// Pads or truncates `cell` to exactly `width` characters.
+fn fit_cell(cell: &str, width: usize) -> String {
+    let count = cell.chars().count();
+    if count <= width {
+        format!("{}{}", cell, " ".repeat(width - count))
+    } else {
+        let mut truncated: String = cell.chars().take(width.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// The part of `line` visible at horizontal scroll `offset` in a `width`-wide view.
+pub fn slice_columns(line: &str, offset: usize, width: usize) -> String {
+    line.chars().skip(offset).take(width).collect()
+}
+
+/// Rewrites `$...$` and `$$...$$` math outside code as unicode where feasible:
+/// Greek letters, operators, super/subscripts, fractions and roots.
+pub fn prettify_math(text: &str) -> String {
+    let mut in_fence = false;
+    text.lines()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                return line.to_string();
+            }
+            if in_fence || !line.contains('$') {
+                return line.to_string();
+            }
+            // Even segments are prose, odd segments are inline code
+            line.split('`')
+                .enumerate()
+                .map(|(i, segment)| if i % 2 == 0 { prettify_math_segment(segment) } else { segment.to_string() })
+                .collect::<Vec<_>>()
+                .join("`")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn prettify_math_segment(segment: &str) -> String {
+    let segment = DISPLAY_MATH.replace_all(segment, |caps: &Captures| math_to_unicode(&caps[1]));
+    INLINE_MATH
+        .replace_all(&segment, |caps: &Captures| {
+            if looks_like_math(&caps[1]) {
+                math_to_unicode(&caps[1])
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Keeps prices like "$5 and $10" from being read as math.
+fn looks_like_math(content: &str) -> bool {
+    content.contains(['\\', '^', '_', '{', '='])
+        || (content.chars().count() <= 3 && content.chars().all(|c| c.is_alphabetic()))
+}
+
+fn math_to_unicode(math: &str) -> String {
+    let math = TEXT.replace_all(math, "$1");
+    let math = FRAC.replace_all(&math, "($1)/($2)");
+    let math = SQRT.replace_all(&math, "√($1)");
+    let math = COMMAND.replace_all(&math, |caps: &Captures| symbol(&caps[1]).map_or_else(|| caps[0].to_string(), |s| s.to_string()));
+    let math = SUPERSCRIPT.replace_all(&math, |caps: &Captures| script(&caps[1], '^', superscript_char));
+    let math = SUBSCRIPT.replace_all(&math, |caps: &Captures| script(&caps[1], '_', subscript_char));
+    math.trim().to_string()
+}
+
+/// Converts a `^x`/`_{xy}` argument, falling back to `^(xy)` if any character
+/// has no unicode equivalent.
+fn script(arg: &str, marker: char, convert: fn(char) -> Option<char>) -> String {
+    let inner = arg.strip_prefix('{').and_then(|a| a.strip_suffix('}')).unwrap_or(arg);
+    match inner.chars().map(convert).collect::<Option<String>>() {
+        Some(converted) => converted,
+        None if inner.chars().count() == 1 => format!("{}{}", marker, inner),
+        None => format!("{}({})", marker, inner),
+    }
+}
+
+fn superscript_char(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰', '1' => '¹', '2' => '²', '3' => '³', '4' => '⁴',
+        '5' => '⁵', '6' => '⁶', '7' => '⁷', '8' => '⁸', '9' => '⁹',
+        '+' => '⁺', '-' => '⁻', '=' => '⁼', '(' => '⁽', ')' => '⁾',
+        'n' => 'ⁿ', 'i' => 'ⁱ', 'x' => 'ˣ', 'T' => 'ᵀ',
+        _ => return None,
+    })
+}
+
+fn subscript_char(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀', '1' => '₁', '2' => '₂', '3' => '₃', '4' => '₄',
+        '5' => '₅', '6' => '₆', '7' => '₇', '8' => '₈', '9' => '₉',
+        '+' => '₊', '-' => '₋', '=' => '₌', '(' => '₍', ')' => '₎',
+        'a' => 'ₐ', 'e' => 'ₑ', 'o' => 'ₒ', 'x' => 'ₓ', 'i' => 'ᵢ',
+        'j' => 'ⱼ', 'k' => 'ₖ', 'n' => 'ₙ', 'm' => 'ₘ', 't' => 'ₜ',
+        _ => return None,
+    })
+}
+
+fn symbol(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "alpha" => "α", "beta" => "β", "gamma" => "γ", "delta" => "δ", "epsilon" => "ε",
+        "theta" => "θ", "lambda" => "λ", "mu" => "μ", "pi" => "π", "rho" => "ρ",
+        "sigma" => "σ", "tau" => "τ", "phi" => "φ", "omega" => "ω",
+        "Delta" => "Δ", "Sigma" => "Σ", "Omega" => "Ω", "Pi" => "Π",
+        "times" => "×", "cdot" => "·", "div" => "÷", "pm" => "±",
+        "le" | "leq" => "≤", "ge" | "geq" => "≥", "neq" | "ne" => "≠", "approx" => "≈",
+        "infty" => "∞", "sum" => "∑", "prod" => "∏", "int" => "∫", "partial" => "∂",
+        "to" | "rightarrow" => "→", "leftarrow" => "←", "Rightarrow" => "⇒",
+        "in" => "∈", "notin" => "∉", "subset" => "⊂", "cup" => "∪", "cap" => "∩",
+        "forall" => "∀", "exists" => "∃", "nabla" => "∇", "log" => "log", "ln" => "ln",
+        "left" | "right" => "",
+        _ => return None,
+    })
+}
+
+/// Guesses the language of an untagged code block from shebangs, JSON syntax
+/// and telltale keywords. Returns `None` when nothing stands out.
+pub fn detect_language(lines: &[String]) -> Option<&'static str> {
+    let first = lines.iter().find(|line| !line.trim().is_empty())?.trim();
+    if let Some(shebang) = first.strip_prefix("#!") {
+        return if shebang.contains("python") {
+            Some("python")
+        } else if shebang.contains("node") {
+            Some("javascript")
+        } else {
+            Some("bash")
+        };
+    }
+    let code = lines.join("\n");
+    if (first.starts_with('{') || first.starts_with('[')) && serde_json::from_str::<serde_json::Value>(&code).is_ok() {
+        return Some("json");
+    }
+    LANGUAGE_HINTS
+        .iter()
+        .map(|(language, pattern)| (*language, lines.iter().filter(|line| pattern.is_match(line)).count()))
+        .filter(|(_, score)| *score > 0)
+        // On a tie the language listed first wins
+        .fold(None, |best: Option<(&str, usize)>, candidate| match best {
+            Some(best) if best.1 >= candidate.1 => Some(best),
+            _ => Some(candidate),
+        })
+        .map(|(language, _)| language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_shrink_to_fit_and_math_becomes_unicode() {
+        let blocks = split_blocks("Results:\n| name | description |\n|---|---|\n| a | a very long description indeed |");
+        assert_eq!(blocks.len(), 2);
+        if let Block::Table(table) = &blocks[1] {
+            let lines = layout_table(table, 30);
+            assert!(lines.iter().all(|l| l.chars().count() <= 30));
+        } else {
+            panic!("expected a table block");
+        }
+
+        assert_eq!(prettify_math(r"Energy: $E = mc^2$"), "Energy: E = mc²");
+        assert_eq!(prettify_math("costs $5 and $10"), "costs $5 and $10");
+    }
+
+    #[test]
+    fn parses_prose_lines_and_inline_emphasis() {
+        let lines = parse_lines("## Setup ##\n- **Install** it\n2. run `make`\n> note\n---\nkeep snake_case and 2 * 3");
+        assert_eq!(lines[0].kind, LineKind::Heading(2));
+        assert_eq!(lines[0].runs, vec![("Setup".to_string(), Emphasis::default())]);
+        assert_eq!(lines[1].kind, LineKind::ListItem { indent: 0, marker: "•".to_string() });
+        assert_eq!(lines[1].runs[0], ("Install".to_string(), Emphasis { bold: true, ..Emphasis::default() }));
+        assert_eq!(lines[2].kind, LineKind::ListItem { indent: 0, marker: "2.".to_string() });
+        assert_eq!(lines[2].runs[1], ("make".to_string(), Emphasis { code: true, ..Emphasis::default() }));
+        assert_eq!(lines[3].kind, LineKind::Quote);
+        assert_eq!(lines[4].kind, LineKind::Rule);
+        assert_eq!(lines[5].runs, vec![("keep snake_case and 2 * 3".to_string(), Emphasis::default())]);
+
+        let runs = parse_inline("*a* ~~b~~ _c_");
+        assert!(runs[0].1.italic && runs[2].1.strike && runs[4].1.italic);
+    }
+
+    #[test]
+    fn detects_languages_of_untagged_blocks() {
+        let lines = |code: &str| code.lines().map(str::to_string).collect::<Vec<_>>();
+        assert_eq!(detect_language(&lines("sudo systemctl restart nginx\njournalctl -u nginx | grep error")), Some("bash"));
+        assert_eq!(detect_language(&lines("{\"a\": [1, 2]}")), Some("json"));
+        assert_eq!(detect_language(&lines("fn main() {\n    let mut x = 1;\n}")), Some("rust"));
+        assert_eq!(detect_language(&lines("def f(x):\n    return x")), Some("python"));
+        assert_eq!(detect_language(&lines("hello world")), None);
+    }
+
+    #[test]
+    fn collects_code_blocks_in_order() {
+        let text = "Try:\n```bash\ndf -h\ndu -sh *\n```\nthen\n```\nfree -m\n```";
+        assert_eq!(code_blocks(text), vec!["df -h\ndu -sh *", "free -m"]);
+        assert!(code_blocks("no code here").is_empty());
+    }
+}
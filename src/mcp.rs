@@ -1,5 +1,11 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use sha2::{Digest, Sha256};
 use regex::Regex;
 use lazy_static::lazy_static;
 use redis::aio::MultiplexedConnection;
@@ -7,6 +13,39 @@ use redis::AsyncCommands;
 use uuid::Uuid;
 use log::{error, info}; // Import log::error and info
 
+/// Version of the task envelope. Workers reject tasks with a newer major
+/// version instead of guessing at fields they do not know.
+pub const PROTOCOL_VERSION: &str = "1.5";
+
+/// How long to wait for a worker to return a task's result.
+pub const RESULT_TIMEOUT_SECS: u64 = 30;
+
+/// Prefix of the key a worker sets to its hostname when it picks up a task.
+pub const TASK_STATUS_PREFIX: &str = "mcp::status::";
+
+/// List of tasks that could not be parsed or failed to execute.
+pub const DEAD_LETTER_KEY: &str = "mcp::dead_letter";
+
+/// Marks a task whose outcome should go to the worker's webhook, because
+/// nobody is waiting for it in the TUI anymore.
+pub const NOTIFY_PREFIX: &str = "mcp::notify::";
+
+/// How long such a mark waits for its task to finish.
+const NOTIFY_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Prefix of the key each worker refreshes every 10 seconds while it is
+/// alive. It expires 30 seconds after the last refresh.
+pub const HEARTBEAT_PREFIX: &str = "mcp::heartbeat::";
+
+/// Hash of hostname -> `WorkerInfo` JSON that workers publish on startup.
+pub const WORKER_REGISTRY_KEY: &str = "mcp::workers";
+
+/// Hash of hostname -> `Inventory` JSON, the workers' latest snapshots.
+pub const INVENTORY_KEY: &str = "mcp::inventory";
+
+/// Counter bumped to ask every worker for a fresh inventory snapshot.
+pub const INVENTORY_REQUEST_KEY: &str = "mcp::inventory::request";
+
 // --- Task & Tool Data Structures ---
 
 /// Represents a tool call identified from the LLM's output.
@@ -14,16 +53,117 @@ use log::{error, info}; // Import log::error and info
 pub struct ToolCall {
     pub tool: String,
     pub params: Value,
+    /// Post-conditions for the worker to check against the result, e.g.
+    /// `exit_code == 0` or `stdout contains 'active (running)'`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expect: Vec<String>,
 }
 
 /// Represents a task payload to be sent to an mcp-worker via Redis.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Task {
+    /// Envelope version, `major.minor`. Missing on tasks from older clients.
+    #[serde(default = "legacy_protocol_version")]
+    pub protocol_version: String,
     pub id: String,
     // target_host is specified for potential future routing, not currently used by worker.
     pub target_host: String, 
     pub task_type: TaskType,
     pub details: Value,
+    /// Added in protocol 1.1; older tasks are treated as interactive.
+    #[serde(default)]
+    pub priority: Priority,
+    /// Added in protocol 1.2: who asked for the task and why it was allowed.
+    #[serde(default)]
+    pub context: TaskContext,
+    /// Added in protocol 1.3: the tool call's `expect` block.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expect: Vec<String>,
+    /// Added in protocol 1.5: `details` is a string sealed with the shared
+    /// `PayloadKey`, and the worker seals the result the same way.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+}
+
+/// Ties a task to the chat that triggered it, for worker logs and the audit
+/// trail. Fields missing from an older TUI's tasks are left empty.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default)]
+pub struct TaskContext {
+    pub conversation_id: String,
+    /// Local user running the TUI.
+    pub user: String,
+    pub approval: Option<ApprovalRecord>,
+    /// Added in protocol 1.4: the identity the workers advertised for the
+    /// call when it was confirmed, e.g. `deploy@web-1 (sudo)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as: Option<String>,
+}
+
+/// How a task came to be run.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    /// The model asked for it and the user confirmed.
+    Approved,
+    /// The model asked for it and the user said no; never submitted.
+    Declined,
+    /// The model asked for it and nobody answered the confirmation in time;
+    /// never submitted.
+    TimedOut,
+    /// The user ran it directly, e.g. from a quick action or a retry.
+    UserInitiated,
+}
+
+/// The decision on a tool call, with a SHA-256 hash binding it to the exact
+/// call so the TUI audit log and worker logs can be matched up, whichever
+/// build wrote them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApprovalRecord {
+    pub decision: ApprovalDecision,
+    /// RFC 3339 time of the decision.
+    pub decided_at: String,
+    pub hash: String,
+}
+
+impl ApprovalRecord {
+    pub fn new(conversation_id: &str, tool_call: &ToolCall, decision: ApprovalDecision) -> Self {
+        let decided_at = chrono::Utc::now().to_rfc3339();
+        let hash = approval_hash(conversation_id, tool_call, decision, &decided_at);
+        Self { decision, decided_at, hash }
+    }
+}
+
+/// Hex SHA-256 of the call and its decision, each field ended by a NUL so
+/// none can run into the next.
+fn approval_hash(conversation_id: &str, tool_call: &ToolCall, decision: ApprovalDecision, decided_at: &str) -> String {
+    let mut hasher = Sha256::new();
+    for field in [conversation_id, &tool_call.tool, &tool_call.params.to_string(), &format!("{:?}", decision), decided_at] {
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Which queue a task waits in. Workers drain interactive tasks first but
+/// still take a background task every few pops so those are not starved.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Triggered by the user, who is waiting on the result.
+    #[default]
+    Interactive,
+    /// Scheduled or background jobs.
+    Background,
+}
+
+fn legacy_protocol_version() -> String {
+    "0.0".to_string()
+}
+
+/// The major part of a `major.minor` protocol version.
+pub fn protocol_major(version: &str) -> Option<u64> {
+    version.split('.').next()?.trim().parse().ok()
 }
 
 /// The type of task for the worker to execute.
@@ -34,6 +174,132 @@ pub enum TaskType {
     SHELL,
 }
 
+/// A tool a worker can run, as advertised in the worker registry.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ToolSchema {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// JSON schema of the tool's `params`.
+    #[serde(default)]
+    pub parameters: Value,
+    /// The queue tasks for this tool are pushed to.
+    pub task_type: TaskType,
+}
+
+/// What a worker publishes to the registry when it starts.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WorkerInfo {
+    pub hostname: String,
+    pub task_types: Vec<TaskType>,
+    #[serde(default)]
+    pub tools: Vec<ToolSchema>,
+    /// Account the worker process runs as. Missing on older workers.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Whether the worker accepts privileged actions.
+    #[serde(default)]
+    pub allow_privileged: bool,
+    /// Actions the worker runs through `sudo -u <user>` rather than as itself.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub run_as: BTreeMap<String, String>,
+    /// Whether the worker has a `PayloadKey` and can take encrypted tasks.
+    #[serde(default)]
+    pub encryption: bool,
+}
+
+impl WorkerInfo {
+    /// Whom `tool_call` would run as on this worker, e.g. `deploy@web-1 (sudo)`,
+    /// or `None` if the worker does not offer the tool or report its user.
+    pub fn identity(&self, tool_call: &ToolCall) -> Option<String> {
+        if !self.tools.iter().any(|tool| tool.name == tool_call.tool) {
+            return None;
+        }
+        let action = tool_call.params.get("action").and_then(Value::as_str);
+        match action.and_then(|action| self.run_as.get(action)) {
+            Some(user) => Some(format!("{}@{} (sudo)", user, self.hostname)),
+            None => Some(format!("{}@{}", self.user.as_deref()?, self.hostname)),
+        }
+    }
+}
+
+/// Whom `tool_call` would run as across the workers offering it, for the
+/// confirmation modal and the audit trail.
+pub fn run_identity(workers: &[WorkerInfo], tool_call: &ToolCall) -> Option<String> {
+    let mut identities: Vec<String> = workers.iter().filter_map(|worker| worker.identity(tool_call)).collect();
+    identities.sort();
+    identities.dedup();
+    (!identities.is_empty()).then(|| identities.join(", "))
+}
+
+/// A running container, as listed by `docker ps`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Container {
+    pub name: String,
+    pub image: String,
+    pub status: String,
+}
+
+/// What a worker reports about its host for `/inventory`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Inventory {
+    pub hostname: String,
+    pub os: String,
+    #[serde(default)]
+    pub containers: Vec<Container>,
+    /// TCP ports something is listening on.
+    #[serde(default)]
+    pub listening_ports: Vec<u16>,
+    /// The `INVENTORY_REQUEST_KEY` value the snapshot answers.
+    #[serde(default)]
+    pub request: u64,
+    /// RFC 3339 time the snapshot was taken.
+    #[serde(default)]
+    pub collected_at: String,
+}
+
+/// A task a worker gave up on, kept with the reason so it can be inspected
+/// and requeued.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DeadLetter {
+    /// The queue the task was popped from, and is requeued to.
+    pub queue: String,
+    /// The task exactly as it was popped.
+    pub payload: String,
+    pub error: String,
+    #[serde(default)]
+    pub worker: String,
+    /// RFC 3339 time of the failure.
+    #[serde(default)]
+    pub failed_at: String,
+}
+
+/// Whether a result met every post-condition of its tool call.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationStatus {
+    Verified,
+    Failed,
+}
+
+/// One checked post-condition.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpectationCheck {
+    pub expect: String,
+    pub passed: bool,
+    /// Why the expectation could not be evaluated, e.g. a missing field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Added by workers to a result as `verification` when the task had an
+/// `expect` block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub status: VerificationStatus,
+    pub checks: Vec<ExpectationCheck>,
+}
+
 // --- Parsing Logic ---
 
 /// Parses a tool call from the LLM's response string.
@@ -58,19 +324,52 @@ pub fn parse_tool_call(response: &str) -> Option<ToolCall> {
 
 // --- Redis MCP Interaction Functions ---
 
-pub async fn submit_task(conn: &mut MultiplexedConnection, tool_call: &ToolCall) -> Result<String, String> {
-    let task_id = Uuid::new_v4().to_string();
-    let task_type = match tool_call.tool.as_str() {
+/// The queue for a tool no worker has registered.
+pub fn default_task_type(tool: &str) -> TaskType {
+    match tool {
         "exec" | "shell" => TaskType::SHELL,
         "docker" => TaskType::DOCKER,
         _ => TaskType::SHELL, // Default to SHELL for unknown tools
+    }
+}
+
+/// The Redis list a task waits in. Interactive tasks keep the original queue
+/// names so older workers still serve them.
+pub fn queue_key(task_type: &TaskType, priority: Priority) -> String {
+    let base = match task_type {
+        TaskType::SHELL => "mcp::tasks::shell",
+        TaskType::DOCKER => "mcp::tasks::docker",
     };
+    match priority {
+        Priority::Interactive => base.to_string(),
+        Priority::Background => format!("{}::background", base),
+    }
+}
 
+pub async fn submit_task(
+    conn: &mut MultiplexedConnection,
+    tool_call: &ToolCall,
+    task_type: TaskType,
+    priority: Priority,
+    context: TaskContext,
+    key: Option<&PayloadKey>,
+) -> Result<String, String> {
+    let task_id = Uuid::new_v4().to_string();
+
+    let details = match key {
+        Some(key) => Value::String(key.seal(&tool_call.params.to_string(), &task_id)?),
+        None => tool_call.params.clone(),
+    };
     let task = Task {
+        protocol_version: PROTOCOL_VERSION.to_string(),
         id: task_id.clone(),
         target_host: "any".to_string(), // Target logic can be enhanced later
         task_type,
-        details: tool_call.params.clone(),
+        details,
+        priority,
+        context,
+        expect: tool_call.expect.clone(),
+        encrypted: key.is_some(),
     };
 
     let task_json = match serde_json::to_string(&task) {
@@ -78,12 +377,9 @@ pub async fn submit_task(conn: &mut MultiplexedConnection, tool_call: &ToolCall)
         Err(e) => return Err(format!("Failed to serialize task: {}", e)),
     };
 
-    let queue_key = match task.task_type {
-        TaskType::SHELL => "mcp::tasks::shell",
-        TaskType::DOCKER => "mcp::tasks::docker",
-    };
+    let queue_key = queue_key(&task.task_type, task.priority);
     
-    let rpush_result: redis::RedisResult<()> = conn.rpush(queue_key, &task_json).await;
+    let rpush_result: redis::RedisResult<()> = conn.rpush(&queue_key, &task_json).await;
     match rpush_result {
         Ok(_) => {
             info!("Pushed task {} to Redis queue '{}'", task_id, queue_key);
@@ -97,7 +393,7 @@ pub async fn poll_result(conn: &mut MultiplexedConnection, task_id: &str) -> Res
     let result_key = format!("mcp::result::{}", task_id);
     info!("Waiting for result on key '{}'", result_key);
 
-    let blpop_result: redis::RedisResult<Vec<String>> = conn.blpop(&result_key, 30.0).await; // 30 second timeout
+    let blpop_result: redis::RedisResult<Vec<String>> = conn.blpop(&result_key, RESULT_TIMEOUT_SECS as f64).await;
 
     match blpop_result {
         Ok(result_vec) => {
@@ -110,3 +406,457 @@ pub async fn poll_result(conn: &mut MultiplexedConnection, task_id: &str) -> Res
         Err(e) => Err(format!("Failed to get result from Redis: {}", e)),
     }
 }
+
+/// Waits up to `timeout` seconds for a task's result. `Ok(None)` means it has
+/// not arrived yet.
+pub async fn wait_result(conn: &mut MultiplexedConnection, task_id: &str, timeout: f64) -> Result<Option<String>, String> {
+    let result_key = format!("mcp::result::{}", task_id);
+    let blpop_result: redis::RedisResult<Option<(String, String)>> = conn.blpop(&result_key, timeout).await;
+    match blpop_result {
+        Ok(Some((_, result))) => Ok(Some(result)),
+        Ok(None) => Ok(None),
+        Err(e) => Err(format!("Failed to get result from Redis: {}", e)),
+    }
+}
+
+// --- Payload Encryption ---
+
+/// The `[encryption]` section: a key shared with the workers so task details
+/// and results cross Redis sealed. Without one, payloads are plain JSON.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct EncryptionConfig {
+    /// Base64 of 32 random bytes, e.g. from `openssl rand -base64 32`.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// A command printing the key, e.g. `secret-tool lookup service lucius`,
+    /// to keep it in the system keyring rather than the config file.
+    #[serde(default)]
+    pub key_command: Option<String>,
+}
+
+impl EncryptionConfig {
+    /// The configured key, `None` when encryption is off, or why it could
+    /// not be loaded.
+    pub fn load(&self) -> Result<Option<PayloadKey>, String> {
+        if let Some(key) = &self.key {
+            return PayloadKey::from_base64(key).map(Some);
+        }
+        let Some(command) = &self.key_command else {
+            return Ok(None);
+        };
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .map_err(|e| format!("Failed to run key_command: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("key_command exited with {}", output.status));
+        }
+        PayloadKey::from_base64(&String::from_utf8_lossy(&output.stdout)).map(Some)
+    }
+}
+
+/// Symmetric ChaCha20-Poly1305 key for task payloads. Sealed text is base64
+/// of a random nonce followed by the ciphertext. The task id is bound in as
+/// associated data, so a sealed payload cannot be replayed under another task.
+#[derive(Clone)]
+pub struct PayloadKey(chacha20poly1305::Key);
+
+impl PayloadKey {
+    pub fn from_base64(text: &str) -> Result<Self, String> {
+        let bytes = BASE64.decode(text.trim()).map_err(|e| format!("Invalid encryption key: {}", e))?;
+        if bytes.len() != 32 {
+            return Err(format!("Invalid encryption key: expected 32 bytes, got {}", bytes.len()));
+        }
+        Ok(Self(*chacha20poly1305::Key::from_slice(&bytes)))
+    }
+
+    pub fn seal(&self, plaintext: &str, task_id: &str) -> Result<String, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&self.0)
+            .encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: task_id.as_bytes() })
+            .map_err(|e| format!("Failed to encrypt payload: {}", e))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(BASE64.encode(sealed))
+    }
+
+    pub fn open(&self, sealed: &str, task_id: &str) -> Result<String, String> {
+        let bytes = BASE64.decode(sealed.trim()).map_err(|e| format!("Invalid encrypted payload: {}", e))?;
+        if bytes.len() < 12 {
+            return Err("Invalid encrypted payload: too short".to_string());
+        }
+        let (nonce, ciphertext) = bytes.split_at(12);
+        let plaintext = ChaCha20Poly1305::new(&self.0)
+            .decrypt(chacha20poly1305::Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: task_id.as_bytes() })
+            .map_err(|_| "Failed to decrypt payload: wrong key, wrong task or tampered data".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| format!("Invalid encrypted payload: {}", e))
+    }
+}
+
+/// A result as written to Redis: sealed into `{"encrypted": "..."}` when
+/// there is a key.
+pub fn seal_result(key: Option<&PayloadKey>, task_id: &str, result: &str) -> Result<String, String> {
+    match key {
+        Some(key) => Ok(serde_json::json!({ "encrypted": key.seal(result, task_id)? }).to_string()),
+        None => Ok(result.to_string()),
+    }
+}
+
+/// The plain result from what a worker wrote. With a key, results must be
+/// sealed: anyone who can write to Redis could fake a plain one.
+pub fn open_result(key: Option<&PayloadKey>, task_id: &str, result: &str) -> Result<String, String> {
+    let sealed = serde_json::from_str::<Value>(result)
+        .ok()
+        .and_then(|value| value.get("encrypted")?.as_str().map(str::to_string));
+    match (sealed, key) {
+        (Some(sealed), Some(key)) => key.open(&sealed, task_id),
+        (Some(_), None) => Err("The result is encrypted, but no [encryption] key is configured.".to_string()),
+        (None, Some(_)) => Err("Refused an unsealed result: with an [encryption] key configured, results must be sealed.".to_string()),
+        (None, None) => Ok(result.to_string()),
+    }
+}
+
+/// Returns the hostname of the worker that has picked up `task_id`, if any.
+pub async fn task_worker(conn: &mut MultiplexedConnection, task_id: &str) -> Option<String> {
+    let status_key = format!("{}{}", TASK_STATUS_PREFIX, task_id);
+    conn.get(&status_key).await.unwrap_or(None)
+}
+
+/// The error a worker reported instead of a result, if any, e.g. a rejected
+/// protocol version.
+pub fn result_error_message(result: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(result).ok()?;
+    value.get("error")?.as_str().map(str::to_string)
+}
+
+/// Checks a result object (`exit_code`, `stdout`, ...) against `expect`.
+/// Each expectation is `<field> <op> <value>` where `op` is one of `==`, `!=`,
+/// `>`, `>=`, `<`, `<=`, `contains` or `not contains`; dotted fields reach
+/// into nested objects and string values may be quoted.
+pub fn verify(result: &Value, expect: &[String]) -> Verification {
+    let checks: Vec<ExpectationCheck> = expect
+        .iter()
+        .map(|expectation| match check_expectation(result, expectation) {
+            Ok(passed) => ExpectationCheck { expect: expectation.clone(), passed, error: None },
+            Err(e) => ExpectationCheck { expect: expectation.clone(), passed: false, error: Some(e) },
+        })
+        .collect();
+    let status = if checks.iter().all(|c| c.passed) { VerificationStatus::Verified } else { VerificationStatus::Failed };
+    Verification { status, checks }
+}
+
+fn check_expectation(result: &Value, expectation: &str) -> Result<bool, String> {
+    const OPERATORS: [&str; 8] = [" not contains ", " contains ", " == ", " != ", " >= ", " <= ", " > ", " < "];
+    let (field, op, expected) = OPERATORS
+        .iter()
+        .filter_map(|op| expectation.find(op).map(|at| (at, *op)))
+        .min_by_key(|(at, _)| *at)
+        .map(|(at, op)| (expectation[..at].trim(), op.trim(), expectation[at + op.len()..].trim()))
+        .ok_or_else(|| format!("No operator in '{}'", expectation))?;
+
+    let pointer = format!("/{}", field.replace('.', "/"));
+    let actual = result.pointer(&pointer).ok_or_else(|| format!("Result has no field '{}'", field))?;
+    let expected = expected
+        .strip_prefix('\'')
+        .and_then(|e| e.strip_suffix('\''))
+        .or_else(|| expected.strip_prefix('"').and_then(|e| e.strip_suffix('"')))
+        .unwrap_or(expected);
+    let actual_text = match actual {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+
+    match op {
+        "contains" => Ok(actual_text.contains(expected)),
+        "not contains" => Ok(!actual_text.contains(expected)),
+        "==" | "!=" => {
+            let equal = match (actual.as_f64(), expected.parse::<f64>()) {
+                (Some(a), Ok(e)) => a == e,
+                _ => actual_text == expected,
+            };
+            Ok(equal == (op == "=="))
+        }
+        _ => {
+            let a = actual.as_f64().ok_or_else(|| format!("Field '{}' is not a number", field))?;
+            let e: f64 = expected.parse().map_err(|_| format!("'{}' is not a number", expected))?;
+            Ok(match op {
+                ">" => a > e,
+                ">=" => a >= e,
+                "<" => a < e,
+                _ => a <= e,
+            })
+        }
+    }
+}
+
+/// The worker's verification of a result, if the task had an `expect` block.
+pub fn result_verification(result: &str) -> Option<Verification> {
+    let value: Value = serde_json::from_str(result).ok()?;
+    serde_json::from_value(value.get("verification")?.clone()).ok()
+}
+
+/// Whether a worker's result reports a failure: a nonzero exit code, an
+/// `error` field or a failed status. Plain-text results count as success.
+pub fn result_is_error(result: &str) -> bool {
+    let value: Value = match serde_json::from_str(result) {
+        Ok(value) => value,
+        Err(_) => return false,
+    };
+    let exit_code = value.get("exit_code").or_else(|| value.get("exit_status")).and_then(Value::as_i64);
+    let status = value.get("status").and_then(Value::as_str);
+    exit_code.is_some_and(|code| code != 0)
+        || value.get("error").is_some_and(|error| !error.is_null())
+        || matches!(status, Some("error") | Some("failed") | Some("timeout"))
+        || value["verification"]["status"].as_str() == Some("failed")
+}
+
+/// Reads the registration of every worker whose heartbeat is current; the
+/// entries of stopped workers stay until a running one removes them. Entries
+/// that fail to parse are logged and skipped so one bad worker does not hide
+/// the others.
+pub async fn fetch_workers(conn: &mut MultiplexedConnection) -> Result<Vec<WorkerInfo>, String> {
+    let entries: HashMap<String, String> = conn
+        .hgetall(WORKER_REGISTRY_KEY)
+        .await
+        .map_err(|e| format!("Failed to read worker registry: {}", e))?;
+    let workers: Vec<WorkerInfo> = entries
+        .into_iter()
+        .filter_map(|(hostname, json)| match serde_json::from_str(&json) {
+            Ok(worker) => Some(worker),
+            Err(e) => {
+                error!("Invalid registry entry for worker {}: {}", hostname, e);
+                None
+            }
+        })
+        .collect();
+    if workers.is_empty() {
+        return Ok(workers);
+    }
+    let heartbeats: Vec<String> = workers.iter().map(|worker| format!("{}{}", HEARTBEAT_PREFIX, worker.hostname)).collect();
+    let beats: Vec<Option<String>> = conn
+        .mget(&heartbeats)
+        .await
+        .map_err(|e| format!("Failed to read worker heartbeats: {}", e))?;
+    Ok(live_workers(workers, &beats))
+}
+
+/// The workers whose heartbeat, in the same order, is set.
+fn live_workers(workers: Vec<WorkerInfo>, beats: &[Option<String>]) -> Vec<WorkerInfo> {
+    workers.into_iter().zip(beats).filter(|(_, beat)| beat.is_some()).map(|(worker, _)| worker).collect()
+}
+
+/// Whether `hostname`'s heartbeat is current, i.e. the worker is running.
+pub async fn worker_alive(conn: &mut MultiplexedConnection, hostname: &str) -> Result<bool, String> {
+    conn.exists(format!("{}{}", HEARTBEAT_PREFIX, hostname))
+        .await
+        .map_err(|e| format!("Failed to read heartbeat of {}: {}", hostname, e))
+}
+
+/// Merges the tools advertised by all workers. Workers offering the same tool
+/// are interchangeable, so the first schema for each name wins.
+pub fn aggregate_tools(workers: &[WorkerInfo]) -> Vec<ToolSchema> {
+    let mut tools: Vec<ToolSchema> = Vec::new();
+    for tool in workers.iter().flat_map(|worker| &worker.tools) {
+        if !tools.iter().any(|known| known.name == tool.name) {
+            tools.push(tool.clone());
+        }
+    }
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    tools
+}
+
+/// Asks every worker for a fresh inventory snapshot, returning the request
+/// number their snapshots will carry.
+pub async fn request_inventory(conn: &mut MultiplexedConnection) -> Result<u64, String> {
+    conn.incr(INVENTORY_REQUEST_KEY, 1)
+        .await
+        .map_err(|e| format!("Failed to request inventory: {}", e))
+}
+
+/// Reads every worker's latest inventory snapshot, sorted by hostname.
+pub async fn fetch_inventory(conn: &mut MultiplexedConnection) -> Result<Vec<Inventory>, String> {
+    let entries: HashMap<String, String> = conn
+        .hgetall(INVENTORY_KEY)
+        .await
+        .map_err(|e| format!("Failed to read inventory: {}", e))?;
+    let mut hosts: Vec<Inventory> = entries
+        .into_iter()
+        .filter_map(|(hostname, json)| match serde_json::from_str(&json) {
+            Ok(inventory) => Some(inventory),
+            Err(e) => {
+                error!("Invalid inventory entry for worker {}: {}", hostname, e);
+                None
+            }
+        })
+        .collect();
+    hosts.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+    Ok(hosts)
+}
+
+/// Asks the worker running `task_id` to report its outcome to its webhook,
+/// for tasks still running when the TUI quits.
+pub async fn request_notification(conn: &mut MultiplexedConnection, task_id: &str) -> Result<(), String> {
+    conn.set_ex(format!("{}{}", NOTIFY_PREFIX, task_id), 1, NOTIFY_TTL_SECS)
+        .await
+        .map_err(|e| format!("Failed to request notification for task {}: {}", task_id, e))
+}
+
+/// Number of tasks waiting in the dead-letter queue.
+pub async fn dead_letter_count(conn: &mut MultiplexedConnection) -> Result<usize, String> {
+    conn.llen(DEAD_LETTER_KEY).await.map_err(|e| format!("Failed to read dead-letter queue: {}", e))
+}
+
+/// Every dead-lettered task, oldest first.
+pub async fn dead_letters(conn: &mut MultiplexedConnection) -> Result<Vec<DeadLetter>, String> {
+    let entries: Vec<String> = conn
+        .lrange(DEAD_LETTER_KEY, 0, -1)
+        .await
+        .map_err(|e| format!("Failed to read dead-letter queue: {}", e))?;
+    Ok(entries
+        .iter()
+        .map(|json| {
+            serde_json::from_str(json).unwrap_or_else(|e| DeadLetter {
+                queue: String::new(),
+                payload: json.clone(),
+                error: format!("Unreadable dead-letter entry: {}", e),
+                worker: String::new(),
+                failed_at: String::new(),
+            })
+        })
+        .collect())
+}
+
+/// Moves the dead letter at `index` back onto the queue it came from.
+pub async fn requeue_dead_letter(conn: &mut MultiplexedConnection, index: usize) -> Result<DeadLetter, String> {
+    let entry: Option<String> = conn
+        .lindex(DEAD_LETTER_KEY, index as isize)
+        .await
+        .map_err(|e| format!("Failed to read dead-letter queue: {}", e))?;
+    let entry = entry.ok_or_else(|| format!("No dead letter #{}", index + 1))?;
+    let letter: DeadLetter = serde_json::from_str(&entry).map_err(|e| format!("Unreadable dead-letter entry: {}", e))?;
+    if letter.queue.is_empty() {
+        return Err(format!("Dead letter #{} does not record its queue", index + 1));
+    }
+
+    let requeued: redis::RedisResult<()> = redis::pipe()
+        .atomic()
+        .lrem(DEAD_LETTER_KEY, 1, &entry)
+        .ignore()
+        .rpush(&letter.queue, &letter.payload)
+        .ignore()
+        .query_async(conn)
+        .await;
+    match requeued {
+        Ok(_) => {
+            info!("Requeued dead letter to '{}'", letter.queue);
+            Ok(letter)
+        }
+        Err(e) => Err(format!("Failed to requeue dead letter: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approval_hashes_are_sha256_of_the_call() {
+        let call = ToolCall { tool: "docker".to_string(), params: serde_json::json!({ "action": "ps" }), expect: vec![] };
+        let at = "2026-10-16T00:00:00+00:00";
+        let hash = approval_hash("conv", &call, ApprovalDecision::Approved, at);
+        assert_eq!(hash, "9a2fdaa0d8d219c24eecf3a3f6022b6352d2acefd46a6138f798a9c2ec22f45a");
+        assert_ne!(approval_hash("conv", &call, ApprovalDecision::Declined, at), hash);
+        assert_ne!(approval_hash("con", &ToolCall { tool: "vdocker".to_string(), ..call.clone() }, ApprovalDecision::Approved, at), hash);
+
+        // Workers read contexts from older TUIs that lack some fields
+        let context: TaskContext = serde_json::from_str(r#"{"conversation_id":"conv"}"#).unwrap();
+        assert!(context.user.is_empty() && context.approval.is_none());
+    }
+
+    #[test]
+    fn sealed_results_open_with_the_same_key_only() {
+        let key = PayloadKey::from_base64(&BASE64.encode([7u8; 32])).unwrap();
+        let other = PayloadKey::from_base64(&BASE64.encode([8u8; 32])).unwrap();
+        let result = r#"{"exit_code":0,"stdout":"Success"}"#;
+        let sealed = seal_result(Some(&key), "task-1", result).unwrap();
+        assert!(!sealed.contains("Success"));
+        assert_eq!(open_result(Some(&key), "task-1", &sealed).unwrap(), result);
+        assert!(open_result(Some(&other), "task-1", &sealed).is_err());
+        assert!(open_result(Some(&key), "task-2", &sealed).is_err());
+        assert!(open_result(None, "task-1", &sealed).is_err());
+        assert!(open_result(Some(&key), "task-1", result).is_err());
+        assert_eq!(open_result(None, "task-1", result).unwrap(), result);
+        assert!(PayloadKey::from_base64("c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn run_identity_follows_sudo_profiles() {
+        let worker = |hostname: &str, run_as: &[(&str, &str)]| WorkerInfo {
+            hostname: hostname.to_string(),
+            task_types: vec![TaskType::DOCKER],
+            tools: vec![ToolSchema { name: "docker".to_string(), description: String::new(), parameters: Value::Null, task_type: TaskType::DOCKER }],
+            user: Some("lucius".to_string()),
+            allow_privileged: false,
+            encryption: false,
+            run_as: run_as.iter().map(|(action, user)| (action.to_string(), user.to_string())).collect(),
+        };
+        let workers = [worker("web-1", &[("restart", "deploy")]), worker("web-2", &[])];
+        let call = |action: &str| ToolCall { tool: "docker".to_string(), params: serde_json::json!({ "action": action }), expect: vec![] };
+        assert_eq!(run_identity(&workers, &call("ps")).as_deref(), Some("lucius@web-1, lucius@web-2"));
+        assert_eq!(run_identity(&workers, &call("restart")).as_deref(), Some("deploy@web-1 (sudo), lucius@web-2"));
+        let other = ToolCall { tool: "shell".to_string(), params: Value::Null, expect: vec![] };
+        assert_eq!(run_identity(&workers, &other), None);
+    }
+
+    #[test]
+    fn only_live_workers_offer_their_tools() {
+        let schema = |name: &str, task_type: TaskType| ToolSchema { name: name.to_string(), description: String::new(), parameters: Value::Null, task_type };
+        let worker = |hostname: &str, tools: Vec<ToolSchema>| WorkerInfo {
+            hostname: hostname.to_string(),
+            task_types: vec![],
+            tools,
+            user: None,
+            allow_privileged: false,
+            encryption: false,
+            run_as: BTreeMap::new(),
+        };
+        let workers = vec![
+            worker("web-1", vec![schema("docker", TaskType::DOCKER), schema("exec", TaskType::SHELL)]),
+            worker("web-2", vec![schema("docker", TaskType::SHELL)]),
+            worker("gone", vec![schema("backup", TaskType::SHELL)]),
+        ];
+        let live = live_workers(workers, &[Some("now".to_string()), Some("now".to_string()), None]);
+        let hosts: Vec<&str> = live.iter().map(|worker| worker.hostname.as_str()).collect();
+        assert_eq!(hosts, ["web-1", "web-2"]);
+
+        // The first worker's schema wins for a shared tool, and tools come sorted
+        let tools = aggregate_tools(&live);
+        let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(names, ["docker", "exec"]);
+        assert_eq!(tools[0].task_type, TaskType::DOCKER);
+    }
+
+    #[test]
+    fn failed_results_are_recognized() {
+        assert!(result_is_error(r#"{"exit_code": 1}"#));
+        assert!(result_is_error(r#"{"exit_status": 2, "stdout": ""}"#));
+        assert!(result_is_error(r#"{"error": "no such container"}"#));
+        assert!(result_is_error(r#"{"status": "timeout"}"#));
+        assert!(result_is_error(r#"{"exit_code": 0, "verification": {"status": "failed"}}"#));
+        assert!(!result_is_error(r#"{"exit_code": 0, "error": null}"#));
+        assert!(!result_is_error("plain text output"));
+    }
+
+    #[test]
+    fn verifies_expectations_against_result() {
+        let result = serde_json::json!({ "exit_code": 0, "stdout": "nginx: active (running)" });
+        let expect = vec!["exit_code == 0".to_string(), "stdout contains 'active (running)'".to_string()];
+        assert_eq!(verify(&result, &expect).status, VerificationStatus::Verified);
+
+        let failing = vec!["exit_code != 0".to_string(), "stderr contains 'x'".to_string()];
+        let verification = verify(&result, &failing);
+        assert_eq!(verification.status, VerificationStatus::Failed);
+        assert!(verification.checks[1].error.is_some());
+    }
+}
@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use lucius::mcp::{self, ToolCall};
+
+use crate::llm::ResponseDebug;
+use crate::markdown;
+
+/// Who a chat entry is from, which decides how it is shown and whether it
+/// reaches the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    User,
+    Assistant,
+    /// A tool call proposed by the model or started from the palette.
+    ToolCall,
+    ToolResult,
+    /// Something that went wrong, shown as-is and never sent.
+    Error,
+    /// Status output such as dead letters or the inventory, never sent.
+    Notice,
+}
+
+/// One entry of the conversation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+    /// The call a `ToolCall` entry proposes, or the one a tool result or
+    /// tool error answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call: Option<ToolCall>,
+    pub timestamp: DateTime<Utc>,
+    /// How many more times this same error came up, shown as a ×N counter.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub repeats: u32,
+    /// An answer stopped with Esc. The `[cancelled]` marker is only shown,
+    /// never sent back to the model.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub cancelled: bool,
+    /// How the backend said an answer ended, shown with `d` on the focused
+    /// message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<ResponseDebug>,
+    /// How long the model took to answer: the backend's own measure where
+    /// it gives one, the time the request took otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self { role, content: content.into(), tool_call: None, timestamp: Utc::now(), repeats: 0, cancelled: false, debug: None, duration_ms: None }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+
+    /// What had streamed of an answer when it was cancelled.
+    pub fn cancelled(partial: impl Into<String>) -> Self {
+        Self { cancelled: true, ..Self::new(Role::Assistant, partial) }
+    }
+
+    /// An error, shown as "Error: <detail>".
+    pub fn error(detail: impl std::fmt::Display) -> Self {
+        Self::new(Role::Error, format!("Error: {}", detail))
+    }
+
+    pub fn notice(content: impl Into<String>) -> Self {
+        Self::new(Role::Notice, content)
+    }
+
+    pub fn tool_call(call: &ToolCall) -> Self {
+        let content = serde_json::to_string_pretty(call).unwrap_or_default();
+        Self { tool_call: Some(call.clone()), ..Self::new(Role::ToolCall, content) }
+    }
+
+    pub fn tool_result(call: &ToolCall, result: impl Into<String>) -> Self {
+        Self { tool_call: Some(call.clone()), ..Self::new(Role::ToolResult, result) }
+    }
+
+    /// A tool task that could not be submitted, polled or run, e.g.
+    /// "Error from worker: ...".
+    pub fn tool_error(call: &ToolCall, text: impl Into<String>) -> Self {
+        Self { tool_call: Some(call.clone()), ..Self::new(Role::Error, text) }
+    }
+
+    /// The entry as shown in the chat, e.g. "You: ...".
+    pub fn display_text(&self) -> String {
+        let prefix = match self.role {
+            Role::User => "You: ",
+            Role::Assistant => "Lucius: ",
+            Role::ToolCall => "Tool Call: ",
+            Role::ToolResult => "Tool Result: ",
+            Role::Error | Role::Notice => "",
+        };
+        let mut text = format!("{}{}", prefix, self.content);
+        if self.cancelled {
+            text.push_str(if self.content.is_empty() { "[cancelled]" } else { " [cancelled]" });
+        }
+        if self.repeats > 0 {
+            text.push_str(&format!(" ×{}", self.repeats + 1));
+        }
+        text
+    }
+
+    /// Whether the entry is sent to the model at all. Errors (including
+    /// declined or disabled tool calls) and notices only live in the UI, as
+    /// does an answer cancelled before anything streamed.
+    pub fn is_sent(&self) -> bool {
+        match self.role {
+            Role::User | Role::ToolCall | Role::ToolResult => true,
+            Role::Assistant => !self.content.trim().is_empty(),
+            Role::Error | Role::Notice => false,
+        }
+    }
+
+    /// Whether the entry is an intermediate tool step rather than part of the
+    /// conversation proper.
+    pub fn is_tool_step(&self) -> bool {
+        match self.role {
+            Role::ToolCall | Role::ToolResult => true,
+            Role::Error => self.tool_call.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Whether the entry reports a failed tool execution: a submit/poll error
+    /// or a result the worker marked as failed.
+    pub fn is_failed_tool_step(&self) -> bool {
+        match self.role {
+            Role::Error => self.tool_call.is_some(),
+            Role::ToolResult => mcp::result_is_error(&self.content),
+            _ => false,
+        }
+    }
+
+    /// Reads an entry saved before messages had roles, when the role was a
+    /// prefix of the text.
+    pub fn from_legacy(text: &str, timestamp: DateTime<Utc>) -> Self {
+        let (role, content) = if let Some(content) = text.strip_prefix("You: ") {
+            (Role::User, content)
+        } else if let Some(content) = text.strip_prefix("Lucius: ") {
+            if let Some(partial) = content.strip_suffix("[cancelled]") {
+                return Self { timestamp, ..Self::cancelled(partial.trim_end()) };
+            }
+            (Role::Assistant, content)
+        } else if let Some(content) = text.strip_prefix("Tool Call: ") {
+            (Role::ToolCall, content)
+        } else if let Some(content) = text.strip_prefix("Tool Result: ") {
+            (Role::ToolResult, content)
+        } else if text.starts_with("Error") {
+            (Role::Error, text)
+        } else {
+            (Role::Notice, text)
+        };
+        let tool_call = match role {
+            Role::ToolCall => serde_json::from_str(content).ok(),
+            _ => None,
+        };
+        Self { role, content: content.to_string(), tool_call, timestamp, repeats: 0, cancelled: false, debug: None, duration_ms: None }
+    }
+}
+
+/// The shapes the copy menu puts a message on the clipboard in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    /// As shown in the chat, "Lucius: " prefix included.
+    Raw,
+    /// The content alone.
+    Text,
+    /// The fenced code blocks, without their fences.
+    Code,
+    /// A markdown block quote, for pasting into issues.
+    Quoted,
+}
+
+impl ChatMessage {
+    /// The message in `format`, or `None` for code when it has no blocks.
+    pub fn copy_text(&self, format: CopyFormat) -> Option<String> {
+        match format {
+            CopyFormat::Raw => Some(self.display_text()),
+            CopyFormat::Text => Some(self.content.trim().to_string()),
+            CopyFormat::Code => {
+                let blocks = markdown::code_blocks(&self.content);
+                (!blocks.is_empty()).then(|| blocks.join("\n\n"))
+            }
+            CopyFormat::Quoted => Some(
+                self.content
+                    .trim()
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_entries_keep_their_role_and_text() {
+        let now = Utc::now();
+        let call = ChatMessage::from_legacy("Tool Call: {\"tool\": \"exec\", \"params\": {\"command\": \"uptime\"}}", now);
+        assert_eq!(call.role, Role::ToolCall);
+        assert_eq!(call.tool_call.map(|call| call.tool).as_deref(), Some("exec"));
+
+        for text in ["You: hi", "Lucius: hello", "Lucius: hel [cancelled]", "Tool Result: {\"exit_code\": 0}", "Error: offline", "MCP: 2 hosts"] {
+            assert_eq!(ChatMessage::from_legacy(text, now).display_text(), text);
+        }
+        assert!(!ChatMessage::from_legacy("Error from worker: boom", now).is_sent());
+        assert_eq!(ChatMessage::from_legacy("Lucius: hel [cancelled]", now).content, "hel");
+        assert!(!ChatMessage::cancelled("").is_sent());
+    }
+
+    #[test]
+    fn messages_copy_in_each_format() {
+        let answer = ChatMessage::new(Role::Assistant, "Restart it:\n\n```bash\nsystemctl restart nginx\n```");
+        assert_eq!(answer.copy_text(CopyFormat::Raw).unwrap(), "Lucius: Restart it:\n\n```bash\nsystemctl restart nginx\n```");
+        assert_eq!(answer.copy_text(CopyFormat::Code).unwrap(), "systemctl restart nginx");
+        assert_eq!(answer.copy_text(CopyFormat::Quoted).unwrap(), "> Restart it:\n>\n> ```bash\n> systemctl restart nginx\n> ```");
+        assert_eq!(ChatMessage::new(Role::User, "hi").copy_text(CopyFormat::Code), None);
+    }
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::llm::{Model, ProviderKind};
+
+const CACHE_FILENAME: &str = "models.json";
+
+/// The model list last fetched from the provider, shown at launch until the
+/// first refresh answers.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModelCache {
+    /// The provider and endpoint the list came from; another one's list is
+    /// never shown.
+    pub source: String,
+    pub fetched_at: DateTime<Utc>,
+    pub models: Vec<String>,
+}
+
+impl ModelCache {
+    pub fn new(config: &Config, models: &[Model]) -> Self {
+        Self { source: source(config), fetched_at: Utc::now(), models: models.iter().map(|model| model.name.clone()).collect() }
+    }
+
+    /// The cached list of the provider `config` points at.
+    pub fn load(config: &Config) -> Option<Self> {
+        Self::parse(&fs::read_to_string(cache_path()).ok()?, config)
+    }
+
+    fn parse(content: &str, config: &Config) -> Option<Self> {
+        let cache: Self = serde_json::from_str(content)
+            .map_err(|e| log::warn!("Ignoring unreadable model cache: {}", e))
+            .ok()?;
+        (cache.source == source(config)).then_some(cache)
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(cache_path(), json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::error!("Failed to write the model cache: {}", e);
+        }
+    }
+
+    pub fn models(&self) -> Vec<Model> {
+        self.models.iter().map(|name| Model { name: name.clone(), size: None }).collect()
+    }
+}
+
+/// e.g. `ollama http://localhost:11434`.
+fn source(config: &Config) -> String {
+    let endpoint = match config.provider {
+        ProviderKind::Ollama => config.ollama_url.clone(),
+        ProviderKind::Anthropic => config.anthropic.url.clone(),
+    };
+    format!("{} {}", config.provider.label(), endpoint.unwrap_or_default()).trim_end().to_string()
+}
+
+fn cache_path() -> PathBuf {
+    Config::data_dir().join(CACHE_FILENAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(name: &str) -> Model {
+        Model { name: name.to_string(), size: Some(1) }
+    }
+
+    #[test]
+    fn cached_list_is_only_loaded_for_its_own_source() {
+        let mut config = Config { ollama_url: Some("http://localhost:11434".into()), ..Config::default() };
+        let cache = ModelCache::new(&config, &[model("llama3.1:8b"), model("qwen2.5:7b")]);
+        assert_eq!(cache.source, "ollama http://localhost:11434");
+        let json = serde_json::to_string(&cache).unwrap();
+
+        let loaded = ModelCache::parse(&json, &config).unwrap();
+        assert_eq!(loaded.models().iter().map(|model| model.name.as_str()).collect::<Vec<_>>(), vec!["llama3.1:8b", "qwen2.5:7b"]);
+        assert!(loaded.models().iter().all(|model| model.size.is_none()));
+
+        config.ollama_url = Some("http://gpu-box:11434".into());
+        assert!(ModelCache::parse(&json, &config).is_none());
+        config.ollama_url = Some("http://localhost:11434".into());
+        config.provider = ProviderKind::Anthropic;
+        assert!(ModelCache::parse(&json, &config).is_none());
+        assert!(ModelCache::parse("not json", &config).is_none());
+    }
+}
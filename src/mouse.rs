@@ -1,14 +1,133 @@
+use ratatui::layout::Rect;
+use unicode_width::UnicodeWidthChar;
+
+/// A row of the conversation as drawn, after wrapping.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Row {
+    pub text: String,
+    /// Whether the row carries on the line above it, wrapped to fit.
+    pub continues: bool,
+}
+
+/// Maps a screen cell to a row and column of the conversation, `area` being
+/// the text inside the borders and padding and `scroll` the rows scrolled
+/// past. Cells outside the text give `None`.
 pub fn get_text_coordinates(
-    layout_area: ratatui::layout::Rect,
+    layout_area: Rect,
+    scroll: u16,
     mouse_x: u16,
     mouse_y: u16,
 ) -> Option<(usize, usize)> {
-    // For now, just a placeholder
-    if mouse_y >= layout_area.y && mouse_y < layout_area.y + layout_area.height {
-        let line_index = (mouse_y - layout_area.y) as usize;
-        let char_index = (mouse_x - layout_area.x) as usize;
-        Some((line_index, char_index))
-    } else {
-        None
-    }
-}
\ No newline at end of file
+    if mouse_x < layout_area.x || mouse_x >= layout_area.right() || mouse_y < layout_area.y || mouse_y >= layout_area.bottom() {
+        return None;
+    }
+    let row = scroll as usize + (mouse_y - layout_area.y) as usize;
+    Some((row, (mouse_x - layout_area.x) as usize))
+}
+
+/// Moves a cell outside `area` onto its nearest edge, so dragging past the
+/// text still selects up to it.
+pub fn clamp_to(area: Rect, mouse_x: u16, mouse_y: u16) -> (u16, u16) {
+    (
+        mouse_x.clamp(area.x, area.right().saturating_sub(1).max(area.x)),
+        mouse_y.clamp(area.y, area.bottom().saturating_sub(1).max(area.y)),
+    )
+}
+
+/// The selected characters of each row, as `(row, from, to)` with `to`
+/// exclusive. Both ends of the selection, given as screen columns, are
+/// included, in whichever order they were dragged.
+pub fn selected_columns(rows: &[Row], anchor: (usize, usize), head: (usize, usize)) -> Vec<(usize, usize, usize)> {
+    let (start, end) = if anchor <= head { (anchor, head) } else { (head, anchor) };
+    (start.0..=end.0.min(rows.len().saturating_sub(1)))
+        .filter(|&row| row < rows.len())
+        .map(|row| {
+            let text = &rows[row].text;
+            let len = text.chars().count();
+            let from = if row == start.0 { char_at(text, start.1) } else { 0 };
+            let to = if row == end.0 { (char_at(text, end.1) + 1).min(len) } else { len };
+            (row, from, to.max(from))
+        })
+        .collect()
+}
+
+/// The index of the character of `text` drawn over screen column `column`,
+/// wide characters taking two. Columns past the end give its length.
+fn char_at(text: &str, column: usize) -> usize {
+    let mut start = 0;
+    for (index, c) in text.chars().enumerate() {
+        start += c.width().unwrap_or(0);
+        if start > column {
+            return index;
+        }
+    }
+    text.chars().count()
+}
+
+/// The text between the two ends of a selection. Rows wrapped from one line
+/// are joined back without a line break.
+pub fn selected_text(rows: &[Row], anchor: (usize, usize), head: (usize, usize)) -> String {
+    let mut text = String::new();
+    for (index, (row, from, to)) in selected_columns(rows, anchor, head).into_iter().enumerate() {
+        if index > 0 && !rows[row].continues {
+            text.push('\n');
+        }
+        text.extend(rows[row].text.chars().skip(from).take(to - from));
+    }
+    text
+}
+
+/// Whether the terminal named by `$TERM` reports mouse events. The Linux
+/// console, serial terminals and dumb ones do not, and the capture escape
+/// sequences only get in the way of their own selection there.
+pub fn terminal_reports_mouse() -> bool {
+    // The Windows console reports the mouse without terminfo
+    cfg!(windows) || reports_mouse(std::env::var("TERM").ok().as_deref())
+}
+
+fn reports_mouse(term: Option<&str>) -> bool {
+    match term {
+        None | Some("") => false,
+        Some(term) => !["dumb", "linux", "cons25", "vt52", "vt100", "vt102", "vt220"]
+            .iter()
+            .any(|plain| term == *plain || term.strip_prefix(plain).is_some_and(|rest| rest.starts_with('-'))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_follows_the_drawn_rows() {
+        let rows = vec![
+            Row { text: "Lucius: the quick brown ".to_string(), continues: false },
+            Row { text: "fox jumps".to_string(), continues: true },
+            Row { text: "```".to_string(), continues: false },
+        ];
+        // Inside a border and a padding of one cell, scrolled one row down
+        let area = Rect::new(2, 2, 24, 2);
+        assert_eq!(get_text_coordinates(area, 1, 1, 2), None);
+        assert_eq!(get_text_coordinates(area, 1, 6, 2), Some((1, 4)));
+        assert_eq!(clamp_to(area, 40, 0), (25, 2));
+
+        // Dragged backwards, from "jumps" to "brown"
+        assert_eq!(selected_text(&rows, (1, 8), (0, 18)), "brown fox jumps");
+        assert_eq!(selected_text(&rows, (0, 8), (2, 50)), "the quick brown fox jumps\n```");
+
+        // Wide characters take two columns, either of which selects them
+        let rows = vec![Row { text: "日本語 ok".to_string(), continues: false }];
+        assert_eq!(selected_text(&rows, (0, 3), (0, 4)), "本語");
+        assert_eq!(selected_text(&rows, (0, 7), (0, 8)), "ok");
+    }
+
+    #[test]
+    fn plain_consoles_do_not_report_the_mouse() {
+        assert!(reports_mouse(Some("xterm-256color")));
+        assert!(reports_mouse(Some("tmux-256color")));
+        assert!(!reports_mouse(Some("linux")));
+        assert!(!reports_mouse(Some("vt100-am")));
+        assert!(!reports_mouse(Some("dumb")));
+        assert!(!reports_mouse(None));
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+/// The parameters that can be set, in the order they are listed.
+pub const PARAMETERS: &[&str] = &["temperature", "top_p", "num_ctx", "seed"];
+
+/// Sampling parameters sent with chat requests: the `[options]` section, and
+/// a conversation's `/set` overrides on top of it. Unset ones keep the
+/// model's defaults. Ollama gets all of them in `options`; Anthropic only
+/// understands `temperature` and `top_p`.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ModelOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Ollama's context window in tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    /// Fixed seed for reproducible answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+impl ModelOptions {
+    /// These options with every parameter `overrides` sets replacing ours.
+    pub fn overlay(&self, overrides: &ModelOptions) -> ModelOptions {
+        ModelOptions {
+            temperature: overrides.temperature.or(self.temperature),
+            top_p: overrides.top_p.or(self.top_p),
+            num_ctx: overrides.num_ctx.or(self.num_ctx),
+            seed: overrides.seed.or(self.seed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ModelOptions::default()
+    }
+
+    /// Applies `key=value` pairs separated by spaces, e.g.
+    /// `temperature=0.2 seed=42`. An empty value unsets the parameter.
+    /// Nothing changes unless every pair is valid.
+    pub fn apply(&mut self, pairs: &str) -> Result<(), String> {
+        let mut updated = self.clone();
+        for pair in pairs.split_whitespace() {
+            let (key, value) = pair.split_once('=').ok_or_else(|| format!("expected key=value, got '{}'", pair))?;
+            match key {
+                "temperature" => updated.temperature = parse(key, value)?,
+                "top_p" => updated.top_p = parse(key, value)?,
+                "num_ctx" => updated.num_ctx = parse(key, value)?,
+                "seed" => updated.seed = parse(key, value)?,
+                _ => return Err(format!("unknown parameter '{}' (expected one of {})", key, PARAMETERS.join(", "))),
+            }
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    /// The parameters that are set, as `key=value` pairs in the form
+    /// `apply` reads.
+    pub fn describe(&self) -> String {
+        let values = [
+            self.temperature.map(|v| v.to_string()),
+            self.top_p.map(|v| v.to_string()),
+            self.num_ctx.map(|v| v.to_string()),
+            self.seed.map(|v| v.to_string()),
+        ];
+        PARAMETERS
+            .iter()
+            .zip(values)
+            .filter_map(|(key, value)| value.map(|value| format!("{}={}", key, value)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The parameters that are set, as fields of Ollama's `options` object.
+    pub fn to_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<Option<T>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value.parse().map(Some).map_err(|_| format!("invalid value '{}' for {}", value, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_are_set_unset_and_layered() {
+        let mut config = ModelOptions::default();
+        config.apply("temperature=0.7 num_ctx=8192").unwrap();
+        let mut session = ModelOptions::default();
+        session.apply("temperature=0.2 seed=42").unwrap();
+
+        let effective = config.overlay(&session);
+        assert_eq!(effective.describe(), "temperature=0.2 num_ctx=8192 seed=42");
+        assert_eq!(effective.to_json().get("num_ctx"), Some(&serde_json::json!(8192)));
+        assert!(!effective.to_json().contains_key("top_p"));
+
+        session.apply("seed=").unwrap();
+        assert_eq!(session.seed, None);
+        assert!(session.apply("temperature=0.1 top_k=5").is_err());
+        assert!(session.apply("seed=abc").is_err());
+        assert_eq!(session.temperature, Some(0.2));
+    }
+}
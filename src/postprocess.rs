@@ -0,0 +1,157 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A single built-in filter applied to assistant output before it is displayed
+/// and stored in the chat history.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Strip trailing whitespace from every line and from the end of the message.
+    TrimTrailingWhitespace,
+    /// Turn `~~~` fences into backtick fences, unindent fence lines and close a dangling fence.
+    NormalizeCodeFences,
+    /// Wrap bare URLs in markdown autolinks (`<https://...>`).
+    AutoLinkUrls,
+    /// Mask e-mail addresses and IPv4 addresses.
+    RedactPii,
+    /// Mask a small list of profane words.
+    Profanity,
+}
+
+/// A user-defined regex replacement, applied after the built-in filters.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegexReplacement {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+/// The `[postprocess]` section of the config file.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PostProcessConfig {
+    #[serde(default = "default_filters")]
+    pub filters: Vec<Filter>,
+    #[serde(default)]
+    pub replacements: Vec<RegexReplacement>,
+}
+
+impl Default for PostProcessConfig {
+    fn default() -> Self {
+        Self {
+            filters: default_filters(),
+            replacements: vec![],
+        }
+    }
+}
+
+fn default_filters() -> Vec<Filter> {
+    vec![Filter::TrimTrailingWhitespace, Filter::NormalizeCodeFences]
+}
+
+lazy_static! {
+    static ref URL_REGEX: Regex = Regex::new(r"(^|[^<(\[])(https?://[^\s<>()\[\]]+)").unwrap();
+    static ref EMAIL_REGEX: Regex = Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").unwrap();
+    static ref IPV4_REGEX: Regex = Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").unwrap();
+    static ref PROFANITY_REGEX: Regex = Regex::new(r"(?i)\b(fuck\w*|shit\w*|bitch\w*|asshole\w*|bastard\w*|damn)\b").unwrap();
+}
+
+/// Runs the configured filters, in order, followed by the user's regex replacements.
+pub fn apply(config: &PostProcessConfig, text: &str) -> String {
+    let mut output = text.to_string();
+    for filter in &config.filters {
+        output = match filter {
+            Filter::TrimTrailingWhitespace => trim_trailing_whitespace(&output),
+            Filter::NormalizeCodeFences => normalize_code_fences(&output),
+            Filter::AutoLinkUrls => map_prose(&output, |line| URL_REGEX.replace_all(line, "$1<$2>").into_owned()),
+            Filter::RedactPii => map_prose(&output, |line| {
+                let line = EMAIL_REGEX.replace_all(line, "[email redacted]");
+                IPV4_REGEX.replace_all(&line, "[ip redacted]").into_owned()
+            }),
+            // Code and the command output quoted in it are left as they are
+            Filter::Profanity => map_prose(&output, |line| {
+                PROFANITY_REGEX.replace_all(line, |caps: &regex::Captures| "*".repeat(caps[0].chars().count())).into_owned()
+            }),
+        };
+    }
+
+    for replacement in &config.replacements {
+        match Regex::new(&replacement.pattern) {
+            Ok(re) => output = re.replace_all(&output, replacement.replacement.as_str()).into_owned(),
+            Err(e) => log::error!("Skipping invalid postprocess regex '{}': {}", replacement.pattern, e),
+        }
+    }
+    output
+}
+
+fn trim_trailing_whitespace(text: &str) -> String {
+    text.lines().map(|line| line.trim_end()).collect::<Vec<_>>().join("\n").trim_end().to_string()
+}
+
+fn normalize_code_fences(text: &str) -> String {
+    let mut in_fence = false;
+    let mut lines: Vec<String> = text
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if let Some(rest) = trimmed.strip_prefix("```").or_else(|| trimmed.strip_prefix("~~~")) {
+                in_fence = !in_fence;
+                format!("```{}", rest.trim())
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if in_fence {
+        lines.push("```".to_string());
+    }
+    lines.join("\n")
+}
+
+/// Applies `f` to every line that is not inside a fenced code block.
+fn map_prose<F: Fn(&str) -> String>(text: &str, f: F) -> String {
+    let mut in_fence = false;
+    text.lines()
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+                line.to_string()
+            } else if in_fence {
+                line.to_string()
+            } else {
+                f(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only(filter: Filter) -> PostProcessConfig {
+        PostProcessConfig { filters: vec![filter], replacements: vec![] }
+    }
+
+    #[test]
+    fn profanity_is_masked_in_prose_only() {
+        let text = "Damn, the disk is full.\n```\n$ grep -c damn app.log\nshit happens\n```\nWhat a shitshow.";
+        assert_eq!(
+            apply(&only(Filter::Profanity), text),
+            "****, the disk is full.\n```\n$ grep -c damn app.log\nshit happens\n```\nWhat a ********."
+        );
+    }
+
+    #[test]
+    fn pii_and_links_leave_code_alone() {
+        let text = "Mail ops@example.com at https://example.com/status\n```\nping 10.0.0.1\n```";
+        assert_eq!(apply(&only(Filter::RedactPii), text), "Mail [email redacted] at https://example.com/status\n```\nping 10.0.0.1\n```");
+        assert_eq!(apply(&only(Filter::AutoLinkUrls), "See https://example.com/status"), "See <https://example.com/status>");
+    }
+
+    #[test]
+    fn unclosed_fences_are_closed_and_trailing_spaces_trimmed() {
+        let config = PostProcessConfig::default();
+        assert_eq!(apply(&config, "Run:  \n~~~ bash \nls   \n\n"), "Run:\n```bash\nls\n```");
+    }
+}
@@ -0,0 +1,103 @@
+use std::io::{self, Write};
+use termimad::MadSkin;
+
+use crate::clipboard;
+use crate::config::{ClipboardBackend, Config};
+use crate::context;
+use crate::llm::{self, LLMResponse, PromptBuilder};
+use crate::message::ChatMessage;
+use crate::postprocess;
+use crate::session::{self, Session};
+
+/// `lucius quick "prompt"`: a single exchange without the full TUI (no banner,
+/// no history). The answer is printed, copied to the clipboard and appended to
+/// the scratch session, then Lucius exits. Meant to be bound to a WM hotkey.
+pub async fn run(prompt: String) -> io::Result<()> {
+    let mut config = Config::load();
+    let project = context::load_project_context();
+    config.apply_project(project.profile);
+    let system_prompt = project.prompt;
+
+    let prompt = if prompt.trim().is_empty() { read_prompt()? } else { prompt };
+    if prompt.trim().is_empty() {
+        return Ok(());
+    }
+
+    let provider = llm::provider(&config);
+    let model = config.selected_model.clone().unwrap_or_default();
+
+    eprintln!("[{}] ...", model);
+    let request = PromptBuilder::new(model.clone()).system(system_prompt).message(ChatMessage::user(prompt.clone())).build();
+    let answer = match provider.chat_stream(request, None).await {
+        Ok(LLMResponse::FinalResponse(text, _)) => postprocess::apply(&config.postprocess, &text),
+        // Tools need the confirmation flow of the full TUI, so only show the request
+        Ok(LLMResponse::ToolCallDetected(tool)) => {
+            format!("Tool Call: {}", serde_json::to_string_pretty(&tool).unwrap_or_default())
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Ok(());
+        }
+    };
+
+    MadSkin::default().print_text(&answer);
+
+    let copied = match clipboard::resolve(config.clipboard.backend) {
+        ClipboardBackend::Osc52 => clipboard::write_osc52(&answer).map_err(|e| e.to_string()),
+        _ => clipboard::copy_with_external_tool(&answer),
+    };
+    if let Err(e) = copied {
+        log::warn!("Quick mode could not copy the answer: {}", e);
+    }
+    let scratch = session::find(session::SCRATCH_ID).and_then(|meta| session::load(&meta));
+    session::save(&with_exchange(scratch, &model, prompt, answer));
+    Ok(())
+}
+
+/// Asks for the prompt interactively when none was given on the command line.
+fn read_prompt() -> io::Result<String> {
+    eprint!("> ");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// The scratch session with the exchange added at its end, started on first
+/// use. It is starred so pruning never removes it, and opens from the session
+/// browser like any other.
+fn with_exchange(scratch: Option<Session>, model: &str, prompt: String, answer: String) -> Session {
+    let mut scratch = scratch.unwrap_or_else(|| Session {
+        id: session::SCRATCH_ID.to_string(),
+        title: Some("Scratch".to_string()),
+        starred: true,
+        tags: vec![],
+        notes: None,
+        model: None,
+        messages: vec![],
+        system_prompt: None,
+        enabled_tools: None,
+        options: Default::default(),
+        lucius_context: None,
+        lucius_profile: None,
+        branch_of: None,
+    });
+    scratch.model = Some(model.to_string());
+    scratch.messages.extend([ChatMessage::user(prompt), ChatMessage::assistant(answer)]);
+    scratch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchanges_add_up_in_one_scratch_session() {
+        let scratch = with_exchange(None, "llama3", "disk full?".to_string(), "Almost.".to_string());
+        assert_eq!((scratch.id.as_str(), scratch.starred), (session::SCRATCH_ID, true));
+        let scratch = with_exchange(Some(scratch), "qwen", "and now?".to_string(), "Fine.".to_string());
+        assert_eq!(scratch.messages.iter().map(|msg| msg.content.as_str()).collect::<Vec<_>>(), ["disk full?", "Almost.", "and now?", "Fine."]);
+        assert_eq!(scratch.model.as_deref(), Some("qwen"));
+        assert_eq!(scratch.file_name(), "scratch-scratch.json");
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::llm::{ollama::OllamaProvider, ProviderError};
+
+/// Files larger than this are left out of the index; they are rarely source.
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// Directories never worth indexing, besides hidden ones.
+const SKIP_DIRS: &[&str] = &["target", "node_modules", "vendor", "dist", "build", "__pycache__"];
+/// Chunks sent to the embeddings endpoint per request.
+const EMBED_BATCH: usize = 16;
+
+/// The `[rag]` section: retrieval of project excerpts built by `/index`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RagConfig {
+    /// Ollama embedding model, e.g. `nomic-embed-text`.
+    #[serde(default = "default_model")]
+    pub model: String,
+    /// How many chunks are added to the context for each prompt.
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    /// Lines per chunk when files are split for embedding.
+    #[serde(default = "default_chunk_lines")]
+    pub chunk_lines: usize,
+}
+
+fn default_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_top_k() -> usize {
+    4
+}
+
+fn default_chunk_lines() -> usize {
+    40
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self { model: default_model(), top_k: default_top_k(), chunk_lines: default_chunk_lines() }
+    }
+}
+
+/// A slice of a project file and its embedding.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Chunk {
+    /// Path relative to the indexed directory.
+    pub path: String,
+    /// 1-based line the chunk starts at.
+    pub line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+/// The embedded chunks of one project directory, saved under the config
+/// directory so repositories stay clean.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Index {
+    pub root: PathBuf,
+    /// Embedding model the vectors came from; queries must use the same.
+    pub model: String,
+    pub files: usize,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Index {
+    /// The saved index of `root`, if `/index` was run there.
+    pub fn load(root: &Path) -> Option<Self> {
+        let content = fs::read_to_string(index_path(root)).ok()?;
+        serde_json::from_str(&content)
+            .map_err(|e| log::warn!("Ignoring unreadable index for {}: {}", root.display(), e))
+            .ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = index_path(&self.root);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// The `top_k` chunks closest to `query`, most similar first.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<&Chunk> {
+        let mut scored: Vec<(f32, &Chunk)> = self.chunks.iter().map(|chunk| (cosine(query, &chunk.vector), chunk)).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().take(top_k).map(|(_, chunk)| chunk).collect()
+    }
+}
+
+/// Named after a SHA-256 of the root, which stays the same across builds
+/// so indexes are found again after an upgrade.
+fn index_path(root: &Path) -> PathBuf {
+    let digest = Sha256::digest(root.to_string_lossy().as_bytes());
+    let name: String = digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    Config::data_dir().join("indexes").join(format!("{}.json", name))
+}
+
+/// Embeds every text file under `root`, reporting `(done, total)` chunks to
+/// `progress` after each batch.
+pub async fn build(root: &Path, ollama: &OllamaProvider, config: &RagConfig, progress: impl Fn(usize, usize)) -> Result<Index, ProviderError> {
+    let (walk_root, chunk_lines) = (root.to_path_buf(), config.chunk_lines);
+    let (mut chunks, indexed_files) = tokio::task::spawn_blocking(move || project_chunks(&walk_root, chunk_lines)).await?;
+
+    let total = chunks.len();
+    progress(0, total);
+    for (batch, start) in chunks.chunks_mut(EMBED_BATCH).zip((0..).step_by(EMBED_BATCH)) {
+        // The path gives the model a hint about what the chunk is
+        let inputs: Vec<String> = batch.iter().map(|chunk| format!("{}\n{}", chunk.path, chunk.text)).collect();
+        let vectors = ollama.embed(&config.model, &inputs).await?;
+        for (chunk, vector) in batch.iter_mut().zip(vectors) {
+            chunk.vector = vector;
+        }
+        progress(start + batch.len(), total);
+    }
+    Ok(Index { root: root.to_path_buf(), model: config.model.clone(), files: indexed_files, chunks })
+}
+
+/// A chunk retrieved for a prompt, without its embedding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Excerpt {
+    pub path: String,
+    pub line: usize,
+    pub text: String,
+}
+
+impl Excerpt {
+    /// What identifies the chunk across retrievals.
+    pub fn key(&self) -> (String, usize) {
+        (self.path.clone(), self.line)
+    }
+}
+
+/// The `top_k` chunks of `index` most relevant to `prompt`, leaving out the
+/// ones in `skip`.
+pub async fn retrieve(index: &Index, ollama: &OllamaProvider, top_k: usize, prompt: &str, skip: &HashSet<(String, usize)>) -> Result<Vec<Excerpt>, ProviderError> {
+    if index.chunks.is_empty() || top_k == 0 {
+        return Ok(vec![]);
+    }
+    let query = ollama.embed(&index.model, &[prompt.to_string()]).await?;
+    let Some(query) = query.first() else {
+        return Ok(vec![]);
+    };
+    Ok(index
+        .search(query, top_k + skip.len())
+        .into_iter()
+        .map(|chunk| Excerpt { path: chunk.path.clone(), line: chunk.line, text: chunk.text.clone() })
+        .filter(|excerpt| !skip.contains(&excerpt.key()))
+        .take(top_k)
+        .collect())
+}
+
+/// The excerpts formatted for the system prompt.
+pub fn excerpts_prompt(excerpts: &[Excerpt]) -> Option<String> {
+    if excerpts.is_empty() {
+        return None;
+    }
+    let excerpts: Vec<String> = excerpts
+        .iter()
+        .map(|excerpt| format!("`{}` (from line {}):\n```\n{}\n```", excerpt.path, excerpt.line, excerpt.text))
+        .collect();
+    Some(format!(
+        "Excerpts from the project files that may be relevant to the user's question:\n\n{}",
+        excerpts.join("\n\n")
+    ))
+}
+
+/// Reads and splits the files under `root` into chunks still to be
+/// embedded, with the number of files they came from.
+fn project_chunks(root: &Path, chunk_lines: usize) -> (Vec<Chunk>, usize) {
+    let mut files = Vec::new();
+    project_files(root, &mut files);
+    let mut chunks = Vec::new();
+    let mut indexed_files = 0;
+    for file in &files {
+        let Ok(text) = fs::read_to_string(file) else {
+            continue; // Not UTF-8, so not text worth embedding
+        };
+        let path = file.strip_prefix(root).unwrap_or(file).display().to_string();
+        let before = chunks.len();
+        chunks.extend(chunk_text(&text, chunk_lines).into_iter().map(|(line, text)| Chunk {
+            path: path.clone(),
+            line,
+            text,
+            vector: vec![],
+        }));
+        if chunks.len() > before {
+            indexed_files += 1;
+        }
+    }
+    (chunks, indexed_files)
+}
+
+/// Collects the files under `dir` worth indexing, skipping hidden entries,
+/// build output and large files.
+fn project_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() && !SKIP_DIRS.contains(&name.as_str()) {
+            project_files(&entry.path(), files);
+        } else if kind.is_file() && entry.metadata().is_ok_and(|meta| meta.len() <= MAX_FILE_BYTES) {
+            files.push(entry.path());
+        }
+    }
+}
+
+/// Splits `text` into chunks of `lines` lines, skipping blank ones, with the
+/// 1-based line each starts at.
+fn chunk_text(text: &str, lines: usize) -> Vec<(usize, String)> {
+    let all: Vec<&str> = text.lines().collect();
+    all.chunks(lines.max(1))
+        .enumerate()
+        .map(|(i, chunk)| (i * lines.max(1) + 1, chunk.join("\n")))
+        .filter(|(_, chunk)| !chunk.trim().is_empty())
+        .collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_files_and_ranks_by_similarity() {
+        assert_eq!(chunk_text("a\nb\nc\n\n\n\ne", 2), vec![(1, "a\nb".to_string()), (3, "c\n".to_string()), (7, "e".to_string())]);
+
+        let chunk = |path: &str, vector: Vec<f32>| Chunk { path: path.to_string(), line: 1, text: String::new(), vector };
+        let index = Index {
+            chunks: vec![chunk("far", vec![0.0, 1.0]), chunk("near", vec![1.0, 0.1]), chunk("empty", vec![])],
+            ..Index::default()
+        };
+        let found: Vec<&str> = index.search(&[1.0, 0.0], 2).iter().map(|chunk| chunk.path.as_str()).collect();
+        assert_eq!(found, ["near", "far"]);
+    }
+
+    #[test]
+    fn index_files_are_named_by_a_stable_hash_of_the_root() {
+        let path = index_path(Path::new("/srv/project"));
+        assert_eq!(path.file_name().unwrap(), "4f3f91f6b0b47e3b.json");
+        assert_eq!(path, index_path(Path::new("/srv/project")));
+        assert_ne!(path, index_path(Path::new("/srv/other")));
+    }
+
+    #[test]
+    fn hidden_build_and_large_files_are_not_chunked() {
+        let root = std::env::temp_dir().join(format!("lucius-rag-{}", std::process::id()));
+        for dir in ["src", "target", ".git"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(root.join("README.md"), "one\ntwo\nthree\n").unwrap();
+        fs::write(root.join("target/out.txt"), "built").unwrap();
+        fs::write(root.join(".git/HEAD"), "ref").unwrap();
+        fs::write(root.join("big.log"), "x".repeat(MAX_FILE_BYTES as usize + 1)).unwrap();
+        fs::write(root.join("blank.txt"), "\n\n").unwrap();
+
+        let (chunks, files) = project_chunks(&root, 2);
+        let found: Vec<(&str, usize)> = chunks.iter().map(|chunk| (chunk.path.as_str(), chunk.line)).collect();
+        assert_eq!(found, [("README.md", 1), ("README.md", 3), ("src/main.rs", 1)]);
+        assert_eq!(files, 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn excerpts_name_their_file_and_line() {
+        assert_eq!(excerpts_prompt(&[]), None);
+        let excerpt = Excerpt { path: "ops.md".to_string(), line: 3, text: "df -h".to_string() };
+        assert_eq!(excerpt.key(), ("ops.md".to_string(), 3));
+        let prompt = excerpts_prompt(&[excerpt]).unwrap();
+        assert!(prompt.ends_with("`ops.md` (from line 3):\n```\ndf -h\n```"));
+    }
+}
@@ -0,0 +1,92 @@
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Memory a model takes beyond its file: KV cache and runtime buffers.
+const OVERHEAD: f64 = 1.2;
+
+/// The `[resources]` section: how much memory the Ollama host has, for
+/// telling whether a model fits before it is picked.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct ResourcesConfig {
+    /// RAM plus VRAM available to Ollama. Defaults to this machine's memory
+    /// when the server runs here; unknown for a remote one.
+    #[serde(default)]
+    pub memory_gb: Option<f64>,
+}
+
+/// Bytes of memory the Ollama host has, if known.
+pub fn host_memory(config: &Config) -> Option<u64> {
+    if let Some(gb) = config.resources.memory_gb {
+        return Some((gb * GB) as u64);
+    }
+    if !is_local(config.ollama_url.as_deref().unwrap_or_default()) {
+        return None;
+    }
+    // Read once; the settings screen asks on every frame
+    static MEM_TOTAL: OnceLock<Option<u64>> = OnceLock::new();
+    *MEM_TOTAL.get_or_init(|| {
+        // `MemTotal:       16318480 kB`
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    })
+}
+
+/// Whether the server at `url` runs on this machine.
+fn is_local(url: &str) -> bool {
+    let Some(host) = reqwest::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)) else {
+        return false;
+    };
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => host.eq_ignore_ascii_case("localhost"),
+    }
+}
+
+/// Whether a model of `size` bytes on disk fits in `memory` bytes once loaded.
+pub fn fits(size: u64, memory: u64) -> bool {
+    size as f64 * OVERHEAD <= memory as f64
+}
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// e.g. "4.9 GB" or "274 MB".
+pub fn format_bytes(bytes: u64) -> String {
+    let gb = bytes as f64 / GB;
+    if gb >= 1.0 {
+        format!("{:.1} GB", gb)
+    } else {
+        format!("{} MB", bytes / (1024 * 1024))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_models_do_not_fit_small_hosts() {
+        let host = 8 * 1024 * 1024 * 1024;
+        // llama3.1:8b at Q4 against a 70B one
+        assert!(fits(4_920_000_000, host));
+        assert!(!fits(42_500_000_000, host));
+        assert_eq!(format_bytes(4_920_000_000), "4.6 GB");
+        assert_eq!(format_bytes(274 * 1024 * 1024), "274 MB");
+    }
+
+    #[test]
+    fn only_loopback_hosts_are_local() {
+        assert!(is_local("http://localhost:11434"));
+        assert!(is_local("http://127.0.0.1:11434/"));
+        assert!(is_local("http://[::1]:11434"));
+        assert!(!is_local("http://gpu-box:11434"));
+        assert!(!is_local("http://notlocalhost.example.com"));
+        assert!(!is_local("http://gpu-box:11434/?via=localhost"));
+        assert!(!is_local("localhost:11434"));
+    }
+}
@@ -0,0 +1,689 @@
+use std::fs;
+use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::budget;
+use crate::config::Config;
+use crate::context::ProjectProfile;
+use crate::message::{ChatMessage, Role};
+use crate::options::ModelOptions;
+
+const SESSIONS_DIRNAME: &str = "sessions";
+const INDEX_FILENAME: &str = "index.json";
+
+/// Id of the session `lucius quick` appends its exchanges to.
+pub const SCRATCH_ID: &str = "scratch";
+
+/// Most search matches listed, newest sessions first.
+const MAX_SEARCH_HITS: usize = 200;
+
+/// Characters of context kept on each side of a search match.
+const SNIPPET_CONTEXT: usize = 30;
+
+/// A conversation as saved to `sessions/` in the Lucius data dir, under its
+/// title once it has one (see `Session::file_name`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "SessionFile")]
+pub struct Session {
+    pub id: String,
+    /// Set by renaming the session; otherwise its first prompt is the title.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Starred sessions are never pruned.
+    #[serde(default)]
+    pub starred: bool,
+    /// Labels like `incident` or `k8s`, set with `/tag`, without the `#`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Written by the model when the conversation was last closed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<SessionNotes>,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    /// The conversation template's system prompt, if one was applied.
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub enabled_tools: Option<Vec<String>>,
+    /// Parameters set with `/set` in this conversation.
+    #[serde(default, skip_serializing_if = "ModelOptions::is_empty")]
+    pub options: ModelOptions,
+    /// LUCIUS.md as the conversation kept it when an edit was applied only
+    /// to new conversations; empty when it had none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lucius_context: Option<String>,
+    /// The front matter that went with `lucius_context`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lucius_profile: Option<ProjectProfile>,
+    /// Set on a conversation forked off another one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_of: Option<BranchPoint>,
+}
+
+/// Where a branch was forked: it started as a copy of `session` up to and
+/// including message `message`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchPoint {
+    pub session: String,
+    pub message: usize,
+}
+
+/// A short account of a conversation (what was asked, which tools ran, how
+/// it ended), written when it is closed with `[sessions] notes` on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionNotes {
+    pub text: String,
+    /// How many messages the conversation had; notes are written again once
+    /// it has grown.
+    pub messages: usize,
+}
+
+/// A session file as read from disk, which may predate `ChatMessage` and
+/// hold plain prefixed strings with their timestamps alongside.
+#[derive(Deserialize)]
+struct SessionFile {
+    id: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    starred: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    notes: Option<SessionNotes>,
+    #[serde(default)]
+    model: Option<String>,
+    messages: Vec<StoredMessage>,
+    #[serde(default)]
+    timestamps: Vec<DateTime<Utc>>,
+    #[serde(default)]
+    system_prompt: Option<String>,
+    #[serde(default)]
+    enabled_tools: Option<Vec<String>>,
+    #[serde(default)]
+    options: ModelOptions,
+    #[serde(default)]
+    lucius_context: Option<String>,
+    #[serde(default)]
+    lucius_profile: Option<ProjectProfile>,
+    #[serde(default)]
+    branch_of: Option<BranchPoint>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredMessage {
+    Message(Box<ChatMessage>),
+    Legacy(String),
+}
+
+impl From<SessionFile> for Session {
+    fn from(file: SessionFile) -> Self {
+        let mut last_call = None;
+        let messages = file
+            .messages
+            .into_iter()
+            .enumerate()
+            .map(|(index, stored)| match stored {
+                StoredMessage::Message(message) => *message,
+                StoredMessage::Legacy(text) => {
+                    let timestamp = file.timestamps.get(index).copied().unwrap_or_else(Utc::now);
+                    let mut message = ChatMessage::from_legacy(&text, timestamp);
+                    // Results and tool errors answered the call just before them
+                    match message.role {
+                        Role::ToolCall => last_call = message.tool_call.clone(),
+                        Role::ToolResult => message.tool_call = last_call.clone(),
+                        Role::Error if ["Error from worker:", "Error polling result:", "Error submitting task:"].iter().any(|p| text.starts_with(p)) => {
+                            message.tool_call = last_call.clone();
+                        }
+                        _ => {}
+                    }
+                    message
+                }
+            })
+            .collect();
+        Session {
+            id: file.id,
+            title: file.title,
+            starred: file.starred,
+            tags: file.tags,
+            notes: file.notes,
+            model: file.model,
+            messages,
+            system_prompt: file.system_prompt,
+            enabled_tools: file.enabled_tools,
+            options: file.options,
+            lucius_context: file.lucius_context,
+            lucius_profile: file.lucius_profile,
+            branch_of: file.branch_of,
+        }
+    }
+}
+
+/// A session's entry in `sessions/index.json`, enough to list it in the
+/// session browser without reading every conversation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMeta {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub updated: DateTime<Utc>,
+    pub message_count: usize,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub branch_of: Option<BranchPoint>,
+    /// The session's file in the sessions directory; `<id>.json` when unset,
+    /// as in indexes written before files were named after titles.
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+/// The `[sessions]` section: limits on stored sessions, enforced on startup
+/// and by `/sessions clean`, and whether closed sessions get notes and new
+/// ones a title. Unset limits don't apply.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RetentionConfig {
+    /// Have the model write notes for each conversation as it is closed.
+    #[serde(default)]
+    pub notes: bool,
+    /// Have the model title each conversation after its first answer.
+    #[serde(default = "default_auto_title")]
+    pub auto_title: bool,
+    #[serde(default)]
+    pub max_sessions: Option<usize>,
+    #[serde(default)]
+    pub max_total_mb: Option<u64>,
+    #[serde(default)]
+    pub max_age_days: Option<i64>,
+}
+
+fn default_auto_title() -> bool {
+    true
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            notes: false,
+            auto_title: default_auto_title(),
+            max_sessions: None,
+            max_total_mb: None,
+            max_age_days: None,
+        }
+    }
+}
+
+impl RetentionConfig {
+    fn is_unlimited(&self) -> bool {
+        self.max_sessions.is_none() && self.max_total_mb.is_none() && self.max_age_days.is_none()
+    }
+}
+
+impl Session {
+    /// The name shown for the session: its title, or its first prompt.
+    pub fn display_title(&self) -> String {
+        self.title
+            .clone()
+            .or_else(|| self.messages.iter().find(|msg| msg.role == Role::User).map(|msg| budget::preview(&msg.content)))
+            .unwrap_or_else(|| self.id.clone())
+    }
+
+    fn meta(&self) -> SessionMeta {
+        SessionMeta {
+            id: self.id.clone(),
+            title: self.display_title(),
+            model: self.model.clone(),
+            updated: self.messages.last().map_or_else(Utc::now, |msg| msg.timestamp),
+            message_count: self.messages.len(),
+            starred: self.starred,
+            tags: self.tags.clone(),
+            notes: self.notes.as_ref().map(|notes| notes.text.clone()),
+            branch_of: self.branch_of.clone(),
+            file: Some(self.file_name()),
+        }
+    }
+
+    /// The name of the session's file: its title's slug with the start of
+    /// its id, so equal titles don't collide, e.g.
+    /// `restart-nginx-on-web-1-3f2a1b9c.json`; `<id>.json` until it has one.
+    pub fn file_name(&self) -> String {
+        match self.title.as_deref().map(slug).filter(|slug| !slug.is_empty()) {
+            Some(slug) => format!("{}-{}.json", slug, self.id.get(..8).unwrap_or(&self.id)),
+            None => format!("{}.json", self.id),
+        }
+    }
+
+    /// Whether closing the conversation should have notes written for it:
+    /// something was answered, and not since the last notes.
+    pub fn needs_notes(&self) -> bool {
+        self.messages.iter().any(|msg| msg.role == Role::Assistant)
+            && self.notes.as_ref().is_none_or(|notes| notes.messages != self.messages.len())
+    }
+}
+
+/// A tag as stored: lowercase, without the leading `#`. `None` for words
+/// that are not a tag, such as ones with punctuation.
+pub fn normalize_tag(word: &str) -> Option<String> {
+    let tag = word.trim_start_matches('#').to_lowercase();
+    let valid = !tag.is_empty() && tag.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '/'));
+    valid.then_some(tag)
+}
+
+/// Applies `/tag` arguments to `tags`: words add a tag and words prefixed
+/// with `-` remove one. Returns the first word that is not a valid tag.
+pub fn edit_tags(tags: &mut Vec<String>, args: &str) -> Result<(), String> {
+    let mut edited = tags.clone();
+    for word in args.split_whitespace() {
+        let (remove, word) = match word.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, word),
+        };
+        let tag = normalize_tag(word).ok_or_else(|| word.to_string())?;
+        if remove {
+            edited.retain(|known| *known != tag);
+        } else if !edited.contains(&tag) {
+            edited.push(tag);
+        }
+    }
+    *tags = edited;
+    Ok(())
+}
+
+/// A title as a file name part: lowercase words joined by `-`, e.g.
+/// `restart-nginx-on-web-1`. Empty when the title has no letters or digits.
+pub fn slug(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .take(48)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// Tags as shown: `#incident #k8s`.
+pub fn format_tags(tags: &[String]) -> String {
+    tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" ")
+}
+
+/// The directory holding one file per conversation. Created on first use.
+pub fn sessions_dir() -> PathBuf {
+    let path = Config::data_dir().join(SESSIONS_DIRNAME);
+    fs::create_dir_all(&path).ok();
+    path
+}
+
+/// Where a session is saved, as its index entry says.
+fn meta_path(meta: &SessionMeta) -> PathBuf {
+    sessions_dir().join(meta.file.clone().unwrap_or_else(|| format!("{}.json", meta.id)))
+}
+
+/// Writes the session to its file, replacing the previous save, and updates
+/// its index entry. A new or changed title moves the file to its new name.
+pub fn save(session: &Session) {
+    save_into(session, list());
+}
+
+/// `save` with the index already read.
+fn save_into(session: &Session, mut index: Vec<SessionMeta>) {
+    let path = sessions_dir().join(session.file_name());
+    let result = serde_json::to_string(session)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::error!("Failed to save session {}: {}", path.display(), e);
+        return;
+    }
+    if let Some(previous) = index.iter().find(|meta| meta.id == session.id).map(meta_path).filter(|previous| *previous != path) {
+        if let Err(e) = fs::remove_file(&previous) {
+            log::warn!("Failed to remove the old session file {}: {}", previous.display(), e);
+        }
+    }
+    index.retain(|meta| meta.id != session.id);
+    index.push(session.meta());
+    write_index(&index);
+}
+
+/// Every saved session, most recently updated first. The index is rebuilt
+/// from the session files when it is missing or unreadable.
+pub fn list() -> Vec<SessionMeta> {
+    let path = sessions_dir().join(INDEX_FILENAME);
+    let mut index = match fs::read_to_string(&path).map(|json| serde_json::from_str::<Vec<SessionMeta>>(&json)) {
+        Ok(Ok(index)) => index,
+        Ok(Err(e)) => {
+            log::warn!("Rebuilding unreadable session index {}: {}", path.display(), e);
+            rebuild_index()
+        }
+        Err(_) => rebuild_index(),
+    };
+    index.sort_by_key(|meta| std::cmp::Reverse(meta.updated));
+    index
+}
+
+fn rebuild_index() -> Vec<SessionMeta> {
+    let index: Vec<SessionMeta> = fs::read_dir(sessions_dir())
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json") && !path.ends_with(INDEX_FILENAME))
+        .filter_map(|path| {
+            let session = serde_json::from_str::<Session>(&fs::read_to_string(&path).ok()?).ok()?;
+            // Whatever the file is called, e.g. `<id>.json` from before titles named files
+            let file = path.file_name()?.to_str()?.to_string();
+            Some(SessionMeta { file: Some(file), ..session.meta() })
+        })
+        .collect();
+    write_index(&index);
+    index
+}
+
+fn write_index(index: &[SessionMeta]) {
+    let path = sessions_dir().join(INDEX_FILENAME);
+    let result = serde_json::to_string(index)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::error!("Failed to write session index {}: {}", path.display(), e);
+    }
+}
+
+/// The index entry of session `id`.
+pub fn find(id: &str) -> Option<SessionMeta> {
+    list().into_iter().find(|meta| meta.id == id)
+}
+
+/// Reads the saved session an index entry points to.
+pub fn load(meta: &SessionMeta) -> Option<Session> {
+    let path = meta_path(meta);
+    let content = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(session) => Some(session),
+        Err(e) => {
+            log::warn!("Ignoring unreadable session {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Loads saved session `id`, applies `edit` and saves it, reading the index
+/// once. Returns whether there was such a session.
+pub fn update(id: &str, edit: impl FnOnce(&mut Session)) -> bool {
+    let index = list();
+    let Some(mut session) = index.iter().find(|meta| meta.id == id).and_then(load) else {
+        return false;
+    };
+    edit(&mut session);
+    save_into(&session, index);
+    true
+}
+
+/// Gives a saved session a title of its own.
+pub fn rename(id: &str, title: &str) {
+    update(id, |session| session.title = Some(title.to_string()));
+}
+
+/// Stores notes written for a saved session.
+pub fn set_notes(id: &str, notes: SessionNotes) {
+    update(id, |session| session.notes = Some(notes));
+}
+
+/// Stars or unstars a saved session.
+pub fn set_starred(id: &str, starred: bool) {
+    update(id, |session| session.starred = starred);
+}
+
+/// Deletes saved sessions and their index entries.
+pub fn delete(sessions: &[SessionMeta]) {
+    for meta in sessions {
+        let path = meta_path(meta);
+        if let Err(e) = fs::remove_file(&path) {
+            log::error!("Failed to delete session {}: {}", path.display(), e);
+        }
+    }
+    let mut index = list();
+    index.retain(|meta| !sessions.iter().any(|deleted| deleted.id == meta.id));
+    write_index(&index);
+}
+
+/// What pruning would remove: expired sessions, then the oldest ones until
+/// the count and size limits hold. Starred sessions and `keep` are exempt.
+/// `sessions` pairs each entry with its file size and is newest first.
+pub fn prune_plan(sessions: &[(SessionMeta, u64)], retention: &RetentionConfig, keep: &str, now: DateTime<Utc>) -> Vec<SessionMeta> {
+    if retention.is_unlimited() {
+        return vec![];
+    }
+    let mut count = sessions.len();
+    let mut bytes: u64 = sessions.iter().map(|(_, size)| size).sum();
+    let max_bytes = retention.max_total_mb.map(|mb| mb * 1024 * 1024);
+    let mut removed = Vec::new();
+    for (meta, size) in sessions.iter().rev() {
+        if meta.starred || meta.id == keep {
+            continue;
+        }
+        let expired = retention.max_age_days.is_some_and(|days| now - meta.updated > chrono::Duration::days(days));
+        let over_count = retention.max_sessions.is_some_and(|max| count > max);
+        let over_size = max_bytes.is_some_and(|max| bytes > max);
+        if expired || over_count || over_size {
+            count -= 1;
+            bytes -= size;
+            removed.push(meta.clone());
+        }
+    }
+    removed
+}
+
+/// The saved sessions `retention` would remove, with their total size.
+/// The most recently updated session is always kept.
+pub fn prune_candidates(retention: &RetentionConfig) -> (Vec<SessionMeta>, u64) {
+    let sessions: Vec<(SessionMeta, u64)> = list()
+        .into_iter()
+        .map(|meta| {
+            let size = fs::metadata(meta_path(&meta)).map(|m| m.len()).unwrap_or(0);
+            (meta, size)
+        })
+        .collect();
+    let keep = sessions.first().map(|(meta, _)| meta.id.clone()).unwrap_or_default();
+    let plan = prune_plan(&sessions, retention, &keep, Utc::now());
+    let bytes = sessions
+        .iter()
+        .filter(|(meta, _)| plan.iter().any(|removed| removed.id == meta.id))
+        .map(|(_, size)| size)
+        .sum();
+    (plan, bytes)
+}
+
+/// Deletes what `retention` allows to go, returning how many were removed.
+pub fn prune(retention: &RetentionConfig) -> usize {
+    let (plan, _) = prune_candidates(retention);
+    for meta in &plan {
+        log::info!("Pruning session {} ({})", meta.id, meta.title);
+    }
+    if !plan.is_empty() {
+        delete(&plan);
+    }
+    plan.len()
+}
+
+/// The most recently saved session, if any, but the scratch one: quick
+/// lookups from `lucius quick` do not become the conversation to resume.
+pub fn load_latest() -> Option<Session> {
+    load(list().iter().find(|meta| meta.id != SCRATCH_ID)?)
+}
+
+/// A place in a saved session where a search query occurs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub session: SessionMeta,
+    /// The matching message, or `None` when the title, model or notes matched.
+    pub message: Option<usize>,
+    pub snippet: String,
+}
+
+/// Case-insensitive search through every saved session's title, model, notes
+/// and messages, most recently updated sessions first.
+pub fn search(query: &str) -> Vec<SearchHit> {
+    let needle = query.trim().to_lowercase();
+    if needle.is_empty() {
+        return vec![];
+    }
+    let mut hits = Vec::new();
+    for meta in list() {
+        let metadata = format!("{} · {}", meta.title, meta.model.as_deref().unwrap_or("-"));
+        if metadata.to_lowercase().contains(&needle) {
+            hits.push(SearchHit { session: meta.clone(), message: None, snippet: metadata });
+        } else if let Some(snippet) = meta.notes.as_deref().and_then(|notes| snippet(notes, &needle)) {
+            hits.push(SearchHit { session: meta.clone(), message: None, snippet });
+        }
+        let Some(session) = load(&meta) else {
+            continue;
+        };
+        for (index, msg) in session.messages.iter().enumerate() {
+            if let Some(snippet) = snippet(&msg.display_text(), &needle) {
+                hits.push(SearchHit { session: meta.clone(), message: Some(index), snippet });
+            }
+        }
+        if hits.len() >= MAX_SEARCH_HITS {
+            hits.truncate(MAX_SEARCH_HITS);
+            break;
+        }
+    }
+    hits
+}
+
+/// The match of `needle` (already lowercase) in `text` with a little context
+/// on both sides, on one line.
+fn snippet(text: &str, needle: &str) -> Option<String> {
+    let flat: Vec<char> = text.split_whitespace().collect::<Vec<_>>().join(" ").chars().collect();
+    let lower: Vec<char> = flat.iter().flat_map(|c| c.to_lowercase()).collect();
+    // Lowercasing can change the length of a few characters; skip rather than misplace
+    if lower.len() != flat.len() {
+        return text.to_lowercase().contains(needle).then(|| budget::preview(text));
+    }
+    let needle: Vec<char> = needle.chars().collect();
+    let start = lower.windows(needle.len()).position(|window| window == needle.as_slice())?;
+    let from = start.saturating_sub(SNIPPET_CONTEXT);
+    let to = (start + needle.len() + SNIPPET_CONTEXT).min(flat.len());
+    let mut snippet: String = flat[from..to].iter().collect();
+    if from > 0 {
+        snippet.insert(0, '…');
+    }
+    if to < flat.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(id: &str, days_old: i64, starred: bool, now: DateTime<Utc>) -> (SessionMeta, u64) {
+        let meta = SessionMeta {
+            id: id.to_string(),
+            title: id.to_string(),
+            model: None,
+            updated: now - chrono::Duration::days(days_old),
+            message_count: 2,
+            starred,
+            tags: vec![],
+            notes: None,
+            branch_of: None,
+            file: None,
+        };
+        (meta, 1024 * 1024)
+    }
+
+    #[test]
+    fn prune_plan_removes_oldest_unstarred_first() {
+        let now = Utc::now();
+        let sessions = vec![meta("a", 0, false, now), meta("b", 5, false, now), meta("c", 10, true, now), meta("d", 20, false, now)];
+        let ids = |plan: Vec<SessionMeta>| plan.into_iter().map(|m| m.id).collect::<Vec<_>>();
+
+        let by_count = RetentionConfig { max_sessions: Some(2), ..Default::default() };
+        assert_eq!(ids(prune_plan(&sessions, &by_count, "a", now)), vec!["d", "b"]);
+
+        let by_age = RetentionConfig { max_age_days: Some(7), ..Default::default() };
+        assert_eq!(ids(prune_plan(&sessions, &by_age, "a", now)), vec!["d"]);
+
+        let by_size = RetentionConfig { max_total_mb: Some(3), ..Default::default() };
+        assert_eq!(ids(prune_plan(&sessions, &by_size, "a", now)), vec!["d"]);
+
+        assert!(prune_plan(&sessions, &RetentionConfig::default(), "a", now).is_empty());
+    }
+
+    #[test]
+    fn tags_are_normalized_added_and_removed() {
+        let mut tags = vec!["incident".to_string()];
+        edit_tags(&mut tags, "#K8s homelab #incident").unwrap();
+        assert_eq!(format_tags(&tags), "#incident #k8s #homelab");
+        edit_tags(&mut tags, "-#incident").unwrap();
+        assert_eq!(tags, vec!["k8s", "homelab"]);
+        assert_eq!(edit_tags(&mut tags, "ok #bad!"), Err("#bad!".to_string()));
+        assert_eq!(tags, vec!["k8s", "homelab"]);
+    }
+
+    #[test]
+    fn notes_are_due_once_answered_and_again_after_growing() {
+        let mut session = Session { id: "a".into(), title: None, starred: false, tags: vec![], notes: None, model: None, messages: vec![ChatMessage::user("hi")], system_prompt: None, enabled_tools: None, options: Default::default(), lucius_context: None, lucius_profile: None, branch_of: None };
+        assert!(!session.needs_notes());
+        session.messages.push(ChatMessage::assistant("hello"));
+        assert!(session.needs_notes());
+        session.notes = Some(SessionNotes { text: "Greeted.".into(), messages: 2 });
+        assert!(!session.needs_notes());
+        session.messages.push(ChatMessage::user("bye"));
+        assert!(session.needs_notes());
+    }
+
+    #[test]
+    fn legacy_timestamps_move_onto_their_messages() {
+        let json = r#"{"id": "a", "messages": ["You: disk full?", "Lucius: Almost.", "You: and now?"],
+            "timestamps": ["2024-05-01T10:00:00Z", "2024-05-01T10:00:05Z", "2024-05-01T10:01:00Z"]}"#;
+        let mut session: Session = serde_json::from_str(json).unwrap();
+        let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(session.messages[1].timestamp, at("2024-05-01T10:00:05Z"));
+
+        // Removing a message takes its time along, and nothing is kept alongside
+        session.messages.remove(0);
+        assert_eq!(session.messages.iter().map(|msg| msg.timestamp).collect::<Vec<_>>(), [at("2024-05-01T10:00:05Z"), at("2024-05-01T10:01:00Z")]);
+        let saved = serde_json::to_value(&session).unwrap();
+        assert!(saved.get("timestamps").is_none());
+        let reloaded: Session = serde_json::from_value(saved).unwrap();
+        assert_eq!(reloaded.messages[0].timestamp, at("2024-05-01T10:00:05Z"));
+    }
+
+    #[test]
+    fn slugs_keep_words_only() {
+        assert_eq!(slug("Restart nginx on web-1!"), "restart-nginx-on-web-1");
+        assert_eq!(slug("  ¿Qué pasó?  "), "qué-pasó");
+        assert_eq!(slug("***"), "");
+
+        let mut session = Session { id: "3f2a1b9c-0000".into(), title: None, starred: false, tags: vec![], notes: None, model: None, messages: vec![], system_prompt: None, enabled_tools: None, options: Default::default(), lucius_context: None, lucius_profile: None, branch_of: None };
+        assert_eq!(session.file_name(), "3f2a1b9c-0000.json");
+        session.title = Some("Restart nginx".into());
+        assert_eq!(session.file_name(), "restart-nginx-3f2a1b9c.json");
+    }
+
+    #[test]
+    fn snippet_keeps_context_around_the_match() {
+        let text = "You: the nginx container keeps restarting\nafter the last deploy, can you check its logs and the compose file?";
+        let found = snippet(text, "compose").unwrap();
+        assert!(found.starts_with('…'));
+        assert!(found.contains("the compose file?"));
+        assert!(!found.contains('\n'));
+        assert_eq!(snippet("You: Redis is DOWN", "down").as_deref(), Some("You: Redis is DOWN"));
+        assert_eq!(snippet("You: all good", "redis"), None);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use lucius::mcp::PayloadKey;
+
+use crate::audit;
+use crate::templates::ConversationTemplate;
+
+/// Prefix of the per-user keys, next to the `mcp::` ones of the task queue.
+const KEY_PREFIX: &str = "lucius::user::";
+/// How long shared data outlives the last machine that wrote it: 30 days.
+const SHARED_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+/// Marks an entry sealed with the `[encryption]` key.
+const SEALED_PREFIX: &str = "sealed:";
+
+/// The `[sync]` section: share prompt history and templates between
+/// machines through the MCP Redis server.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SyncConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Namespace of the synced data. Defaults to the login name, so set it
+    /// when that differs between machines.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Prompts kept in the shared history.
+    #[serde(default = "default_max_prompts")]
+    pub max_prompts: usize,
+}
+
+fn default_max_prompts() -> usize {
+    1000
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self { enabled: false, user: None, max_prompts: default_max_prompts() }
+    }
+}
+
+impl SyncConfig {
+    pub fn user(&self) -> String {
+        self.user.clone().unwrap_or_else(audit::current_user)
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}::{}", KEY_PREFIX, self.user(), name)
+    }
+}
+
+/// The shared prompt history, oldest first like the local one. A prompt
+/// sent again counts where it was last sent.
+pub async fn load_prompts(conn: &mut MultiplexedConnection, config: &SyncConfig, key: Option<&PayloadKey>) -> Result<Vec<String>, String> {
+    let list = config.key("prompts");
+    let newest_first: Vec<String> = conn
+        .lrange(&list, 0, config.max_prompts as isize - 1)
+        .await
+        .map_err(|e| format!("Failed to read the shared prompt history: {}", e))?;
+    Ok(readable_prompts(&newest_first, key, &list))
+}
+
+/// The prompts of the `list` entries, newest first, that can be read with
+/// `key`, oldest first and each only once.
+fn readable_prompts(newest_first: &[String], key: Option<&PayloadKey>, list: &str) -> Vec<String> {
+    let mut prompts: Vec<String> = Vec::new();
+    for prompt in newest_first.iter().filter_map(|entry| open(key, list, entry)) {
+        if !prompts.contains(&prompt) {
+            prompts.push(prompt);
+        }
+    }
+    prompts.reverse();
+    prompts
+}
+
+/// Adds a prompt to the shared history and drops the oldest ones beyond
+/// `max_prompts`. An earlier copy is removed when it can be matched; sealed
+/// copies never compare equal, so `load_prompts` skips those instead.
+pub async fn push_prompt(conn: &mut MultiplexedConnection, config: &SyncConfig, key: Option<&PayloadKey>, prompt: &str) -> Result<(), String> {
+    let list = config.key("prompts");
+    let entry = seal(key, &list, prompt)?;
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    if key.is_none() {
+        pipe.lrem(&list, 0, prompt).ignore();
+    }
+    let pushed: redis::RedisResult<()> = pipe
+        .lpush(&list, entry)
+        .ignore()
+        .ltrim(&list, 0, config.max_prompts as isize - 1)
+        .ignore()
+        .expire(&list, SHARED_TTL_SECS)
+        .ignore()
+        .query_async(conn)
+        .await;
+    pushed.map_err(|e| format!("Failed to share the prompt: {}", e))
+}
+
+/// The templates the other machines share, by the name of the machine.
+/// Each publishes its own list, so one deleted there disappears here too.
+pub async fn load_templates(conn: &mut MultiplexedConnection, config: &SyncConfig, key: Option<&PayloadKey>) -> Result<Vec<ConversationTemplate>, String> {
+    let library = config.key("template_library");
+    let machines: HashMap<String, String> = conn
+        .hgetall(&library)
+        .await
+        .map_err(|e| format!("Failed to read the shared templates: {}", e))?;
+    let mut machines: Vec<(String, String)> = machines.into_iter().filter(|(machine, _)| *machine != hostname()).collect();
+    machines.sort();
+    let mut templates = Vec::new();
+    for (machine, entry) in machines {
+        let Some(json) = open(key, &library, &entry) else {
+            continue;
+        };
+        match serde_json::from_str::<Vec<ConversationTemplate>>(&json) {
+            Ok(shared) => templates.extend(shared),
+            Err(e) => log::warn!("Invalid templates shared by {}: {}", machine, e),
+        }
+    }
+    Ok(templates)
+}
+
+/// Publishes this machine's templates, replacing what it shared before.
+pub async fn save_templates(conn: &mut MultiplexedConnection, config: &SyncConfig, key: Option<&PayloadKey>, templates: &[ConversationTemplate]) -> Result<(), String> {
+    let library = config.key("template_library");
+    let json = serde_json::to_string(templates).map_err(|e| e.to_string())?;
+    let saved: redis::RedisResult<()> = redis::pipe()
+        .atomic()
+        .hset(&library, hostname(), seal(key, &library, &json)?)
+        .ignore()
+        .expire(&library, SHARED_TTL_SECS)
+        .ignore()
+        .query_async(conn)
+        .await;
+    saved.map_err(|e| format!("Failed to share the templates: {}", e))
+}
+
+/// `text` as stored under `name`: sealed when there is a key.
+fn seal(key: Option<&PayloadKey>, name: &str, text: &str) -> Result<String, String> {
+    match key {
+        Some(key) => Ok(format!("{}{}", SEALED_PREFIX, key.seal(text, name)?)),
+        None => Ok(text.to_string()),
+    }
+}
+
+/// The text of an entry stored under `name`. With a key only sealed entries
+/// are taken, as anyone who can write to Redis could add a plain one;
+/// without one, sealed entries cannot be read.
+fn open(key: Option<&PayloadKey>, name: &str, entry: &str) -> Option<String> {
+    match (entry.strip_prefix(SEALED_PREFIX), key) {
+        (Some(sealed), Some(key)) => key.open(sealed, name).map_err(|e| log::warn!("Skipping a shared entry of {}: {}", name, e)).ok(),
+        (None, None) => Some(entry.to_string()),
+        _ => None,
+    }
+}
+
+/// The name this machine shares its templates under.
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The local templates followed by the shared ones this machine does not
+/// have. A local template wins over a shared one of the same name.
+pub fn merge_templates(local: &[ConversationTemplate], shared: Vec<ConversationTemplate>) -> Vec<ConversationTemplate> {
+    let mut merged = local.to_vec();
+    for template in shared {
+        if !merged.iter().any(|known| known.name == template.name) {
+            merged.push(template);
+        }
+    }
+    merged
+}
+
+/// Shared prompts followed by the ones sent here before they arrived,
+/// without repeating any.
+pub fn merge_prompts(shared: Vec<String>, local: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = shared.into_iter().filter(|prompt| !local.contains(prompt)).collect();
+    merged.extend(local.iter().cloned());
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str, first_message: &str) -> ConversationTemplate {
+        ConversationTemplate { name: name.to_string(), system_prompt: None, tools: None, first_message: Some(first_message.to_string()) }
+    }
+
+    #[test]
+    fn local_entries_win_and_shared_ones_are_added() {
+        let local = vec![template("Postgres tuning", "local")];
+        let shared = vec![template("Postgres tuning", "shared"), template("Nginx", "shared")];
+        let merged = merge_templates(&local, shared);
+        assert_eq!(merged, vec![template("Postgres tuning", "local"), template("Nginx", "shared")]);
+
+        let prompts = merge_prompts(vec!["df -h".into(), "uptime".into()], &["uptime".into(), "free -m".into()]);
+        assert_eq!(prompts, vec!["df -h", "uptime", "free -m"]);
+    }
+
+    #[test]
+    fn shared_entries_are_sealed_with_the_key() {
+        let key = PayloadKey::from_base64("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").unwrap();
+        let list = "lucius::user::alice::prompts";
+        let sealed = seal(Some(&key), list, "uptime").unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX) && !sealed.contains("uptime"));
+        assert_eq!(open(Some(&key), list, &sealed).as_deref(), Some("uptime"));
+        // Sealed for another user's list, unreadable without the key, or planted in the clear
+        assert_eq!(open(Some(&key), "lucius::user::bob::prompts", &sealed), None);
+        assert_eq!(open(None, list, &sealed), None);
+        assert_eq!(open(Some(&key), list, "uptime"), None);
+        assert_eq!(seal(None, list, "uptime").unwrap(), "uptime");
+
+        // Sealed copies differ, so a prompt sent again is only counted where it was last sent
+        let newest_first: Vec<String> = ["df -h", "uptime", "df -h"].iter().map(|prompt| seal(Some(&key), list, prompt).unwrap()).collect();
+        assert_eq!(readable_prompts(&newest_first, Some(&key), list), ["uptime", "df -h"]);
+    }
+}
@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+
+use crate::audit;
+use lucius::mcp::{TaskContext, ToolCall};
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const SYSLOG_SOCKET: &str = "/dev/log";
+/// `user.info`, as facility * 8 + severity.
+const SYSLOG_PRIORITY: u8 = 14;
+
+/// The `[syslog]` section: mirror prompts, tool approvals and executed
+/// commands to the system log for compliance. Model output is never sent.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SyslogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub target: SyslogTarget,
+    /// `SYSLOG_IDENTIFIER` in the journal, the tag in syslog lines.
+    #[serde(default = "default_identifier")]
+    pub identifier: String,
+}
+
+fn default_identifier() -> String {
+    "lucius".to_string()
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self { enabled: false, target: SyslogTarget::default(), identifier: default_identifier() }
+    }
+}
+
+/// Which socket records go to.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogTarget {
+    /// journald when it is running, syslog otherwise.
+    #[default]
+    Auto,
+    /// journald's native socket, keeping every field searchable.
+    Journald,
+    /// `/dev/log`, with the fields as `key="value"` pairs in the message.
+    Syslog,
+}
+
+/// One transcript record: what happened, and the fields that go with it.
+struct Record {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+/// Mirrors a prompt the user sent.
+pub fn prompt(config: &SyslogConfig, conversation_id: &str, text: &str) {
+    if !config.enabled {
+        return;
+    }
+    send(config, Record {
+        message: format!("prompt: {}", text),
+        fields: vec![
+            ("LUCIUS_EVENT", "prompt".to_string()),
+            ("LUCIUS_CONVERSATION", conversation_id.to_string()),
+            ("LUCIUS_USER", audit::current_user()),
+            ("LUCIUS_PROMPT", text.to_string()),
+        ],
+    });
+}
+
+/// Mirrors a tool call with its approval: declined and timed-out calls as
+/// approvals, submitted ones as commands with their task id.
+pub fn tool_call(config: &SyslogConfig, task_id: Option<&str>, tool_call: &ToolCall, context: &TaskContext) {
+    if !config.enabled {
+        return;
+    }
+    send(config, tool_call_record(task_id, tool_call, context));
+}
+
+fn tool_call_record(task_id: Option<&str>, tool_call: &ToolCall, context: &TaskContext) -> Record {
+    // Spelled as in audit.log, e.g. "timed_out"
+    let decision = context
+        .approval
+        .as_ref()
+        .and_then(|approval| serde_json::to_value(approval.decision).ok())
+        .and_then(|decision| decision.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let params = tool_call.params.to_string();
+    let event = if task_id.is_some() { "command" } else { "approval" };
+    let mut fields = vec![
+        ("LUCIUS_EVENT", event.to_string()),
+        ("LUCIUS_CONVERSATION", context.conversation_id.clone()),
+        ("LUCIUS_USER", context.user.clone()),
+        ("LUCIUS_TOOL", tool_call.tool.clone()),
+        ("LUCIUS_PARAMS", params.clone()),
+        ("LUCIUS_DECISION", decision.clone()),
+    ];
+    if let Some(approval) = &context.approval {
+        fields.push(("LUCIUS_APPROVAL_HASH", approval.hash.clone()));
+    }
+    if let Some(run_as) = &context.run_as {
+        fields.push(("LUCIUS_RUN_AS", run_as.clone()));
+    }
+    if let Some(task_id) = task_id {
+        fields.push(("LUCIUS_TASK_ID", task_id.to_string()));
+    }
+    Record { message: format!("{} {} ({}): {}", event, tool_call.tool, decision, params), fields }
+}
+
+#[cfg(unix)]
+fn send(config: &SyslogConfig, record: Record) {
+    use std::os::unix::net::UnixDatagram;
+    use std::path::Path;
+
+    let journald = match config.target {
+        SyslogTarget::Auto => Path::new(JOURNALD_SOCKET).exists(),
+        SyslogTarget::Journald => true,
+        SyslogTarget::Syslog => false,
+    };
+    let (socket, payload) = if journald {
+        (JOURNALD_SOCKET, journald_payload(&config.identifier, &record))
+    } else {
+        (SYSLOG_SOCKET, syslog_line(&config.identifier, &record).into_bytes())
+    };
+    let result = UnixDatagram::unbound().and_then(|datagram| datagram.send_to(&payload, socket));
+    if let Err(e) = result {
+        log::error!("Failed to write the transcript record to {}: {}", socket, e);
+    }
+}
+
+#[cfg(not(unix))]
+fn send(_config: &SyslogConfig, _record: Record) {
+    log::warn!("[syslog] is enabled, but there is no syslog or journald on this platform.");
+}
+
+/// journald's native datagram: one `KEY=value` line per field, or for values
+/// spanning lines the key, a newline, the little-endian length and the value.
+fn journald_payload(identifier: &str, record: &Record) -> Vec<u8> {
+    let mut payload = Vec::new();
+    let standard = [("MESSAGE", record.message.as_str()), ("PRIORITY", "6"), ("SYSLOG_IDENTIFIER", identifier)];
+    for (key, value) in standard.into_iter().chain(record.fields.iter().map(|(key, value)| (*key, value.as_str()))) {
+        payload.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            payload.push(b'\n');
+            payload.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            payload.push(b'=');
+        }
+        payload.extend_from_slice(value.as_bytes());
+        payload.push(b'\n');
+    }
+    payload
+}
+
+/// A BSD syslog line whose message carries the fields as `key="value"`
+/// pairs, with quotes and line breaks escaped so a record stays on one line.
+fn syslog_line(identifier: &str, record: &Record) -> String {
+    let fields: Vec<String> = record
+        .fields
+        .iter()
+        .map(|(key, value)| {
+            let key = key.trim_start_matches("LUCIUS_").to_lowercase();
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", key, value)
+        })
+        .collect();
+    format!(
+        "<{}>{} {}[{}]: {}",
+        SYSLOG_PRIORITY,
+        chrono::Local::now().format("%b %e %H:%M:%S"),
+        identifier,
+        std::process::id(),
+        fields.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_keep_their_fields_in_both_formats() {
+        let record = Record {
+            message: "prompt: why\nis it down".to_string(),
+            fields: vec![("LUCIUS_EVENT", "prompt".to_string()), ("LUCIUS_PROMPT", "why\nis \"it\" down".to_string())],
+        };
+        let payload = journald_payload("lucius", &record);
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&22u64.to_le_bytes());
+        expected.extend_from_slice(b"prompt: why\nis it down\nPRIORITY=6\nSYSLOG_IDENTIFIER=lucius\nLUCIUS_EVENT=prompt\nLUCIUS_PROMPT\n");
+        assert!(payload.starts_with(&expected));
+
+        let line = syslog_line("lucius", &record);
+        assert!(line.starts_with("<14>"));
+        assert!(line.ends_with(&format!("lucius[{}]: event=\"prompt\" prompt=\"why\\nis \\\"it\\\" down\"", std::process::id())));
+    }
+}
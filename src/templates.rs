@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::sync;
+
+/// A conversation starter: an extra system prompt, the tools the model may use,
+/// and a first message seeded into the input box.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ConversationTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Tool names the model may call. `None` leaves every tool enabled.
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+    #[serde(default)]
+    pub first_message: Option<String>,
+}
+
+/// The templates shipped with Lucius.
+pub fn builtin_templates() -> Vec<ConversationTemplate> {
+    vec![
+        ConversationTemplate {
+            name: "Incident triage".to_string(),
+            system_prompt: Some(
+                "You are assisting with a live production incident. Establish impact, timeline and recent changes first. \
+                 Propose read-only diagnostic commands before any remediation, and state the risk of every command you suggest."
+                    .to_string(),
+            ),
+            tools: Some(vec!["exec".to_string(), "remote_exec".to_string()]),
+            first_message: Some("We have an incident: ".to_string()),
+        },
+        ConversationTemplate {
+            name: "Dockerfile review".to_string(),
+            system_prompt: Some(
+                "Review Dockerfiles for image size, layer caching, pinned base images, non-root users, secrets baked into layers \
+                 and missing healthchecks. Answer with concrete diffs."
+                    .to_string(),
+            ),
+            tools: Some(vec![]),
+            first_message: Some("Please review this Dockerfile:\n".to_string()),
+        },
+        ConversationTemplate {
+            name: "K8s troubleshooting".to_string(),
+            system_prompt: Some(
+                "You are troubleshooting a Kubernetes workload. Work from kubectl get/describe/logs/events output, \
+                 explain the most likely cause, and give the next command to run."
+                    .to_string(),
+            ),
+            tools: Some(vec!["exec".to_string()]),
+            first_message: Some("A pod is failing: ".to_string()),
+        },
+    ]
+}
+
+/// Every template offered by the new-conversation picker: a blank conversation,
+/// the built-ins, then the user's own from `[[templates]]` in the config and
+/// the ones shared by other machines.
+pub fn all_templates(config: &Config, shared: &[ConversationTemplate]) -> Vec<ConversationTemplate> {
+    let blank = ConversationTemplate {
+        name: "Blank conversation".to_string(),
+        system_prompt: None,
+        tools: None,
+        first_message: None,
+    };
+    let mut templates = vec![blank];
+    templates.extend(builtin_templates());
+    templates.extend(sync::merge_templates(&config.templates, shared.to_vec()));
+    templates
+}
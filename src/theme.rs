@@ -0,0 +1,174 @@
+use std::str::FromStr;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+/// A built-in palette to start from.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// The `[theme]` section. `name` picks a built-in palette; each color set
+/// next to it overrides that palette's choice. Colors are names such as
+/// `"lightcyan"`, 256-color indexes such as `"208"`, or `"#rrggbb"`.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub name: ThemeName,
+    /// Borders of the conversation, input and settings boxes.
+    pub border: Option<String>,
+    /// Popup borders, focus markers, headings, the model name and the tab bar.
+    pub accent: Option<String>,
+    /// Stars, hint labels and search matches.
+    pub highlight: Option<String>,
+    pub user: Option<String>,
+    pub assistant: Option<String>,
+    /// Tool calls and tool results.
+    pub tool: Option<String>,
+    /// Errors, failed tool steps and the unread divider.
+    pub error: Option<String>,
+    /// Timestamps, separators, code fences and other secondary chrome.
+    pub muted: Option<String>,
+    /// Descriptions, snippets and quotes.
+    pub secondary: Option<String>,
+    pub code: Option<String>,
+    /// Status messages, "Connected" and passed verifications.
+    pub status_ok: Option<String>,
+    /// "Disconnected" and failed verifications.
+    pub status_error: Option<String>,
+    /// Background and text of the confirmation and file action popups.
+    pub popup_bg: Option<String>,
+    pub popup_fg: Option<String>,
+}
+
+/// The resolved colors the renderer draws with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub border: Color,
+    pub accent: Color,
+    pub highlight: Color,
+    pub user: Color,
+    pub assistant: Color,
+    pub tool: Color,
+    pub error: Color,
+    pub muted: Color,
+    pub secondary: Color,
+    pub code: Color,
+    pub status_ok: Color,
+    pub status_error: Color,
+    pub popup_bg: Color,
+    pub popup_fg: Color,
+}
+
+impl Theme {
+    /// The original look, for dark terminal backgrounds.
+    pub fn dark() -> Self {
+        Self {
+            border: Color::Reset,
+            accent: Color::LightCyan,
+            highlight: Color::Yellow,
+            user: Color::Reset,
+            assistant: Color::Reset,
+            tool: Color::Reset,
+            error: Color::LightRed,
+            muted: Color::DarkGray,
+            secondary: Color::Gray,
+            code: Color::LightYellow,
+            status_ok: Color::Green,
+            status_error: Color::Red,
+            popup_bg: Color::DarkGray,
+            popup_fg: Color::White,
+        }
+    }
+
+    /// Darker accents that stay readable on light backgrounds.
+    pub fn light() -> Self {
+        Self {
+            border: Color::DarkGray,
+            accent: Color::Blue,
+            highlight: Color::Magenta,
+            user: Color::Reset,
+            assistant: Color::Reset,
+            tool: Color::DarkGray,
+            error: Color::Red,
+            muted: Color::DarkGray,
+            secondary: Color::DarkGray,
+            code: Color::Magenta,
+            status_ok: Color::Green,
+            status_error: Color::Red,
+            popup_bg: Color::Gray,
+            popup_fg: Color::Black,
+        }
+    }
+
+    /// The palette named in `config` with its overrides applied. Colors that
+    /// do not parse are logged and keep the palette's choice.
+    pub fn from_config(config: &ThemeConfig) -> Self {
+        let mut theme = match config.name {
+            ThemeName::Dark => Self::dark(),
+            ThemeName::Light => Self::light(),
+        };
+        let overrides = [
+            ("border", &config.border, &mut theme.border),
+            ("accent", &config.accent, &mut theme.accent),
+            ("highlight", &config.highlight, &mut theme.highlight),
+            ("user", &config.user, &mut theme.user),
+            ("assistant", &config.assistant, &mut theme.assistant),
+            ("tool", &config.tool, &mut theme.tool),
+            ("error", &config.error, &mut theme.error),
+            ("muted", &config.muted, &mut theme.muted),
+            ("secondary", &config.secondary, &mut theme.secondary),
+            ("code", &config.code, &mut theme.code),
+            ("status_ok", &config.status_ok, &mut theme.status_ok),
+            ("status_error", &config.status_error, &mut theme.status_error),
+            ("popup_bg", &config.popup_bg, &mut theme.popup_bg),
+            ("popup_fg", &config.popup_fg, &mut theme.popup_fg),
+        ];
+        for (key, value, color) in overrides {
+            if let Some(parsed) = value.as_deref().and_then(|value| parse_color(key, value)) {
+                *color = parsed;
+            }
+        }
+        theme
+    }
+}
+
+/// The color `value` of the `[theme]` key `key` names, if it is one.
+fn parse_color(key: &str, value: &str) -> Option<Color> {
+    let parsed = Color::from_str(value.trim()).ok();
+    if parsed.is_none() {
+        log::warn!("Ignoring [theme] {} = {:?}: not a color name, 256-color index or #rrggbb", key, value);
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_apply_on_top_of_the_named_palette() {
+        let config = ThemeConfig {
+            name: ThemeName::Light,
+            accent: Some("#ff8800".to_string()),
+            code: Some("not a color".to_string()),
+            ..ThemeConfig::default()
+        };
+        let theme = Theme::from_config(&config);
+        assert_eq!(theme.accent, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.code, Theme::light().code);
+        assert_eq!(Theme::from_config(&ThemeConfig::default()), Theme::dark());
+    }
+
+    #[test]
+    fn colors_are_names_indexes_or_hex() {
+        assert_eq!(parse_color("accent", "lightcyan"), Some(Color::LightCyan));
+        assert_eq!(parse_color("accent", " 208 "), Some(Color::Indexed(208)));
+        assert_eq!(parse_color("accent", "#0a0B0c"), Some(Color::Rgb(10, 11, 12)));
+        assert_eq!(parse_color("accent", "light sky"), None);
+        assert_eq!(parse_color("accent", "#12345"), None);
+    }
+}
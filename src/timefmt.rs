@@ -0,0 +1,110 @@
+use std::fmt::Write;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The `[time]` section of the config file.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TimeConfig {
+    /// `strftime`-style format for absolute timestamps, in local time.
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// Show "2m ago" style timestamps until expanded.
+    #[serde(default = "default_relative")]
+    pub relative: bool,
+    /// Show timestamps and generation times after messages at launch;
+    /// Alt+T toggles them.
+    #[serde(default = "default_show")]
+    pub show: bool,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        Self {
+            format: default_format(),
+            relative: default_relative(),
+            show: default_show(),
+        }
+    }
+}
+
+fn default_format() -> String {
+    "%Y-%m-%d %H:%M:%S".to_string()
+}
+
+fn default_relative() -> bool {
+    true
+}
+
+fn default_show() -> bool {
+    true
+}
+
+/// Formats `ts` relative to `now`: "just now", "2m ago", "3h ago", "5d ago".
+pub fn relative(ts: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - ts).num_seconds().max(0);
+    match seconds {
+        0..=9 => "just now".to_string(),
+        10..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// Formats `ts` in the local timezone. Falls back to RFC 3339 when the
+/// configured format string is invalid instead of panicking mid-draw.
+pub fn absolute(ts: DateTime<Utc>, format: &str) -> String {
+    let local = ts.with_timezone(&Local);
+    let mut out = String::new();
+    if write!(out, "{}", local.format(format)).is_err() {
+        log::warn!("Invalid time format '{}', falling back to RFC 3339", format);
+        return local.to_rfc3339();
+    }
+    out
+}
+
+/// The timestamp shown next to messages and tasks. `expanded` forces the
+/// absolute form.
+pub fn display(ts: DateTime<Utc>, config: &TimeConfig, expanded: bool) -> String {
+    if config.relative && !expanded {
+        relative(ts, Utc::now())
+    } else {
+        absolute(ts, &config.format)
+    }
+}
+
+/// A generation time: "850ms", "4.2s" or "1m 05s".
+pub fn duration(ms: u64) -> String {
+    match ms {
+        0..=999 => format!("{}ms", ms),
+        1000..=59_999 => format!("{:.1}s", ms as f64 / 1000.0),
+        _ => format!("{}m {:02}s", ms / 60_000, ms % 60_000 / 1000),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_switch_units_at_a_second_and_a_minute() {
+        assert_eq!(duration(0), "0ms");
+        assert_eq!(duration(999), "999ms");
+        assert_eq!(duration(1000), "1.0s");
+        assert_eq!(duration(4_240), "4.2s");
+        assert_eq!(duration(59_999), "60.0s");
+        assert_eq!(duration(60_000), "1m 00s");
+        assert_eq!(duration(65_400), "1m 05s");
+        assert_eq!(duration(3_725_000), "62m 05s");
+    }
+
+    #[test]
+    fn relative_times_round_down_and_never_go_negative() {
+        let now = Utc::now();
+        assert_eq!(relative(now + chrono::Duration::seconds(30), now), "just now");
+        assert_eq!(relative(now - chrono::Duration::seconds(42), now), "42s ago");
+        assert_eq!(relative(now - chrono::Duration::seconds(150), now), "2m ago");
+        assert_eq!(relative(now - chrono::Duration::hours(5), now), "5h ago");
+        assert_eq!(relative(now - chrono::Duration::days(3), now), "3d ago");
+    }
+}
@@ -1,5 +1,9 @@
-use lucius::mcp::ToolCall;
+use lucius::mcp::{ToolCall, ToolSchema};
 use crate::llm::Model;
+use crate::hints::Hint;
+use crate::templates::ConversationTemplate;
+use crate::forms::ToolForm;
+use crate::session::{SearchHit, Session, SessionMeta};
 
 #[derive(Clone)]
 pub enum AppMode {
@@ -7,17 +11,86 @@ pub enum AppMode {
     Settings,
     Help,
     Confirmation(ConfirmationModal),
+    /// Hint mode: each URL or file path is labelled with a letter that selects it.
+    Hints(Vec<Hint>),
+    /// Quick actions for a file path picked in hint mode.
+    PathActions(String),
+    /// The formats the focused message can be copied in, by its index.
+    CopyMenu(usize),
+    /// New-conversation picker listing the available templates.
+    TemplatePicker(Vec<ConversationTemplate>),
+    /// Tool palette listing every tool the workers registered, favorites first.
+    ToolPalette(Vec<ToolSchema>),
+    /// What the next request will carry, with token estimates and toggles.
+    ContextPanel,
+    /// Side panel of the attached files and retrieved excerpts, with sizes.
+    AttachedPanel,
+    /// Saved sessions, most recent first, to open, rename or delete.
+    SessionBrowser(Vec<SessionMeta>),
+    /// Search through every saved session, with the matches for the query.
+    Search(Vec<SearchHit>),
+    /// Editor for the conversation's own system prompt, under LUCIUS.md.
+    PromptEditor,
+    /// The old and new text of a LUCIUS.md edited mid-conversation, asking
+    /// where the edit applies.
+    ContextDiff,
 }
 
 // --- Enums for Background Task Communication ---
 
+/// Actions waiting for the background worker. It takes each one quickly, as
+/// answers and tool tasks run in tasks of their own, so this only fills up
+/// when the worker is stuck.
+pub const ACTION_QUEUE: usize = 32;
+/// Updates waiting for the UI, which drains them all on every wake-up. A full
+/// queue holds back the stream readers rather than losing chunks.
+pub const UPDATE_QUEUE: usize = 256;
+
 /// Actions that the UI thread can send to the background worker task.
 #[derive(Clone)]
 pub enum Action {
-    /// Trigger a refresh of the Ollama models and connection status.
-    RefreshModelsAndStatus,
+    /// Trigger a refresh of the Ollama models and connection status. The
+    /// scheduled one, run every minute, only reports what changed.
+    RefreshModelsAndStatus { scheduled: bool },
     /// Send a new chat message to the LLM.
     SendMessage(String),
+    /// Run a tool directly, without asking the LLM first.
+    RunTool(ToolCall),
+    /// Open the confirmation form for a tool picked from the palette, then run it.
+    ConfirmTool(ToolCall),
+    /// Show the tasks in the MCP dead-letter queue.
+    ListDeadLetters,
+    /// Requeue one dead-lettered task by its 0-based index, or all of them.
+    RequeueDeadLetters(Option<usize>),
+    /// Ask the workers for fresh inventory snapshots and load them as context.
+    RefreshInventory,
+    /// Embed the files of the current directory for retrieval (`/index`).
+    IndexProject,
+    /// Load a newly selected model with a tiny generation, so the first real
+    /// prompt does not wait for it.
+    WarmUpModel(String),
+    /// Download a model with Ollama's `/api/pull`.
+    PullModel(String),
+    /// Remove a model from the Ollama server.
+    DeleteModel(String),
+    /// Fetch a model's details from `/api/show` for the info popup.
+    ShowModelInfo(String),
+    /// Free the memory a loaded Ollama model takes.
+    UnloadModel(String),
+    /// Have the model write notes for a conversation that was closed.
+    WriteNotes(Box<Session>),
+    /// Load the prompt history and templates shared through Redis (`[sync]`).
+    SyncHistory,
+    /// Add a sent prompt to the shared history.
+    SharePrompt(String),
+}
+
+impl Action {
+    /// Refreshes that run again on their own later, so skipping one under
+    /// load loses nothing the user asked for.
+    pub fn is_refresh(&self) -> bool {
+        matches!(self, Action::RefreshModelsAndStatus { scheduled: true })
+    }
 }
 
 /// Updates that the background worker task can send back to the UI thread.
@@ -26,8 +99,14 @@ pub enum Update {
     Models(Vec<Model>),
     /// The connection status of the Ollama server has been checked.
     Status(bool),
-    /// A chunk of the LLM's response has been received.
-    LLMChunk(String),
+    /// A chunk of the LLM's response has been received for the conversation
+    /// with this id, from the generation with this number.
+    LLMChunk(String, u64, String),
+    /// The generation is over and its answer, if any, is in the
+    /// conversation's history, so its streamed text can be dropped.
+    LLMDone(String, u64),
+    /// The prompt history shared by other machines, oldest first.
+    PromptHistory(Vec<String>),
 }
 
 
@@ -38,6 +117,17 @@ impl PartialEq for AppMode {
             (AppMode::Settings, AppMode::Settings) => true,
             (AppMode::Help, AppMode::Help) => true,
             (AppMode::Confirmation(a), AppMode::Confirmation(b)) => a == b,
+            (AppMode::Hints(a), AppMode::Hints(b)) => a == b,
+            (AppMode::PathActions(a), AppMode::PathActions(b)) => a == b,
+            (AppMode::CopyMenu(a), AppMode::CopyMenu(b)) => a == b,
+            (AppMode::TemplatePicker(a), AppMode::TemplatePicker(b)) => a == b,
+            (AppMode::ToolPalette(a), AppMode::ToolPalette(b)) => a == b,
+            (AppMode::ContextPanel, AppMode::ContextPanel) => true,
+            (AppMode::AttachedPanel, AppMode::AttachedPanel) => true,
+            (AppMode::SessionBrowser(a), AppMode::SessionBrowser(b)) => a == b,
+            (AppMode::Search(a), AppMode::Search(b)) => a == b,
+            (AppMode::PromptEditor, AppMode::PromptEditor) => true,
+            (AppMode::ContextDiff, AppMode::ContextDiff) => true,
             _ => false,
         }
     }
@@ -45,25 +135,39 @@ impl PartialEq for AppMode {
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum Focus {
+    Provider,
     Url,
+    /// Ollama's `keep_alive`, cycled with Left/Right.
+    KeepAlive,
+    ApiKey,
+    ModelName,
     McpUrl,
+    /// The `[options]` model parameters.
+    Parameters,
     Models,
 }
 
 pub enum ConfirmationModal {
     ExecuteTool {
         tool_call: ToolCall,
-        confirm_tx: Option<tokio::sync::oneshot::Sender<bool>>,
+        /// Editable view of the params when the tool has a schema.
+        form: Option<ToolForm>,
+        /// Receives the params to run with, or `None` if the call was declined.
+        confirm_tx: Option<tokio::sync::oneshot::Sender<Option<serde_json::Value>>>,
+        /// When the call is declined if still unanswered.
+        deadline: Option<std::time::Instant>,
     },
 }
 
 impl Clone for ConfirmationModal {
     fn clone(&self) -> Self {
         match self {
-            ConfirmationModal::ExecuteTool { tool_call, .. } => {
+            ConfirmationModal::ExecuteTool { tool_call, form, deadline, .. } => {
                 ConfirmationModal::ExecuteTool {
                     tool_call: tool_call.clone(),
+                    form: form.clone(),
                     confirm_tx: None, // Can't clone the sender
+                    deadline: *deadline,
                 }
             }
         }
@@ -78,24 +182,6 @@ impl PartialEq for ConfirmationModal {
     }
 }
 
-pub const HELP_MESSAGE: &str = r#"
---- Help ---
-Ctrl+H: Toggle Help
-Ctrl+S: Toggle Settings
-Ctrl+Q: Quit
-Ctrl+L: Clear Chat
-Ctrl+Y: Yank (Copy) Last Response
-Ctrl+T: MCP Status
-Esc: Interrupt current stream (if any)
-Mouse Scroll: Scroll chat history
-Shift + Mouse Drag: Select text for copying
-Enter: Send message (Chat mode), Select model (Settings mode)
-Tab: Switch focus (Settings mode)
-Ctrl+R: Refresh models (Settings mode)
-Esc: Go to Chat (Settings mode)
------------------
-"#;
-
 pub const ASCII_ART: &str = r#"
  _               _              ____ _     ___ 
 | |   _   _  ___(_)_   _ ___   / ___| |   |_ _|
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+/// Token cap for brief answers; generous enough that a short answer is not
+/// cut off mid-sentence.
+const BRIEF_MAX_TOKENS: u32 = 400;
+
+/// How long answers should be: a line in the system prompt plus a cap on
+/// generated tokens. Set with `/verbosity` or cycled with `Alt+V`.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    Brief,
+    #[default]
+    Normal,
+    Detailed,
+}
+
+impl Verbosity {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "brief" => Some(Verbosity::Brief),
+            "normal" => Some(Verbosity::Normal),
+            "detailed" => Some(Verbosity::Detailed),
+            _ => None,
+        }
+    }
+
+    /// The next preset, for cycling with `Alt+V`.
+    pub fn next(self) -> Self {
+        match self {
+            Verbosity::Brief => Verbosity::Normal,
+            Verbosity::Normal => Verbosity::Detailed,
+            Verbosity::Detailed => Verbosity::Brief,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Verbosity::Brief => "brief",
+            Verbosity::Normal => "normal",
+            Verbosity::Detailed => "detailed",
+        }
+    }
+
+    /// The length instruction added to the system prompt; none for `Normal`,
+    /// which leaves LUCIUS.md in charge.
+    pub fn instruction(self) -> Option<String> {
+        match self {
+            Verbosity::Brief => Some(
+                "Answer as briefly as possible: the command, value or fact asked for, with at most one or two \
+                 sentences of explanation. No preamble, no summary."
+                    .to_string(),
+            ),
+            Verbosity::Normal => None,
+            Verbosity::Detailed => Some(
+                "Answer in depth: explain the reasoning and trade-offs, give complete examples, and mention \
+                 pitfalls and alternatives worth knowing."
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Ollama's `num_predict` for chat requests: a tight cap for brief
+    /// answers, `-1` (no limit) for detailed ones, and the model's own
+    /// default otherwise.
+    pub fn num_predict(self) -> Option<i32> {
+        match self {
+            Verbosity::Brief => Some(BRIEF_MAX_TOKENS as i32),
+            Verbosity::Normal => None,
+            Verbosity::Detailed => Some(-1),
+        }
+    }
+
+    /// Caps a provider's configured `max_tokens` for this preset.
+    pub fn max_tokens(self, configured: u32) -> u32 {
+        match self {
+            Verbosity::Brief => configured.min(BRIEF_MAX_TOKENS),
+            Verbosity::Normal | Verbosity::Detailed => configured,
+        }
+    }
+}
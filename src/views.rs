@@ -0,0 +1,66 @@
+pub mod chat;
+pub mod help;
+pub mod modal;
+pub mod settings;
+
+use ratatui::{
+    prelude::{Frame, Layout, Direction, Constraint},
+    layout::Rect,
+};
+
+use crate::app::{App, SharedState};
+use crate::theme::Theme;
+use crate::ui::AppMode;
+
+use chat::ChatView;
+use help::HelpView;
+use modal::ModalView;
+use settings::SettingsView;
+
+pub(crate) const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// A screen of the TUI. `update` settles the view state that depends on the
+/// frame, such as the chat's scroll offset, and `draw` then renders it.
+pub trait View {
+    fn update(&mut self, _app: &mut App, _state: &SharedState, _area: Rect) {}
+
+    /// Takes the app mutably only for widget state: list selections and the
+    /// blocks of text editors.
+    fn draw(&mut self, f: &mut Frame, app: &mut App, state: &SharedState);
+}
+
+pub fn draw_ui(f: &mut Frame, app: &mut App, state: &SharedState) {
+    let theme = Theme::from_config(&state.config.theme);
+
+    // Render based on the current mode from the shared state.
+    let mut view: Box<dyn View + '_> = match &state.mode {
+        AppMode::Chat => Box::new(ChatView::new(theme, None)),
+        AppMode::Hints(found) => Box::new(ChatView::new(theme, Some(found.as_slice()))),
+        AppMode::Settings => Box::new(SettingsView::new(theme)),
+        AppMode::Help => Box::new(HelpView::new(theme)),
+        _ => Box::new(ModalView::new(theme)),
+    };
+    view.update(app, state, f.area());
+    view.draw(f, app, state);
+}
+
+/// Returns a `width` x `height` rectangle centered in `area`.
+pub(crate) fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(height),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(width),
+            Constraint::Min(0),
+        ])
+        .split(popup_layout[1])[1]
+}
@@ -0,0 +1,981 @@
+use std::collections::{HashMap, HashSet};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use ratatui::{
+    prelude::{Frame, Layout, Direction, Constraint, Style},
+    widgets::{Block, Borders, Paragraph, Padding, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    text::{Line, Span, Text},
+    layout::{Alignment, Rect},
+    style::{Color, Modifier},
+};
+use lucius::mcp;
+
+use crate::app::{self, App, PendingTask, SharedState};
+use crate::budget::AttachedSource;
+use crate::hints::{self, Hint, HintKind};
+use crate::i18n::{t, tf};
+use crate::markdown;
+use crate::mouse;
+use crate::message::{ChatMessage, Role};
+use crate::llm::ResponseDebug;
+use crate::timefmt;
+use crate::theme::Theme;
+use crate::verbosity::Verbosity;
+use crate::ui::ASCII_ART;
+
+use super::{View, SPINNER_FRAMES};
+
+/// The conversation with the status line, input box and bottom bar. In hint
+/// mode it also labels the links and paths that can be picked.
+pub struct ChatView<'a> {
+    theme: Theme,
+    hints: Option<&'a [Hint]>,
+    /// Banner, conversation, status line, attachment chips, input and
+    /// bottom bar, as laid out by `update`.
+    chunks: Vec<Rect>,
+    attached: Vec<String>,
+    conversation: Text<'static>,
+    /// Rows of the conversation after wrapping, for the scrollbar.
+    rows: usize,
+}
+
+/// The conversation's lines as last wrapped, each with its rows, so only
+/// the lines that changed since the last frame are wrapped again.
+#[derive(Default)]
+pub struct WrapCache {
+    width: usize,
+    lines: Vec<(Line<'static>, Vec<Line<'static>>)>,
+}
+
+impl WrapCache {
+    /// The rows of each of `lines` wrapped to `width` columns.
+    fn wrap(&mut self, lines: Vec<Line<'static>>, width: usize) -> Vec<Vec<Line<'static>>> {
+        if width != self.width {
+            self.width = width;
+            self.lines.clear();
+        }
+        self.lines.truncate(lines.len());
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, line)| {
+                match self.lines.get(index) {
+                    Some((cached, rows)) if *cached == line => return rows.clone(),
+                    _ => {}
+                }
+                let rows = wrap_line(line.clone(), width);
+                if index < self.lines.len() {
+                    self.lines[index] = (line, rows.clone());
+                } else {
+                    self.lines.push((line, rows.clone()));
+                }
+                rows
+            })
+            .collect()
+    }
+}
+
+/// The turn each message belongs to and the tool steps of each turn, worked
+/// out again only when the history changes, for folding turns.
+#[derive(Default)]
+pub struct TurnCache {
+    /// `history_revision` and length of the history last worked out.
+    history: (u64, usize),
+    /// Index of the user message that starts each message's turn.
+    starts: Vec<Option<usize>>,
+    /// The tool calls, results and worker errors of each turn, by its start.
+    steps: HashMap<usize, Vec<usize>>,
+}
+
+impl TurnCache {
+    fn update(&mut self, history: &[ChatMessage], revision: u64) {
+        if self.history == (revision, history.len()) {
+            return;
+        }
+        self.history = (revision, history.len());
+        self.starts.clear();
+        self.steps.clear();
+        let mut start = None;
+        for (index, msg) in history.iter().enumerate() {
+            if msg.role == Role::User {
+                start = Some(index);
+            }
+            self.starts.push(start);
+            if let Some(start) = start.filter(|_| msg.is_tool_step()) {
+                self.steps.entry(start).or_default().push(index);
+            }
+        }
+    }
+
+    /// The tool steps folded away with message `index`, if it is one.
+    fn folded_steps(&self, index: usize, folded: &HashSet<usize>) -> Option<&[usize]> {
+        let start = self.starts.get(index).copied().flatten().filter(|start| folded.contains(start))?;
+        self.steps.get(&start).filter(|steps| steps.binary_search(&index).is_ok()).map(Vec::as_slice)
+    }
+}
+
+/// The conversation laid out as lines, before it is scrolled.
+struct Conversation {
+    text: Text<'static>,
+    /// First line of each message.
+    message_starts: Vec<usize>,
+    focused_start: Option<usize>,
+}
+
+impl<'a> ChatView<'a> {
+    pub fn new(theme: Theme, hints: Option<&'a [Hint]>) -> Self {
+        Self { theme, hints, chunks: vec![], attached: vec![], conversation: Text::default(), rows: 0 }
+    }
+}
+
+impl View for ChatView<'_> {
+    fn update(&mut self, app: &mut App, state: &SharedState, area: Rect) {
+        // The compact layout drops the banner, borders and bottom bar
+        let compact = state.config.layout.compact;
+        self.attached = app.attached_files().iter().map(|(path, _)| path.clone()).collect();
+        self.chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(if compact { 0 } else { 7 }), // For ASCII Art
+                Constraint::Min(0),                              // For Conversation
+                Constraint::Length(1),                           // For Status Line
+                Constraint::Length(u16::from(!self.attached.is_empty())), // For Attachment Chips
+                Constraint::Length(if compact { 2 } else { 3 }), // For Input
+                Constraint::Length(if compact { 0 } else { 1 }), // For Bottom Bar
+            ])
+            .split(area)
+            .to_vec();
+        let conversation_area = self.chunks[1];
+        let inner = conversation_block(app, state, &self.theme).inner(conversation_area);
+        app.conversation_area = inner;
+
+        // The compact layout has no border for the scrollbar, so it takes the last column
+        let text_width = if compact { inner.width.saturating_sub(1) } else { inner.width } as usize;
+        app.turns.update(&state.chat_history, state.history_revision);
+        let Conversation { text, message_starts, focused_start } = conversation(app, state, text_width, self.hints, &self.theme);
+        // Wrapped here rather than by the paragraph, so every screen row maps
+        // back to the text drawn on it
+        let mut first_rows = Vec::with_capacity(text.lines.len());
+        let mut rows = Vec::new();
+        let mut continues = Vec::new();
+        for wrapped in app.wrap_cache.wrap(text.lines, text_width) {
+            first_rows.push(rows.len());
+            continues.extend((0..wrapped.len()).map(|row| row > 0));
+            rows.extend(wrapped);
+        }
+        let row_of = |line: usize| first_rows.get(line).copied().unwrap_or(rows.len());
+        let message_starts: Vec<usize> = message_starts.into_iter().map(row_of).collect();
+        let mut focused_start = focused_start.map(row_of);
+        // The Ctrl+F search matches the rows before the divider goes in, and
+        // a match jumped to is scrolled a third of the way down the pane
+        if let Some(search) = &mut app.chat_search {
+            let texts: Vec<mouse::Row> = rows.iter().zip(&continues).map(|(line, &continues)| mouse::Row { text: line_text(line), continues }).collect();
+            search.find(&texts);
+            if std::mem::take(&mut search.jump) {
+                if let Some(&(row, _, _)) = search.hits.get(search.current).and_then(|pieces| pieces.first()) {
+                    focused_start = Some(row.saturating_sub(inner.height as usize / 3));
+                }
+            }
+        }
+        let divider = app.settle_scroll(rows.len(), inner.height as usize, focused_start, &message_starts, state.chat_history.len(), state.config.scroll.follow_stream);
+        if let Some(row) = divider {
+            rows.insert(row, Line::styled(t("chat.new_divider"), Style::default().fg(self.theme.error)).centered());
+            continues.insert(row, false);
+            if let Some(search) = &mut app.chat_search {
+                search.hits.iter_mut().flatten().filter(|piece| piece.0 >= row).for_each(|piece| piece.0 += 1);
+            }
+        }
+        app.conversation_rows = rows
+            .iter()
+            .zip(continues)
+            .map(|(line, continues)| mouse::Row { text: line_text(line), continues })
+            .collect();
+        if let Some(search) = &app.chat_search {
+            for (index, pieces) in search.hits.iter().enumerate() {
+                let background = if index == search.current { self.theme.accent } else { self.theme.highlight };
+                for &(row, from, to) in pieces {
+                    highlight(&mut rows[row], from, to, Style::default().fg(Color::Black).bg(background));
+                }
+            }
+        }
+        if let Some((anchor, head)) = app.selection_range {
+            for (row, from, to) in mouse::selected_columns(&app.conversation_rows, anchor, head) {
+                highlight(&mut rows[row], from, to, Style::default().add_modifier(Modifier::REVERSED));
+            }
+        }
+        self.rows = rows.len();
+        self.conversation = Text::from(rows);
+    }
+
+    fn draw(&mut self, f: &mut Frame, app: &mut App, state: &SharedState) {
+        let area = f.area();
+        let theme = &self.theme;
+        let chunks = &self.chunks;
+        let attached = &self.attached;
+        let compact = state.config.layout.compact;
+
+        // ASCII Art
+        if !compact {
+            let ascii_art = Paragraph::new(ASCII_ART).alignment(Alignment::Center);
+            f.render_widget(ascii_art, chunks[0]);
+        }
+
+        // Already wrapped to the width by `update`
+        let history = Paragraph::new(std::mem::take(&mut self.conversation))
+            .scroll((app.scroll, 0))
+            .block(conversation_block(app, state, theme));
+        f.render_widget(history, chunks[1]);
+
+        // A scrollbar on the right edge once the conversation outgrows the pane
+        let height = app.conversation_area.height as usize;
+        if self.rows > height {
+            let mut scrollbar_state = ScrollbarState::new(self.rows - height).position(app.scroll as usize).viewport_content_length(height);
+            let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .track_style(Style::default().fg(theme.muted))
+                .thumb_style(Style::default().fg(theme.accent));
+            let track = Rect { y: app.conversation_area.y, height: app.conversation_area.height, ..chunks[1] };
+            f.render_stateful_widget(scrollbar, track, &mut scrollbar_state);
+        }
+
+        // Status line from shared state, or the search bar over it
+        let status_text = if let Some(search) = &app.chat_search {
+            let position = if !search.hits.is_empty() {
+                format!("{}/{}", search.current + 1, search.hits.len())
+            } else if search.query.is_empty() {
+                String::new()
+            } else {
+                t("chat.search_none").to_string()
+            };
+            let query = if search.editing { format!("{}▏", search.query) } else { search.query.clone() };
+            let keys = t(if search.editing { "chat.search_keys_editing" } else { "chat.search_keys" });
+            tf("chat.search", &[("query", query.as_str()), ("position", position.as_str()), ("keys", keys)])
+        } else if let Some((msg, _)) = &state.status_message {
+            msg.clone()
+        } else if let Some((model, started)) = &state.warming_up {
+            let elapsed = started.elapsed();
+            let spinner = if app.animate { SPINNER_FRAMES[(elapsed.as_millis() / 100) as usize % SPINNER_FRAMES.len()] } else { "⠿" };
+            format!("{} {}", spinner, tf("status.warming", &[("model", model.as_str()), ("secs", elapsed.as_secs().to_string().as_str())]))
+        } else {
+            let lucius_md_count = state.lucius_files.len();
+            let mcp_server_count = if state.redis_conn.is_some() { 1 } else { 0 };
+            let (tokens, window) = app.context_usage(state);
+            let mut using = tf(
+                "chat.using",
+                &[
+                    ("lucius_md", lucius_md_count.to_string().as_str()),
+                    ("mcp", mcp_server_count.to_string().as_str()),
+                    ("tokens", tokens.to_string().as_str()),
+                    ("window", window.to_string().as_str()),
+                ],
+            );
+            // Clicking the status line opens the panel these are counted in
+            let included: Vec<_> = app.attached_items(state).into_iter().filter(|item| item.included).collect();
+            let excerpts = included.iter().filter(|item| matches!(item.source, AttachedSource::Excerpt(..))).count();
+            let files = included.len() - excerpts;
+            if !included.is_empty() {
+                using.push_str(&tf("chat.using_attached", &[("files", files.to_string().as_str()), ("excerpts", excerpts.to_string().as_str())]));
+            }
+            using
+        };
+        let status_line = Paragraph::new(status_text)
+            .style(if app.chat_search.is_some() {
+                Style::default().fg(theme.accent)
+            } else if state.status_message.is_some() {
+                Style::default().fg(theme.status_ok)
+            } else {
+                Style::default().fg(theme.muted)
+            });
+        let active_model_name = state.models.get(app.model_list_state.selected().unwrap_or(0))
+            .map(|model| model.name.clone())
+            .unwrap_or_else(|| t("chat.no_model").to_string());
+        let mut model_label = tf("chat.model", &[("model", active_model_name.as_str())]);
+        if state.config.verbosity != Verbosity::Normal {
+            model_label = format!("{} · {}", model_label, state.config.verbosity.label());
+        }
+        let model_width = model_label.chars().count() as u16 + 1;
+        let model_paragraph = Paragraph::new(model_label)
+            .alignment(Alignment::Right)
+            .style(Style::default().fg(theme.accent));
+        let current_dir = std::env::current_dir()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| t("chat.dir_error").to_string());
+        if compact {
+            // Status, directory and model share the one line above the input,
+            // the directory cut down to its end to leave room for the status
+            let dir_room = (chunks[2].width / 4) as usize;
+            let dir_label = tf("chat.dir", &[("dir", "")]);
+            let dir = path_tail(&current_dir, dir_room.saturating_sub(dir_label.chars().count() + 1));
+            let dir_paragraph = Paragraph::new(tf("chat.dir", &[("dir", dir.as_str())])).style(Style::default().fg(theme.accent));
+            let status_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(dir_room as u16), Constraint::Length(model_width)])
+                .split(chunks[2]);
+            f.render_widget(status_line, status_chunks[0]);
+            f.render_widget(dir_paragraph, status_chunks[1]);
+            f.render_widget(model_paragraph.clone(), status_chunks[2]);
+            app.status_area = status_chunks[0];
+        } else {
+            f.render_widget(status_line, chunks[2]);
+            app.status_area = chunks[2];
+        }
+
+        if !attached.is_empty() {
+            f.render_widget(Paragraph::new(attachment_chips(attached, theme)), chunks[3]);
+        }
+
+        // UI-specific widgets from App
+        let mut input_block = app::input_block(compact, theme);
+        if state.config.input.vim {
+            input_block = input_block.title(Line::styled(app.vim.label(), Style::default().fg(theme.accent)).right_aligned());
+        }
+        app.textarea.set_block(input_block);
+        f.render_widget(&app.textarea, chunks[4]);
+        if let Some(completion) = app.input_completion(&state.config) {
+            // Ghost text right after the cursor, inside the input box's border
+            let typed = app.textarea.lines()[0].chars().count() as u16;
+            let inset = if compact { 0 } else { 1 };
+            let x = chunks[4].x + inset + typed;
+            let right_edge = chunks[4].x + chunks[4].width.saturating_sub(inset);
+            if x < right_edge {
+                // The first cell is where the cursor is drawn, so keep it reversed
+                let mut chars = completion.chars();
+                let first = chars.next().map(String::from).unwrap_or_default();
+                let ghost = Paragraph::new(Line::from(vec![
+                    Span::styled(first, Style::default().fg(theme.muted).add_modifier(Modifier::REVERSED)),
+                    Span::styled(chars.as_str().to_string(), Style::default().fg(theme.muted)),
+                ]));
+                f.render_widget(ghost, Rect::new(x, chunks[4].y + 1, right_edge - x, 1));
+            }
+        }
+
+        if !compact {
+            let bottom_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(chunks[5]);
+
+            let dir_paragraph = Paragraph::new(tf("chat.dir", &[("dir", current_dir.as_str())]))
+                .style(Style::default().fg(theme.accent));
+            f.render_widget(dir_paragraph, bottom_chunks[0]);
+            f.render_widget(model_paragraph, bottom_chunks[1]);
+        }
+
+        // Hint mode's key line goes over the status line
+        if self.hints.is_some() {
+            let hint_line = Paragraph::new(t("modal.hint_line"))
+                .style(Style::default().fg(Color::Black).bg(theme.highlight));
+            let chat_bottom = chunks[1].y + chunks[1].height;
+            f.render_widget(hint_line, Rect::new(area.x, chat_bottom, area.width, 1));
+        }
+    }
+}
+
+/// The last `width` columns of `path`, starting with "…" when it is cut.
+fn path_tail(path: &str, width: usize) -> String {
+    if path.width() <= width {
+        return path.to_string();
+    }
+    let mut tail = Vec::new();
+    let mut columns = 1;
+    for c in path.chars().rev() {
+        columns += c.width().unwrap_or(0);
+        if columns > width {
+            break;
+        }
+        tail.push(c);
+    }
+    std::iter::once('…').chain(tail.into_iter().rev()).collect()
+}
+
+/// The frame around the conversation: a rounded, padded box, or in the
+/// compact layout only the tab bar, and only when there are tabs to show.
+/// Without mouse capture the box names the scrolling keys at the bottom.
+fn conversation_block(app: &App, state: &SharedState, theme: &Theme) -> Block<'static> {
+    if state.config.layout.compact {
+        let block = Block::default();
+        if state.tabs.len() > 1 { block.title(chat_title(state, theme)) } else { block }
+    } else {
+        let block = Block::default()
+            .title(chat_title(state, theme))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(theme.border))
+            .padding(Padding::new(1, 1, 1, 1));
+        if app.mouse_capture {
+            block
+        } else {
+            block.title_bottom(Line::styled(t("chat.scroll_keys"), Style::default().fg(theme.muted)).right_aligned())
+        }
+    }
+}
+
+/// Word-wraps a line to `width` columns, keeping the styles of its spans.
+/// Spaces stay at the end of the row they follow, so the rows join back
+/// into the line; a word wider than a row is broken at the edge. Wide
+/// characters such as CJK and emoji take two columns.
+fn wrap_line(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let cells = cells(&line);
+    let widths: Vec<usize> = cells.iter().map(|(c, _)| c.width().unwrap_or(0)).collect();
+    if width == 0 || widths.iter().sum::<usize>() <= width {
+        return vec![line];
+    }
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < cells.len() {
+        // As many characters as fit, and always at least one
+        let mut end = start;
+        let mut used = 0;
+        while end < cells.len() && (end == start || used + widths[end] <= width) {
+            used += widths[end];
+            end += 1;
+        }
+        if end < cells.len() {
+            if let Some(space) = cells[start..end].iter().rposition(|(c, _)| *c == ' ').filter(|&space| space > 0) {
+                end = start + space + 1;
+            }
+        }
+        rows.push(Line { style: line.style, alignment: line.alignment, spans: spans_of(&cells[start..end]) });
+        start = end;
+    }
+    rows
+}
+
+/// Patches `style` onto columns `from..to` of a line, for the mouse
+/// selection and search matches.
+fn highlight(line: &mut Line<'static>, from: usize, to: usize, style: Style) {
+    let mut cells = cells(line);
+    for cell in cells.iter_mut().take(to).skip(from) {
+        cell.1 = cell.1.patch(style);
+    }
+    line.spans = spans_of(&cells);
+}
+
+/// The text of a line, without its styles.
+fn line_text(line: &Line<'static>) -> String {
+    line.spans.iter().map(|span| span.content.as_ref()).collect()
+}
+
+/// The characters of a line with the style of the span each is in.
+fn cells(line: &Line<'static>) -> Vec<(char, Style)> {
+    line.spans.iter().flat_map(|span| span.content.chars().map(move |c| (c, span.style))).collect()
+}
+
+/// Groups styled characters back into spans, one per run of a style.
+fn spans_of(cells: &[(char, Style)]) -> Vec<Span<'static>> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    for &(c, style) in cells {
+        match spans.last_mut() {
+            Some(span) if span.style == style => span.content.to_mut().push(c),
+            _ => spans.push(Span::styled(c.to_string(), style)),
+        }
+    }
+    spans
+}
+
+/// Lays out the messages, the answer being streamed, pending tool tasks and
+/// follow-up chips, message by message so each one can carry its timestamp.
+fn conversation(app: &App, state: &SharedState, text_width: usize, shown_hints: Option<&[Hint]>, theme: &Theme) -> Conversation {
+    let compact = state.config.layout.compact;
+    let separator = Line::styled("─".repeat(text_width), Style::default().fg(theme.muted));
+    let mut conversation_text = Text::default();
+    let mut focused_start = None;
+    let mut message_starts = Vec::new();
+    for (index, msg) in state.chat_history.iter().enumerate() {
+        // Tool steps of a folded turn collapse into one line at the first of them
+        if let Some(steps) = app.turns.folded_steps(index, &app.folded_turns) {
+            if steps.first() == Some(&index) && compact && !conversation_text.lines.is_empty() {
+                conversation_text.lines.push(separator.clone());
+            }
+            message_starts.push(conversation_text.lines.len());
+            if steps.first() == Some(&index) {
+                let focused = steps.iter().any(|step| app.focused_message == Some(*step));
+                if focused {
+                    focused_start = Some(conversation_text.lines.len());
+                }
+                conversation_text.lines.push(folded_turn_line(state, steps, focused, theme));
+            }
+            continue;
+        }
+        let focused = app.focused_message == Some(index);
+        // Without a focused message, Alt+Left/Right scroll every message together
+        let hscroll = if focused || app.focused_message.is_none() { app.hscroll as usize } else { 0 };
+        let mut lines = Vec::new();
+        let mut code_blocks = 0;
+        for block in markdown::split_blocks(&msg.display_text()) {
+            match block {
+                markdown::Block::Markdown(md) => {
+                    lines.extend(markdown_lines(&md, text_width, shown_hints, theme));
+                }
+                markdown::Block::Table(table) => {
+                    lines.extend(table_lines(&table, text_width, hscroll));
+                }
+                markdown::Block::Code { info, lines: code } => {
+                    let wrap = app.wrapped_messages.contains(&index);
+                    let mut block_lines = code_lines(&info, &code, text_width, hscroll, wrap, theme);
+                    // The block last copied with Ctrl+K gets accented fences
+                    if app.code_block == Some((index, code_blocks)) {
+                        let fence = Style::default().fg(theme.accent).add_modifier(Modifier::BOLD);
+                        if let Some(closing) = block_lines.last_mut() {
+                            closing.spans.iter_mut().for_each(|span| span.style = fence);
+                        }
+                        if let Some(opening) = block_lines.first_mut() {
+                            opening.spans.iter_mut().for_each(|span| span.style = fence);
+                            opening.spans.push(Span::styled(format!("  {}", t("chat.code_copied")), fence));
+                        }
+                    }
+                    code_blocks += 1;
+                    lines.extend(block_lines);
+                }
+            }
+        }
+        let verification = match msg.role {
+            Role::ToolResult => mcp::result_verification(&msg.content),
+            _ => None,
+        };
+        if let Some(verification) = verification {
+            if let Some(last) = lines.last_mut() {
+                let chip = match verification.checks.iter().find(|check| !check.passed) {
+                    Some(check) => Span::styled(
+                        tf("chat.verify_failed", &[("expect", check.expect.as_str())]),
+                        Style::default().fg(Color::Black).bg(theme.status_error),
+                    ),
+                    None => Span::styled(t("chat.verified"), Style::default().fg(Color::Black).bg(theme.status_ok)),
+                };
+                last.spans.push(Span::raw("  "));
+                last.spans.push(chip);
+            }
+        }
+        if state.is_failed_tool_message(index) {
+            if let Some(last) = lines.last_mut() {
+                last.spans.push(Span::raw("  "));
+                last.spans.push(Span::styled(t("chat.retry"), Style::default().fg(Color::Black).bg(theme.error)));
+            }
+        }
+        if compact && !conversation_text.lines.is_empty() {
+            conversation_text.lines.push(separator.clone());
+        }
+        let role_color = match msg.role {
+            Role::User => theme.user,
+            Role::Assistant => theme.assistant,
+            Role::ToolCall | Role::ToolResult => theme.tool,
+            Role::Error => theme.error,
+            Role::Notice => Color::Reset,
+        };
+        // Spans keep their own colors; the role color fills in the rest
+        for line in &mut lines {
+            line.style = line.style.fg(role_color);
+        }
+        if let Some(debug) = msg.debug.as_ref().filter(|_| app.debug_messages.contains(&index)) {
+            lines.extend(debug_lines(debug, theme));
+        }
+        message_starts.push(conversation_text.lines.len());
+        if let Some(first) = lines.first_mut() {
+            if app.show_stamps {
+                first.spans.push(Span::styled(message_stamp(msg, &state.config.time, app.expand_timestamps), Style::default().fg(theme.muted)));
+            }
+            if focused {
+                first.spans.insert(0, Span::styled("▶ ", Style::default().fg(theme.accent)));
+                focused_start = Some(conversation_text.lines.len());
+            }
+        }
+        conversation_text.lines.extend(lines);
+    }
+
+    // Until the first chunk comes in, a spinner stands in for the answer
+    let partial = app.streaming.get(&state.conversation_id).filter(|_| state.busy_since().is_some());
+    if let Some(since) = state.busy_since().filter(|_| partial.is_none()) {
+        let elapsed = since.elapsed();
+        let spinner = if app.animate { SPINNER_FRAMES[(elapsed.as_millis() / 100) as usize % SPINNER_FRAMES.len()] } else { "⠿" };
+        let text = tf("chat.thinking", &[("secs", elapsed.as_secs().to_string().as_str())]);
+        conversation_text.lines.push(Line::styled(format!("{} {}", spinner, text), Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC)));
+    }
+
+    // The answer being streamed, with a cursor where the next chunk goes
+    if let Some((_, partial)) = partial {
+        let mut lines = Vec::new();
+        for block in markdown::split_blocks(&format!("Lucius: {}", partial)) {
+            match block {
+                markdown::Block::Markdown(md) => lines.extend(markdown_lines(&md, text_width, shown_hints, theme)),
+                markdown::Block::Table(table) => lines.extend(table_lines(&table, text_width, app.hscroll as usize)),
+                markdown::Block::Code { info, lines: code } => {
+                    lines.extend(code_lines(&info, &code, text_width, app.hscroll as usize, false, theme));
+                }
+            }
+        }
+        let cursor = if app.cursor_visible() { "▌" } else { " " };
+        match lines.last_mut() {
+            Some(last) => last.spans.push(Span::styled(cursor, Style::default().fg(theme.accent))),
+            None => lines.push(Line::from(cursor)),
+        }
+        conversation_text.lines.extend(lines);
+    }
+
+    // Tool tasks still waiting on a worker stand in for their results
+    for task in state.pending_tasks.iter().filter(|task| task.conversation == state.conversation_id) {
+        conversation_text.lines.push(pending_task_line(task, app.animate, theme));
+    }
+
+    if !state.active_followups().is_empty() {
+        conversation_text.lines.push(Line::from(""));
+        conversation_text.lines.push(followup_chips(state.active_followups(), theme));
+    }
+
+    Conversation { text: conversation_text, message_starts, focused_start }
+}
+
+/// An animated "⚙ exec on web-1 (12s)" line for a task awaiting its result.
+/// Without animations the spinner stands still.
+fn pending_task_line(task: &PendingTask, animate: bool, theme: &Theme) -> Line<'static> {
+    let elapsed = task.started.elapsed();
+    let spinner = if animate { SPINNER_FRAMES[(elapsed.as_millis() / 100) as usize % SPINNER_FRAMES.len()] } else { "⠿" };
+    let secs = elapsed.as_secs().to_string();
+    let text = match &task.worker {
+        Some(worker) => tf("chat.task_running", &[("tool", task.tool.as_str()), ("worker", worker.as_str()), ("secs", secs.as_str())]),
+        None => tf("chat.task_queued", &[("tool", task.tool.as_str()), ("secs", secs.as_str())]),
+    };
+    Line::styled(format!("{} {}", spinner, text), Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC))
+}
+
+/// Lays out a markdown table to the conversation width. Rows are cut to the
+/// visible window rather than wrapped, so wide tables scroll horizontally.
+fn table_lines(table: &markdown::Table, width: usize, hscroll: usize) -> Vec<Line<'static>> {
+    markdown::layout_table(table, width)
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let visible = markdown::slice_columns(row, hscroll, width);
+            // The header is the second line, after the top border
+            if i == 1 {
+                Line::styled(visible, Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                Line::from(visible)
+            }
+        })
+        .collect()
+}
+
+/// Renders a fenced code block. Long lines are cut to the visible window and
+/// scroll horizontally unless the message's code is toggled to soft-wrap.
+fn code_lines(info: &str, code: &[String], width: usize, hscroll: usize, wrap: bool, theme: &Theme) -> Vec<Line<'static>> {
+    let fence_style = Style::default().fg(theme.muted);
+    let code_style = Style::default().fg(theme.code);
+    let mut lines = vec![Line::styled(format!("```{}", info), fence_style)];
+    if info.is_empty() {
+        if let Some(language) = markdown::detect_language(code) {
+            lines[0] = Line::from(vec![
+                Span::styled(format!("```{}", language), fence_style),
+                Span::styled(format!("  {}", t("chat.language_detected")), fence_style.add_modifier(Modifier::ITALIC)),
+            ]);
+        }
+    }
+    for line in code {
+        if wrap {
+            lines.push(Line::styled(line.clone(), code_style));
+        } else if line.chars().count() > hscroll + width {
+            // Mark lines that continue past the right edge
+            let visible = markdown::slice_columns(line, hscroll, width.saturating_sub(1));
+            lines.push(Line::from(vec![Span::styled(visible, code_style), Span::styled("→", fence_style)]));
+        } else {
+            lines.push(Line::styled(markdown::slice_columns(line, hscroll, width), code_style));
+        }
+    }
+    lines.push(Line::styled("```", fence_style));
+    lines
+}
+
+/// The conversation title, followed by a tab bar once several are open.
+fn chat_title(state: &SharedState, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![Span::raw(t("chat.title").to_string())];
+    if state.tabs.len() > 1 {
+        for (index, label) in state.tab_labels().into_iter().enumerate() {
+            let style = if index == state.active_tab {
+                Style::default().fg(Color::Black).bg(theme.accent)
+            } else {
+                Style::default().fg(theme.muted)
+            };
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled(format!(" {} {} ", index + 1, label), style));
+        }
+    }
+    Line::from(spans)
+}
+
+/// The dim suffix of a message: when it was added and, for answers, how long
+/// the model took, with its speed when the backend counted the tokens.
+fn message_stamp(msg: &ChatMessage, time: &timefmt::TimeConfig, expanded: bool) -> String {
+    let mut stamp = format!("  · {}", timefmt::display(msg.timestamp, time, expanded));
+    if let Some(ms) = msg.duration_ms {
+        stamp.push_str(&format!(" · {}", timefmt::duration(ms)));
+        if let Some(tokens) = msg.debug.as_ref().and_then(|debug| debug.output_tokens).filter(|_| ms > 0) {
+            stamp.push_str(&format!(" · {:.0} tok/s", tokens as f64 * 1000.0 / ms as f64));
+        }
+    }
+    stamp
+}
+
+/// How an answer ended, under it: finish reason and token counts, then the
+/// raw final chunk, pretty-printed when it is JSON.
+fn debug_lines(debug: &ResponseDebug, theme: &Theme) -> Vec<Line<'static>> {
+    let unknown = || "?".to_string();
+    let style = Style::default().fg(theme.muted);
+    let summary = tf(
+        "chat.response_debug",
+        &[
+            ("reason", debug.finish_reason.clone().unwrap_or_else(unknown).as_str()),
+            ("prompt", debug.prompt_tokens.map_or_else(unknown, |n| n.to_string()).as_str()),
+            ("output", debug.output_tokens.map_or_else(unknown, |n| n.to_string()).as_str()),
+        ],
+    );
+    let raw = serde_json::from_str::<serde_json::Value>(&debug.raw)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or_else(|_| debug.raw.clone());
+    std::iter::once(summary)
+        .chain(raw.lines().map(|line| format!("  {}", line)))
+        .map(|line| Line::styled(line, style))
+        .collect()
+}
+
+/// The line standing in for a folded turn's tool steps.
+fn folded_turn_line(state: &SharedState, steps: &[usize], focused: bool, theme: &Theme) -> Line<'static> {
+    let failed = steps.iter().filter(|&&index| state.is_failed_tool_message(index)).count();
+    let style = Style::default().fg(theme.muted);
+    let mut spans = vec![Span::styled(
+        tf("chat.folded_steps", &[("count", steps.len().to_string().as_str()), ("tools", state.tool_steps_summary(steps).as_str())]),
+        style,
+    )];
+    if failed > 0 {
+        spans.push(Span::styled(tf("chat.folded_failed", &[("count", failed.to_string().as_str())]), Style::default().fg(theme.error)));
+    }
+    if focused {
+        spans.insert(0, Span::styled("▶ ", Style::default().fg(theme.accent)));
+    }
+    Line::from(spans)
+}
+
+/// The files the next prompt carries, e.g. "📎 src/main.rs  📎 Cargo.toml".
+fn attachment_chips(paths: &[String], theme: &Theme) -> Line<'static> {
+    let mut spans = Vec::new();
+    for path in paths {
+        spans.push(Span::styled(format!(" 📎 {} ", path), Style::default().fg(Color::Black).bg(theme.accent)));
+        spans.push(Span::raw(" "));
+    }
+    Line::from(spans)
+}
+
+/// Renders suggested follow-ups as chips named after the keys that send
+/// them, e.g. `[F1] How do I...`.
+fn followup_chips(followups: &[String], theme: &Theme) -> Line<'static> {
+    let mut spans = Vec::new();
+    for (i, followup) in followups.iter().enumerate() {
+        spans.push(Span::styled(format!(" F{} ", i + 1), Style::default().fg(Color::Black).bg(theme.accent)));
+        spans.push(Span::styled(format!(" {}  ", followup), Style::default().fg(theme.accent)));
+    }
+    Line::from(spans)
+}
+
+/// Renders markdown prose as styled lines: headings, list items, block
+/// quotes, rules and inline emphasis. Styles live on the spans, so they
+/// survive the paragraph's wrapping and scrolling.
+fn markdown_lines(md: &str, width: usize, shown_hints: Option<&[Hint]>, theme: &Theme) -> Vec<Line<'static>> {
+    let marker_style = Style::default().fg(theme.accent);
+    markdown::parse_lines(&markdown::prettify_math(md))
+        .into_iter()
+        .map(|line| {
+            let (mut spans, base) = match &line.kind {
+                markdown::LineKind::Paragraph => (vec![], Style::default()),
+                markdown::LineKind::Heading(1) => {
+                    (vec![], Style::default().fg(theme.accent).add_modifier(Modifier::BOLD | Modifier::UNDERLINED))
+                }
+                markdown::LineKind::Heading(2) => (vec![], Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+                markdown::LineKind::Heading(_) => (vec![], Style::default().add_modifier(Modifier::BOLD)),
+                markdown::LineKind::ListItem { indent, marker } => {
+                    (vec![Span::styled(format!("{}{} ", " ".repeat(*indent), marker), marker_style)], Style::default())
+                }
+                markdown::LineKind::Quote => (
+                    vec![Span::styled("▎ ", Style::default().fg(theme.muted))],
+                    Style::default().fg(theme.secondary).add_modifier(Modifier::ITALIC),
+                ),
+                markdown::LineKind::Rule => return Line::styled("─".repeat(width), Style::default().fg(theme.muted)),
+            };
+            for (text, emphasis) in line.runs {
+                let mut style = base;
+                if emphasis.bold {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if emphasis.italic {
+                    style = style.add_modifier(Modifier::ITALIC);
+                }
+                if emphasis.strike {
+                    style = style.add_modifier(Modifier::CROSSED_OUT);
+                }
+                if emphasis.code {
+                    style = style.fg(theme.code);
+                }
+                spans.push(Span::styled(text, style));
+            }
+            linkify(spans, shown_hints, theme)
+        })
+        .collect()
+}
+
+/// Underlines URLs in a rendered line, keeping the styles of the spans they
+/// cross, and in hint mode prefixes each URL or file path with the letter
+/// that selects it.
+fn linkify(spans: Vec<Span<'static>>, shown_hints: Option<&[Hint]>, theme: &Theme) -> Line<'static> {
+    let hint_style = Style::default().fg(Color::Black).bg(theme.highlight).add_modifier(Modifier::BOLD);
+    let text: String = spans.iter().map(|span| span.content.as_ref()).collect();
+    let mut links = Vec::new();
+    let mut last = 0;
+    for (start, end, kind) in hints::find_spans(&text) {
+        if start < last {
+            continue;
+        }
+        let target = &text[start..end];
+        let label = shown_hints.and_then(|shown| {
+            shown.iter().position(|h| {
+                h.kind == kind && match kind {
+                    // Long URLs may be cut by wrapping, so match on the visible prefix
+                    HintKind::Url => h.target.starts_with(target),
+                    HintKind::Path => h.target == target,
+                }
+            })
+        });
+        // Paths are only decorated while they can be picked
+        if kind == HintKind::Path && label.is_none() {
+            continue;
+        }
+        links.push((start, end, label));
+        last = end;
+    }
+    if links.is_empty() {
+        return Line::from(spans);
+    }
+
+    let mut out = Vec::new();
+    let mut offset = 0;
+    for span in spans {
+        let span_end = offset + span.content.len();
+        let mut pos = offset;
+        while pos < span_end {
+            match links.iter().find(|(start, end, _)| *end > pos && *start < span_end) {
+                Some(&(start, end, label)) if start <= pos => {
+                    if let Some(index) = label.filter(|_| start == pos) {
+                        out.push(Span::styled(hints::hint_label(index).to_string(), hint_style));
+                    }
+                    let stop = end.min(span_end);
+                    out.push(Span::styled(text[pos..stop].to_string(), span.style.add_modifier(Modifier::UNDERLINED)));
+                    pos = stop;
+                }
+                Some(&(start, _, _)) => {
+                    out.push(Span::styled(text[pos..start].to_string(), span.style));
+                    pos = start;
+                }
+                None => {
+                    out.push(Span::styled(text[pos..span_end].to_string(), span.style));
+                    pos = span_end;
+                }
+            }
+        }
+        offset = span_end;
+    }
+    Line::from(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(rows: &[Line<'static>]) -> Vec<String> {
+        rows.iter().map(line_text).collect()
+    }
+
+    #[test]
+    fn wide_characters_wrap_by_their_columns() {
+        let line = Line::from(vec![Span::raw("日本語の"), Span::styled("テキスト", Style::default().fg(Color::Red))]);
+        let rows = wrap_line(line, 5);
+        assert_eq!(texts(&rows), ["日本", "語の", "テキ", "スト"]);
+        assert_eq!(rows[1].spans.len(), 1);
+        assert_eq!(rows[2].spans[0].style.fg, Some(Color::Red));
+
+        assert_eq!(texts(&wrap_line(Line::raw("🦀 crab and 🦀"), 8)), ["🦀 crab ", "and 🦀"]);
+        // Narrow enough for nothing, a row still takes one character
+        assert_eq!(texts(&wrap_line(Line::raw("語語"), 1)), ["語", "語"]);
+    }
+
+    #[test]
+    fn stamps_show_the_time_taken_and_speed_of_answers() {
+        let time = timefmt::TimeConfig::default();
+        let question = ChatMessage::user("disk full?");
+        assert_eq!(message_stamp(&question, &time, false), "  · just now");
+
+        let mut answer = ChatMessage { duration_ms: Some(4_000), ..ChatMessage::assistant("yes") };
+        assert_eq!(message_stamp(&answer, &time, false), "  · just now · 4.0s");
+        answer.debug = Some(ResponseDebug { finish_reason: None, prompt_tokens: None, output_tokens: Some(100), generation_ms: None, raw: String::new() });
+        assert_eq!(message_stamp(&answer, &time, false), "  · just now · 4.0s · 25 tok/s");
+        // No speed for an answer timed at nothing
+        answer.duration_ms = Some(0);
+        assert_eq!(message_stamp(&answer, &time, false), "  · just now · 0ms");
+        assert!(message_stamp(&answer, &time, true).starts_with(&format!("  · {}", timefmt::absolute(answer.timestamp, &time.format))));
+    }
+
+    #[test]
+    fn pending_tasks_show_their_worker_once_picked_up() {
+        let theme = Theme::dark();
+        let mut task = PendingTask { id: "t".to_string(), conversation: "c".to_string(), tool: "docker".to_string(), worker: None, started: std::time::Instant::now() - std::time::Duration::from_secs(12) };
+        let queued = line_text(&pending_task_line(&task, false, &theme));
+        assert!(queued.starts_with("⠿ ") && queued.contains("docker") && queued.contains("12s"));
+        task.worker = Some("web-1".to_string());
+        let running = line_text(&pending_task_line(&task, false, &theme));
+        assert!(running.contains("web-1") && running != queued);
+    }
+
+    #[test]
+    fn unchanged_lines_are_not_wrapped_again() {
+        let mut cache = WrapCache::default();
+        let lines = vec![Line::raw("the quick brown fox"), Line::raw("jumps")];
+        assert_eq!(cache.wrap(lines.clone(), 10).iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        // Another row for an edited line only
+        let edited = vec![lines[0].clone(), Line::raw("jumps over the lazy dog")];
+        let rows = cache.wrap(edited, 10);
+        assert_eq!(texts(&rows[1]), ["jumps ", "over the ", "lazy dog"]);
+        assert_eq!(cache.lines.len(), 2);
+        assert_eq!(cache.wrap(vec![Line::raw("the quick brown fox")], 30)[0].len(), 1);
+        assert_eq!(cache.lines.len(), 1);
+    }
+
+    #[test]
+    fn long_directories_keep_their_end() {
+        assert_eq!(path_tail("/srv/app", 20), "/srv/app");
+        assert_eq!(path_tail("/home/ops/projects/lucius", 10), "…ts/lucius");
+        assert_eq!(path_tail("/home/ops/データ", 8), "…/データ");
+        assert_eq!(path_tail("/srv/app", 0), "…");
+    }
+
+    #[test]
+    fn turns_are_worked_out_once_per_history_change() {
+        let call = mcp::ToolCall { tool: "exec".to_string(), params: serde_json::json!({ "command": "df -h" }), expect: vec![] };
+        let mut state = SharedState::for_tests();
+        state.chat_history.extend([
+            ChatMessage::user("disk full?"),
+            ChatMessage::tool_call(&call),
+            ChatMessage::tool_result(&call, "98%"),
+            ChatMessage::assistant("Almost."),
+            ChatMessage::user("and memory?"),
+        ]);
+        let mut turns = TurnCache::default();
+        turns.update(&state.chat_history, state.history_revision);
+        let folded: HashSet<usize> = [0, 4].into();
+        assert_eq!(turns.folded_steps(2, &folded), Some(&[1, 2][..]));
+        assert_eq!(turns.folded_steps(3, &folded), None);
+        assert_eq!(turns.folded_steps(1, &HashSet::new()), None);
+
+        // Removing a step is picked up even though a new message keeps the length
+        state.remove_message(1);
+        state.chat_history.push(ChatMessage::tool_call(&call));
+        turns.update(&state.chat_history, state.history_revision);
+        let folded: HashSet<usize> = [0, 3].into();
+        assert_eq!(turns.folded_steps(1, &folded), Some(&[1][..]));
+        assert_eq!(turns.folded_steps(4, &folded), Some(&[4][..]));
+    }
+}
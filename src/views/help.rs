@@ -0,0 +1,33 @@
+use ratatui::{
+    prelude::{Frame, Style},
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
+
+use crate::app::{App, SharedState};
+use crate::commands;
+use crate::i18n::{t, tf};
+use crate::theme::Theme;
+
+use super::View;
+
+/// The keybindings and slash commands, over the whole screen.
+pub struct HelpView {
+    theme: Theme,
+}
+
+impl HelpView {
+    pub fn new(theme: Theme) -> Self {
+        Self { theme }
+    }
+}
+
+impl View for HelpView {
+    fn draw(&mut self, f: &mut Frame, app: &mut App, _state: &SharedState) {
+        let help_block = Block::default().title(t("help.title")).borders(Borders::ALL).border_style(Style::default().fg(self.theme.border));
+        // Mouse bindings only where the mouse is captured, keyboard stand-ins otherwise
+        let mouse = t(if app.mouse_capture { "help.mouse" } else { "help.no_mouse" });
+        let help_text = format!("{}\n{}\n{}", tf("help.body", &[("mouse", mouse)]), t("help.slash_commands"), commands::help_lines());
+        let help_paragraph = Paragraph::new(help_text).wrap(Wrap { trim: true }).block(help_block);
+        f.render_widget(help_paragraph, f.area());
+    }
+}
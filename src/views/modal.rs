@@ -0,0 +1,451 @@
+use std::time::Instant;
+use ratatui::{
+    prelude::{Frame, Layout, Direction, Constraint, Style},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    text::{Line, Span},
+    layout::{Alignment, Rect},
+    style::{Color, Modifier},
+};
+
+use lucius::mcp;
+use crate::app::{App, SharedState};
+use crate::budget::AttachedSource;
+use crate::diff::{self, DiffRow};
+use crate::forms::ToolForm;
+use crate::i18n::{t, tf};
+use crate::session;
+use crate::theme::Theme;
+use crate::timefmt;
+use crate::ui::{AppMode, ConfirmationModal};
+
+use super::chat::ChatView;
+use super::{centered_rect, View};
+
+/// A popup over the chat: the pickers, the session browser and search, the
+/// prompt editor, file actions and tool confirmations.
+pub struct ModalView {
+    theme: Theme,
+    chat: ChatView<'static>,
+}
+
+impl ModalView {
+    pub fn new(theme: Theme) -> Self {
+        Self { theme, chat: ChatView::new(theme, None) }
+    }
+}
+
+impl View for ModalView {
+    fn update(&mut self, app: &mut App, state: &SharedState, area: Rect) {
+        self.chat.update(app, state, area);
+    }
+
+    fn draw(&mut self, f: &mut Frame, app: &mut App, state: &SharedState) {
+        // The chat stays visible behind the popup
+        self.chat.draw(f, app, state);
+
+        let area = f.area();
+        let theme = &self.theme;
+        match &state.mode {
+            AppMode::TemplatePicker(choices) => {
+                let popup_area = centered_rect(area, 60, (choices.len() as u16 + 2).min(area.height));
+                let items: Vec<ListItem> = choices.iter().map(|t| ListItem::new(t.name.as_str())).collect();
+                let list = List::new(items)
+                    .block(Block::default().title(t("modal.templates_title")).borders(Borders::ALL).border_style(Style::default().fg(theme.accent)))
+                    .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                    .highlight_symbol(">>");
+                f.render_widget(Clear, popup_area);
+                f.render_stateful_widget(list, popup_area, &mut app.template_list_state);
+            }
+            AppMode::ToolPalette(tools) => {
+                let popup_area = centered_rect(area, 70.min(area.width), (tools.len() as u16 + 3).min(area.height));
+                let items: Vec<ListItem> = tools
+                    .iter()
+                    .map(|tool| {
+                        let star = if state.config.favorite_tools.contains(&tool.name) { "★ " } else { "  " };
+                        ListItem::new(Line::from(vec![
+                            Span::styled(star, Style::default().fg(theme.highlight)),
+                            Span::styled(tool.name.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                            Span::styled(format!("  {}", tool.description), Style::default().fg(theme.secondary)),
+                        ]))
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .title(t("modal.tools_title"))
+                            .title_bottom(t("modal.tools_keys"))
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(theme.accent)),
+                    )
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">>");
+                f.render_widget(Clear, popup_area);
+                f.render_stateful_widget(list, popup_area, &mut app.tool_list_state);
+            }
+            AppMode::ContextPanel => {
+                let context_items = state.context_items();
+                let (included, window) = state.context_usage();
+                let popup_area = centered_rect(area, 80.min(area.width), (context_items.len() as u16 + 2).clamp(5, area.height));
+                let items: Vec<ListItem> = context_items
+                    .iter()
+                    .map(|item| {
+                        let (check, style) = if item.included {
+                            ("[x] ", Style::default())
+                        } else {
+                            ("[ ] ", Style::default().fg(theme.muted).add_modifier(Modifier::CROSSED_OUT))
+                        };
+                        ListItem::new(Line::from(vec![
+                            Span::raw(check),
+                            Span::styled(format!("{:>7} ", tf("context.tokens", &[("tokens", item.tokens.to_string().as_str())])), Style::default().fg(theme.accent)),
+                            Span::styled(item.label.clone(), style),
+                        ]))
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .title(tf("context.title", &[("tokens", included.to_string().as_str()), ("window", window.to_string().as_str())]))
+                            .title_bottom(t("context.keys"))
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(theme.accent)),
+                    )
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">>");
+                f.render_widget(Clear, popup_area);
+                f.render_stateful_widget(list, popup_area, &mut app.context_list_state);
+            }
+            AppMode::AttachedPanel => {
+                // Docked on the right so the conversation stays readable
+                let width = (area.width * 2 / 5).max(36).min(area.width);
+                let panel_area = Rect { x: area.right() - width, width, ..area };
+                let attached = app.attached_items(state);
+                let items: Vec<ListItem> = attached
+                    .iter()
+                    .map(|item| {
+                        let (check, style) = if item.included {
+                            ("[x] ", Style::default())
+                        } else {
+                            ("[ ] ", Style::default().fg(theme.muted).add_modifier(Modifier::CROSSED_OUT))
+                        };
+                        let kind = match item.source {
+                            AttachedSource::Pending(_) => t("attached.pending"),
+                            AttachedSource::Sent(_) => t("attached.file"),
+                            AttachedSource::Excerpt(..) => t("attached.excerpt"),
+                        };
+                        ListItem::new(Line::from(vec![
+                            Span::raw(check),
+                            Span::styled(format!("{:>8} ", format!("{:.1} KB", item.bytes as f64 / 1024.0)), Style::default().fg(theme.accent)),
+                            Span::styled(format!("{} ", kind), Style::default().fg(theme.secondary)),
+                            Span::styled(item.label.clone(), style),
+                        ]))
+                    })
+                    .collect();
+                let items = if items.is_empty() {
+                    vec![ListItem::new(Span::styled(t("attached.empty"), Style::default().fg(theme.muted)))]
+                } else {
+                    items
+                };
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .title(t("attached.title"))
+                            .title_bottom(t("attached.keys"))
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(theme.accent)),
+                    )
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">>");
+                f.render_widget(Clear, panel_area);
+                f.render_stateful_widget(list, panel_area, &mut app.attached_list_state);
+            }
+            AppMode::SessionBrowser(sessions) => {
+                let rows = sessions.len()
+                    + sessions.iter().filter(|meta| meta.notes.is_some()).count()
+                    + sessions.iter().filter(|meta| meta.branch_of.is_some()).count();
+                let popup_area = centered_rect(area, 90.min(area.width), (rows as u16 + 2).clamp(5, area.height));
+                let items: Vec<ListItem> = sessions
+                    .iter()
+                    .map(|meta| {
+                        let current = if meta.id == state.conversation_id { "● " } else { "  " };
+                        let star = if meta.starred { "★ " } else { "  " };
+                        let branch = if meta.branch_of.is_some() { "⑂ " } else { "" };
+                        let tags = if meta.tags.is_empty() { String::new() } else { format!(" {}", session::format_tags(&meta.tags)) };
+                        let mut lines = vec![Line::from(vec![
+                            Span::styled(current, Style::default().fg(theme.accent)),
+                            Span::styled(star, Style::default().fg(theme.highlight)),
+                            Span::styled(branch, Style::default().fg(theme.accent)),
+                            Span::styled(meta.title.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                            Span::styled(tags, Style::default().fg(theme.accent)),
+                            Span::styled(
+                                format!(
+                                    "  {} · {} · {}",
+                                    timefmt::display(meta.updated, &state.config.time, app.expand_timestamps),
+                                    meta.model.as_deref().unwrap_or("-"),
+                                    tf("modal.sessions_messages", &[("count", meta.message_count.to_string().as_str())]),
+                                ),
+                                Style::default().fg(theme.secondary),
+                            ),
+                        ])];
+                        if let Some(point) = &meta.branch_of {
+                            // The parent may have been deleted since
+                            let parent = sessions.iter().find(|parent| parent.id == point.session).map_or(point.session.as_str(), |parent| parent.title.as_str());
+                            let count = (point.message + 1).to_string();
+                            let origin = tf("modal.sessions_branch", &[("title", parent), ("count", count.as_str())]);
+                            lines.push(Line::from(Span::styled(format!("    {}", origin), Style::default().fg(theme.muted))));
+                        }
+                        if let Some(notes) = &meta.notes {
+                            lines.push(Line::from(Span::styled(format!("    {}", notes), Style::default().fg(theme.muted))));
+                        }
+                        ListItem::new(lines)
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .title(match &app.session_tag_filter {
+                                Some(tag) => tf("modal.sessions_title_tag", &[("tag", tag.as_str())]),
+                                None => t("modal.sessions_title").to_string(),
+                            })
+                            .title_bottom(t("modal.sessions_keys"))
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(theme.accent)),
+                    )
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">>");
+                f.render_widget(Clear, popup_area);
+                f.render_stateful_widget(list, popup_area, &mut app.session_list_state);
+
+                if let Some(editor) = &mut app.session_rename {
+                    let rename_area = centered_rect(area, 60.min(area.width), 3);
+                    editor.set_block(Block::default().title(t("modal.sessions_rename")).borders(Borders::ALL).border_style(Style::default().fg(theme.highlight)));
+                    f.render_widget(Clear, rename_area);
+                    f.render_widget(&*editor, rename_area);
+                }
+            }
+            AppMode::Search(hits) => {
+                let popup_area = centered_rect(area, 100.min(area.width), area.height.saturating_sub(4).max(6));
+                let [input_area, list_area] = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(3), Constraint::Min(0)])
+                    .areas(popup_area);
+                let query = app.search_input.lines().join(" ");
+                let items: Vec<ListItem> = hits
+                    .iter()
+                    .map(|hit| {
+                        let mut spans = vec![Span::styled(hit.session.title.clone(), Style::default().add_modifier(Modifier::BOLD))];
+                        if let Some(index) = hit.message {
+                            spans.push(Span::styled(format!(" #{}", index + 1), Style::default().fg(theme.muted)));
+                        }
+                        spans.push(Span::raw("  "));
+                        spans.extend(highlight_match(&hit.snippet, &query, theme));
+                        ListItem::new(Line::from(spans))
+                    })
+                    .collect();
+                let title = tf("modal.search_title", &[("count", hits.len().to_string().as_str())]);
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .title(title)
+                            .title_bottom(t("modal.search_keys"))
+                            .borders(Borders::ALL)
+                            .border_style(Style::default().fg(theme.accent)),
+                    )
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">>");
+                app.search_input.set_block(Block::default().title(t("modal.search_query")).borders(Borders::ALL).border_style(Style::default().fg(theme.highlight)));
+                f.render_widget(Clear, popup_area);
+                f.render_widget(&app.search_input, input_area);
+                f.render_stateful_widget(list, list_area, &mut app.search_list_state);
+            }
+            AppMode::PromptEditor => {
+                let popup_area = centered_rect(area, 100.min(area.width), area.height.saturating_sub(4).max(8));
+                let lucius = state.lucius_context.as_deref().unwrap_or_default();
+                let lucius_height = if lucius.is_empty() { 0 } else { (lucius.lines().count() as u16 + 2).min(popup_area.height / 3) };
+                let [lucius_area, editor_area] = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(lucius_height), Constraint::Min(0)])
+                    .areas(popup_area);
+                f.render_widget(Clear, popup_area);
+                if !lucius.is_empty() {
+                    let block = Block::default().title(t("modal.prompt_lucius")).borders(Borders::ALL).border_style(Style::default().fg(theme.border));
+                    f.render_widget(Paragraph::new(lucius).style(Style::default().fg(theme.muted)).wrap(Wrap { trim: false }).block(block), lucius_area);
+                }
+                app.prompt_editor.set_block(
+                    Block::default()
+                        .title(t("modal.prompt_title"))
+                        .title_bottom(t("modal.prompt_keys"))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(theme.accent)),
+                );
+                f.render_widget(&app.prompt_editor, editor_area);
+            }
+            AppMode::ContextDiff => {
+                let Some(change) = &state.context_change else {
+                    return;
+                };
+                let popup_area = centered_rect(area, area.width.saturating_sub(4), area.height.saturating_sub(4).max(8));
+                let block = Block::default()
+                    .title(t("modal.context_diff_title"))
+                    .title_bottom(t("modal.context_diff_keys"))
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(theme.accent));
+                let [old_area, new_area] = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .areas(block.inner(popup_area));
+                // Not wrapped, so the two sides stay row for row
+                let rows = diff::side_by_side(change.old.as_deref().unwrap_or_default(), change.project.prompt.as_deref().unwrap_or_default());
+                let (old_lines, new_lines): (Vec<Line>, Vec<Line>) = rows
+                    .into_iter()
+                    .map(|row| match row {
+                        DiffRow::Same(line) => (Line::from(format!("  {}", line)), Line::from(format!("  {}", line))),
+                        DiffRow::Changed(old, new) => (
+                            old.map_or_else(Line::default, |line| Line::styled(format!("- {}", line), Style::default().fg(theme.error))),
+                            new.map_or_else(Line::default, |line| Line::styled(format!("+ {}", line), Style::default().fg(theme.status_ok))),
+                        ),
+                    })
+                    .unzip();
+                f.render_widget(Clear, popup_area);
+                f.render_widget(block, popup_area);
+                for (lines, title, side) in [(old_lines, t("modal.context_diff_old"), old_area), (new_lines, t("modal.context_diff_new"), new_area)] {
+                    let pane = Block::default().title(title).borders(Borders::ALL).border_style(Style::default().fg(theme.border));
+                    f.render_widget(Paragraph::new(lines).scroll((app.context_diff_scroll, 0)).block(pane), side);
+                }
+            }
+            AppMode::CopyMenu(_) => {
+                let popup_area = centered_rect(area, 50.min(area.width), 7);
+                let text: Vec<Line> = vec![
+                    Line::from(t("modal.copy_raw")),
+                    Line::from(t("modal.copy_text")),
+                    Line::from(t("modal.copy_code")),
+                    Line::from(t("modal.copy_quoted")),
+                    Line::from(t("modal.cancel")),
+                ];
+                let block = Block::default()
+                    .title(t("modal.copy_title"))
+                    .borders(Borders::ALL)
+                    .style(Style::default().bg(theme.popup_bg).fg(theme.popup_fg));
+                f.render_widget(Clear, popup_area);
+                f.render_widget(Paragraph::new(text).block(block), popup_area);
+            }
+            AppMode::PathActions(path) => {
+                let popup_area = centered_rect(area, 60, 9);
+                let text: Vec<Line> = vec![
+                    Line::from(path.clone()),
+                    Line::from(""),
+                    Line::from(t("modal.file_edit")),
+                    Line::from(t("modal.file_attach")),
+                    Line::from(t("modal.file_ls")),
+                    Line::from(t("modal.file_head")),
+                    Line::from(t("modal.cancel")),
+                ];
+                let block = Block::default()
+                    .title(t("modal.file_actions_title"))
+                    .borders(Borders::ALL)
+                    .style(Style::default().bg(theme.popup_bg).fg(theme.popup_fg));
+                f.render_widget(Clear, popup_area);
+                f.render_widget(Paragraph::new(text).block(block), popup_area);
+            }
+            AppMode::Confirmation(ConfirmationModal::ExecuteTool { ref tool_call, ref form, deadline, .. }) => {
+                let block = Block::default()
+                    .title(t("modal.confirm_title"))
+                    .borders(Borders::ALL)
+                    .style(Style::default().bg(theme.popup_bg).fg(theme.popup_fg));
+
+                let run_as = mcp::run_identity(&state.workers, tool_call)
+                    .map(|identity| Line::from(tf("modal.run_as", &[("identity", identity.as_str())])));
+                if let Some(form) = form {
+                    let mut text: Vec<Line> = vec![
+                        Line::from(t("modal.confirm_question")),
+                        Line::from(tf("modal.tool", &[("tool", tool_call.tool.as_str())])),
+                    ];
+                    text.extend(run_as);
+                    text.push(Line::from(""));
+                    text.extend(form_lines(form, theme));
+                    text.push(Line::from(""));
+                    if let Some(error) = &form.error {
+                        text.push(Line::styled(error.clone(), Style::default().fg(theme.error)));
+                    }
+                    text.push(Line::from(t("modal.form_keys")));
+                    text.extend(auto_deny_line(*deadline, theme));
+                    let popup_area = centered_rect(area, 70.min(area.width), (text.len() as u16 + 2).min(area.height));
+                    f.render_widget(Clear, popup_area);
+                    f.render_widget(Paragraph::new(text).block(block).wrap(Wrap { trim: false }), popup_area);
+                    return;
+                }
+
+                let mut text: Vec<Line> = vec![
+                    Line::from(t("modal.confirm_question")),
+                    Line::from(""),
+                    Line::from(tf("modal.tool", &[("tool", tool_call.tool.as_str())])),
+                    Line::from(tf("modal.params", &[("params", tool_call.params.to_string().as_str())])),
+                ];
+                text.extend(run_as);
+                text.push(Line::from(""));
+                text.push(Line::from(t("modal.confirm_keys")));
+                text.extend(auto_deny_line(*deadline, theme));
+                let popup_area = centered_rect(area, 60, text.len() as u16 + 2);
+
+                let paragraph = Paragraph::new(text).block(block).alignment(Alignment::Center).wrap(Wrap { trim: true });
+                f.render_widget(paragraph, popup_area);
+            }
+            AppMode::Chat | AppMode::Hints(_) | AppMode::Settings | AppMode::Help => {}
+        }
+    }
+}
+
+/// The countdown to a confirmation being declined on its own, if it has one.
+fn auto_deny_line(deadline: Option<Instant>, theme: &Theme) -> Option<Line<'static>> {
+    let seconds = deadline?.saturating_duration_since(Instant::now()).as_secs().to_string();
+    Some(Line::styled(tf("modal.auto_deny", &[("seconds", seconds.as_str())]), Style::default().fg(theme.muted)))
+}
+
+/// One line per form field: name with a required marker, type, and value,
+/// with the selected field highlighted and a cursor while it is edited.
+fn form_lines(form: &ToolForm, theme: &Theme) -> Vec<Line<'static>> {
+    let name_width = form.fields.iter().map(|field| field.name.chars().count() + 1).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for (index, field) in form.fields.iter().enumerate() {
+        let selected = index == form.selected;
+        let marker = if field.required { "*" } else { "" };
+        let mut value = Span::raw(field.value.clone());
+        if selected {
+            value = value.style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+        let mut spans = vec![
+            Span::raw(if selected { "▶ " } else { "  " }),
+            Span::styled(format!("{:<width$}", format!("{}{}", field.name, marker), width = name_width), Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(format!(" ({}) ", field.kind), Style::default().fg(theme.secondary)),
+            value,
+        ];
+        if selected && form.editing {
+            spans.push(Span::raw("▏"));
+        }
+        lines.push(Line::from(spans));
+        if selected {
+            if let Some(description) = &field.description {
+                lines.push(Line::styled(format!("    {}", description), Style::default().fg(theme.secondary)));
+            }
+        }
+    }
+    lines
+}
+
+/// A search snippet with the first case-insensitive match of `query` highlighted.
+fn highlight_match(snippet: &str, query: &str, theme: &Theme) -> Vec<Span<'static>> {
+    let gray = Style::default().fg(theme.secondary);
+    let query = query.trim();
+    let found = (!query.is_empty())
+        .then(|| snippet.to_lowercase().find(&query.to_lowercase()))
+        .flatten()
+        // Lowercasing may shift byte offsets for a few scripts; fall back to plain text then
+        .filter(|&start| snippet.len() == snippet.to_lowercase().len() && snippet.is_char_boundary(start + query.len()));
+    match found {
+        Some(start) => vec![
+            Span::styled(snippet[..start].to_string(), gray),
+            Span::styled(snippet[start..start + query.len()].to_string(), Style::default().fg(Color::Black).bg(theme.highlight)),
+            Span::styled(snippet[start + query.len()..].to_string(), gray),
+        ],
+        None => vec![Span::styled(snippet.to_string(), gray)],
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::Utc;
+use ratatui::{
+    prelude::{Frame, Layout, Direction, Constraint, Style},
+    widgets::{Block, Borders, Clear, Gauge, List, ListItem, Paragraph, Wrap},
+    text::{Line, Span},
+    style::Modifier,
+};
+
+use crate::app::{App, SharedState};
+use crate::catalog::CATALOG;
+use crate::i18n::{t, tf};
+use crate::llm::{Model, ProviderKind};
+use crate::llm::ollama::{KeepAlive, LoadedModel, ModelInfo};
+use crate::resources;
+use crate::theme::Theme;
+use crate::timefmt;
+use crate::ui::Focus;
+
+use super::{centered_rect, View};
+
+/// The settings screen: provider fields, MCP host, model parameters, the
+/// connection status and the model list, with the model info and pull
+/// popups on top.
+pub struct SettingsView {
+    theme: Theme,
+}
+
+impl SettingsView {
+    pub fn new(theme: Theme) -> Self {
+        Self { theme }
+    }
+}
+
+impl View for SettingsView {
+    fn draw(&mut self, f: &mut Frame, app: &mut App, state: &SharedState) {
+        let area = f.area();
+        let theme = self.theme;
+        let anthropic = state.config.provider == ProviderKind::Anthropic;
+        let mut constraints = vec![Constraint::Length(3)]; // Provider
+        if anthropic {
+            constraints.extend([Constraint::Length(3), Constraint::Length(3)]); // API key, model name
+        } else {
+            constraints.extend([Constraint::Length(3), Constraint::Length(3)]); // Ollama URL editor, keep-alive
+        }
+        constraints.extend([
+            Constraint::Length(3), // MCP Redis Host editor
+            Constraint::Length(3), // Model parameters editor
+            Constraint::Length(3), // Status
+            Constraint::Min(0),    // Models list
+        ]);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
+        let current_focus = app.focus;
+        let focused_border = |block: Block<'static>, focus: Focus| {
+            if current_focus == focus {
+                block.border_style(Style::default().fg(theme.accent))
+            } else {
+                block.border_style(Style::default().fg(theme.border))
+            }
+        };
+
+        let provider = Paragraph::new(tf("settings.provider_value", &[("provider", state.config.provider.label())]))
+            .block(focused_border(Block::default().borders(Borders::ALL).title(t("settings.provider")), Focus::Provider));
+        f.render_widget(provider, chunks[0]);
+
+        let mut row = 1;
+        if anthropic {
+            app.api_key_editor.set_block(focused_border(Block::default().borders(Borders::ALL).title(t("settings.api_key")), Focus::ApiKey));
+            f.render_widget(&app.api_key_editor, chunks[row]);
+            app.model_name_editor.set_block(focused_border(Block::default().borders(Borders::ALL).title(t("settings.model_name")), Focus::ModelName));
+            f.render_widget(&app.model_name_editor, chunks[row + 1]);
+            row += 2;
+        } else {
+            let ollama_url_editor_block = Block::default().borders(Borders::ALL).title(t("settings.ollama_url"));
+            app.url_editor.set_block(focused_border(ollama_url_editor_block, Focus::Url));
+            f.render_widget(&app.url_editor, chunks[row]);
+            let keep_alive = Paragraph::new(tf("settings.keep_alive_value", &[("keep_alive", keep_alive_label(&state.config.keep_alive).as_str())]))
+                .block(focused_border(Block::default().borders(Borders::ALL).title(t("settings.keep_alive")), Focus::KeepAlive));
+            f.render_widget(keep_alive, chunks[row + 1]);
+            row += 2;
+        }
+
+        let mcp_url_editor_block = Block::default().borders(Borders::ALL).title(t("settings.mcp_host"));
+        app.mcp_url_editor.set_block(focused_border(mcp_url_editor_block, Focus::McpUrl));
+        f.render_widget(&app.mcp_url_editor, chunks[row]);
+        row += 1;
+
+        let options_editor_block = Block::default().borders(Borders::ALL).title(t("settings.parameters"));
+        app.options_editor.set_block(focused_border(options_editor_block, Focus::Parameters));
+        f.render_widget(&app.options_editor, chunks[row]);
+
+        let host_memory = resources::host_memory(&state.config);
+        let status_block = Block::default().title(t("settings.status")).borders(Borders::ALL).border_style(Style::default().fg(theme.border));
+        if let Some(pull) = &state.pulling {
+            let (done, total) = pull.bytes.unwrap_or((0, 0));
+            let label = tf("settings.pulling", &[("model", pull.model.as_str()), ("status", pull.status.as_str())]);
+            let gauge = Gauge::default()
+                .block(status_block)
+                .gauge_style(Style::default().fg(theme.accent))
+                .ratio(if total > 0 { (done as f64 / total as f64).min(1.0) } else { 0.0 })
+                .label(label);
+            f.render_widget(gauge, chunks[row + 1]);
+        } else {
+            let (mut status_text, status_color) = if state.status {
+                (t("settings.connected").to_string(), theme.status_ok)
+            } else {
+                (t("settings.disconnected").to_string(), theme.status_error)
+            };
+            // What Ollama holds in memory, and how much the host has
+            if !state.loaded_models.is_empty() {
+                let loaded: Vec<String> = state.loaded_models.iter().map(loaded_label).collect();
+                status_text.push_str(&format!(" · {}", tf("settings.loaded", &[("models", loaded.join(", ").as_str())])));
+            }
+            if let Some(memory) = host_memory.filter(|_| state.config.provider == ProviderKind::Ollama) {
+                status_text.push_str(&format!(" · {}", tf("settings.host_memory", &[("memory", resources::format_bytes(memory).as_str())])));
+            }
+            let status = Paragraph::new(status_text).style(Style::default().fg(status_color)).block(status_block);
+            f.render_widget(status, chunks[row + 1]);
+        }
+
+        let models_title = match state.models_cached {
+            _ if state.shows_catalog() => t("settings.models_catalog").to_string(),
+            Some(fetched_at) => tf("settings.models_cached", &[("age", timefmt::relative(fetched_at, Utc::now()).as_str())]),
+            None => t("settings.models").to_string(),
+        };
+        let (items, keys): (Vec<ListItem>, _) = if state.shows_catalog() {
+            (CATALOG.iter().map(|entry| ListItem::new(entry.row())).collect(), t("settings.catalog_keys"))
+        } else {
+            (state.models.iter().map(|model| ListItem::new(model_line(model, state, host_memory, &theme))).collect(), t("settings.models_keys"))
+        };
+        let models_block = Block::default().title(models_title).title_bottom(keys).borders(Borders::ALL);
+        let list = List::new(items)
+            .block(focused_border(models_block, Focus::Models))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+            .highlight_symbol(">>");
+
+        // Correctly render the stateful widget
+        f.render_stateful_widget(list, chunks[row + 2], &mut app.model_list_state);
+
+        if let Some(info) = &state.model_info {
+            let info_area = centered_rect(area, 80.min(area.width), area.height.saturating_sub(4).max(8));
+            let block = Block::default()
+                .title(info.name.clone())
+                .title_bottom(t("settings.info_keys"))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.accent));
+            f.render_widget(Clear, info_area);
+            f.render_widget(Paragraph::new(model_info_lines(info, &theme)).wrap(Wrap { trim: false }).scroll((app.model_info_scroll, 0)).block(block), info_area);
+        }
+
+        if let Some(editor) = &mut app.model_pull {
+            let pull_area = centered_rect(area, 60.min(area.width), 3);
+            editor.set_block(Block::default().title(t("settings.pull_name")).borders(Borders::ALL).border_style(Style::default().fg(theme.highlight)));
+            f.render_widget(Clear, pull_area);
+            f.render_widget(&*editor, pull_area);
+        }
+    }
+}
+
+/// A loaded model in the status field, e.g. "llama3.1:8b 5.4 GB (100% GPU)".
+fn loaded_label(loaded: &LoadedModel) -> String {
+    let gpu = (loaded.size_vram * 100).checked_div(loaded.size).unwrap_or(0);
+    format!("{} {} ({}% GPU)", loaded.name, resources::format_bytes(loaded.size), gpu)
+}
+
+/// A model in the settings list: its name, size on disk, and whether it is
+/// loaded or too big for the host.
+fn model_line(model: &Model, state: &SharedState, host_memory: Option<u64>, theme: &Theme) -> Line<'static> {
+    let mut spans = vec![Span::raw(model.name.clone())];
+    if let Some(size) = model.size {
+        spans.push(Span::styled(format!("  {}", resources::format_bytes(size)), Style::default().fg(theme.muted)));
+    }
+    if state.loaded_models.iter().any(|loaded| loaded.name == model.name) {
+        spans.push(Span::styled(format!("  {}", t("settings.model_loaded")), Style::default().fg(theme.status_ok)));
+    }
+    if let (Some(size), Some(memory)) = (model.size, host_memory) {
+        if !resources::fits(size, memory) {
+            spans.push(Span::styled(format!("  {}", t("settings.model_too_big")), Style::default().fg(theme.status_error)));
+        }
+    }
+    Line::from(spans)
+}
+
+/// The keep-alive setting as shown in its field.
+fn keep_alive_label(keep_alive: &Option<KeepAlive>) -> String {
+    match keep_alive {
+        None => t("settings.keep_alive_default").to_string(),
+        Some(KeepAlive::Seconds(-1)) => t("settings.keep_alive_forever").to_string(),
+        Some(KeepAlive::Seconds(0)) => t("settings.keep_alive_unload").to_string(),
+        Some(KeepAlive::Seconds(seconds)) => format!("{}s", seconds),
+        Some(KeepAlive::Duration(duration)) => duration.clone(),
+    }
+}
+
+/// The model info popup: the model's numbers first, then its template and
+/// license in full.
+fn model_info_lines(info: &ModelInfo, theme: &Theme) -> Vec<Line<'static>> {
+    let unknown = t("settings.info_unknown");
+    let field = |label: &str, value: Option<String>| {
+        Line::from(vec![
+            Span::styled(format!("{}: ", label), Style::default().fg(theme.secondary)),
+            Span::raw(value.unwrap_or_else(|| unknown.to_string())),
+        ])
+    };
+    let mut lines = vec![
+        field(t("settings.info_parameters"), info.parameter_size.clone()),
+        field(t("settings.info_quantization"), info.quantization.clone()),
+        field(t("settings.info_family"), info.family.clone()),
+        field(t("settings.info_context"), info.context_length.map(|tokens| tokens.to_string())),
+    ];
+    for (label, text) in [(t("settings.info_template"), &info.template), (t("settings.info_license"), &info.license)] {
+        lines.push(Line::default());
+        lines.push(Line::from(Span::styled(format!("{}:", label), Style::default().fg(theme.secondary))));
+        match text {
+            Some(text) => lines.extend(text.lines().map(|line| Line::from(Span::styled(line.to_string(), Style::default().fg(theme.code))))),
+            None => lines.push(Line::from(unknown)),
+        }
+    }
+    lines
+}
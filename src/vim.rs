@@ -0,0 +1,234 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use tui_textarea::{CursorMove, TextArea};
+
+/// The `[input]` section: how the chat input box is edited.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct InputConfig {
+    /// Modal vim-style editing: `Esc` for normal mode, `i`/`a`/`o` to insert.
+    #[serde(default)]
+    pub vim: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum VimMode {
+    /// Keys go to the text area as usual. New input starts here, so typing
+    /// works right away.
+    #[default]
+    Insert,
+    Normal,
+    Visual,
+}
+
+/// Vim editing state of the input box.
+#[derive(Debug, Default)]
+pub struct Vim {
+    pub mode: VimMode,
+    /// The first key of a two-key command such as `dd`, `yy` or `gg`.
+    pending: Option<char>,
+    /// Whether the yanked text is whole lines, which `p` puts below the
+    /// current line instead of after the cursor.
+    linewise: bool,
+}
+
+impl Vim {
+    /// The mode shown in the input box title.
+    pub fn label(&self) -> &'static str {
+        match self.mode {
+            VimMode::Insert => "-- INSERT --",
+            VimMode::Normal => "-- NORMAL --",
+            VimMode::Visual => "-- VISUAL --",
+        }
+    }
+
+    /// Back to insert mode for a fresh input box.
+    pub fn reset(&mut self) {
+        *self = Vim::default();
+    }
+
+    /// Handles `key` for the input box. Returns false for keys that should
+    /// go on to the text area (and the chat's own bindings): everything in
+    /// insert mode except `Esc`, and keys with Ctrl or Alt held.
+    pub fn handle_key(&mut self, textarea: &mut TextArea<'_>, key: KeyEvent) -> bool {
+        if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+            return false;
+        }
+        match self.mode {
+            VimMode::Insert => {
+                if key.code != KeyCode::Esc {
+                    return false;
+                }
+                // Like vim, leaving insert mode steps back onto the last character
+                textarea.move_cursor(CursorMove::Back);
+                self.mode = VimMode::Normal;
+            }
+            VimMode::Normal => self.normal(textarea, key.code),
+            VimMode::Visual => self.visual(textarea, key.code),
+        }
+        true
+    }
+
+    fn normal(&mut self, textarea: &mut TextArea<'_>, code: KeyCode) {
+        let pending = self.pending.take();
+        if move_cursor(textarea, code, pending) {
+            return;
+        }
+        let KeyCode::Char(c) = code else {
+            return;
+        };
+        match (pending, c) {
+            (Some('d'), 'd') => {
+                self.yank_line(textarea);
+                delete_line(textarea);
+            }
+            (Some('y'), 'y') => self.yank_line(textarea),
+            (None, 'd' | 'y' | 'g') => self.pending = Some(c),
+            (None, 'i') => self.mode = VimMode::Insert,
+            (None, 'a') => {
+                textarea.move_cursor(CursorMove::Forward);
+                self.mode = VimMode::Insert;
+            }
+            (None, 'I') => {
+                textarea.move_cursor(CursorMove::Head);
+                self.mode = VimMode::Insert;
+            }
+            (None, 'A') => {
+                textarea.move_cursor(CursorMove::End);
+                self.mode = VimMode::Insert;
+            }
+            (None, 'o') => {
+                textarea.move_cursor(CursorMove::End);
+                textarea.insert_newline();
+                self.mode = VimMode::Insert;
+            }
+            (None, 'O') => {
+                textarea.move_cursor(CursorMove::Head);
+                textarea.insert_newline();
+                textarea.move_cursor(CursorMove::Up);
+                self.mode = VimMode::Insert;
+            }
+            (None, 'x') => {
+                textarea.delete_next_char();
+            }
+            (None, 'D') => {
+                textarea.delete_line_by_end();
+            }
+            (None, 'C') => {
+                textarea.delete_line_by_end();
+                self.mode = VimMode::Insert;
+            }
+            (None, 'p') => self.put(textarea),
+            (None, 'u') => {
+                textarea.undo();
+            }
+            (None, 'v') => {
+                textarea.start_selection();
+                self.mode = VimMode::Visual;
+            }
+            _ => {}
+        }
+    }
+
+    fn visual(&mut self, textarea: &mut TextArea<'_>, code: KeyCode) {
+        let pending = self.pending.take();
+        if move_cursor(textarea, code, pending) {
+            return;
+        }
+        match code {
+            KeyCode::Char('y') => {
+                textarea.copy();
+                self.linewise = false;
+            }
+            KeyCode::Char('d' | 'x') => {
+                textarea.cut();
+                self.linewise = false;
+            }
+            KeyCode::Char('g') => {
+                self.pending = Some('g');
+                return;
+            }
+            KeyCode::Esc | KeyCode::Char('v') => textarea.cancel_selection(),
+            _ => return,
+        }
+        self.mode = VimMode::Normal;
+    }
+
+    fn yank_line(&mut self, textarea: &mut TextArea<'_>) {
+        let (row, _) = textarea.cursor();
+        let line = textarea.lines().get(row).cloned().unwrap_or_default();
+        textarea.set_yank_text(line);
+        self.linewise = true;
+    }
+
+    fn put(&self, textarea: &mut TextArea<'_>) {
+        if self.linewise {
+            let line = textarea.yank_text();
+            textarea.move_cursor(CursorMove::End);
+            textarea.insert_newline();
+            textarea.insert_str(line);
+            textarea.move_cursor(CursorMove::Head);
+        } else {
+            textarea.move_cursor(CursorMove::Forward);
+            textarea.paste();
+        }
+    }
+}
+
+/// Applies a motion key; returns false if `code` is not one.
+fn move_cursor(textarea: &mut TextArea<'_>, code: KeyCode, pending: Option<char>) -> bool {
+    let motion = match code {
+        KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => CursorMove::Back,
+        KeyCode::Char('l') | KeyCode::Right => CursorMove::Forward,
+        KeyCode::Char('j') | KeyCode::Down => CursorMove::Down,
+        KeyCode::Char('k') | KeyCode::Up => CursorMove::Up,
+        KeyCode::Char('w') => CursorMove::WordForward,
+        KeyCode::Char('b') => CursorMove::WordBack,
+        KeyCode::Char('0' | '^') | KeyCode::Home => CursorMove::Head,
+        KeyCode::Char('$') | KeyCode::End => CursorMove::End,
+        KeyCode::Char('G') => CursorMove::Bottom,
+        KeyCode::Char('g') if pending == Some('g') => CursorMove::Top,
+        _ => return false,
+    };
+    textarea.move_cursor(motion);
+    true
+}
+
+/// Removes the cursor's line, or empties it when it is the only one.
+fn delete_line(textarea: &mut TextArea<'_>) {
+    let (row, _) = textarea.cursor();
+    let last = textarea.lines().len() - 1;
+    textarea.move_cursor(CursorMove::Head);
+    textarea.delete_line_by_end();
+    if row < last {
+        textarea.delete_next_char();
+    } else if row > 0 {
+        textarea.delete_char();
+        textarea.move_cursor(CursorMove::Head);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(vim: &mut Vim, textarea: &mut TextArea<'_>, keys: &str) {
+        for c in keys.chars() {
+            let code = if c == '\u{1b}' { KeyCode::Esc } else { KeyCode::Char(c) };
+            if !vim.handle_key(textarea, KeyEvent::new(code, KeyModifiers::NONE)) {
+                textarea.input(tui_textarea::Input::from(KeyEvent::new(code, KeyModifiers::NONE)));
+            }
+        }
+    }
+
+    #[test]
+    fn yank_delete_and_put_whole_lines() {
+        let mut textarea = TextArea::new(vec!["one".to_string(), "two".to_string(), "three".to_string()]);
+        let mut vim = Vim::default();
+        press(&mut vim, &mut textarea, "\u{1b}ggyyjddp");
+        assert_eq!(textarea.lines(), ["one", "three", "two"]);
+
+        press(&mut vim, &mut textarea, "kyyGpAx\u{1b}");
+        assert_eq!(textarea.lines(), ["one", "three", "two", "threex"]);
+        assert_eq!(vim.mode, VimMode::Normal);
+    }
+}